@@ -21,15 +21,67 @@ extern "C" Checkpoint chkpt;
 } // namespace wanco
 
 extern "C" struct ExecEnv {
+  // reserved up to the maximum size, so it never moves after instantiation
   int8_t *memory_base;
-  int32_t memory_size;
+  // in pages
+  int64_t memory_size;
   wanco::MigrationState migration_state;
   int32_t argc;
   uint8_t **argv;
 };
 
+// A wasm table. Imported tables are defined by the host.
+extern "C" struct Table {
+  // function indices or externref handles (-1 if null)
+  // lib-rt replaces them with its own storage when the table grows
+  int32_t *elements;
+  int32_t size;
+};
+
+// Limits of a table declared by the module.
+extern "C" struct TableLimits {
+  int64_t min;
+  // -1 if not declared
+  int64_t max;
+};
+
+// A memory other than memory 0, which is pointed by ExecEnv.
+extern "C" struct Memory {
+  // lib-rt reserves the memory and sets it, and it never moves afterwards
+  int8_t *base;
+  // in pages (initial size until allocated)
+  int64_t size;
+  // in pages, declared by the module or the hard limit
+  int64_t max;
+};
+
 // defined in wasm AOT module
-extern "C" const int32_t INIT_MEMORY_SIZE;
+// initial size of memory 0 in pages
+extern "C" const int64_t INIT_MEMORY_SIZE;
+// maximum size of memory 0 in pages, declared by the module or the hard limit
+extern "C" const int64_t MAX_MEMORY_SIZE;
+// non-zero if the module relies on guard pages instead of explicit bounds
+// checks
+extern "C" const int32_t USE_GUARD_PAGES;
+// non-zero if the linear memory is shared between threads
+extern "C" const int32_t SHARED_MEMORY;
+// names of functions in the name section (nullptr if not named)
+extern "C" const char *const FUNCTION_NAMES[];
+extern "C" const int32_t NUM_FUNCTIONS;
+// memories other than memory 0 in the order of the memory index space
+extern "C" Memory EXTRA_MEMORIES[];
+extern "C" const int32_t NUM_EXTRA_MEMORIES;
+// all tables in the order of the table index space
+extern "C" Table *const TABLES[];
+extern "C" const int32_t NUM_TABLES;
+// limits of each table, which the host must respect for imported tables
+extern "C" const TableLimits TABLE_LIMITS[];
+// remaining size of each data segment (0 once it is dropped)
+extern "C" int32_t DATA_SEGMENT_SIZES[];
+extern "C" const int32_t NUM_DATA_SEGMENTS;
+// remaining size of each element segment (0 once it is dropped)
+extern "C" int32_t ELEM_SEGMENT_SIZES[];
+extern "C" const int32_t NUM_ELEM_SEGMENTS;
 extern "C" void aot_main(ExecEnv *);
 
 // defined in wrt.c
@@ -37,7 +89,11 @@ extern "C" ExecEnv exec_env;
 namespace wanco {
 extern "C" Checkpoint chkpt;
 extern "C" stackmap::Stackmap g_stackmap;
+// Buffer of the linear memory restored from a checkpoint, which is copied into
+// the reserved memory and cleared when the memory is placed.
+// The linear memory itself is not backed by it.
 extern std::string linear_memory;
 
-std::string allocate_memory(int32_t num_pages);
+// entry of executables (see main.cc)
+auto wanco_main(int argc, char **argv) -> int;
 } // namespace wanco
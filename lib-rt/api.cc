@@ -2,10 +2,13 @@
 #include "arch/arch.h"
 #include "chkpt/chkpt.h"
 #include "elf/elf.h"
+#include "exception/exception.h"
 #include "osr/wasm_stacktrace.h"
 #include "stackmap/stackmap.h"
 #include "stacktrace/stacktrace.h"
+#include "trap/trap.h"
 #include "wanco.h"
+#include <algorithm>
 #include <chrono>
 #include <cstdint>
 #include <cstdlib>
@@ -16,34 +19,111 @@
 #include <thread>
 #include <ucontext.h>
 #include <unistd.h>
+#include <unordered_map>
+#include <vector>
 
 namespace wanco {
 
 // defined in wrt.cc
-int32_t extend_memory(ExecEnv *exec_env, int32_t inc_pages);
+int64_t extend_memory(ExecEnv *exec_env, int32_t mem, int64_t inc_pages);
+void store_dropped_segments();
+void store_extra_memories();
 
 } // namespace wanco
 
 // defined by AOT module
 extern "C" void store_globals(ExecEnv *);
+
+// defined below
 extern "C" void store_table(ExecEnv *);
 
-extern "C" int32_t memory_grow(ExecEnv *exec_env, int32_t inc_pages) {
-  return wanco::extend_memory(exec_env, inc_pages);
+// The delta and the result are i64 regardless of the index type of the memory
+extern "C" int64_t memory_grow(ExecEnv *exec_env, int32_t mem,
+                               int64_t inc_pages) {
+  return wanco::extend_memory(exec_env, mem, inc_pages);
+}
+
+extern "C" void trap(ExecEnv *exec_env, wanco::TrapKind kind) {
+  wanco::trap(kind);
+}
+
+namespace wanco {
+
+// Maximum number of elements of the table, regardless of the declared maximum
+constexpr uint64_t MAX_TABLE_SIZE = 10000000;
+
+// Elements of the tables once they have been moved out of their definitions
+static std::unordered_map<Table *, std::vector<int32_t>> table_storage;
+
+static void resize_table(Table *table, uint32_t size, int32_t init) {
+  // The initial elements are defined by the AOT module or the host
+  auto &storage = table_storage[table];
+  if (storage.data() != table->elements) {
+    storage.assign(table->elements, table->elements + table->size);
+  }
+  storage.resize(size, init);
+  table->elements = storage.data();
+  table->size = size;
+}
+
+} // namespace wanco
+
+// Returns the old size of the table, or -1 on failure.
+// maximum is -1 if the table has no maximum size.
+extern "C" int32_t table_grow(ExecEnv *exec_env, Table *table, int32_t init,
+                              int32_t delta, int64_t maximum) {
+  uint64_t old_size = static_cast<uint32_t>(table->size);
+  uint64_t new_size = old_size + static_cast<uint32_t>(delta);
+  if (new_size > UINT32_MAX || new_size > wanco::MAX_TABLE_SIZE) {
+    return -1;
+  }
+  if (maximum >= 0 && new_size > static_cast<uint64_t>(maximum)) {
+    return -1;
+  }
+  wanco::resize_table(table, new_size, init);
+  return old_size;
+}
+
+// The bounds are checked by the AOT module
+extern "C" void table_fill(ExecEnv *exec_env, Table *table, int32_t index,
+                           int32_t value, int32_t len) {
+  std::fill_n(table->elements + static_cast<uint32_t>(index),
+              static_cast<uint32_t>(len), value);
+}
+
+// The table provided to modules importing "spectest" "table"
+static int32_t spectest_table_elements[10] = {-1, -1, -1, -1, -1,
+                                              -1, -1, -1, -1, -1};
+extern "C" {
+Table spectest_table = {
+    .elements = spectest_table_elements,
+    .size = 10,
+};
 }
 
-/* Print a string from memory */
-extern "C" void print(ExecEnv *exec_env, int32_t offset, int32_t len) {
+/*
+** Built-in host functions used by the tests and demos.
+** They are weak so that host files given to wanco can override them.
+*/
+
+/* "myenv" "print": print a string from memory */
+extern "C" __attribute__((weak)) void
+wanco_import_5myenv5print_li_v(ExecEnv *exec_env, int64_t offset,
+                               int32_t len) {
   for (int i = 0; i < len; i++) {
     putchar(exec_env->memory_base[offset + i]);
   }
 }
 
-extern "C" void print_i32(ExecEnv *exec_env, int32_t i32) {
+/* "env" "print_i32" */
+extern "C" __attribute__((weak)) void
+wanco_import_3env9print_i32_i_v(ExecEnv *exec_env, int32_t i32) {
   std::cout << std::dec << i32 << std::endl;
 }
 
-extern "C" void sleep_msec(ExecEnv *exec_env, int32_t ms) {
+/* "env" "sleep_msec" */
+extern "C" __attribute__((weak)) void
+wanco_import_3env10sleep_msec_i_v(ExecEnv *exec_env, int32_t ms) {
   std::this_thread::sleep_for(std::chrono::milliseconds(ms));
 }
 
@@ -92,7 +172,10 @@ extern "C" void start_checkpoint(ExecEnv *exec_env) {
   // store the globals, table, and memory
   store_globals(exec_env);
   store_table(exec_env);
+  wanco::store_dropped_segments();
+  wanco::store_exceptions(wanco::chkpt);
   wanco::chkpt.memory_size = exec_env->memory_size;
+  wanco::store_extra_memories();
 
   // write snapshot
   {
@@ -165,6 +248,15 @@ extern "C" void push_local_f64(ExecEnv *exec_env, double f64) {
   wanco::chkpt.frames.back().locals.push_back(wanco::Value(f64));
 }
 
+extern "C" void push_local_v128(ExecEnv *exec_env, wanco::v128_t v128) {
+  ASSERT(exec_env->migration_state ==
+             wanco::MigrationState::STATE_CHECKPOINT_CONTINUE &&
+         "Invalid migration state");
+  wanco::Value v(v128);
+  DEBUG_LOG << "call to push_local_v128 -> " << v.to_string() << std::endl;
+  wanco::chkpt.frames.back().locals.push_back(v);
+}
+
 // stack
 extern "C" void push_i32(ExecEnv *exec_env, int32_t i32) {
   ASSERT(exec_env->migration_state ==
@@ -199,6 +291,15 @@ extern "C" void push_f64(ExecEnv *exec_env, double f64) {
   wanco::chkpt.frames.back().stack.push_back(wanco::Value(f64));
 }
 
+extern "C" void push_v128(ExecEnv *exec_env, wanco::v128_t v128) {
+  ASSERT(exec_env->migration_state ==
+             wanco::MigrationState::STATE_CHECKPOINT_CONTINUE &&
+         "Invalid migration state");
+  wanco::Value v(v128);
+  DEBUG_LOG << "call to push_v128 -> " << v.to_string() << std::endl;
+  wanco::chkpt.frames.back().stack.push_back(v);
+}
+
 // globals
 extern "C" void push_global_i32(ExecEnv *exec_env, int32_t i32) {
   ASSERT(exec_env->migration_state ==
@@ -232,13 +333,27 @@ extern "C" void push_global_f64(ExecEnv *exec_env, double f64) {
   wanco::chkpt.globals.push_back(wanco::Value(f64));
 }
 
+extern "C" void push_global_v128(ExecEnv *exec_env, wanco::v128_t v128) {
+  ASSERT(exec_env->migration_state ==
+             wanco::MigrationState::STATE_CHECKPOINT_CONTINUE &&
+         "Invalid migration state");
+  wanco::Value v(v128);
+  DEBUG_LOG << "call to push_global_v128 -> " << v.to_string() << std::endl;
+  wanco::chkpt.globals.push_back(v);
+}
+
 // table
-extern "C" void push_table_index(ExecEnv *exec_env, int32_t index) {
+extern "C" void store_table(ExecEnv *exec_env) {
   ASSERT(exec_env->migration_state ==
              wanco::MigrationState::STATE_CHECKPOINT_CONTINUE &&
          "Invalid migration state");
-  DEBUG_LOG << "call to push_table_index -> " << index << std::endl;
-  wanco::chkpt.table.push_back(index);
+  for (int32_t i = 0; i < NUM_TABLES; i++) {
+    const Table *table = TABLES[i];
+    DEBUG_LOG << "call to store_table -> table[" << i << "] has "
+              << table->size << " elements" << std::endl;
+    wanco::chkpt.tables.emplace_back(table->elements,
+                                     table->elements + table->size);
+  }
 }
 
 namespace wanco {
@@ -335,6 +450,15 @@ extern "C" int32_t get_pc_from_frame(ExecEnv *exec_env) {
   return ret;
 }
 
+extern "C" int32_t get_fn_index_from_frame(ExecEnv *exec_env) {
+  ASSERT(!wanco::chkpt.frames.empty() && "No frame to restore");
+  ASSERT(exec_env->migration_state == wanco::MigrationState::STATE_RESTORE &&
+         "Invalid migration state");
+  int32_t ret = wanco::chkpt.frames.front().fn_index;
+  DEBUG_LOG << "call to get_fn_index_from_frame -> " << ret << std::endl;
+  return ret;
+}
+
 extern "C" int32_t pop_front_local_i32(ExecEnv *exec_env) {
   ASSERT(!wanco::chkpt.frames.empty() && "No frame to restore");
   ASSERT(!wanco::chkpt.frames.front().locals.empty() && "No local to pop");
@@ -375,6 +499,16 @@ extern "C" double pop_front_local_f64(ExecEnv *exec_env) {
   return v.f64;
 }
 
+extern "C" wanco::v128_t pop_front_local_v128(ExecEnv *exec_env) {
+  ASSERT(!wanco::chkpt.frames.empty() && "No frame to restore");
+  ASSERT(!wanco::chkpt.frames.front().locals.empty() && "No local to pop");
+  wanco::Value v = wanco::chkpt.frames.front().locals.front();
+  DEBUG_LOG << "call to pop_front_local -> " << v.to_string() << std::endl;
+  wanco::chkpt.frames.front().locals.pop_front();
+  ASSERT(v.get_type() == wanco::Value::Type::V128 && "Invalid type");
+  return v.v128;
+}
+
 extern "C" int32_t pop_i32(ExecEnv *exec_env) {
   ASSERT(!wanco::chkpt.restore_stack.empty() && "Stack empty");
   wanco::Value v = wanco::chkpt.restore_stack.front();
@@ -414,6 +548,15 @@ extern "C" double pop_f64(ExecEnv *exec_env) {
   // wanco::check_restore_finished (exec_env);
   return v.f64;
 }
+
+extern "C" wanco::v128_t pop_v128(ExecEnv *exec_env) {
+  ASSERT(!wanco::chkpt.restore_stack.empty() && "Stack empty");
+  wanco::Value v = wanco::chkpt.restore_stack.front();
+  DEBUG_LOG << "call to pop -> " << v.to_string() << std::endl;
+  wanco::chkpt.restore_stack.pop_front();
+  ASSERT(v.get_type() == wanco::Value::Type::V128 && "Invalid type");
+  return v.v128;
+}
 extern "C" int32_t pop_front_global_i32(ExecEnv *exec_env) {
   ASSERT(exec_env->migration_state == wanco::MigrationState::STATE_RESTORE &&
          "Invalid migration state");
@@ -458,13 +601,29 @@ extern "C" double pop_front_global_f64(ExecEnv *exec_env) {
   return v.f64;
 }
 
+extern "C" wanco::v128_t pop_front_global_v128(ExecEnv *exec_env) {
+  ASSERT(exec_env->migration_state == wanco::MigrationState::STATE_RESTORE &&
+         "Invalid migration state");
+  ASSERT(!wanco::chkpt.globals.empty() && "No global to pop");
+  wanco::Value v = wanco::chkpt.globals.front();
+  DEBUG_LOG << "call to pop_front_global -> " << v.to_string() << std::endl;
+  wanco::chkpt.globals.pop_front();
+  ASSERT(v.get_type() == wanco::Value::Type::V128 && "Invalid type");
+  return v.v128;
+}
+
 // table
-extern "C" int32_t pop_front_table_index(ExecEnv *exec_env) {
+extern "C" void restore_table(ExecEnv *exec_env) {
   ASSERT(exec_env->migration_state == wanco::MigrationState::STATE_RESTORE &&
          "Invalid migration state");
-  ASSERT(!wanco::chkpt.table.empty() && "Table is empty");
-  int32_t idx = wanco::chkpt.table.front();
-  DEBUG_LOG << "call to pop_front_table_index -> " << idx << std::endl;
-  wanco::chkpt.table.pop_front();
-  return idx;
+  ASSERT(wanco::chkpt.tables.size() == static_cast<size_t>(NUM_TABLES) &&
+         "Number of tables mismatch");
+  for (int32_t i = 0; i < NUM_TABLES; i++) {
+    const auto &elements = wanco::chkpt.tables[i];
+    DEBUG_LOG << "call to restore_table -> table[" << i << "] has "
+              << elements.size() << " elements" << std::endl;
+    wanco::resize_table(TABLES[i], elements.size(), -1);
+    std::copy(elements.begin(), elements.end(), TABLES[i]->elements);
+  }
+  wanco::chkpt.tables.clear();
 }
@@ -11,10 +11,44 @@
                "push r13 \n\t"                                                 \
                "push r14 \n\t"                                                 \
                "push r15 \n\t"                                                 \
+               "sub rsp, 256 \n\t"                                             \
+               "movdqu xmmword ptr [rsp + 0], xmm0 \n\t"                       \
+               "movdqu xmmword ptr [rsp + 16], xmm1 \n\t"                      \
+               "movdqu xmmword ptr [rsp + 32], xmm2 \n\t"                      \
+               "movdqu xmmword ptr [rsp + 48], xmm3 \n\t"                      \
+               "movdqu xmmword ptr [rsp + 64], xmm4 \n\t"                      \
+               "movdqu xmmword ptr [rsp + 80], xmm5 \n\t"                      \
+               "movdqu xmmword ptr [rsp + 96], xmm6 \n\t"                      \
+               "movdqu xmmword ptr [rsp + 112], xmm7 \n\t"                     \
+               "movdqu xmmword ptr [rsp + 128], xmm8 \n\t"                     \
+               "movdqu xmmword ptr [rsp + 144], xmm9 \n\t"                     \
+               "movdqu xmmword ptr [rsp + 160], xmm10 \n\t"                    \
+               "movdqu xmmword ptr [rsp + 176], xmm11 \n\t"                    \
+               "movdqu xmmword ptr [rsp + 192], xmm12 \n\t"                    \
+               "movdqu xmmword ptr [rsp + 208], xmm13 \n\t"                    \
+               "movdqu xmmword ptr [rsp + 224], xmm14 \n\t"                    \
+               "movdqu xmmword ptr [rsp + 240], xmm15 \n\t"                    \
                ".att_syntax \n\t");
 
 #define WANCO_RESTORE_REGISTERS(regs)                                          \
   asm volatile(".intel_syntax noprefix \n\t"                                   \
+               "movdqu %5, xmmword ptr [rsp + 0] \n\t"                         \
+               "movdqu %6, xmmword ptr [rsp + 16] \n\t"                        \
+               "movdqu %7, xmmword ptr [rsp + 32] \n\t"                        \
+               "movdqu %8, xmmword ptr [rsp + 48] \n\t"                        \
+               "movdqu %9, xmmword ptr [rsp + 64] \n\t"                        \
+               "movdqu %10, xmmword ptr [rsp + 80] \n\t"                       \
+               "movdqu %11, xmmword ptr [rsp + 96] \n\t"                       \
+               "movdqu %12, xmmword ptr [rsp + 112] \n\t"                      \
+               "movdqu %13, xmmword ptr [rsp + 128] \n\t"                      \
+               "movdqu %14, xmmword ptr [rsp + 144] \n\t"                      \
+               "movdqu %15, xmmword ptr [rsp + 160] \n\t"                      \
+               "movdqu %16, xmmword ptr [rsp + 176] \n\t"                      \
+               "movdqu %17, xmmword ptr [rsp + 192] \n\t"                      \
+               "movdqu %18, xmmword ptr [rsp + 208] \n\t"                      \
+               "movdqu %19, xmmword ptr [rsp + 224] \n\t"                      \
+               "movdqu %20, xmmword ptr [rsp + 240] \n\t"                      \
+               "add rsp, 256 \n\t"                                             \
                "pop %0 \n\t"                                                   \
                "pop %1 \n\t"                                                   \
                "pop %2 \n\t"                                                   \
@@ -22,7 +56,15 @@
                "pop %4 \n\t"                                                   \
                ".att_syntax \n\t"                                              \
                : "=r"((regs).r15), "=r"((regs).r14), "=r"((regs).r13),         \
-                 "=r"((regs).r12), "=r"((regs).rbx));
+                 "=r"((regs).r12), "=r"((regs).rbx),                           \
+                 "=x"((regs).xmm[0]), "=x"((regs).xmm[1]),                     \
+                 "=x"((regs).xmm[2]), "=x"((regs).xmm[3]),                     \
+                 "=x"((regs).xmm[4]), "=x"((regs).xmm[5]),                     \
+                 "=x"((regs).xmm[6]), "=x"((regs).xmm[7]),                     \
+                 "=x"((regs).xmm[8]), "=x"((regs).xmm[9]),                     \
+                 "=x"((regs).xmm[10]), "=x"((regs).xmm[11]),                   \
+                 "=x"((regs).xmm[12]), "=x"((regs).xmm[13]),                   \
+                 "=x"((regs).xmm[14]), "=x"((regs).xmm[15]));
 
 namespace wanco {
 
@@ -192,6 +234,8 @@ inline auto reg_to_string(Register &reg) -> std::string {
   }
 }
 
+using xmm_t = uint8_t __attribute__((vector_size(16)));
+
 // For System V ABI.
 struct CallerSavedRegisters {
   uint64_t rbx;
@@ -200,6 +244,9 @@ struct CallerSavedRegisters {
   uint64_t r13;
   uint64_t r14;
   uint64_t r15;
+  // xmm registers are saved as well because floating-point and vector values
+  // may be located in them.
+  xmm_t xmm[16];
 
   uint64_t get_value(Register reg) const {
     switch (reg) {
@@ -218,6 +265,14 @@ struct CallerSavedRegisters {
       exit(1);
     }
   }
+
+  const xmm_t &get_xmm(Register reg) const {
+    if (reg < Register::XMM0 || reg > Register::XMM15) {
+      Fatal() << "Invalid register " << reg_to_string(reg) << '\n';
+      exit(1);
+    }
+    return xmm[static_cast<int>(reg) - static_cast<int>(Register::XMM0)];
+  }
 };
 
 } // namespace wanco
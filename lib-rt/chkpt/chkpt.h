@@ -1,5 +1,6 @@
 #pragma once
 #include "wanco.h"
+#include <cstring>
 #include <deque>
 #include <fstream>
 #include <string>
@@ -10,6 +11,9 @@ namespace wanco {
 // 1page = 64KiB
 const int32_t PAGE_SIZE = 65536;
 
+// v128 values are passed as <2 x i64> vectors (same as __m128i)
+using v128_t = int64_t __attribute__((vector_size(16)));
+
 class Value {
 public:
   enum class Type {
@@ -17,12 +21,14 @@ public:
     I64,
     F32,
     F64,
+    V128,
   };
 
   Value(int32_t i32) : i32(i32), type(Type::I32) {}
   Value(int64_t i64) : i64(i64), type(Type::I64) {}
   Value(float f32) : f32(f32), type(Type::F32) {}
   Value(double f64) : f64(f64), type(Type::F64) {}
+  Value(v128_t v128) : v128(v128), type(Type::V128) {}
 
   Type get_type() const { return type; }
 
@@ -36,6 +42,15 @@ public:
       return "<type=f32, value=" + std::to_string(f32) + ">";
     case Type::F64:
       return "<type=f64, value=" + std::to_string(f64) + ">";
+    case Type::V128: {
+      uint8_t bytes[16];
+      std::memcpy(bytes, &v128, sizeof(bytes));
+      char hex[33];
+      for (int i = 0; i < 16; i++) {
+        snprintf(hex + i * 2, 3, "%02x", bytes[i]);
+      }
+      return "<type=v128, value=0x" + std::string(hex) + ">";
+    }
     }
     __builtin_unreachable();
   }
@@ -45,6 +60,7 @@ public:
     int64_t i64;
     float f32;
     double f64;
+    v128_t v128;
   };
 
 private:
@@ -60,12 +76,30 @@ public:
   std::vector<Value> stack;
 };
 
+// A wasm exception. The payload is the tag parameters laid out by the AOT
+// module.
+struct Exception {
+  // -1 if released
+  int32_t tag;
+  std::vector<uint8_t> payload;
+};
+
 class Checkpoint {
 public:
   std::deque<Frame> frames;
   std::deque<Value> globals;
-  std::deque<int32_t> table;
-  int memory_size = 0;
+  // elements of every table in the order of the table index space
+  std::vector<std::vector<int32_t>> tables;
+  // whether each data segment has been dropped
+  std::vector<bool> data_dropped;
+  // whether each element segment has been dropped
+  std::vector<bool> elem_dropped;
+  // exception table indexed by the handles saved in locals
+  std::vector<Exception> exceptions;
+  // in pages
+  int64_t memory_size = 0;
+  // contents of the memories other than memory 0
+  std::vector<std::string> memories;
 
   // リストア時にはframesではなく、こちらに値スタックを詰む。
   // 値スタックをpopする前に、framesのpop操作が行われるため。
@@ -74,8 +108,12 @@ public:
   void clear() {
     frames.clear();
     globals.clear();
-    table.clear();
+    tables.clear();
+    data_dropped.clear();
+    elem_dropped.clear();
+    exceptions.clear();
     memory_size = 0;
+    memories.clear();
     restore_stack.clear();
   }
 
@@ -25,6 +25,13 @@ static wanco::Value decode_value_proto(const chkpt::Value &v) {
     double f64 = v.f64();
     return wanco::Value(f64);
   } break;
+  case chkpt::Type::V128: {
+    const std::string &bytes = v.v128();
+    ASSERT(bytes.size() == sizeof(v128_t) && "Invalid v128 value");
+    v128_t v128;
+    std::memcpy(&v128, bytes.data(), sizeof(v128_t));
+    return wanco::Value(v128);
+  } break;
   default:
     ASSERT(false && "Invalid type");
     return wanco::Value(0);
@@ -67,14 +74,28 @@ wanco::Checkpoint decode_checkpoint_proto(std::ifstream &f) {
     ret.globals.push_back(v);
   }
 
-  for (const auto &t : buf.table()) {
-    ret.table.push_back(t);
+  for (const auto &t : buf.tables()) {
+    ret.tables.emplace_back(t.elements().begin(), t.elements().end());
+  }
+
+  ret.data_dropped.assign(buf.data_dropped().begin(),
+                          buf.data_dropped().end());
+  ret.elem_dropped.assign(buf.elem_dropped().begin(),
+                          buf.elem_dropped().end());
+
+  for (const auto &e : buf.exceptions()) {
+    ret.exceptions.push_back(wanco::Exception{
+        e.tag(), std::vector<uint8_t>(e.payload().begin(), e.payload().end())});
+  }
+
+  for (const auto &m : buf.memories()) {
+    ret.memories.push_back(m.data());
   }
 
   ret.memory_size = buf.memory_size();
-  linear_memory = allocate_memory(ret.memory_size);
 
   if (USE_LZ4) {
+    linear_memory.assign(ret.memory_size * PAGE_SIZE, '\0');
     Info() << "Decompressing memory: " << std::dec << ret.memory_size
            << " pages (" << ret.memory_size * PAGE_SIZE << " bytes)"
            << std::endl;
@@ -116,6 +137,10 @@ static chkpt::Value encode_value_proto(const wanco::Value &v) {
     ret.set_type(chkpt::Type::F64);
     ret.set_f64(v.f64);
     break;
+  case wanco::Value::Type::V128:
+    ret.set_type(chkpt::Type::V128);
+    ret.set_v128(&v.v128, sizeof(v128_t));
+    break;
   default:
     ASSERT(false && "Invalid type");
   }
@@ -153,8 +178,28 @@ void encode_checkpoint_proto(std::ofstream &ofs, Checkpoint &chkpt,
     buf.add_globals()->CopyFrom(v);
   }
 
-  for (const auto &t : chkpt.table) {
-    buf.add_table(t);
+  for (const auto &t : chkpt.tables) {
+    chkpt::Table *table = buf.add_tables();
+    for (const auto &elem : t) {
+      table->add_elements(elem);
+    }
+  }
+
+  for (const bool dropped : chkpt.data_dropped) {
+    buf.add_data_dropped(dropped);
+  }
+  for (const bool dropped : chkpt.elem_dropped) {
+    buf.add_elem_dropped(dropped);
+  }
+
+  for (const auto &e : chkpt.exceptions) {
+    chkpt::Exception *exception = buf.add_exceptions();
+    exception->set_tag(e.tag);
+    exception->set_payload(e.payload.data(), e.payload.size());
+  }
+
+  for (const auto &m : chkpt.memories) {
+    buf.add_memories()->set_data(m);
   }
 
   buf.set_memory_size(chkpt.memory_size);
@@ -0,0 +1,122 @@
+#include "exception/exception.h"
+#include "aot.h"
+#include "wanco.h"
+#include <cxxabi.h>
+#include <mutex>
+#include <utility>
+#include <vector>
+
+namespace wanco {
+
+// The key function emits the typeinfo of WasmException in this file.
+WasmException::~WasmException() = default;
+
+// tag of released entries
+constexpr int32_t RELEASED = -1;
+
+// Wasm exceptions are referred to by handles, which are indices of this table.
+// The AOT module keeps the handles of caught exceptions in locals, so the
+// table is saved in checkpoints as it is and the handles stay valid.
+static std::mutex exceptions_mutex;
+static std::vector<Exception> exceptions;
+static std::vector<int32_t> free_handles;
+
+// Set when an exported function throws an exception to the host
+static thread_local bool uncaught_exception = false;
+
+static auto get_exception(int32_t handle) -> Exception & {
+  ASSERT(0 <= handle && static_cast<size_t>(handle) < exceptions.size() &&
+         exceptions[handle].tag != RELEASED && "Invalid exception handle");
+  return exceptions[handle];
+}
+
+static auto allocate_exception(int32_t tag, std::vector<uint8_t> payload)
+    -> int32_t {
+  std::lock_guard<std::mutex> lock(exceptions_mutex);
+  if (free_handles.empty()) {
+    exceptions.push_back(Exception{tag, std::move(payload)});
+    return exceptions.size() - 1;
+  }
+  int32_t handle = free_handles.back();
+  free_handles.pop_back();
+  exceptions[handle] = Exception{tag, std::move(payload)};
+  return handle;
+}
+
+void store_exceptions(Checkpoint &chkpt) {
+  std::lock_guard<std::mutex> lock(exceptions_mutex);
+  chkpt.exceptions = exceptions;
+}
+
+void restore_exceptions(const Checkpoint &chkpt) {
+  std::lock_guard<std::mutex> lock(exceptions_mutex);
+  exceptions = chkpt.exceptions;
+  free_handles.clear();
+  for (size_t i = 0; i < exceptions.size(); i++) {
+    if (exceptions[i].tag == RELEASED) {
+      free_handles.push_back(i);
+    }
+  }
+}
+
+} // namespace wanco
+
+// Allocate an exception whose payload is filled by the caller.
+extern "C" int32_t exception_new(ExecEnv *exec_env, int32_t tag,
+                                 int64_t size) {
+  return wanco::allocate_exception(tag, std::vector<uint8_t>(size));
+}
+
+extern "C" int32_t exception_clone(ExecEnv *exec_env, int32_t handle) {
+  wanco::Exception exception;
+  {
+    std::lock_guard<std::mutex> lock(wanco::exceptions_mutex);
+    exception = wanco::get_exception(handle);
+  }
+  return wanco::allocate_exception(exception.tag, std::move(exception.payload));
+}
+
+extern "C" [[noreturn]] void exception_rethrow(ExecEnv *exec_env,
+                                               int32_t handle) {
+  throw wanco::WasmException(handle);
+}
+
+// Called in landing pads with the exception object of the C++ runtime.
+extern "C" int32_t exception_catch(ExecEnv *exec_env, void *exn) {
+  auto *exception =
+      static_cast<wanco::WasmException *>(__cxxabiv1::__cxa_begin_catch(exn));
+  int32_t handle = exception->handle;
+  __cxxabiv1::__cxa_end_catch();
+  return handle;
+}
+
+extern "C" int32_t exception_tag(ExecEnv *exec_env, int32_t handle) {
+  std::lock_guard<std::mutex> lock(wanco::exceptions_mutex);
+  return wanco::get_exception(handle).tag;
+}
+
+extern "C" uint8_t *exception_payload(ExecEnv *exec_env, int32_t handle) {
+  std::lock_guard<std::mutex> lock(wanco::exceptions_mutex);
+  return wanco::get_exception(handle).payload.data();
+}
+
+// Release an exception. -1 means no exception.
+extern "C" void exception_release(ExecEnv *exec_env, int32_t handle) {
+  if (handle == -1) {
+    return;
+  }
+  std::lock_guard<std::mutex> lock(wanco::exceptions_mutex);
+  wanco::get_exception(handle) = wanco::Exception{wanco::RELEASED, {}};
+  wanco::free_handles.push_back(handle);
+}
+
+// Called in the landing pads of exported functions, which return to the host
+// instead of unwinding through its frames.
+extern "C" void exception_uncaught(ExecEnv *exec_env, void *exn) {
+  exception_release(exec_env, exception_catch(exec_env, exn));
+  wanco::uncaught_exception = true;
+}
+
+extern "C" int32_t wanco_uncaught_exception(ExecEnv *env) {
+  return std::exchange(wanco::uncaught_exception, false) ? 1 : 0;
+}
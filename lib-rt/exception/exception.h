@@ -0,0 +1,23 @@
+#pragma once
+#include "chkpt/chkpt.h"
+#include <cstdint>
+
+namespace wanco {
+
+// C++ exception which carries a wasm exception.
+// The AOT module catches it by its typeinfo (_ZTIN5wanco13WasmExceptionE).
+struct WasmException {
+  // index of the exception table
+  int32_t handle;
+
+  explicit WasmException(int32_t handle) : handle(handle) {}
+  virtual ~WasmException();
+};
+
+// Save the exception table to the checkpoint.
+void store_exceptions(Checkpoint &chkpt);
+
+// Restore the exception table from the checkpoint.
+void restore_exceptions(const Checkpoint &chkpt);
+
+} // namespace wanco
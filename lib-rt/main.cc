@@ -0,0 +1,7 @@
+// The entry of executables. Libraries do not pull this file from libwanco_rt.a
+// since they do not reference `main`.
+#include "aot.h"
+
+auto main(int argc, char **argv) -> int {
+  return wanco::wanco_main(argc, argv);
+}
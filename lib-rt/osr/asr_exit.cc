@@ -4,6 +4,7 @@
 #include "stacktrace/stacktrace.h"
 #include "wanco.h"
 #include <algorithm>
+#include <cstring>
 #include <deque>
 #include <map>
 #include <memory>
@@ -142,6 +143,8 @@ static Value::Type decode_value_type(int32_t encoded) {
     return Value::Type::F32;
   case 3:
     return Value::Type::F64;
+  case 4:
+    return Value::Type::V128;
   default:
     Fatal() << "Invalid value type" << '\n';
     exit(1);
@@ -167,6 +170,12 @@ static Value value_from_memory(const uint8_t *addr, Value::Type ty) {
     return Value{*reinterpret_cast<const float *>(addr)};
   case Value::Type::F64:
     return Value{*reinterpret_cast<const double *>(addr)};
+  case Value::Type::V128: {
+    // may be unaligned
+    v128_t v128;
+    std::memcpy(&v128, addr, sizeof(v128_t));
+    return Value{v128};
+  }
   default:
     Fatal() << "Invalid value type" << '\n';
     exit(1);
@@ -180,6 +189,12 @@ static Value retrieve_value(const stackmap::Stackmap &stackmap,
   switch (loc.kind) {
   case stackmap::LocationKind::REGISTER: {
     Register reg{loc.dwarf_regnum};
+    if (reg >= Register::XMM0 && reg <= Register::XMM15) {
+      // floating-point and vector values
+      ASSERT(!loc_is_ptr && "Pointer in xmm register");
+      const xmm_t &xmm = regs.get_xmm(reg);
+      return value_from_memory(reinterpret_cast<const uint8_t *>(&xmm), ty);
+    }
     uint64_t value = regs.get_value(reg);
     if (loc_is_ptr)
       return value_from_memory(reinterpret_cast<const uint8_t *>(value), ty);
@@ -0,0 +1,126 @@
+#include "thread/thread.h"
+#include "aot.h"
+#include "wanco.h"
+#include <atomic>
+#include <chrono>
+#include <condition_variable>
+#include <cstdint>
+#include <list>
+#include <mutex>
+#include <thread>
+#include <unordered_map>
+
+// defined in wasm AOT module if it exports `wasi_thread_start`
+extern "C" __attribute__((weak)) void aot_wasi_thread_start(ExecEnv *,
+                                                            int32_t tid,
+                                                            int32_t start_arg);
+
+namespace wanco {
+
+// Results of memory.atomic.wait
+constexpr int32_t WAIT_OK = 0;
+constexpr int32_t WAIT_NOT_EQUAL = 1;
+constexpr int32_t WAIT_TIMED_OUT = 2;
+
+struct Waiter {
+  std::condition_variable cv;
+  bool notified = false;
+};
+
+// Guards the waiter lists. Comparing the expected value and enqueueing the
+// waiter are done while holding it, so no notification is lost.
+static std::mutex wait_mutex;
+// Waiters on each address in FIFO order
+static std::unordered_map<void *, std::list<Waiter *>> waiters;
+
+static std::atomic<int32_t> next_tid{1};
+static std::atomic<int32_t> running_threads{0};
+
+int32_t num_running_threads() { return running_threads.load(); }
+
+template <typename T>
+static auto atomic_wait(T *addr, T expected, int64_t timeout_ns) -> int32_t {
+  std::unique_lock<std::mutex> lock(wait_mutex);
+  if (__atomic_load_n(addr, __ATOMIC_SEQ_CST) != expected) {
+    return WAIT_NOT_EQUAL;
+  }
+
+  Waiter waiter;
+  auto &queue = waiters[addr];
+  auto it = queue.insert(queue.end(), &waiter);
+  // a negative timeout means infinity
+  if (timeout_ns < 0) {
+    waiter.cv.wait(lock, [&] { return waiter.notified; });
+  } else {
+    waiter.cv.wait_for(lock, std::chrono::nanoseconds(timeout_ns),
+                       [&] { return waiter.notified; });
+  }
+
+  if (waiter.notified) {
+    return WAIT_OK;
+  }
+  // timed out, so remove the waiter by ourselves
+  queue.erase(it);
+  if (queue.empty()) {
+    waiters.erase(addr);
+  }
+  return WAIT_TIMED_OUT;
+}
+
+} // namespace wanco
+
+// The address is checked by the AOT module
+extern "C" int32_t memory_atomic_wait32(ExecEnv *exec_env, int32_t *addr,
+                                        int32_t expected, int64_t timeout_ns) {
+  return wanco::atomic_wait(addr, expected, timeout_ns);
+}
+
+extern "C" int32_t memory_atomic_wait64(ExecEnv *exec_env, int64_t *addr,
+                                        int64_t expected, int64_t timeout_ns) {
+  return wanco::atomic_wait(addr, expected, timeout_ns);
+}
+
+// Returns the number of woken waiters.
+extern "C" int32_t memory_atomic_notify(ExecEnv *exec_env, int32_t *addr,
+                                        int32_t count) {
+  std::lock_guard<std::mutex> lock(wanco::wait_mutex);
+  auto found = wanco::waiters.find(addr);
+  if (found == wanco::waiters.end()) {
+    return 0;
+  }
+
+  auto &queue = found->second;
+  uint32_t woken = 0;
+  while (!queue.empty() && woken < static_cast<uint32_t>(count)) {
+    auto *waiter = queue.front();
+    queue.pop_front();
+    waiter->notified = true;
+    waiter->cv.notify_one();
+    woken++;
+  }
+  if (queue.empty()) {
+    wanco::waiters.erase(found);
+  }
+  return woken;
+}
+
+// wasi-threads: run `wasi_thread_start(tid, start_arg)` in a new thread.
+// Returns the thread id, or a negative value on failure.
+extern "C" int32_t wasi_thread_spawn(ExecEnv *exec_env, int32_t start_arg) {
+  if (aot_wasi_thread_start == nullptr) {
+    Warn() << "wasi_thread_start is not exported" << '\n';
+    return -1;
+  }
+
+  int32_t const tid = wanco::next_tid.fetch_add(1);
+  // thread ids must be in [1, 2^29)
+  if (tid >= (1 << 29)) {
+    return -1;
+  }
+  wanco::running_threads.fetch_add(1);
+  std::thread([exec_env, tid, start_arg] {
+    aot_wasi_thread_start(exec_env, tid, start_arg);
+    wanco::running_threads.fetch_sub(1);
+  }).detach();
+  return tid;
+}
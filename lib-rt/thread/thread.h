@@ -0,0 +1,10 @@
+#pragma once
+#include <cstdint>
+
+namespace wanco {
+
+// Number of threads spawned by `wasi_thread_spawn` which are still running.
+// This can be read in signal handlers.
+int32_t num_running_threads();
+
+} // namespace wanco
@@ -0,0 +1,106 @@
+#include "trap/trap.h"
+#include "aot.h"
+#include "osr/wasm_stacktrace.h"
+#include "stacktrace/stacktrace.h"
+#include "wanco.h"
+#include <cstdio>
+#include <cstdlib>
+#include <optional>
+#include <string>
+
+namespace wanco {
+
+// Maximum distance between a stackmap record and the return address of the
+// call following it.
+constexpr uint64_t MAX_CALL_INSN_SIZE = 8;
+
+const char *trap_message(TrapKind kind) {
+  switch (kind) {
+  case TrapKind::MEMORY_OUT_OF_BOUNDS:
+    return "memory access out of bounds";
+  case TrapKind::INTEGER_DIVIDE_BY_ZERO:
+    return "integer divide by zero";
+  case TrapKind::INTEGER_OVERFLOW:
+    return "integer overflow";
+  case TrapKind::INVALID_CONVERSION_TO_INTEGER:
+    return "invalid conversion to integer";
+  case TrapKind::UNREACHABLE:
+    return "unreachable executed";
+  case TrapKind::INDIRECT_CALL_TYPE_MISMATCH:
+    return "indirect call type mismatch";
+  case TrapKind::UNDEFINED_ELEMENT:
+    return "undefined element";
+  case TrapKind::UNINITIALIZED_ELEMENT:
+    return "uninitialized element";
+  case TrapKind::TABLE_OUT_OF_BOUNDS:
+    return "out of bounds table access";
+  case TrapKind::UNALIGNED_ATOMIC:
+    return "unaligned atomic";
+  case TrapKind::NULL_REFERENCE:
+    return "null reference";
+  case TrapKind::ATOMIC_WAIT_NON_SHARED:
+    return "atomic wait on non-shared memory";
+  }
+  return "unknown trap";
+}
+
+// Find the wasm instruction which corresponds to the native frame.
+// Trap sites and call sites (with checkpoint enabled) are recorded in the
+// stackmap just before the call instruction.
+static auto lookup_insn(int32_t func_index, uint64_t pc_offset)
+    -> std::optional<int32_t> {
+  std::optional<int32_t> insn;
+  uint64_t nearest = MAX_CALL_INSN_SIZE + 1;
+  for (const auto &record : g_stackmap.stkmap_records) {
+    auto loc = WasmLocation::from_stackmap_id(record->patchpoint_id);
+    if (loc.get_func() != func_index ||
+        record->instruction_offset > pc_offset) {
+      continue;
+    }
+    uint64_t distance = pc_offset - record->instruction_offset;
+    if (distance < nearest) {
+      nearest = distance;
+      insn = loc.get_insn();
+    }
+  }
+  // -1 represents a function entry
+  if (insn == -1) {
+    return std::nullopt;
+  }
+  return insn;
+}
+
+static auto print_wasm_backtrace() -> void {
+  const auto native_trace = get_stack_trace();
+
+  fprintf(stderr, "Wasm backtrace:\n");
+  int depth = 0;
+  // the innermost frame is at the back
+  for (auto it = native_trace.rbegin(); it != native_trace.rend(); it++) {
+    const auto &func_name = it->function_name;
+    if (!func_name.starts_with("func_")) {
+      continue;
+    }
+    int32_t const func_index = std::stoi(func_name.substr(5));
+
+    std::string line = "  #" + std::to_string(depth) + ": " + func_name;
+    if (0 <= func_index && func_index < NUM_FUNCTIONS &&
+        FUNCTION_NAMES[func_index] != nullptr) {
+      line += " (" + std::string(FUNCTION_NAMES[func_index]) + ")";
+    }
+    auto insn = lookup_insn(func_index, it->pc_offset);
+    if (insn.has_value()) {
+      line += ", insn " + std::to_string(insn.value());
+    }
+    fprintf(stderr, "%s\n", line.c_str());
+    depth++;
+  }
+}
+
+void trap(TrapKind kind) {
+  fprintf(stderr, "Error: wasm trap: %s\n", trap_message(kind));
+  print_wasm_backtrace();
+  exit(TRAP_EXIT_STATUS_BASE + static_cast<int32_t>(kind));
+}
+
+} // namespace wanco
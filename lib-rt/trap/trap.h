@@ -0,0 +1,31 @@
+#pragma once
+#include <cstdint>
+
+namespace wanco {
+
+// Kinds of wasm traps.
+// This must be kept in sync with `TrapKind` in wanco/src/compile/trap.rs.
+enum class TrapKind : int32_t {
+  MEMORY_OUT_OF_BOUNDS = 0,
+  INTEGER_DIVIDE_BY_ZERO = 1,
+  INTEGER_OVERFLOW = 2,
+  INVALID_CONVERSION_TO_INTEGER = 3,
+  UNREACHABLE = 4,
+  INDIRECT_CALL_TYPE_MISMATCH = 5,
+  UNDEFINED_ELEMENT = 6,
+  UNINITIALIZED_ELEMENT = 7,
+  TABLE_OUT_OF_BOUNDS = 8,
+  UNALIGNED_ATOMIC = 9,
+  NULL_REFERENCE = 10,
+  ATOMIC_WAIT_NON_SHARED = 11,
+};
+
+// The process exits with TRAP_EXIT_STATUS_BASE + kind when a trap occurs.
+constexpr int TRAP_EXIT_STATUS_BASE = 100;
+
+const char *trap_message(TrapKind kind);
+
+// Report a wasm trap with a wasm backtrace and terminate the execution.
+[[noreturn]] void trap(TrapKind kind);
+
+} // namespace wanco
@@ -1,14 +1,22 @@
 #include "aot.h"
 #include "chkpt/chkpt.h"
+#include "exception/exception.h"
+#include "thread/thread.h"
+#include "trap/trap.h"
 #include "wanco.h"
+#include <algorithm>
 #include <chrono>
 #include <csignal>
+#include <cstdlib>
 #include <cstdio>
+#include <cstring>
 #include <execinfo.h>
+#include <mutex>
 #include <string>
 #include <string_view>
 #include <sys/mman.h>
 #include <ucontext.h>
+#include <unistd.h>
 
 // global instancce of execution environment
 ExecEnv exec_env;
@@ -22,8 +30,25 @@ uint64_t RESTORE_START_TIME = 0;
 Checkpoint chkpt;
 // global instance of stackmap info
 stackmap::Stackmap g_stackmap;
-// global instance of linear memory
+// contents of the linear memory loaded from a checkpoint until it is placed
 std::string linear_memory;
+// contents of the other memories loaded from a checkpoint (see EXTRA_MEMORIES)
+std::vector<std::string> extra_memories;
+
+// Size of the virtual region reserved for the linear memory when guard pages
+// are used. Any 32-bit address plus a 32-bit static offset falls into it.
+constexpr uint64_t GUARDED_MEMORY_SIZE = (1ULL << 33) + PAGE_SIZE;
+
+// Upper bound of the virtual region reserved for a memory without guard
+// pages, since the maximum of a 64-bit memory exceeds the address space.
+constexpr uint64_t MAX_RESERVED_MEMORY_SIZE = 1ULL << 40;
+
+// Serializes memory.grow from multiple threads
+static std::mutex grow_mutex;
+
+// Maximum number of pages of every memory given by --max-memory
+// (-1 if only the limits declared by the module apply)
+static int64_t memory_cap = -1;
 
 static std::string_view USAGE = R"(WebAssembly AOT executable
 USAGE: <this file> [options] -- [arguments]
@@ -32,54 +57,241 @@ OPTIONS:
   no options: Run the WebAssembly AOT module from the beginning
   --help: Display this message and exit
   --restore <FILE>: Restore an execution from a checkpoint file
+  --max-memory <PAGES>: Limit the number of pages of each memory
 )";
 
+// Whether the address is in the guard pages of the linear memory, i.e. in
+// the part of its reserved region which is not committed.
+static auto is_guard_page_fault(void *addr) -> bool {
+  if (USE_GUARD_PAGES == 0 || exec_env.memory_base == nullptr) {
+    return false;
+  }
+  auto *fault = reinterpret_cast<int8_t *>(addr);
+  auto *committed_end = exec_env.memory_base +
+                        static_cast<uint64_t>(exec_env.memory_size) * PAGE_SIZE;
+  return committed_end <= fault &&
+         fault < exec_env.memory_base + GUARDED_MEMORY_SIZE;
+}
+
+static void write_stderr(const char *msg) {
+  write(STDERR_FILENO, msg, strlen(msg));
+}
+
 // signal handler for debugging
-static void signal_segv_handler(int signum) {
+// Only async-signal-safe functions can be used here, so traps caught by guard
+// pages are reported without a wasm backtrace.
+static void signal_segv_handler(int signum, siginfo_t *info, void * /*ctx*/) {
   void *array[10];
   size_t size = 0;
   ASSERT(signum == SIGSEGV && "Unexpected signal");
 
+  // faults in the guard pages are out-of-bounds accesses by the module
+  if (is_guard_page_fault(info->si_addr)) {
+    const TrapKind kind = TrapKind::MEMORY_OUT_OF_BOUNDS;
+    write_stderr("Error: wasm trap: ");
+    write_stderr(trap_message(kind));
+    write_stderr("\n");
+    _exit(TRAP_EXIT_STATUS_BASE + static_cast<int32_t>(kind));
+  }
+
   // get void*'s for all entries on the stack
   size = backtrace(array, 10);
 
   // print out all the frames to stderr
-  fprintf(stderr, "Error: segmentation fault\n");
+  write_stderr("Error: segmentation fault\n");
   backtrace_symbols_fd(array, size, STDERR_FILENO);
-  exit(1);
+  _exit(1);
 }
 
 static void signal_chkpt_handler(int signum) {
   ASSERT(signum == SIGCHKPT && "Unexpected signal");
+  if (num_running_threads() != 0) {
+    // only async-signal-safe functions can be used here
+    const char msg[] = "Warning: checkpoint of multiple threads is not "
+                       "supported, ignoring the request\n";
+    write(STDERR_FILENO, msg, sizeof(msg) - 1);
+    return;
+  }
   exec_env.migration_state = MigrationState::STATE_CHECKPOINT_START;
 }
 
 struct Config {
   std::string restore_file;
+  int64_t max_memory = -1;
 } __attribute__((aligned(32)));
 
-std::string allocate_memory(int32_t num_pages) {
-  uint64_t const num_bytes = num_pages * PAGE_SIZE;
-  std::string new_memory(num_bytes, 0);
-  return new_memory;
+// Size of the virtual region reserved for the memory.
+// Every memory is reserved up to its maximum so that its base never moves.
+static auto reserved_size(int32_t mem) -> uint64_t {
+  if (mem == 0 && USE_GUARD_PAGES != 0) {
+    return GUARDED_MEMORY_SIZE;
+  }
+  int64_t max_pages = mem == 0 ? MAX_MEMORY_SIZE : EXTRA_MEMORIES[mem - 1].max;
+  if (memory_cap >= 0 && memory_cap < max_pages) {
+    max_pages = memory_cap;
+  }
+  // reserve at least a page since mmap rejects an empty region
+  uint64_t const pages = std::max<int64_t>(max_pages, 1);
+  return std::min(pages, MAX_RESERVED_MEMORY_SIZE / PAGE_SIZE) * PAGE_SIZE;
+}
+
+// Reserve the whole region of the memory and make the first pages accessible.
+static auto reserve_memory(int32_t mem, const std::string &init,
+                           int64_t num_pages) -> int8_t * {
+  uint64_t const num_bytes = static_cast<uint64_t>(num_pages) * PAGE_SIZE;
+  uint64_t const reserved = reserved_size(mem);
+  if (num_bytes > reserved) {
+    Fatal() << "Memory " << mem << " is too large to reserve" << '\n';
+    exit(1);
+  }
+  void *base = mmap(nullptr, reserved, PROT_NONE,
+                    MAP_PRIVATE | MAP_ANONYMOUS | MAP_NORESERVE, -1, 0);
+  if (base == MAP_FAILED) {
+    Fatal() << "Failed to reserve memory " << mem << '\n';
+    exit(1);
+  }
+  if (num_bytes != 0 &&
+      mprotect(base, num_bytes, PROT_READ | PROT_WRITE) != 0) {
+    Fatal() << "Failed to commit memory " << mem << '\n';
+    exit(1);
+  }
+  memcpy(base, init.data(), init.size());
+  return reinterpret_cast<int8_t *>(base);
 }
 
-auto extend_memory(ExecEnv *exec_env, int32_t inc_pages) -> int32_t {
-  ASSERT(inc_pages >= 0);
-  int32_t old_size = exec_env->memory_size;
-  int32_t new_size = old_size + inc_pages;
+// Check if a memory can grow by inc_pages within its maximum and the cap.
+static auto within_limit(int64_t old_size, int64_t inc_pages, int64_t max)
+    -> bool {
+  if (memory_cap >= 0 && memory_cap < max) {
+    max = memory_cap;
+  }
+  // compare without computing old_size + inc_pages, which may overflow
+  return old_size <= max && inc_pages <= max - old_size;
+}
+
+// Returns the old size in pages, or -1 on failure.
+auto extend_memory(ExecEnv *exec_env, int32_t mem, int64_t inc_pages)
+    -> int64_t {
+  // a delta of a 64-bit memory is unsigned
+  if (inc_pages < 0) {
+    return -1;
+  }
+  std::lock_guard<std::mutex> lock(grow_mutex);
+  int8_t *base = exec_env->memory_base;
+  int64_t *size = &exec_env->memory_size;
+  int64_t max = MAX_MEMORY_SIZE;
+  if (mem != 0) {
+    base = EXTRA_MEMORIES[mem - 1].base;
+    size = &EXTRA_MEMORIES[mem - 1].size;
+    max = EXTRA_MEMORIES[mem - 1].max;
+  }
+  int64_t old_size = *size;
+  if (!within_limit(old_size, inc_pages, max)) {
+    return -1;
+  }
 
   if (inc_pages == 0) {
     return old_size;
   }
 
-  linear_memory.resize(new_size * PAGE_SIZE, 0);
-
-  exec_env->memory_base = reinterpret_cast<int8_t *>(linear_memory.data());
-  exec_env->memory_size = new_size;
+  // the memory never moves, so just commit the new pages
+  if (old_size + inc_pages >
+      static_cast<int64_t>(reserved_size(mem) / PAGE_SIZE)) {
+    return -1;
+  }
+  if (mprotect(base + static_cast<uint64_t>(old_size) * PAGE_SIZE,
+               static_cast<uint64_t>(inc_pages) * PAGE_SIZE,
+               PROT_READ | PROT_WRITE) != 0) {
+    return -1;
+  }
+  // other threads read the size without the lock
+  __atomic_store_n(size, old_size + inc_pages, __ATOMIC_SEQ_CST);
   return old_size;
 }
 
+// The initial sizes must not exceed the cap given by --max-memory.
+static auto check_initial_sizes() -> void {
+  if (memory_cap < 0) {
+    return;
+  }
+  bool exceeds = INIT_MEMORY_SIZE > memory_cap;
+  for (int32_t i = 0; i < NUM_EXTRA_MEMORIES; i++) {
+    exceeds = exceeds || EXTRA_MEMORIES[i].size > memory_cap;
+  }
+  if (exceeds) {
+    Fatal() << "The initial memory size exceeds --max-memory" << '\n';
+    exit(1);
+  }
+}
+
+// The host must define imported tables within the limits of their imports.
+// The tables defined by the module always satisfy them.
+static auto check_table_limits() -> bool {
+  for (int32_t i = 0; i < NUM_TABLES; i++) {
+    const TableLimits &limits = TABLE_LIMITS[i];
+    int64_t size = TABLES[i]->size;
+    if (size < limits.min || (limits.max >= 0 && size > limits.max)) {
+      Fatal() << "Table " << i << " has " << size
+              << " elements, which is out of the limits of its import"
+              << '\n';
+      return false;
+    }
+  }
+  return true;
+}
+
+// Reserve the memories other than memory 0. On restore, extra_memories holds
+// their contents, which are moved into the reserved regions.
+static auto place_extra_memories(bool restore) -> void {
+  if (restore) {
+    ASSERT(extra_memories.size() ==
+               static_cast<size_t>(NUM_EXTRA_MEMORIES) &&
+           "Number of memories mismatch");
+  }
+  for (int32_t i = 0; i < NUM_EXTRA_MEMORIES; i++) {
+    Memory &memory = EXTRA_MEMORIES[i];
+    if (restore) {
+      memory.size = static_cast<int64_t>(extra_memories[i].size() / PAGE_SIZE);
+      memory.base = reserve_memory(i + 1, extra_memories[i], memory.size);
+    } else {
+      memory.base = reserve_memory(i + 1, "", memory.size);
+    }
+  }
+  extra_memories.clear();
+}
+
+static auto release_extra_memories() -> void {
+  for (int32_t i = 0; i < NUM_EXTRA_MEMORIES; i++) {
+    if (EXTRA_MEMORIES[i].base != nullptr) {
+      munmap(EXTRA_MEMORIES[i].base, reserved_size(i + 1));
+    }
+    EXTRA_MEMORIES[i].base = nullptr;
+    EXTRA_MEMORIES[i].size = 0;
+  }
+}
+
+// Record the contents of the memories other than memory 0.
+void store_extra_memories() {
+  chkpt.memories.clear();
+  for (int32_t i = 0; i < NUM_EXTRA_MEMORIES; i++) {
+    chkpt.memories.emplace_back(
+        reinterpret_cast<char *>(EXTRA_MEMORIES[i].base),
+        static_cast<uint64_t>(EXTRA_MEMORIES[i].size) * PAGE_SIZE);
+  }
+}
+
+// Record which passive segments have been dropped.
+void store_dropped_segments() {
+  chkpt.data_dropped.assign(NUM_DATA_SEGMENTS, false);
+  for (int32_t i = 0; i < NUM_DATA_SEGMENTS; i++) {
+    chkpt.data_dropped[i] = DATA_SEGMENT_SIZES[i] == 0;
+  }
+  chkpt.elem_dropped.assign(NUM_ELEM_SEGMENTS, false);
+  for (int32_t i = 0; i < NUM_ELEM_SEGMENTS; i++) {
+    chkpt.elem_dropped[i] = ELEM_SEGMENT_SIZES[i] == 0;
+  }
+}
+
 static auto parse_from_args(int argc, char **argv) -> Config {
   Config config;
   for (int i = 1; i < argc; i++) {
@@ -90,6 +302,18 @@ static auto parse_from_args(int argc, char **argv) -> Config {
       }
       config.restore_file = argv[i + 1];
       i++;
+    } else if (std::string(argv[i]) == "--max-memory") {
+      if (i + 1 >= argc) {
+        Fatal() << "Error: Missing argument for --max-memory" << '\n';
+        exit(1);
+      }
+      char *end = nullptr;
+      config.max_memory = std::strtoll(argv[i + 1], &end, 10);
+      if (*end != '\0' || config.max_memory < 0) {
+        Fatal() << "Error: Invalid number of pages: " << argv[i + 1] << '\n';
+        exit(1);
+      }
+      i++;
     } else if (std::string(argv[i]) == "--help") {
       std::cerr << USAGE;
       exit(0);
@@ -114,22 +338,50 @@ static auto prepare_checkpoint() -> void {
   }
 }
 
-static auto wanco_main(int argc, char **argv) -> int {
-  signal(SIGSEGV, signal_segv_handler);
+static auto register_segv_handler() -> void {
+  // backtrace loads libgcc on the first call, which is not async-signal-safe
+  void *array[1];
+  backtrace(array, 1);
+
+  struct sigaction segv_action {};
+  segv_action.sa_sigaction = signal_segv_handler;
+  segv_action.sa_flags = SA_SIGINFO;
+  sigemptyset(&segv_action.sa_mask);
+  sigaction(SIGSEGV, &segv_action, nullptr);
+}
+
+// Reserve the linear memory and move the contents restored from a checkpoint
+// (if any) into it.
+static auto place_linear_memory() -> void {
+  exec_env.memory_base =
+      reserve_memory(0, linear_memory, exec_env.memory_size);
+  linear_memory.clear();
+  linear_memory.shrink_to_fit();
+}
+
+static auto release_linear_memory() -> void {
+  munmap(exec_env.memory_base, reserved_size(0));
+}
+
+auto wanco_main(int argc, char **argv) -> int {
+  register_segv_handler();
 
   // Parse CLI arguments
   Config const config = parse_from_args(argc, argv);
 
   prepare_checkpoint();
+  memory_cap = config.max_memory;
 
   if (config.restore_file.empty()) {
-    // Allocate memory
-    int const memory_size = INIT_MEMORY_SIZE;
-    linear_memory = allocate_memory(memory_size);
+    check_initial_sizes();
+    if (!check_table_limits()) {
+      return 1;
+    }
     // Initialize exec_env
+    // The memories are reserved below
     exec_env = ExecEnv{
-        .memory_base = reinterpret_cast<int8_t *>(linear_memory.data()),
-        .memory_size = memory_size,
+        .memory_base = nullptr,
+        .memory_size = INIT_MEMORY_SIZE,
         .migration_state = MigrationState::STATE_NONE,
         .argc = argc,
         .argv = reinterpret_cast<uint8_t **>(argv),
@@ -160,19 +412,49 @@ static auto wanco_main(int argc, char **argv) -> int {
     Info() << "- value stack: " << chkpt.restore_stack.size() << " values"
            << '\n';
 
+    // Passive segments dropped before the checkpoint stay dropped
+    ASSERT(chkpt.data_dropped.size() ==
+               static_cast<size_t>(NUM_DATA_SEGMENTS) &&
+           "Number of data segments mismatch");
+    for (int32_t i = 0; i < NUM_DATA_SEGMENTS; i++) {
+      if (chkpt.data_dropped[i]) {
+        DATA_SEGMENT_SIZES[i] = 0;
+      }
+    }
+    ASSERT(chkpt.elem_dropped.size() ==
+               static_cast<size_t>(NUM_ELEM_SEGMENTS) &&
+           "Number of element segments mismatch");
+    for (int32_t i = 0; i < NUM_ELEM_SEGMENTS; i++) {
+      if (chkpt.elem_dropped[i]) {
+        ELEM_SEGMENT_SIZES[i] = 0;
+      }
+    }
+    // Handles of caught exceptions in locals refer to the saved table
+    restore_exceptions(chkpt);
+    extra_memories = std::move(chkpt.memories);
+    chkpt.memories.clear();
+
     // Initialize exec_env
     exec_env = ExecEnv{
-        .memory_base = reinterpret_cast<int8_t *>(linear_memory.data()),
+        .memory_base = nullptr,
         .memory_size = chkpt.memory_size,
         .migration_state = MigrationState::STATE_RESTORE,
         .argc = argc,
         .argv = reinterpret_cast<uint8_t **>(argv),
     };
   }
+  place_linear_memory();
+  place_extra_memories(!config.restore_file.empty());
+
   // Register signal handler
   signal(SIGCHKPT, signal_chkpt_handler);
 
-  aot_main(&exec_env);
+  try {
+    aot_main(&exec_env);
+  } catch (const WasmException &) {
+    Fatal() << "Uncaught wasm exception" << '\n';
+    exit(1);
+  }
 
   CHKPT_START_TIME = std::chrono::duration_cast<std::chrono::microseconds>(
                          std::chrono::system_clock::now().time_since_epoch())
@@ -180,6 +462,9 @@ static auto wanco_main(int argc, char **argv) -> int {
 
   if (exec_env.migration_state == MigrationState::STATE_CHECKPOINT_CONTINUE) {
     chkpt.memory_size = exec_env.memory_size;
+    store_extra_memories();
+    store_dropped_segments();
+    store_exceptions(chkpt);
 
     // write snapshot
     std::ofstream ofs("checkpoint.pb");
@@ -198,12 +483,113 @@ static auto wanco_main(int argc, char **argv) -> int {
   }
 
   // cleanup
-  munmap(exec_env.memory_base, exec_env.memory_size * PAGE_SIZE);
+  release_linear_memory();
+  release_extra_memories();
   return 0;
 }
 
-} // namespace wanco
+// Library API (see the C header generated by `wanco --emit=staticlib|sharedlib`)
+
+// Globals and tables of the module are in static storage, so it is
+// instantiated at most once at a time. aot_main resets the globals, and
+// wanco_teardown resets the tables, segments and memory sizes to their
+// initial state.
+static bool instantiated = false;
+
+// A table and its elements before the first instantiation
+struct InitialTable {
+  Table table;
+  std::vector<int32_t> elements;
+};
+static std::vector<InitialTable> initial_tables;
+static std::vector<int32_t> initial_data_segment_sizes;
+static std::vector<int32_t> initial_elem_segment_sizes;
+static std::vector<int64_t> initial_extra_memory_sizes;
+static bool initial_state_saved = false;
+
+static void save_initial_state() {
+  for (int32_t i = 0; i < NUM_TABLES; i++) {
+    const Table *table = TABLES[i];
+    initial_tables.push_back(InitialTable{
+        .table = *table,
+        .elements = std::vector<int32_t>(table->elements,
+                                         table->elements + table->size),
+    });
+  }
+  initial_data_segment_sizes.assign(DATA_SEGMENT_SIZES,
+                                    DATA_SEGMENT_SIZES + NUM_DATA_SEGMENTS);
+  initial_elem_segment_sizes.assign(ELEM_SEGMENT_SIZES,
+                                    ELEM_SEGMENT_SIZES + NUM_ELEM_SEGMENTS);
+  for (int32_t i = 0; i < NUM_EXTRA_MEMORIES; i++) {
+    initial_extra_memory_sizes.push_back(EXTRA_MEMORIES[i].size);
+  }
+}
 
-auto main(int argc, char **argv) -> int {
-  return wanco::wanco_main(argc, argv);
+static void reset_initial_state() {
+  for (int32_t i = 0; i < NUM_TABLES; i++) {
+    // The elements grown by table_grow are left in the storage of lib-rt
+    const auto &initial = initial_tables[i];
+    *TABLES[i] = initial.table;
+    std::copy(initial.elements.begin(), initial.elements.end(),
+              TABLES[i]->elements);
+  }
+  std::copy(initial_data_segment_sizes.begin(),
+            initial_data_segment_sizes.end(), DATA_SEGMENT_SIZES);
+  std::copy(initial_elem_segment_sizes.begin(),
+            initial_elem_segment_sizes.end(), ELEM_SEGMENT_SIZES);
+  // The memories are allocated with their initial sizes
+  for (int32_t i = 0; i < NUM_EXTRA_MEMORIES; i++) {
+    EXTRA_MEMORIES[i].size = initial_extra_memory_sizes[i];
+  }
 }
+
+extern "C" void wanco_teardown(ExecEnv *env);
+
+extern "C" auto wanco_init(int argc, char **argv) -> ExecEnv * {
+  if (instantiated) {
+    Fatal() << "The module has already been instantiated" << '\n';
+    return nullptr;
+  }
+  if (!check_table_limits()) {
+    return nullptr;
+  }
+  if (!initial_state_saved) {
+    save_initial_state();
+    initial_state_saved = true;
+  }
+  instantiated = true;
+  register_segv_handler();
+
+  exec_env = ExecEnv{
+      .memory_base = nullptr,
+      .memory_size = INIT_MEMORY_SIZE,
+      .migration_state = MigrationState::STATE_NONE,
+      .argc = argc,
+      .argv = reinterpret_cast<uint8_t **>(argv),
+  };
+  place_linear_memory();
+  place_extra_memories(false);
+
+  // Run the start section and _initialize. aot_main of libraries does not
+  // call _start, which the host calls through its export.
+  try {
+    aot_main(&exec_env);
+  } catch (const WasmException &) {
+    Fatal() << "Uncaught wasm exception" << '\n';
+    wanco_teardown(&exec_env);
+    return nullptr;
+  }
+  return &exec_env;
+}
+
+extern "C" void wanco_teardown(ExecEnv *env) {
+  ASSERT(env == &exec_env && "Invalid exec_env");
+  release_linear_memory();
+  release_extra_memories();
+  exec_env.memory_base = nullptr;
+  exec_env.memory_size = 0;
+  reset_initial_state();
+  instantiated = false;
+}
+
+} // namespace wanco
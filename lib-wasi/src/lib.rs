@@ -17,7 +17,7 @@ static RUNTIME: OnceLock<Runtime> = OnceLock::new();
 #[repr(C)]
 pub(crate) struct ExecEnv {
     memory: *mut u8,
-    memory_size: i32,
+    memory_size: i64,
     migration_state: i32,
     argc: i32,
     argv: *mut *mut c_char,
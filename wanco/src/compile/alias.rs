@@ -0,0 +1,86 @@
+use inkwell::values::{InstructionValue, MetadataValue};
+
+use crate::context::Context;
+
+/// Attach the metadata of an access to a memory.
+/// Memories never overlap each other, ExecEnv or EXTRA_MEMORIES.
+pub(super) fn set_memory_access_metadata<'a>(
+    ctx: &Context<'a, '_>,
+    instruction: InstructionValue<'a>,
+    mem: u32,
+) {
+    let name = format!("memory {}", mem);
+    let noalias: Vec<_> = (0..ctx.memories.len() as u32)
+        .filter(|other| *other != mem)
+        .map(|other| alias_scope(ctx, &format!("memory {}", other)))
+        .chain([alias_scope(ctx, "memory state")])
+        .collect();
+    set_metadata(ctx, instruction, &name, &noalias);
+}
+
+/// Attach the metadata of an access to the base or the size of a memory in ExecEnv or EXTRA_MEMORIES.
+pub(super) fn set_memory_state_metadata<'a>(
+    ctx: &Context<'a, '_>,
+    instruction: InstructionValue<'a>,
+) {
+    let noalias: Vec<_> = (0..ctx.memories.len() as u32)
+        .map(|mem| alias_scope(ctx, &format!("memory {}", mem)))
+        .collect();
+    set_metadata(ctx, instruction, "memory state", &noalias);
+}
+
+/// Mark a load as invariant, which is the case for the bases of memories since they never move.
+pub(super) fn set_invariant_load_metadata<'a>(
+    ctx: &Context<'a, '_>,
+    instruction: InstructionValue<'a>,
+) {
+    let kind_id = ctx.ictx.get_kind_id("invariant.load");
+    instruction
+        .set_metadata(ctx.ictx.metadata_node(&[]), kind_id)
+        .expect("should set metadata");
+}
+
+/// Attach the TBAA type and the alias scope named `name`, which does not alias the scopes in `noalias`.
+fn set_metadata<'a>(
+    ctx: &Context<'a, '_>,
+    instruction: InstructionValue<'a>,
+    name: &str,
+    noalias: &[MetadataValue<'a>],
+) {
+    let i64_type = ctx.inkwell_types.i64_type;
+    let tbaa_root = ctx
+        .ictx
+        .metadata_node(&[ctx.ictx.metadata_string("wanco TBAA").into()]);
+    let tbaa_type = ctx.ictx.metadata_node(&[
+        ctx.ictx.metadata_string(name).into(),
+        tbaa_root.into(),
+        i64_type.const_zero().into(),
+    ]);
+    let tbaa_tag = ctx.ictx.metadata_node(&[
+        tbaa_type.into(),
+        tbaa_type.into(),
+        i64_type.const_zero().into(),
+    ]);
+    let scopes = ctx.ictx.metadata_node(&[alias_scope(ctx, name).into()]);
+    let noalias: Vec<_> = noalias.iter().map(|scope| (*scope).into()).collect();
+    let noalias = ctx.ictx.metadata_node(&noalias);
+
+    for (kind, node) in [
+        ("tbaa", tbaa_tag),
+        ("alias.scope", scopes),
+        ("noalias", noalias),
+    ] {
+        instruction
+            .set_metadata(node, ctx.ictx.get_kind_id(kind))
+            .expect("should set metadata");
+    }
+}
+
+/// Get the alias scope named `name` in the domain of wanco.
+fn alias_scope<'a>(ctx: &Context<'a, '_>, name: &str) -> MetadataValue<'a> {
+    let domain = ctx
+        .ictx
+        .metadata_node(&[ctx.ictx.metadata_string("wanco").into()]);
+    ctx.ictx
+        .metadata_node(&[ctx.ictx.metadata_string(name).into(), domain.into()])
+}
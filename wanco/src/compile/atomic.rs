@@ -0,0 +1,505 @@
+use anyhow::{anyhow, Result};
+use inkwell::{
+    types::{BasicType, IntType},
+    values::{BasicValue, IntValue, PointerValue},
+    AtomicOrdering, AtomicRMWBinOp, IntPredicate,
+};
+use wasmparser::{MemArg, Operator};
+
+use crate::{
+    compile::{
+        alias::set_memory_access_metadata,
+        compile_function::resolve_memarg_pointer,
+        control::UnreachableReason,
+        trap::{gen_trap, gen_trap_if, TrapKind},
+    },
+    context::Context,
+};
+
+/// Compile the instructions of the threads proposal.
+/// Returns false if the operator is not an atomic instruction.
+pub(super) fn compile_op_atomic<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    op: &Operator,
+) -> Result<bool> {
+    let i8_type = ctx.inkwell_types.i8_type;
+    let i16_type = ctx.inkwell_types.i16_type;
+    let i32_type = ctx.inkwell_types.i32_type;
+    let i64_type = ctx.inkwell_types.i64_type;
+
+    match op {
+        Operator::MemoryAtomicNotify { memarg } => {
+            gen_atomic_notify(ctx, exec_env_ptr, memarg)?;
+        }
+        Operator::MemoryAtomicWait32 { memarg } => {
+            gen_atomic_wait(ctx, exec_env_ptr, memarg, i32_type)?;
+        }
+        Operator::MemoryAtomicWait64 { memarg } => {
+            gen_atomic_wait(ctx, exec_env_ptr, memarg, i64_type)?;
+        }
+        Operator::AtomicFence => {
+            ctx.builder
+                .build_fence(AtomicOrdering::SequentiallyConsistent, 0, "")?;
+        }
+        /******************************
+          Atomic loads and stores
+        ******************************/
+        Operator::I32AtomicLoad { memarg } => {
+            gen_atomic_load(ctx, exec_env_ptr, memarg, i32_type, i32_type)?;
+        }
+        Operator::I64AtomicLoad { memarg } => {
+            gen_atomic_load(ctx, exec_env_ptr, memarg, i64_type, i64_type)?;
+        }
+        Operator::I32AtomicLoad8U { memarg } => {
+            gen_atomic_load(ctx, exec_env_ptr, memarg, i32_type, i8_type)?;
+        }
+        Operator::I32AtomicLoad16U { memarg } => {
+            gen_atomic_load(ctx, exec_env_ptr, memarg, i32_type, i16_type)?;
+        }
+        Operator::I64AtomicLoad8U { memarg } => {
+            gen_atomic_load(ctx, exec_env_ptr, memarg, i64_type, i8_type)?;
+        }
+        Operator::I64AtomicLoad16U { memarg } => {
+            gen_atomic_load(ctx, exec_env_ptr, memarg, i64_type, i16_type)?;
+        }
+        Operator::I64AtomicLoad32U { memarg } => {
+            gen_atomic_load(ctx, exec_env_ptr, memarg, i64_type, i32_type)?;
+        }
+        Operator::I32AtomicStore { memarg } | Operator::I64AtomicStore { memarg } => {
+            gen_atomic_store(ctx, exec_env_ptr, memarg, None)?;
+        }
+        Operator::I32AtomicStore8 { memarg } | Operator::I64AtomicStore8 { memarg } => {
+            gen_atomic_store(ctx, exec_env_ptr, memarg, Some(i8_type))?;
+        }
+        Operator::I32AtomicStore16 { memarg } | Operator::I64AtomicStore16 { memarg } => {
+            gen_atomic_store(ctx, exec_env_ptr, memarg, Some(i16_type))?;
+        }
+        Operator::I64AtomicStore32 { memarg } => {
+            gen_atomic_store(ctx, exec_env_ptr, memarg, Some(i32_type))?;
+        }
+        /******************************
+          Atomic read-modify-write
+        ******************************/
+        Operator::I32AtomicRmwAdd { memarg } | Operator::I64AtomicRmwAdd { memarg } => {
+            gen_atomic_rmw(ctx, exec_env_ptr, memarg, AtomicRMWBinOp::Add, None)?;
+        }
+        Operator::I32AtomicRmw8AddU { memarg } | Operator::I64AtomicRmw8AddU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::Add,
+                Some(i8_type),
+            )?;
+        }
+        Operator::I32AtomicRmw16AddU { memarg } | Operator::I64AtomicRmw16AddU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::Add,
+                Some(i16_type),
+            )?;
+        }
+        Operator::I64AtomicRmw32AddU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::Add,
+                Some(i32_type),
+            )?;
+        }
+        Operator::I32AtomicRmwSub { memarg } | Operator::I64AtomicRmwSub { memarg } => {
+            gen_atomic_rmw(ctx, exec_env_ptr, memarg, AtomicRMWBinOp::Sub, None)?;
+        }
+        Operator::I32AtomicRmw8SubU { memarg } | Operator::I64AtomicRmw8SubU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::Sub,
+                Some(i8_type),
+            )?;
+        }
+        Operator::I32AtomicRmw16SubU { memarg } | Operator::I64AtomicRmw16SubU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::Sub,
+                Some(i16_type),
+            )?;
+        }
+        Operator::I64AtomicRmw32SubU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::Sub,
+                Some(i32_type),
+            )?;
+        }
+        Operator::I32AtomicRmwAnd { memarg } | Operator::I64AtomicRmwAnd { memarg } => {
+            gen_atomic_rmw(ctx, exec_env_ptr, memarg, AtomicRMWBinOp::And, None)?;
+        }
+        Operator::I32AtomicRmw8AndU { memarg } | Operator::I64AtomicRmw8AndU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::And,
+                Some(i8_type),
+            )?;
+        }
+        Operator::I32AtomicRmw16AndU { memarg } | Operator::I64AtomicRmw16AndU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::And,
+                Some(i16_type),
+            )?;
+        }
+        Operator::I64AtomicRmw32AndU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::And,
+                Some(i32_type),
+            )?;
+        }
+        Operator::I32AtomicRmwOr { memarg } | Operator::I64AtomicRmwOr { memarg } => {
+            gen_atomic_rmw(ctx, exec_env_ptr, memarg, AtomicRMWBinOp::Or, None)?;
+        }
+        Operator::I32AtomicRmw8OrU { memarg } | Operator::I64AtomicRmw8OrU { memarg } => {
+            gen_atomic_rmw(ctx, exec_env_ptr, memarg, AtomicRMWBinOp::Or, Some(i8_type))?;
+        }
+        Operator::I32AtomicRmw16OrU { memarg } | Operator::I64AtomicRmw16OrU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::Or,
+                Some(i16_type),
+            )?;
+        }
+        Operator::I64AtomicRmw32OrU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::Or,
+                Some(i32_type),
+            )?;
+        }
+        Operator::I32AtomicRmwXor { memarg } | Operator::I64AtomicRmwXor { memarg } => {
+            gen_atomic_rmw(ctx, exec_env_ptr, memarg, AtomicRMWBinOp::Xor, None)?;
+        }
+        Operator::I32AtomicRmw8XorU { memarg } | Operator::I64AtomicRmw8XorU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::Xor,
+                Some(i8_type),
+            )?;
+        }
+        Operator::I32AtomicRmw16XorU { memarg } | Operator::I64AtomicRmw16XorU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::Xor,
+                Some(i16_type),
+            )?;
+        }
+        Operator::I64AtomicRmw32XorU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::Xor,
+                Some(i32_type),
+            )?;
+        }
+        Operator::I32AtomicRmwXchg { memarg } | Operator::I64AtomicRmwXchg { memarg } => {
+            gen_atomic_rmw(ctx, exec_env_ptr, memarg, AtomicRMWBinOp::Xchg, None)?;
+        }
+        Operator::I32AtomicRmw8XchgU { memarg } | Operator::I64AtomicRmw8XchgU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::Xchg,
+                Some(i8_type),
+            )?;
+        }
+        Operator::I32AtomicRmw16XchgU { memarg } | Operator::I64AtomicRmw16XchgU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::Xchg,
+                Some(i16_type),
+            )?;
+        }
+        Operator::I64AtomicRmw32XchgU { memarg } => {
+            gen_atomic_rmw(
+                ctx,
+                exec_env_ptr,
+                memarg,
+                AtomicRMWBinOp::Xchg,
+                Some(i32_type),
+            )?;
+        }
+        Operator::I32AtomicRmwCmpxchg { memarg } | Operator::I64AtomicRmwCmpxchg { memarg } => {
+            gen_atomic_cmpxchg(ctx, exec_env_ptr, memarg, None)?;
+        }
+        Operator::I32AtomicRmw8CmpxchgU { memarg } | Operator::I64AtomicRmw8CmpxchgU { memarg } => {
+            gen_atomic_cmpxchg(ctx, exec_env_ptr, memarg, Some(i8_type))?;
+        }
+        Operator::I32AtomicRmw16CmpxchgU { memarg }
+        | Operator::I64AtomicRmw16CmpxchgU { memarg } => {
+            gen_atomic_cmpxchg(ctx, exec_env_ptr, memarg, Some(i16_type))?;
+        }
+        Operator::I64AtomicRmw32CmpxchgU { memarg } => {
+            gen_atomic_cmpxchg(ctx, exec_env_ptr, memarg, Some(i32_type))?;
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// Get the pointer accessed by an atomic instruction.
+/// Atomic accesses trap unless the effective address is naturally aligned.
+fn resolve_atomic_pointer<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    address_operand: IntValue<'a>,
+    memarg: &MemArg,
+    access_type: IntType<'a>,
+) -> Result<PointerValue<'a>> {
+    let ptr = resolve_memarg_pointer(
+        ctx,
+        exec_env_ptr,
+        address_operand,
+        memarg,
+        access_type.as_basic_type_enum(),
+    )?;
+
+    // The linear memory is aligned to at least 8 bytes, so the host address has the same alignment
+    let ptr_int = ctx
+        .builder
+        .build_ptr_to_int(ptr, ctx.inkwell_types.i64_type, "")?;
+    let align_mask = ctx
+        .inkwell_types
+        .i64_type
+        .const_int(access_type.get_bit_width() as u64 / 8 - 1, false);
+    let misaligned_bits = ctx.builder.build_and(ptr_int, align_mask, "")?;
+    let is_misaligned = ctx.builder.build_int_compare(
+        IntPredicate::NE,
+        misaligned_bits,
+        ctx.inkwell_types.i64_type.const_zero(),
+        "is_misaligned",
+    )?;
+    gen_trap_if(ctx, exec_env_ptr, is_misaligned, TrapKind::UnalignedAtomic)?;
+    Ok(ptr)
+}
+
+fn gen_atomic_load<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    memarg: &MemArg,
+    result_type: IntType<'a>,
+    load_type: IntType<'a>,
+) -> Result<()> {
+    let address_operand = ctx.pop()?.into_int_value();
+    let ptr = resolve_atomic_pointer(ctx, exec_env_ptr, address_operand, memarg, load_type)?;
+    let loaded = ctx.builder.build_load(load_type, ptr, "atomic_loaded")?;
+    let inst = loaded
+        .as_instruction_value()
+        .expect("should be instruction");
+    inst.set_atomic_ordering(AtomicOrdering::SequentiallyConsistent)
+        .map_err(|e| anyhow!(e))?;
+    inst.set_alignment(load_type.get_bit_width() / 8)
+        .map_err(|e| anyhow!(e))?;
+    set_memory_access_metadata(ctx, inst, memarg.memory);
+    let result =
+        ctx.builder
+            .build_int_z_extend_or_bit_cast(loaded.into_int_value(), result_type, "")?;
+    ctx.push(result.as_basic_value_enum());
+    Ok(())
+}
+
+/// Store a value atomically, wrapping it to `narrow_type` if given.
+fn gen_atomic_store<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    memarg: &MemArg,
+    narrow_type: Option<IntType<'a>>,
+) -> Result<()> {
+    let value = ctx.pop()?.into_int_value();
+    let address_operand = ctx.pop()?.into_int_value();
+    let value = gen_narrow(ctx, value, narrow_type)?;
+    let ptr = resolve_atomic_pointer(ctx, exec_env_ptr, address_operand, memarg, value.get_type())?;
+    let inst = ctx.builder.build_store(ptr, value)?;
+    inst.set_atomic_ordering(AtomicOrdering::SequentiallyConsistent)
+        .map_err(|e| anyhow!(e))?;
+    inst.set_alignment(value.get_type().get_bit_width() / 8)
+        .map_err(|e| anyhow!(e))?;
+    set_memory_access_metadata(ctx, inst, memarg.memory);
+    Ok(())
+}
+
+fn gen_atomic_rmw<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    memarg: &MemArg,
+    op: AtomicRMWBinOp,
+    narrow_type: Option<IntType<'a>>,
+) -> Result<()> {
+    let value = ctx.pop()?.into_int_value();
+    let address_operand = ctx.pop()?.into_int_value();
+    let result_type = value.get_type();
+    let value = gen_narrow(ctx, value, narrow_type)?;
+    let ptr = resolve_atomic_pointer(ctx, exec_env_ptr, address_operand, memarg, value.get_type())?;
+    let old =
+        ctx.builder
+            .build_atomicrmw(op, ptr, value, AtomicOrdering::SequentiallyConsistent)?;
+    let result = ctx
+        .builder
+        .build_int_z_extend_or_bit_cast(old, result_type, "")?;
+    ctx.push(result.as_basic_value_enum());
+    Ok(())
+}
+
+fn gen_atomic_cmpxchg<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    memarg: &MemArg,
+    narrow_type: Option<IntType<'a>>,
+) -> Result<()> {
+    let replacement = ctx.pop()?.into_int_value();
+    let expected = ctx.pop()?.into_int_value();
+    let address_operand = ctx.pop()?.into_int_value();
+    let result_type = expected.get_type();
+    // The expected value is wrapped as well as the replacement
+    let expected = gen_narrow(ctx, expected, narrow_type)?;
+    let replacement = gen_narrow(ctx, replacement, narrow_type)?;
+    let ptr = resolve_atomic_pointer(
+        ctx,
+        exec_env_ptr,
+        address_operand,
+        memarg,
+        expected.get_type(),
+    )?;
+    let pair = ctx.builder.build_cmpxchg(
+        ptr,
+        expected,
+        replacement,
+        AtomicOrdering::SequentiallyConsistent,
+        AtomicOrdering::SequentiallyConsistent,
+    )?;
+    let old = ctx
+        .builder
+        .build_extract_value(pair, 0, "old")?
+        .into_int_value();
+    let result = ctx
+        .builder
+        .build_int_z_extend_or_bit_cast(old, result_type, "")?;
+    ctx.push(result.as_basic_value_enum());
+    Ok(())
+}
+
+fn gen_narrow<'a>(
+    ctx: &Context<'a, '_>,
+    value: IntValue<'a>,
+    narrow_type: Option<IntType<'a>>,
+) -> Result<IntValue<'a>> {
+    match narrow_type {
+        Some(ty) => Ok(ctx.builder.build_int_truncate(value, ty, "narrow_value")?),
+        None => Ok(value),
+    }
+}
+
+/// Wake up waiters on the address. The number of woken waiters is pushed.
+fn gen_atomic_notify<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    memarg: &MemArg,
+) -> Result<()> {
+    let count = ctx.pop()?.into_int_value();
+    let address_operand = ctx.pop()?.into_int_value();
+    let ptr = resolve_atomic_pointer(
+        ctx,
+        exec_env_ptr,
+        address_operand,
+        memarg,
+        ctx.inkwell_types.i32_type,
+    )?;
+    let woken = ctx
+        .builder
+        .build_call(
+            ctx.fn_memory_atomic_notify
+                .expect("should define memory_atomic_notify"),
+            &[
+                exec_env_ptr.as_basic_value_enum().into(),
+                ptr.into(),
+                count.into(),
+            ],
+            "woken",
+        )?
+        .try_as_basic_value()
+        .left()
+        .expect("should be basic value");
+    ctx.push(woken);
+    Ok(())
+}
+
+/// Wait until notified if the value at the address equals the expected value.
+/// The result is 0 (ok), 1 (not-equal) or 2 (timed-out).
+fn gen_atomic_wait<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    memarg: &MemArg,
+    value_type: IntType<'a>,
+) -> Result<()> {
+    let timeout = ctx.pop()?.into_int_value();
+    let expected = ctx.pop()?.into_int_value();
+    let address_operand = ctx.pop()?.into_int_value();
+    let ptr = resolve_atomic_pointer(ctx, exec_env_ptr, address_operand, memarg, value_type)?;
+    // No other thread can notify a waiter on a non-shared memory
+    if !ctx.memories[memarg.memory as usize].shared {
+        gen_trap(ctx, exec_env_ptr, TrapKind::AtomicWaitNonShared)?;
+        ctx.unreachable_depth += 1;
+        ctx.unreachable_reason = UnreachableReason::Unreachable;
+        return Ok(());
+    }
+    let fn_wait = if value_type == ctx.inkwell_types.i32_type {
+        ctx.fn_memory_atomic_wait32
+            .expect("should define memory_atomic_wait32")
+    } else {
+        ctx.fn_memory_atomic_wait64
+            .expect("should define memory_atomic_wait64")
+    };
+    let result = ctx
+        .builder
+        .build_call(
+            fn_wait,
+            &[
+                exec_env_ptr.as_basic_value_enum().into(),
+                ptr.into(),
+                expected.into(),
+                timeout.into(),
+            ],
+            "wait_result",
+        )?
+        .try_as_basic_value()
+        .left()
+        .expect("should be basic value");
+    ctx.push(result);
+    Ok(())
+}
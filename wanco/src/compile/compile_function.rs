@@ -1,28 +1,41 @@
 use inkwell::{
-    types::{BasicType, BasicTypeEnum},
+    types::{BasicType, BasicTypeEnum, IntType},
     values::{AnyValue, BasicValue, IntValue, PhiValue, PointerValue},
+    FloatPredicate, IntPredicate,
 };
 use wasmparser::{FunctionBody, MemArg, Operator};
 
 use crate::{
     compile::{
-        compile_type::wasmty_to_llvmty,
+        atomic::compile_op_atomic,
+        compile_type::{llvm_result_types, null_ref, wasmty_default_value, wasmty_to_llvmty},
         control::{
-            gen_block, gen_br, gen_br_table, gen_brif, gen_call, gen_call_indirect, gen_drop,
-            gen_else, gen_end, gen_if, gen_loop, gen_return, gen_select, gen_unreachable,
-            ControlFrame, UnreachableReason,
+            gen_block, gen_br, gen_br_table, gen_brif, gen_call, gen_call_indirect, gen_catch,
+            gen_delegate, gen_drop, gen_else, gen_end, gen_if, gen_loop, gen_rethrow, gen_return,
+            gen_return_call, gen_return_call_indirect, gen_select, gen_throw, gen_throw_ref,
+            gen_try, gen_try_table, gen_unreachable, ControlFrame, UnreachableReason,
         },
         cr::{
             gen_migration_point,
             restore::{gen_finalize_restore_dispatch, gen_restore_dispatch},
         },
         helper::{self, gen_float_compare, gen_int_compare, gen_llvm_intrinsic},
+        simd::compile_op_simd,
+        trap::{gen_trap_if, TrapKind},
     },
     context::{Context, Global, StackFrame},
 };
 use anyhow::{anyhow, bail, Context as _, Result};
 
-use super::helper::{gen_memory_base, gen_memory_size};
+use super::{
+    alias::set_memory_access_metadata,
+    error::{CompileError, CompileErrorKind},
+    helper::{
+        gen_bounds_check, gen_memory_base, gen_memory_caches, gen_memory_size,
+        gen_reload_memory_sizes, gen_table_base, gen_table_bounds_check, gen_table_size,
+        is_memory64, uses_guard_pages,
+    },
+};
 
 pub(super) fn compile_function(ctx: &mut Context<'_, '_>, f: FunctionBody) -> Result<()> {
     log::debug!(
@@ -37,14 +50,10 @@ pub(super) fn compile_function(ctx: &mut Context<'_, '_>, f: FunctionBody) -> Re
     ctx.stack_frames.push(StackFrame::new());
 
     ctx.builder.position_at_end(ret_bb);
-    let ret = current_fn.get_type().get_return_type();
     let mut end_phis: Vec<PhiValue> = Vec::new();
-    if let Some(v) = ret {
+    for v in llvm_result_types(&current_fn.get_type()) {
         log::trace!("- return type {:?}", v);
-        let phi = ctx
-            .builder
-            .build_phi(v, "return_phi")
-            .expect("should build phi");
+        let phi = ctx.builder.build_phi(v, "return_phi")?;
         end_phis.push(phi);
     }
 
@@ -72,13 +81,8 @@ pub(super) fn compile_function(ctx: &mut Context<'_, '_>, f: FunctionBody) -> Re
             .get_nth_param(idx)
             .expect("fail to get_nth_param");
         let ty = current_fn.get_type().get_param_types()[idx as usize];
-        let alloca = ctx
-            .builder
-            .build_alloca(ty, "param")
-            .expect("should build alloca");
-        ctx.builder
-            .build_store(alloca, v)
-            .expect("should build store");
+        let alloca = ctx.builder.build_alloca(ty, "param")?;
+        ctx.builder.build_store(alloca, v)?;
         locals.push((alloca, ty));
     }
 
@@ -86,32 +90,44 @@ pub(super) fn compile_function(ctx: &mut Context<'_, '_>, f: FunctionBody) -> Re
     let mut local_reader = f.get_locals_reader()?;
     let num_locals = local_reader.get_count();
     for _ in 0..num_locals {
-        let (count, valty) = local_reader.read()?;
-        let valty = wasmty_to_llvmty(ctx, &valty)?;
+        let (count, wasmty) = local_reader.read()?;
+        let valty = wasmty_to_llvmty(ctx, &wasmty)?;
+        let default_value = wasmty_default_value(ctx, &wasmty)?;
         for _ in 0..count {
-            let alloca = ctx
-                .builder
-                .build_alloca(valty, "local")
-                .expect("should build alloca");
-            ctx.builder
-                .build_store(alloca, valty.const_zero())
-                .expect("should build store");
+            let alloca = ctx.builder.build_alloca(valty, "local")?;
+            ctx.builder.build_store(alloca, default_value)?;
             locals.push((alloca, valty));
         }
     }
 
+    // Allocate hidden locals for the handles of caught exceptions (see ControlFrame::Try)
+    ctx.exception_slots.clear();
+    for _ in 0..max_try_depth(&f)? {
+        let alloca = ctx
+            .builder
+            .build_alloca(ctx.inkwell_types.i32_type, "exception_slot")?;
+        ctx.builder
+            .build_store(alloca, ctx.inkwell_types.i32_type.const_all_ones())?;
+        locals.push((alloca, ctx.inkwell_types.i32_type.as_basic_type_enum()));
+        ctx.exception_slots.push(alloca);
+    }
+
+    // Load the bases and the sizes of the memories once (see gen_memory_base)
+    if ctx.config.hoist_memory_loads {
+        gen_memory_caches(ctx, &exec_env_ptr)?;
+    }
+
     // entry dispatcher for restore
     if !ctx.config.no_restore && (ctx.config.enable_cr || ctx.config.legacy_cr) {
         ctx.restore_dispatch_bb = None;
         ctx.restore_dispatch_cases = vec![];
-        gen_restore_dispatch(ctx, &exec_env_ptr).expect("should gen restore dispatch")
+        gen_restore_dispatch(ctx, &exec_env_ptr)?
     }
 
     // Generate checkpoint
     if ctx.config.enable_cr || ctx.config.legacy_cr {
         ctx.current_op = Some(u32::MAX);
-        gen_migration_point(ctx, &exec_env_ptr, &locals)
-            .expect("fail to gen_check_state_and_snapshot");
+        gen_migration_point(ctx, &exec_env_ptr, &locals)?;
         ctx.num_migration_points += 1;
     }
 
@@ -119,10 +135,13 @@ pub(super) fn compile_function(ctx: &mut Context<'_, '_>, f: FunctionBody) -> Re
     let mut op_reader = f.get_operators_reader()?.get_binary_reader();
     let mut num_op = 0;
     while !op_reader.eof() {
+        let offset = op_reader.original_position();
         let op = op_reader.read_operator()?;
         log::trace!("- op[{}]: {:?}", num_op, &op);
         ctx.current_op = Some(num_op);
-        compile_op(ctx, &op, &exec_env_ptr, &mut locals)?;
+        compile_op(ctx, &op, &exec_env_ptr, &mut locals).map_err(|e| {
+            CompileError::new(e, ctx.current_function_idx.unwrap(), Some(num_op), offset)
+        })?;
 
         num_op += 1;
     }
@@ -131,14 +150,42 @@ pub(super) fn compile_function(ctx: &mut Context<'_, '_>, f: FunctionBody) -> Re
     if !ctx.config.no_restore && (ctx.config.enable_cr || ctx.config.legacy_cr) {
         ctx.builder
             .position_at_end(ctx.restore_dispatch_bb.unwrap());
-        gen_finalize_restore_dispatch(ctx, &exec_env_ptr)
-            .expect("should gen finalize restore dispatch");
+        gen_finalize_restore_dispatch(ctx, &exec_env_ptr)?;
     }
 
+    ctx.memory_caches.clear();
     ctx.current_fn = None;
     Ok(())
 }
 
+/// Get the maximum nesting depth of try blocks in a function.
+fn max_try_depth(f: &FunctionBody) -> Result<usize> {
+    // Whether each enclosing block is a try block
+    let mut blocks: Vec<bool> = Vec::new();
+    let mut max_depth = 0;
+    let mut op_reader = f.get_operators_reader()?;
+    while !op_reader.eof() {
+        match op_reader.read()? {
+            Operator::Block { .. }
+            | Operator::Loop { .. }
+            | Operator::If { .. }
+            | Operator::TryTable { .. } => {
+                blocks.push(false);
+            }
+            Operator::Try { .. } => {
+                blocks.push(true);
+                let depth = blocks.iter().filter(|is_try| **is_try).count();
+                max_depth = max_depth.max(depth);
+            }
+            Operator::End | Operator::Delegate { .. } => {
+                blocks.pop();
+            }
+            _ => {}
+        }
+    }
+    Ok(max_depth)
+}
+
 fn compile_op<'a>(
     ctx: &mut Context<'a, '_>,
     op: &Operator,
@@ -150,7 +197,9 @@ fn compile_op<'a>(
         match op {
             Operator::Block { blockty: _ }
             | Operator::Loop { blockty: _ }
-            | Operator::If { blockty: _ } => {
+            | Operator::If { blockty: _ }
+            | Operator::Try { blockty: _ }
+            | Operator::TryTable { .. } => {
                 ctx.unreachable_depth += 1;
                 return Ok(());
             }
@@ -165,12 +214,35 @@ fn compile_op<'a>(
                     return Ok(());
                 }
             }
+            Operator::Catch { .. } | Operator::CatchAll => {
+                if ctx.unreachable_depth == 1 {
+                    let tag_index = match op {
+                        Operator::Catch { tag_index } => Some(*tag_index),
+                        _ => None,
+                    };
+                    gen_catch(ctx, exec_env_ptr, tag_index).context("error gen Catch")?;
+                    ctx.unreachable_depth -= 1;
+                    ctx.unreachable_reason = UnreachableReason::Reachable;
+                    log::trace!("- end of unreachable");
+                }
+                return Ok(());
+            }
+            Operator::Delegate { relative_depth } => {
+                if ctx.unreachable_depth == 1 {
+                    gen_delegate(ctx, exec_env_ptr, *relative_depth)
+                        .context("error gen Delegate")?;
+                    ctx.unreachable_reason = UnreachableReason::Reachable;
+                    log::trace!("- end of unreachable");
+                }
+                ctx.unreachable_depth -= 1;
+                return Ok(());
+            }
             Operator::End => match ctx.unreachable_depth {
                 0 => {
                     unreachable!("Unexpected depth 0");
                 }
                 1 => {
-                    gen_end(ctx).context("error gen End")?;
+                    gen_end(ctx, exec_env_ptr).context("error gen End")?;
                     ctx.unreachable_depth -= 1;
                     ctx.unreachable_reason = UnreachableReason::Reachable;
                     log::trace!("- end of unreachable");
@@ -204,7 +276,7 @@ fn compile_op<'a>(
             gen_else(ctx).context("error gen Else")?;
         }
         Operator::Br { relative_depth } => {
-            gen_br(ctx, *relative_depth).context("error gen Br")?;
+            gen_br(ctx, exec_env_ptr, *relative_depth).context("error gen Br")?;
         }
         Operator::BrIf { relative_depth } => {
             gen_brif(ctx, *relative_depth).context("errpr gen BrIf")?;
@@ -218,7 +290,7 @@ fn compile_op<'a>(
                 ctx.current_fn.unwrap().get_name(),
                 ctx.current_fn.unwrap().get_type().get_return_type()
             );
-            gen_end(ctx).context("error gen End")?;
+            gen_end(ctx, exec_env_ptr).context("error gen End")?;
         }
         Operator::Call { function_index } => {
             gen_call(ctx, exec_env_ptr, locals, *function_index).context("error gen Call")?;
@@ -230,20 +302,58 @@ fn compile_op<'a>(
             gen_call_indirect(ctx, exec_env_ptr, locals, *type_index, *table_index)
                 .context("error gen CallIndirect")?;
         }
+        Operator::ReturnCall { function_index } => {
+            gen_return_call(ctx, exec_env_ptr, locals, *function_index)
+                .context("error gen ReturnCall")?;
+        }
+        Operator::ReturnCallIndirect {
+            type_index,
+            table_index,
+        } => {
+            gen_return_call_indirect(ctx, exec_env_ptr, *type_index, *table_index)
+                .context("error gen ReturnCallIndirect")?;
+        }
+        /******************************
+          Exception handling
+        ******************************/
+        Operator::Try { blockty } => {
+            gen_try(ctx, exec_env_ptr, blockty).context("error gen Try")?;
+        }
+        Operator::Catch { tag_index } => {
+            gen_catch(ctx, exec_env_ptr, Some(*tag_index)).context("error gen Catch")?;
+        }
+        Operator::CatchAll => {
+            gen_catch(ctx, exec_env_ptr, None).context("error gen CatchAll")?;
+        }
+        Operator::TryTable { try_table } => {
+            gen_try_table(ctx, exec_env_ptr, try_table).context("error gen TryTable")?;
+        }
+        Operator::ThrowRef => {
+            gen_throw_ref(ctx, exec_env_ptr).context("error gen ThrowRef")?;
+        }
+        Operator::Throw { tag_index } => {
+            gen_throw(ctx, exec_env_ptr, *tag_index).context("error gen Throw")?;
+        }
+        Operator::Rethrow { relative_depth } => {
+            gen_rethrow(ctx, exec_env_ptr, *relative_depth).context("error gen Rethrow")?;
+        }
+        Operator::Delegate { relative_depth } => {
+            gen_delegate(ctx, exec_env_ptr, *relative_depth).context("error gen Delegate")?;
+        }
         Operator::Drop => {
             gen_drop(ctx).context("error gen Drop")?;
         }
         Operator::Return => {
-            gen_return(ctx).context("error gen Return")?;
+            gen_return(ctx, exec_env_ptr).context("error gen Return")?;
         }
-        Operator::Select => {
+        Operator::Select | Operator::TypedSelect { .. } => {
             gen_select(ctx).context("error gen Select")?;
         }
         Operator::Nop => {
             // Do nothing
         }
         Operator::Unreachable => {
-            gen_unreachable(ctx).context("error gen Unreachable")?;
+            gen_unreachable(ctx, exec_env_ptr).context("error gen Unreachable")?;
         }
         /******************************
           Numeric instructions
@@ -263,29 +373,26 @@ fn compile_op<'a>(
                 .const_int(value.bits() as u64, false);
             let i = ctx
                 .builder
-                .build_bit_cast(bits, ctx.inkwell_types.f32_type, "")
-                .expect("should build bit_cast");
+                .build_bit_cast(bits, ctx.inkwell_types.f32_type, "")?;
             ctx.push(i);
         }
         Operator::F64Const { value } => {
             let bits = ctx.inkwell_types.i64_type.const_int(value.bits(), false);
             let i = ctx
                 .builder
-                .build_bit_cast(bits, ctx.inkwell_types.f64_type, "")
-                .expect("should build bit_cast");
+                .build_bit_cast(bits, ctx.inkwell_types.f64_type, "")?;
             ctx.push(i);
         }
         Operator::I32Clz => {
-            let v1 = ctx.pop().expect("stack empty");
+            let v1 = ctx.pop()?;
             gen_llvm_intrinsic(
                 ctx,
                 ctx.inkwell_intrs.ctlz_i32,
                 &[v1.into(), ctx.inkwell_types.bool_type.const_zero().into()],
-            )
-            .expect("error gen I32Clz");
+            )?;
         }
         Operator::I64Clz => {
-            let v1 = ctx.pop().expect("stack empty");
+            let v1 = ctx.pop()?;
             let function = ctx.inkwell_intrs.ctlz_i64;
             let clz = ctx
                 .builder
@@ -293,23 +400,19 @@ fn compile_op<'a>(
                     function,
                     &[v1.into(), ctx.inkwell_types.bool_type.const_zero().into()],
                     "",
-                )
-                .expect("fail build_call llvm_insts")
+                )?
                 .try_as_basic_value()
                 .left()
                 .expect("fail build_call llvm_insts");
-            let res = ctx
-                .builder
-                .build_int_sub(
-                    ctx.inkwell_types.i64_type.const_int(63, false),
-                    clz.into_int_value(),
-                    "",
-                )
-                .expect("fail build_int_sub llvm_insts");
+            let res = ctx.builder.build_int_sub(
+                ctx.inkwell_types.i64_type.const_int(63, false),
+                clz.into_int_value(),
+                "",
+            )?;
             ctx.push(res.as_basic_value_enum());
         }
         Operator::I32Ctz => {
-            let v1 = ctx.pop().expect("stack empty");
+            let v1 = ctx.pop()?;
             gen_llvm_intrinsic(
                 ctx,
                 ctx.inkwell_intrs.cttz_i32,
@@ -318,7 +421,7 @@ fn compile_op<'a>(
             .context("error gen I32Ctz")?;
         }
         Operator::I64Ctz => {
-            let v1 = ctx.pop().expect("stack empty");
+            let v1 = ctx.pop()?;
             gen_llvm_intrinsic(
                 ctx,
                 ctx.inkwell_intrs.cttz_i64,
@@ -327,71 +430,46 @@ fn compile_op<'a>(
             .context("error gen I64Ctz")?;
         }
         Operator::I32Popcnt => {
-            let v1 = ctx.pop().expect("stack empty");
-            gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.ctpop_i32, &[v1.into()])
-                .expect("error gen I32Popcnt");
+            let v1 = ctx.pop()?;
+            gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.ctpop_i32, &[v1.into()])?;
         }
         Operator::I64Popcnt => {
-            let v1 = ctx.pop().expect("stack empty");
-            gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.ctpop_i64, &[v1.into()])
-                .expect("error gen I64Popcnt");
+            let v1 = ctx.pop()?;
+            gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.ctpop_i64, &[v1.into()])?;
         }
         Operator::I32Add | Operator::I64Add => {
-            let (v1, v2) = ctx.pop2();
+            let (v1, v2) = ctx.pop2()?;
             let res = ctx
                 .builder
-                .build_int_add(v1.into_int_value(), v2.into_int_value(), "")
-                .expect("should build int add");
+                .build_int_add(v1.into_int_value(), v2.into_int_value(), "")?;
             ctx.push(res.as_basic_value_enum());
         }
         Operator::I32Sub | Operator::I64Sub => {
-            let (v1, v2) = ctx.pop2();
+            let (v1, v2) = ctx.pop2()?;
             let res = ctx
                 .builder
-                .build_int_sub(v1.into_int_value(), v2.into_int_value(), "")
-                .expect("should build int sub");
+                .build_int_sub(v1.into_int_value(), v2.into_int_value(), "")?;
             ctx.push(res.as_basic_value_enum());
         }
         Operator::I32Mul | Operator::I64Mul => {
-            let (v1, v2) = ctx.pop2();
+            let (v1, v2) = ctx.pop2()?;
             let res = ctx
                 .builder
-                .build_int_mul(v1.into_int_value(), v2.into_int_value(), "")
-                .expect("should build int mul");
+                .build_int_mul(v1.into_int_value(), v2.into_int_value(), "")?;
             ctx.push(res.as_basic_value_enum());
         }
         Operator::I32DivS | Operator::I64DivS => {
-            let (v1, v2) = ctx.pop2();
-            let res = ctx
-                .builder
-                .build_int_signed_div(v1.into_int_value(), v2.into_int_value(), "")
-                .expect("should build int signed div");
-            ctx.push(res.as_basic_value_enum());
+            compile_op_int_div(ctx, exec_env_ptr, true, false).context("error gen DivS")?;
         }
         Operator::I32DivU | Operator::I64DivU => {
-            let (v1, v2) = ctx.pop2();
-            let res = ctx
-                .builder
-                .build_int_unsigned_div(v1.into_int_value(), v2.into_int_value(), "")
-                .expect("should build int unsigned div");
-            ctx.push(res.as_basic_value_enum());
+            compile_op_int_div(ctx, exec_env_ptr, false, false).context("error gen DivU")?;
         }
         /* % operator */
         Operator::I32RemS | Operator::I64RemS => {
-            let (v1, v2) = ctx.pop2();
-            let res = ctx
-                .builder
-                .build_int_signed_rem(v1.into_int_value(), v2.into_int_value(), "")
-                .expect("should build int signed rem");
-            ctx.push(res.as_basic_value_enum());
+            compile_op_int_div(ctx, exec_env_ptr, true, true).context("error gen RemS")?;
         }
         Operator::I32RemU | Operator::I64RemU => {
-            let (v1, v2) = ctx.pop2();
-            let res = ctx
-                .builder
-                .build_int_unsigned_rem(v1.into_int_value(), v2.into_int_value(), "")
-                .expect("should build int unsigned rem");
-            ctx.push(res.as_basic_value_enum());
+            compile_op_int_div(ctx, exec_env_ptr, false, true).context("error gen RemU")?;
         }
         /******************************
             bitwise instructions
@@ -430,359 +508,371 @@ fn compile_op<'a>(
           Conversion instructions
         ******************************/
         Operator::I32WrapI64 => {
-            let v = ctx.pop().expect("stack empty").into_int_value();
+            let v = ctx.pop()?.into_int_value();
             let wraped = ctx
                 .builder
-                .build_int_truncate(v, ctx.inkwell_types.i32_type, "")
-                .expect("error build int truncate");
+                .build_int_truncate(v, ctx.inkwell_types.i32_type, "")?;
             ctx.push(wraped.as_basic_value_enum());
         }
         Operator::I64Extend32S => {
-            let v = ctx.pop().expect("stack empty").into_int_value();
+            let v = ctx.pop()?.into_int_value();
             let narrow_value = ctx
                 .builder
-                .build_int_truncate(v, ctx.inkwell_types.i32_type, "")
-                .expect("error build int truncate");
-            let extended = ctx
-                .builder
-                .build_int_s_extend(narrow_value, ctx.inkwell_types.i64_type, "i64extend32s")
-                .expect("error build int s extend");
+                .build_int_truncate(v, ctx.inkwell_types.i32_type, "")?;
+            let extended = ctx.builder.build_int_s_extend(
+                narrow_value,
+                ctx.inkwell_types.i64_type,
+                "i64extend32s",
+            )?;
             ctx.push(extended.as_basic_value_enum());
         }
         Operator::I64Extend16S => {
-            let v = ctx.pop().expect("stack empty").into_int_value();
+            let v = ctx.pop()?.into_int_value();
             let narrow_value = ctx
                 .builder
-                .build_int_truncate(v, ctx.inkwell_types.i16_type, "")
-                .expect("error build int truncate");
-            let extended = ctx
-                .builder
-                .build_int_s_extend(narrow_value, ctx.inkwell_types.i64_type, "i64extend16s")
-                .expect("error build int s extend");
+                .build_int_truncate(v, ctx.inkwell_types.i16_type, "")?;
+            let extended = ctx.builder.build_int_s_extend(
+                narrow_value,
+                ctx.inkwell_types.i64_type,
+                "i64extend16s",
+            )?;
             ctx.push(extended.as_basic_value_enum());
         }
         Operator::I64Extend8S => {
-            let v = ctx.pop().expect("stack empty").into_int_value();
+            let v = ctx.pop()?.into_int_value();
             let narrow_value = ctx
                 .builder
-                .build_int_truncate(v, ctx.inkwell_types.i8_type, "")
-                .expect("error build int truncate");
-            let extended = ctx
-                .builder
-                .build_int_s_extend(narrow_value, ctx.inkwell_types.i64_type, "i64extend8s")
-                .expect("error build int s extend");
+                .build_int_truncate(v, ctx.inkwell_types.i8_type, "")?;
+            let extended = ctx.builder.build_int_s_extend(
+                narrow_value,
+                ctx.inkwell_types.i64_type,
+                "i64extend8s",
+            )?;
             ctx.push(extended.as_basic_value_enum());
         }
         Operator::I32Extend16S => {
-            let v = ctx.pop().expect("stack empty").into_int_value();
+            let v = ctx.pop()?.into_int_value();
             let narrow_value = ctx
                 .builder
-                .build_int_truncate(v, ctx.inkwell_types.i16_type, "")
-                .expect("error build int truncate");
-            let extended = ctx
-                .builder
-                .build_int_s_extend(narrow_value, ctx.inkwell_types.i32_type, "i32extend16s")
-                .expect("error build int s extend");
+                .build_int_truncate(v, ctx.inkwell_types.i16_type, "")?;
+            let extended = ctx.builder.build_int_s_extend(
+                narrow_value,
+                ctx.inkwell_types.i32_type,
+                "i32extend16s",
+            )?;
             ctx.push(extended.as_basic_value_enum());
         }
         Operator::I32Extend8S => {
-            let v = ctx.pop().expect("stack empty").into_int_value();
+            let v = ctx.pop()?.into_int_value();
             let narrow_value = ctx
                 .builder
-                .build_int_truncate(v, ctx.inkwell_types.i8_type, "")
-                .expect("error build int truncate");
-            let extended = ctx
-                .builder
-                .build_int_s_extend(narrow_value, ctx.inkwell_types.i32_type, "i32extend8s")
-                .expect("error build int s extend");
+                .build_int_truncate(v, ctx.inkwell_types.i8_type, "")?;
+            let extended = ctx.builder.build_int_s_extend(
+                narrow_value,
+                ctx.inkwell_types.i32_type,
+                "i32extend8s",
+            )?;
             ctx.push(extended.as_basic_value_enum());
         }
         Operator::I64ExtendI32U => {
-            let v = ctx.pop().expect("stack empty").into_int_value();
-            let extended = ctx
-                .builder
-                .build_int_z_extend(v, ctx.inkwell_types.i64_type, "i64extendi32u")
-                .expect("error build int z extend");
+            let v = ctx.pop()?.into_int_value();
+            let extended =
+                ctx.builder
+                    .build_int_z_extend(v, ctx.inkwell_types.i64_type, "i64extendi32u")?;
             ctx.push(extended.as_basic_value_enum());
         }
         Operator::I64ExtendI32S => {
-            let v = ctx.pop().expect("stack empty").into_int_value();
-            let extended = ctx
-                .builder
-                .build_int_s_extend(v, ctx.inkwell_types.i64_type, "i64extendi32s")
-                .expect("error build int s extend");
+            let v = ctx.pop()?.into_int_value();
+            let extended =
+                ctx.builder
+                    .build_int_s_extend(v, ctx.inkwell_types.i64_type, "i64extendi32s")?;
             ctx.push(extended.as_basic_value_enum());
         }
         Operator::F32DemoteF64 => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
-            let demoted = ctx
-                .builder
-                .build_float_trunc(v, ctx.inkwell_types.f32_type, "f32demotef64")
-                .expect("error build float trunc");
+            let v = ctx.pop()?.into_float_value();
+            let demoted =
+                ctx.builder
+                    .build_float_trunc(v, ctx.inkwell_types.f32_type, "f32demotef64")?;
             ctx.push(demoted.as_basic_value_enum());
         }
         Operator::F64PromoteF32 => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
-            let promoted = ctx
-                .builder
-                .build_float_ext(v, ctx.inkwell_types.f64_type, "f64promotef32")
-                .expect("error build float ext");
+            let v = ctx.pop()?.into_float_value();
+            let promoted =
+                ctx.builder
+                    .build_float_ext(v, ctx.inkwell_types.f64_type, "f64promotef32")?;
             ctx.push(promoted.as_basic_value_enum());
         }
         Operator::F64ConvertI64S | Operator::F64ConvertI32S => {
-            let v = ctx.pop().expect("stack empty").into_int_value();
-            let converted = ctx
-                .builder
-                .build_signed_int_to_float(v, ctx.inkwell_types.f64_type, "f64converti64s")
-                .expect("error build signed int to float");
+            let v = ctx.pop()?.into_int_value();
+            let converted = ctx.builder.build_signed_int_to_float(
+                v,
+                ctx.inkwell_types.f64_type,
+                "f64converti64s",
+            )?;
             ctx.push(converted.as_basic_value_enum());
         }
         Operator::F64ConvertI64U | Operator::F64ConvertI32U => {
-            let v = ctx.pop().expect("stack empty").into_int_value();
-            let converted = ctx
-                .builder
-                .build_unsigned_int_to_float(v, ctx.inkwell_types.f64_type, "f64converti64u")
-                .expect("error build unsigned int to float");
+            let v = ctx.pop()?.into_int_value();
+            let converted = ctx.builder.build_unsigned_int_to_float(
+                v,
+                ctx.inkwell_types.f64_type,
+                "f64converti64u",
+            )?;
             ctx.push(converted.as_basic_value_enum());
         }
         Operator::F32ConvertI32S | Operator::F32ConvertI64S => {
-            let v = ctx.pop().expect("stack empty").into_int_value();
-            let converted = ctx
-                .builder
-                .build_signed_int_to_float(v, ctx.inkwell_types.f32_type, "f32converti32s")
-                .expect("error build signed int to float");
+            let v = ctx.pop()?.into_int_value();
+            let converted = ctx.builder.build_signed_int_to_float(
+                v,
+                ctx.inkwell_types.f32_type,
+                "f32converti32s",
+            )?;
             ctx.push(converted.as_basic_value_enum());
         }
         Operator::F32ConvertI32U | Operator::F32ConvertI64U => {
-            let v = ctx.pop().expect("stack empty").into_int_value();
-            let converted = ctx
-                .builder
-                .build_unsigned_int_to_float(v, ctx.inkwell_types.f32_type, "f32converti32u")
-                .expect("error build unsigned int to float");
+            let v = ctx.pop()?.into_int_value();
+            let converted = ctx.builder.build_unsigned_int_to_float(
+                v,
+                ctx.inkwell_types.f32_type,
+                "f32converti32u",
+            )?;
             ctx.push(converted.as_basic_value_enum());
         }
         Operator::I64TruncF64S | Operator::I64TruncF32S => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
-            let converted = ctx
-                .builder
-                .build_float_to_signed_int(v, ctx.inkwell_types.i64_type, "i64truncf64s")
-                .expect("error build float to signed int");
-            ctx.push(converted.as_basic_value_enum());
+            compile_op_trunc(ctx, exec_env_ptr, ctx.inkwell_types.i64_type, true)
+                .context("error gen I64TruncS")?;
         }
         Operator::I32TruncF32S | Operator::I32TruncF64S => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
-            let converted = ctx
-                .builder
-                .build_float_to_signed_int(v, ctx.inkwell_types.i32_type, "i32truncf32s")
-                .expect("error build float to signed int");
-            ctx.push(converted.as_basic_value_enum());
+            compile_op_trunc(ctx, exec_env_ptr, ctx.inkwell_types.i32_type, true)
+                .context("error gen I32TruncS")?;
         }
         Operator::I64TruncF64U | Operator::I64TruncF32U => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
-            let converted = ctx
-                .builder
-                .build_float_to_unsigned_int(v, ctx.inkwell_types.i64_type, "i64truncf64u")
-                .expect("error build float to unsigned int");
-            ctx.push(converted.as_basic_value_enum());
+            compile_op_trunc(ctx, exec_env_ptr, ctx.inkwell_types.i64_type, false)
+                .context("error gen I64TruncU")?;
         }
         Operator::I32TruncF32U | Operator::I32TruncF64U => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
-            let converted = ctx
-                .builder
-                .build_float_to_unsigned_int(v, ctx.inkwell_types.i32_type, "i32truncf32u")
-                .expect("error build float to unsigned int");
-            ctx.push(converted.as_basic_value_enum());
+            compile_op_trunc(ctx, exec_env_ptr, ctx.inkwell_types.i32_type, false)
+                .context("error gen I32TruncU")?;
+        }
+        /* saturating float-to-int conversions */
+        Operator::I32TruncSatF32S => {
+            let v = ctx.pop()?.into_float_value();
+            gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.fptosi_sat_i32_f32, &[v.into()])
+                .context("error gen I32TruncSatF32S")?;
+        }
+        Operator::I32TruncSatF32U => {
+            let v = ctx.pop()?.into_float_value();
+            gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.fptoui_sat_i32_f32, &[v.into()])
+                .context("error gen I32TruncSatF32U")?;
+        }
+        Operator::I32TruncSatF64S => {
+            let v = ctx.pop()?.into_float_value();
+            gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.fptosi_sat_i32_f64, &[v.into()])
+                .context("error gen I32TruncSatF64S")?;
+        }
+        Operator::I32TruncSatF64U => {
+            let v = ctx.pop()?.into_float_value();
+            gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.fptoui_sat_i32_f64, &[v.into()])
+                .context("error gen I32TruncSatF64U")?;
+        }
+        Operator::I64TruncSatF32S => {
+            let v = ctx.pop()?.into_float_value();
+            gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.fptosi_sat_i64_f32, &[v.into()])
+                .context("error gen I64TruncSatF32S")?;
+        }
+        Operator::I64TruncSatF32U => {
+            let v = ctx.pop()?.into_float_value();
+            gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.fptoui_sat_i64_f32, &[v.into()])
+                .context("error gen I64TruncSatF32U")?;
+        }
+        Operator::I64TruncSatF64S => {
+            let v = ctx.pop()?.into_float_value();
+            gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.fptosi_sat_i64_f64, &[v.into()])
+                .context("error gen I64TruncSatF64S")?;
+        }
+        Operator::I64TruncSatF64U => {
+            let v = ctx.pop()?.into_float_value();
+            gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.fptoui_sat_i64_f64, &[v.into()])
+                .context("error gen I64TruncSatF64U")?;
         }
         Operator::F64ReinterpretI64 => {
-            let v = ctx.pop().expect("stack empty").into_int_value();
+            let v = ctx.pop()?.into_int_value();
             let reinterpreted = ctx
                 .builder
-                .build_bit_cast(v, ctx.inkwell_types.f64_type, "")
-                .expect("error build bit_cast");
+                .build_bit_cast(v, ctx.inkwell_types.f64_type, "")?;
             ctx.push(reinterpreted);
         }
         Operator::F32ReinterpretI32 => {
-            let v = ctx.pop().expect("stack empty").into_int_value();
+            let v = ctx.pop()?.into_int_value();
             let reinterpreted = ctx
                 .builder
-                .build_bit_cast(v, ctx.inkwell_types.f32_type, "")
-                .expect("error build bit_cast");
+                .build_bit_cast(v, ctx.inkwell_types.f32_type, "")?;
             ctx.push(reinterpreted);
         }
         Operator::I64ReinterpretF64 => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
+            let v = ctx.pop()?.into_float_value();
             let reinterpreted = ctx
                 .builder
-                .build_bit_cast(v, ctx.inkwell_types.i64_type, "")
-                .expect("error build bit_cast");
+                .build_bit_cast(v, ctx.inkwell_types.i64_type, "")?;
             ctx.push(reinterpreted);
         }
         Operator::I32ReinterpretF32 => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
+            let v = ctx.pop()?.into_float_value();
             let reinterpreted = ctx
                 .builder
-                .build_bit_cast(v, ctx.inkwell_types.i32_type, "")
-                .expect("error build bit_cast");
+                .build_bit_cast(v, ctx.inkwell_types.i32_type, "")?;
             ctx.push(reinterpreted);
         }
         /******************************
             Floating
         ******************************/
         Operator::F32Eq | Operator::F64Eq => {
-            gen_float_compare(ctx, inkwell::FloatPredicate::OEQ).expect("error gen compare float");
+            gen_float_compare(ctx, inkwell::FloatPredicate::OEQ)?;
         }
         Operator::F32Ne | Operator::F64Ne => {
-            gen_float_compare(ctx, inkwell::FloatPredicate::UNE).expect("error gen compare float");
+            gen_float_compare(ctx, inkwell::FloatPredicate::UNE)?;
         }
         Operator::F64Lt | Operator::F32Lt => {
-            gen_float_compare(ctx, inkwell::FloatPredicate::OLT).expect("error gen compare float");
+            gen_float_compare(ctx, inkwell::FloatPredicate::OLT)?;
         }
         Operator::F64Gt | Operator::F32Gt => {
-            gen_float_compare(ctx, inkwell::FloatPredicate::OGT).expect("error gen compare float");
+            gen_float_compare(ctx, inkwell::FloatPredicate::OGT)?;
         }
         Operator::F64Le | Operator::F32Le => {
-            gen_float_compare(ctx, inkwell::FloatPredicate::OLE).expect("error gen compare float");
+            gen_float_compare(ctx, inkwell::FloatPredicate::OLE)?;
         }
         Operator::F64Ge | Operator::F32Ge => {
-            gen_float_compare(ctx, inkwell::FloatPredicate::OGE).expect("error gen compare float");
+            gen_float_compare(ctx, inkwell::FloatPredicate::OGE)?;
         }
         Operator::F64Abs => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
+            let v = ctx.pop()?.into_float_value();
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.fabs_f64, &[v.into()])
                 .context("error gen F64Abs")?;
         }
         Operator::F32Abs => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
+            let v = ctx.pop()?.into_float_value();
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.fabs_f32, &[v.into()])
                 .context("error gen F32Abs")?;
         }
         Operator::F64Neg => {
-            let v1 = ctx.pop().expect("stack empty");
+            let v1 = ctx.pop()?;
             let res = ctx
                 .builder
-                .build_float_neg(v1.into_float_value(), "f64neg")
-                .expect("should build float neg");
+                .build_float_neg(v1.into_float_value(), "f64neg")?;
             ctx.push(res.as_basic_value_enum());
         }
         Operator::F32Neg => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
-            let res = ctx
-                .builder
-                .build_float_neg(v, "f32neg")
-                .expect("should build float neg");
+            let v = ctx.pop()?.into_float_value();
+            let res = ctx.builder.build_float_neg(v, "f32neg")?;
             ctx.push(res.as_basic_value_enum());
         }
         Operator::F64Ceil => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
+            let v = ctx.pop()?.into_float_value();
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.ceil_f64, &[v.into()])
                 .context("error gen F64Ceil")?;
         }
         Operator::F32Ceil => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
+            let v = ctx.pop()?.into_float_value();
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.ceil_f32, &[v.into()])
                 .context("error gen F32Ceil")?;
         }
         Operator::F64Floor => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
+            let v = ctx.pop()?.into_float_value();
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.floor_f64, &[v.into()])
                 .context("error gen F64Floor")?;
         }
         Operator::F32Floor => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
+            let v = ctx.pop()?.into_float_value();
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.floor_f32, &[v.into()])
                 .context("error gen F32Floor")?;
         }
         Operator::F64Trunc => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
+            let v = ctx.pop()?.into_float_value();
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.trunc_f64, &[v.into()])
                 .context("error gen F64Trunc")?;
         }
         Operator::F32Trunc => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
+            let v = ctx.pop()?.into_float_value();
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.trunc_f32, &[v.into()])
                 .context("error gen F32Trunc")?;
         }
         Operator::F64Nearest => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
+            let v = ctx.pop()?.into_float_value();
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.nearbyint_f64, &[v.into()])
                 .context("error gen F64Nearest")?;
         }
         Operator::F32Nearest => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
+            let v = ctx.pop()?.into_float_value();
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.nearbyint_f32, &[v.into()])
                 .context("error gen F32Nearest")?;
         }
         Operator::F64Sqrt => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
+            let v = ctx.pop()?.into_float_value();
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.sqrt_f64, &[v.into()])
                 .context("error gen F64Sqrt")?;
         }
         Operator::F32Sqrt => {
-            let v = ctx.pop().expect("stack empty").into_float_value();
+            let v = ctx.pop()?.into_float_value();
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.sqrt_f32, &[v.into()])
                 .context("error gen F64Sqrt")?;
         }
         Operator::F64Add | Operator::F32Add => {
-            let (v1, v2) = ctx.pop2();
-            let res = ctx
-                .builder
-                .build_float_add(v1.into_float_value(), v2.into_float_value(), "")
-                .expect("should build float add");
+            let (v1, v2) = ctx.pop2()?;
+            let res =
+                ctx.builder
+                    .build_float_add(v1.into_float_value(), v2.into_float_value(), "")?;
             ctx.push(res.as_basic_value_enum());
         }
         Operator::F64Sub | Operator::F32Sub => {
-            let (v1, v2) = ctx.pop2();
-            let res = ctx
-                .builder
-                .build_float_sub(v1.into_float_value(), v2.into_float_value(), "")
-                .expect("should build float sub");
+            let (v1, v2) = ctx.pop2()?;
+            let res =
+                ctx.builder
+                    .build_float_sub(v1.into_float_value(), v2.into_float_value(), "")?;
             ctx.push(res.as_basic_value_enum());
         }
         Operator::F64Mul | Operator::F32Mul => {
-            let (v1, v2) = ctx.pop2();
-            let res = ctx
-                .builder
-                .build_float_mul(v1.into_float_value(), v2.into_float_value(), "")
-                .expect("should build float mul");
+            let (v1, v2) = ctx.pop2()?;
+            let res =
+                ctx.builder
+                    .build_float_mul(v1.into_float_value(), v2.into_float_value(), "")?;
             ctx.push(res.as_basic_value_enum());
         }
 
         Operator::F64Div | Operator::F32Div => {
-            let (v1, v2) = ctx.pop2();
-            let res = ctx
-                .builder
-                .build_float_div(v1.into_float_value(), v2.into_float_value(), "")
-                .expect("should build float div");
+            let (v1, v2) = ctx.pop2()?;
+            let res =
+                ctx.builder
+                    .build_float_div(v1.into_float_value(), v2.into_float_value(), "")?;
             ctx.push(res.as_basic_value_enum());
         }
 
         Operator::F64Min => {
-            let (v1, v2) = ctx.pop2();
+            let (v1, v2) = ctx.pop2()?;
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.minnum_f64, &[v1.into(), v2.into()])
                 .context("error gen F64Min")?;
         }
         Operator::F32Min => {
-            let (v1, v2) = ctx.pop2();
+            let (v1, v2) = ctx.pop2()?;
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.minnum_f32, &[v1.into(), v2.into()])
                 .context("error gen F32Min")?;
         }
         Operator::F64Max => {
-            let (v1, v2) = ctx.pop2();
+            let (v1, v2) = ctx.pop2()?;
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.maxnum_f64, &[v1.into(), v2.into()])
                 .context("error gen F64Max")?;
         }
         Operator::F32Max => {
-            let (v1, v2) = ctx.pop2();
+            let (v1, v2) = ctx.pop2()?;
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.maxnum_f32, &[v1.into(), v2.into()])
                 .context("error gen F32Max")?;
         }
         Operator::F32Copysign => {
-            let (v1, v2) = ctx.pop2();
+            let (v1, v2) = ctx.pop2()?;
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.copysign_f32, &[v1.into(), v2.into()])
                 .context("error gen copysign.f32")?;
         }
         Operator::F64Copysign => {
-            let (v1, v2) = ctx.pop2();
+            let (v1, v2) = ctx.pop2()?;
             gen_llvm_intrinsic(ctx, ctx.inkwell_intrs.copysign_f64, &[v1.into(), v2.into()])
                 .context("error gen copysign.f64")?;
         }
@@ -793,28 +883,21 @@ fn compile_op<'a>(
         Operator::LocalGet { local_index } => {
             assert!(*local_index < locals.len() as u32);
             let (value_ptr, ty) = locals[*local_index as usize];
-            let v = ctx
-                .builder
-                .build_load(ty, value_ptr, "")
-                .expect("should build load");
+            let v = ctx.builder.build_load(ty, value_ptr, "")?;
             ctx.push(v);
         }
         // Sets the value of the local variable
         Operator::LocalSet { local_index } => {
             assert!(*local_index < locals.len() as u32);
             let (value_ptr, _) = locals[*local_index as usize];
-            let v = ctx.pop().expect("stack empty");
-            ctx.builder
-                .build_store(value_ptr, v)
-                .expect("should build store");
+            let v = ctx.pop()?;
+            ctx.builder.build_store(value_ptr, v)?;
         }
         Operator::LocalTee { local_index } => {
             assert!(*local_index < locals.len() as u32);
             let (value_ptr, _) = locals[*local_index as usize];
-            let v = ctx.pop().expect("stack empty");
-            ctx.builder
-                .build_store(value_ptr, v)
-                .expect("should build store");
+            let v = ctx.pop()?;
+            ctx.builder.build_store(value_ptr, v)?;
             ctx.push(v);
         }
         Operator::GlobalGet { global_index } => {
@@ -824,11 +907,8 @@ fn compile_op<'a>(
                 Global::Const { value } => {
                     ctx.push(*value);
                 }
-                Global::Mut { ptr, ty } => {
-                    let value = ctx
-                        .builder
-                        .build_load(*ty, ptr.as_pointer_value(), "")
-                        .expect("should build load");
+                Global::Mut { ptr, ty } | Global::Immutable { ptr, ty } => {
+                    let value = ctx.builder.build_load(*ty, ptr.as_pointer_value(), "")?;
                     ctx.push(value);
                 }
             };
@@ -837,7 +917,7 @@ fn compile_op<'a>(
             assert!(*global_index < ctx.globals.len() as u32);
             let global = &ctx.globals[*global_index as usize];
             match global {
-                Global::Const { value: _ } => {
+                Global::Const { .. } | Global::Immutable { .. } => {
                     bail!("Global.Set to const value");
                 }
                 Global::Mut { ptr, ty: _ } => {
@@ -847,21 +927,74 @@ fn compile_op<'a>(
                         .expect("frame empty")
                         .stack
                         .pop()
-                        .expect("stack empty");
-                    ctx.builder
-                        .build_store(ptr.as_pointer_value(), value)
-                        .expect("should build store");
+                        .ok_or(CompileErrorKind::StackUnderflow)?;
+                    ctx.builder.build_store(ptr.as_pointer_value(), value)?;
                 }
             };
         }
+        /******************************
+          Reference instructions
+        ******************************/
+        Operator::RefNull { .. } => {
+            ctx.push(null_ref(ctx).as_basic_value_enum());
+        }
+        Operator::RefIsNull => {
+            let v = ctx.pop()?.into_int_value();
+            let cond =
+                ctx.builder
+                    .build_int_compare(IntPredicate::EQ, v, null_ref(ctx), "is_null")?;
+            let res = ctx
+                .builder
+                .build_int_z_extend(cond, ctx.inkwell_types.i32_type, "")?;
+            ctx.push(res.as_basic_value_enum());
+        }
+        Operator::RefFunc { function_index } => {
+            let i = ctx
+                .inkwell_types
+                .i32_type
+                .const_int(*function_index as u64, false);
+            ctx.push(i.as_basic_value_enum());
+        }
+        /******************************
+          Table instructions
+        ******************************/
+        Operator::TableGet { table } => {
+            compile_op_table_get(ctx, exec_env_ptr, *table).context("error gen TableGet")?;
+        }
+        Operator::TableSet { table } => {
+            compile_op_table_set(ctx, exec_env_ptr, *table).context("error gen TableSet")?;
+        }
+        Operator::TableSize { table } => {
+            compile_op_table_size(ctx, *table).context("error gen TableSize")?;
+        }
+        Operator::TableGrow { table } => {
+            compile_op_table_grow(ctx, exec_env_ptr, *table).context("error gen TableGrow")?;
+        }
+        Operator::TableFill { table } => {
+            compile_op_table_fill(ctx, exec_env_ptr, *table).context("error gen TableFill")?;
+        }
+        Operator::TableCopy {
+            dst_table,
+            src_table,
+        } => {
+            compile_op_table_copy(ctx, exec_env_ptr, *dst_table, *src_table)
+                .context("error gen TableCopy")?;
+        }
+        Operator::TableInit { elem_index, table } => {
+            compile_op_table_init(ctx, exec_env_ptr, *elem_index, *table)
+                .context("error gen TableInit")?;
+        }
+        Operator::ElemDrop { elem_index } => {
+            compile_op_elem_drop(ctx, *elem_index).context("error gen ElemDrop")?;
+        }
         /******************************
           Memory instructions
         ******************************/
-        Operator::MemorySize { mem: _ } => {
-            compile_op_memory_size(ctx, exec_env_ptr).context("error gen MemorySize")?;
+        Operator::MemorySize { mem } => {
+            compile_op_memory_size(ctx, exec_env_ptr, *mem).context("error gen MemorySize")?;
         }
-        Operator::MemoryGrow { mem: _ } => {
-            compile_op_memory_grow(ctx, exec_env_ptr).context("error gen MemoryGrow")?;
+        Operator::MemoryGrow { mem } => {
+            compile_op_memory_grow(ctx, exec_env_ptr, *mem).context("error gen MemoryGrow")?;
         }
         Operator::MemoryCopy { dst_mem, src_mem } => {
             compile_op_memcpy(ctx, exec_env_ptr, *dst_mem, *src_mem)
@@ -870,6 +1003,13 @@ fn compile_op<'a>(
         Operator::MemoryFill { mem } => {
             compile_op_memory_fill(ctx, exec_env_ptr, *mem).context("error gen MemoryFill")?;
         }
+        Operator::MemoryInit { data_index, mem } => {
+            compile_op_memory_init(ctx, exec_env_ptr, *data_index, *mem)
+                .context("error gen MemoryInit")?;
+        }
+        Operator::DataDrop { data_index } => {
+            compile_op_data_drop(ctx, *data_index).context("error gen DataDrop")?;
+        }
         // TODO: memarg
         Operator::I32Load { memarg } => {
             compile_op_load(
@@ -1160,9 +1300,16 @@ fn compile_op<'a>(
         Operator::I32GeU | Operator::I64GeU => {
             gen_int_compare(ctx, inkwell::IntPredicate::UGE).context("error gen GeU")?;
         }
+        /******************************
+          Atomic and vector instructions
+        ******************************/
         _ => {
-            log::error!("Unimplemented instruction {:?}", op);
-            bail!("Unimplemented instruction {:?}", op);
+            if !compile_op_atomic(ctx, exec_env_ptr, op)?
+                && !compile_op_simd(ctx, exec_env_ptr, op)?
+            {
+                log::error!("Unimplemented instruction {:?}", op);
+                bail!(CompileErrorKind::UnsupportedOperator);
+            }
         }
     }
     Ok(())
@@ -1171,25 +1318,141 @@ fn compile_op<'a>(
 pub fn compile_op_memory_size<'a>(
     ctx: &mut Context<'a, '_>,
     exec_env_ptr: &PointerValue<'a>,
+    mem: u32,
 ) -> Result<()> {
-    let size = gen_memory_size(ctx, exec_env_ptr).expect("error gen memory size");
-    ctx.push(size);
+    let size = gen_memory_size(ctx, exec_env_ptr, mem)?.into_int_value();
+    let size = truncate_to_index_type(ctx, size, mem)?;
+    ctx.push(size.as_basic_value_enum());
     Ok(())
 }
 
 pub fn compile_op_memory_grow<'a>(
     ctx: &mut Context<'a, '_>,
     exec_env_ptr: &PointerValue<'a>,
+    mem: u32,
 ) -> Result<()> {
-    let delta = ctx.pop().expect("stack empty");
+    let delta = ctx.pop()?.into_int_value();
+    let delta = zext_to_i64(ctx, delta)?;
     let ret = ctx
         .builder
         .build_call(
             ctx.fn_memory_grow.expect("shold define fn_memory_grow"),
-            &[exec_env_ptr.as_basic_value_enum().into(), delta.into()],
+            &[
+                exec_env_ptr.as_basic_value_enum().into(),
+                ctx.inkwell_types
+                    .i32_type
+                    .const_int(mem as u64, false)
+                    .into(),
+                delta.into(),
+            ],
             "memory_grow",
-        )
-        .expect("should build call")
+        )?
+        .as_any_value_enum()
+        .into_int_value();
+    gen_reload_memory_sizes(ctx, exec_env_ptr)?;
+    // -1 (failure) is kept as it is
+    let ret = truncate_to_index_type(ctx, ret, mem)?;
+    ctx.push(ret.as_basic_value_enum());
+    Ok(())
+}
+
+/// Convert an i64 page count into the index type of the memory.
+fn truncate_to_index_type<'a>(
+    ctx: &Context<'a, '_>,
+    value: IntValue<'a>,
+    mem: u32,
+) -> Result<IntValue<'a>> {
+    if is_memory64(ctx, mem) {
+        return Ok(value);
+    }
+    Ok(ctx
+        .builder
+        .build_int_truncate(value, ctx.inkwell_types.i32_type, "")?)
+}
+
+/// Get the pointer to the element of the table after checking the bounds.
+fn resolve_table_element<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    table: u32,
+    idx: IntValue<'a>,
+) -> Result<PointerValue<'a>> {
+    let idx_ex = zext_to_i64(ctx, idx)?;
+    gen_table_bounds_check(
+        ctx,
+        exec_env_ptr,
+        table,
+        idx_ex,
+        ctx.inkwell_types.i64_type.const_int(1, false),
+        TrapKind::TableOutOfBounds,
+    )?;
+    let table_base = gen_table_base(ctx, table)?;
+    let elem_ptr = unsafe {
+        ctx.builder
+            .build_gep(ctx.inkwell_types.i32_type, table_base, &[idx], "elem_ptr")
+    }?;
+    Ok(elem_ptr)
+}
+
+pub fn compile_op_table_get<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    table: u32,
+) -> Result<()> {
+    let idx = ctx.pop()?.into_int_value();
+    let elem_ptr = resolve_table_element(ctx, exec_env_ptr, table, idx)?;
+    let elem = ctx
+        .builder
+        .build_load(ctx.inkwell_types.i32_type, elem_ptr, "elem")?;
+    ctx.push(elem);
+    Ok(())
+}
+
+pub fn compile_op_table_set<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    table: u32,
+) -> Result<()> {
+    let value = ctx.pop()?;
+    let idx = ctx.pop()?.into_int_value();
+    let elem_ptr = resolve_table_element(ctx, exec_env_ptr, table, idx)?;
+    ctx.builder.build_store(elem_ptr, value)?;
+    Ok(())
+}
+
+pub fn compile_op_table_size(ctx: &mut Context<'_, '_>, table: u32) -> Result<()> {
+    let size = gen_table_size(ctx, table)?;
+    ctx.push(size.as_basic_value_enum());
+    Ok(())
+}
+
+pub fn compile_op_table_grow<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    table: u32,
+) -> Result<()> {
+    let delta = ctx.pop()?;
+    let init = ctx.pop()?;
+    let table = &ctx.tables[table as usize];
+    let table_ptr = table.global.expect("should define table");
+    // -1 means that the table has no maximum size
+    let maximum = match table.ty.maximum {
+        Some(maximum) => ctx.inkwell_types.i64_type.const_int(maximum, false),
+        None => ctx.inkwell_types.i64_type.const_all_ones(),
+    };
+    let ret = ctx
+        .builder
+        .build_call(
+            ctx.fn_table_grow.expect("should define fn_table_grow"),
+            &[
+                exec_env_ptr.as_basic_value_enum().into(),
+                table_ptr.as_pointer_value().into(),
+                init.into(),
+                delta.into(),
+                maximum.into(),
+            ],
+            "table_grow",
+        )?
         .as_any_value_enum()
         .into_int_value()
         .as_basic_value_enum();
@@ -1197,23 +1460,205 @@ pub fn compile_op_memory_grow<'a>(
     Ok(())
 }
 
+pub fn compile_op_table_fill<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    table: u32,
+) -> Result<()> {
+    let len = ctx.pop()?;
+    let value = ctx.pop()?;
+    let idx = ctx.pop()?;
+    // Bulk table operations must trap before writing anything
+    let len_ex = zext_to_i64(ctx, len.into_int_value())?;
+    let idx_ex = zext_to_i64(ctx, idx.into_int_value())?;
+    gen_table_bounds_check(
+        ctx,
+        exec_env_ptr,
+        table,
+        idx_ex,
+        len_ex,
+        TrapKind::TableOutOfBounds,
+    )?;
+    let table_ptr = ctx.tables[table as usize]
+        .global
+        .expect("should define table");
+    ctx.builder.build_call(
+        ctx.fn_table_fill.expect("should define fn_table_fill"),
+        &[
+            exec_env_ptr.as_basic_value_enum().into(),
+            table_ptr.as_pointer_value().into(),
+            idx.into(),
+            value.into(),
+            len.into(),
+        ],
+        "",
+    )?;
+    Ok(())
+}
+
+pub fn compile_op_table_copy<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    dst_table: u32,
+    src_table: u32,
+) -> Result<()> {
+    let len = ctx.pop()?.into_int_value();
+    let src = ctx.pop()?.into_int_value();
+    let dst = ctx.pop()?.into_int_value();
+    // Bulk table operations must trap before writing anything
+    let len_ex = zext_to_i64(ctx, len)?;
+    let src_ex = zext_to_i64(ctx, src)?;
+    let dst_ex = zext_to_i64(ctx, dst)?;
+    gen_table_bounds_check(
+        ctx,
+        exec_env_ptr,
+        src_table,
+        src_ex,
+        len_ex,
+        TrapKind::TableOutOfBounds,
+    )?;
+    gen_table_bounds_check(
+        ctx,
+        exec_env_ptr,
+        dst_table,
+        dst_ex,
+        len_ex,
+        TrapKind::TableOutOfBounds,
+    )?;
+    let src_base = gen_table_base(ctx, src_table)?;
+    let dst_base = gen_table_base(ctx, dst_table)?;
+    let src_ptr = unsafe {
+        ctx.builder
+            .build_gep(ctx.inkwell_types.i32_type, src_base, &[src], "src_ptr")
+    }?;
+    let dst_ptr = unsafe {
+        ctx.builder
+            .build_gep(ctx.inkwell_types.i32_type, dst_base, &[dst], "dst_ptr")
+    }?;
+    // The source and destination may overlap
+    let size = ctx.builder.build_int_mul(
+        len_ex,
+        ctx.inkwell_types.i64_type.const_int(4, false),
+        "copy_size",
+    )?;
+    ctx.builder
+        .build_memmove(dst_ptr, 4, src_ptr, 4, size)
+        .map_err(|e| anyhow!(e))
+        .context("error build_memmove")?;
+    Ok(())
+}
+
+/// Get the pointer to the current size of the element segment.
+fn resolve_element_segment_size<'a>(
+    ctx: &mut Context<'a, '_>,
+    elem_index: u32,
+) -> Result<PointerValue<'a>> {
+    if elem_index as usize >= ctx.element_segments.len() {
+        bail!("Unknown element segment {}", elem_index);
+    }
+    let element_segment_sizes = ctx
+        .global_element_segment_sizes
+        .expect("should define element segment sizes");
+    let size_ptr = unsafe {
+        ctx.builder.build_gep(
+            ctx.inkwell_types.i32_type,
+            element_segment_sizes.as_pointer_value(),
+            &[ctx
+                .inkwell_types
+                .i32_type
+                .const_int(elem_index as u64, false)],
+            "elem_segment_size_ptr",
+        )
+    }?;
+    Ok(size_ptr)
+}
+
+pub fn compile_op_table_init<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    elem_index: u32,
+    table: u32,
+) -> Result<()> {
+    let len = ctx.pop()?.into_int_value();
+    let src = ctx.pop()?.into_int_value();
+    let dst = ctx.pop()?.into_int_value();
+    let len_ex = zext_to_i64(ctx, len)?;
+    let src_ex = zext_to_i64(ctx, src)?;
+    let dst_ex = zext_to_i64(ctx, dst)?;
+
+    // The source must be inside the segment, whose size is 0 once it is dropped
+    let size_ptr = resolve_element_segment_size(ctx, elem_index)?;
+    let segment_size =
+        ctx.builder
+            .build_load(ctx.inkwell_types.i32_type, size_ptr, "elem_segment_size")?;
+    let segment_size = zext_to_i64(ctx, segment_size.into_int_value())?;
+    let src_end = ctx.builder.build_int_add(src_ex, len_ex, "src_end")?;
+    let out_of_bounds =
+        ctx.builder
+            .build_int_compare(IntPredicate::UGT, src_end, segment_size, "out_of_bounds")?;
+    gen_trap_if(ctx, exec_env_ptr, out_of_bounds, TrapKind::TableOutOfBounds)?;
+    gen_table_bounds_check(
+        ctx,
+        exec_env_ptr,
+        table,
+        dst_ex,
+        len_ex,
+        TrapKind::TableOutOfBounds,
+    )?;
+
+    let element_segment = ctx.element_segments[elem_index as usize].global;
+    let src_ptr = unsafe {
+        ctx.builder.build_gep(
+            ctx.inkwell_types.i32_type,
+            element_segment.as_pointer_value(),
+            &[src_ex],
+            "src_ptr",
+        )
+    }?;
+    let table_base = gen_table_base(ctx, table)?;
+    let dst_ptr = unsafe {
+        ctx.builder
+            .build_gep(ctx.inkwell_types.i32_type, table_base, &[dst_ex], "dst_ptr")
+    }?;
+    let size = ctx.builder.build_int_mul(
+        len_ex,
+        ctx.inkwell_types.i64_type.const_int(4, false),
+        "copy_size",
+    )?;
+    ctx.builder
+        .build_memcpy(dst_ptr, 4, src_ptr, 4, size)
+        .map_err(|e| anyhow!(e))
+        .context("error build_memcpy")?;
+    Ok(())
+}
+
+pub fn compile_op_elem_drop(ctx: &mut Context<'_, '_>, elem_index: u32) -> Result<()> {
+    let size_ptr = resolve_element_segment_size(ctx, elem_index)?;
+    ctx.builder
+        .build_store(size_ptr, ctx.inkwell_types.i32_type.const_zero())?;
+    Ok(())
+}
+
 pub fn compile_op_memcpy<'a>(
     ctx: &mut Context<'a, '_>,
     exec_env_ptr: &PointerValue<'a>,
     dst_mem: u32,
     src_mem: u32,
 ) -> Result<()> {
-    // TODO: multi memory
-    assert_eq!(dst_mem, 0);
-    assert_eq!(src_mem, 0);
-
-    let len = ctx.pop().expect("stack empty");
-    let src = ctx.pop().expect("stack empty");
-    let dst = ctx.pop().expect("stack empty");
-    let src_addr = resolve_pointer(ctx, exec_env_ptr, src.into_int_value());
-    let dst_addr = resolve_pointer(ctx, exec_env_ptr, dst.into_int_value());
+    let len = ctx.pop()?;
+    let src = ctx.pop()?;
+    let dst = ctx.pop()?;
+    // Bulk memory operations must trap before writing anything
+    let len_ex = zext_to_i64(ctx, len.into_int_value())?;
+    let src_ex = zext_to_i64(ctx, src.into_int_value())?;
+    let dst_ex = zext_to_i64(ctx, dst.into_int_value())?;
+    gen_bounds_check(ctx, exec_env_ptr, src_mem, src_ex, len_ex)?;
+    gen_bounds_check(ctx, exec_env_ptr, dst_mem, dst_ex, len_ex)?;
+    let src_addr = resolve_pointer(ctx, exec_env_ptr, src_mem, src_ex)?;
+    let dst_addr = resolve_pointer(ctx, exec_env_ptr, dst_mem, dst_ex)?;
+    // The length is i32 if either memory is 32-bit
     ctx.builder
-        .build_memcpy(dst_addr, 1, src_addr, 1, len.into_int_value())
+        .build_memcpy(dst_addr, 1, src_addr, 1, len_ex)
         .map_err(|e| anyhow!(e))
         .context("error build_memcpy")?;
     Ok(())
@@ -1224,17 +1669,19 @@ pub fn compile_op_memory_fill<'a>(
     exec_env_ptr: &PointerValue<'a>,
     mem: u32,
 ) -> Result<()> {
-    // TODO: multi memory
-    assert_eq!(mem, 0);
-
-    let len = ctx.pop().expect("stack empty");
-    let val = ctx.pop().expect("stack empty");
-    let dst = ctx.pop().expect("stack empty");
-    let dst_addr = resolve_pointer(ctx, exec_env_ptr, dst.into_int_value());
-    let val_i8 = ctx
-        .builder
-        .build_int_truncate(val.into_int_value(), ctx.inkwell_types.i8_type, "val_i8")
-        .expect("error build int truncate");
+    let len = ctx.pop()?;
+    let val = ctx.pop()?;
+    let dst = ctx.pop()?;
+    // Bulk memory operations must trap before writing anything
+    let len_ex = zext_to_i64(ctx, len.into_int_value())?;
+    let dst_ex = zext_to_i64(ctx, dst.into_int_value())?;
+    gen_bounds_check(ctx, exec_env_ptr, mem, dst_ex, len_ex)?;
+    let dst_addr = resolve_pointer(ctx, exec_env_ptr, mem, dst_ex)?;
+    let val_i8 = ctx.builder.build_int_truncate(
+        val.into_int_value(),
+        ctx.inkwell_types.i8_type,
+        "val_i8",
+    )?;
     ctx.builder
         .build_memset(dst_addr, 1, val_i8, len.into_int_value())
         .map_err(|e| anyhow!(e))
@@ -1242,12 +1689,210 @@ pub fn compile_op_memory_fill<'a>(
     Ok(())
 }
 
+/// Get the pointer to the current size of the data segment.
+fn resolve_data_segment_size<'a>(
+    ctx: &mut Context<'a, '_>,
+    data_index: u32,
+) -> Result<PointerValue<'a>> {
+    if data_index as usize >= ctx.data_segments.len() {
+        bail!("Unknown data segment {}", data_index);
+    }
+    let data_segment_sizes = ctx
+        .global_data_segment_sizes
+        .expect("should define data segment sizes");
+    let size_ptr = unsafe {
+        ctx.builder.build_gep(
+            ctx.inkwell_types.i32_type,
+            data_segment_sizes.as_pointer_value(),
+            &[ctx
+                .inkwell_types
+                .i32_type
+                .const_int(data_index as u64, false)],
+            "data_segment_size_ptr",
+        )
+    }?;
+    Ok(size_ptr)
+}
+
+pub fn compile_op_memory_init<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    data_index: u32,
+    mem: u32,
+) -> Result<()> {
+    let len = ctx.pop()?;
+    let src = ctx.pop()?;
+    let dst = ctx.pop()?;
+    let len_ex = zext_to_i64(ctx, len.into_int_value())?;
+    let src_ex = zext_to_i64(ctx, src.into_int_value())?;
+    let dst_ex = zext_to_i64(ctx, dst.into_int_value())?;
+
+    // The source must be inside the segment, whose size is 0 once it is dropped
+    let size_ptr = resolve_data_segment_size(ctx, data_index)?;
+    let segment_size =
+        ctx.builder
+            .build_load(ctx.inkwell_types.i32_type, size_ptr, "data_segment_size")?;
+    let segment_size = zext_to_i64(ctx, segment_size.into_int_value())?;
+    let src_end = ctx.builder.build_int_add(src_ex, len_ex, "src_end")?;
+    let out_of_bounds =
+        ctx.builder
+            .build_int_compare(IntPredicate::UGT, src_end, segment_size, "out_of_bounds")?;
+    gen_trap_if(
+        ctx,
+        exec_env_ptr,
+        out_of_bounds,
+        TrapKind::MemoryOutOfBounds,
+    )?;
+    gen_bounds_check(ctx, exec_env_ptr, mem, dst_ex, len_ex)?;
+
+    let data_segment = ctx.data_segments[data_index as usize].global;
+    let src_addr = unsafe {
+        ctx.builder.build_gep(
+            ctx.inkwell_types.i8_type,
+            data_segment.as_pointer_value(),
+            &[src_ex],
+            "src_addr",
+        )
+    }?;
+    let dst_addr = resolve_pointer(ctx, exec_env_ptr, mem, dst_ex)?;
+    ctx.builder
+        .build_memcpy(dst_addr, 1, src_addr, 1, len_ex)
+        .map_err(|e| anyhow!(e))
+        .context("error build_memcpy")?;
+    Ok(())
+}
+
+pub fn compile_op_data_drop(ctx: &mut Context<'_, '_>, data_index: u32) -> Result<()> {
+    let size_ptr = resolve_data_segment_size(ctx, data_index)?;
+    ctx.builder
+        .build_store(size_ptr, ctx.inkwell_types.i32_type.const_zero())?;
+    Ok(())
+}
+
+/// Integer division and remainder, trapping on zero divisors and signed overflow.
+pub fn compile_op_int_div<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    signed: bool,
+    rem: bool,
+) -> Result<()> {
+    let (v1, v2) = ctx.pop2()?;
+    let (lhs, rhs) = (v1.into_int_value(), v2.into_int_value());
+    let int_type = lhs.get_type();
+
+    // division by zero
+    let is_zero =
+        ctx.builder
+            .build_int_compare(IntPredicate::EQ, rhs, int_type.const_zero(), "is_zero")?;
+    gen_trap_if(ctx, exec_env_ptr, is_zero, TrapKind::IntegerDivideByZero)?;
+
+    let res = if signed {
+        // INT_MIN / -1 overflows
+        let int_min = int_type.const_int(1 << (int_type.get_bit_width() - 1), false);
+        let is_min = ctx
+            .builder
+            .build_int_compare(IntPredicate::EQ, lhs, int_min, "is_min")?;
+        let is_neg_one = ctx.builder.build_int_compare(
+            IntPredicate::EQ,
+            rhs,
+            int_type.const_all_ones(),
+            "is_neg_one",
+        )?;
+        let overflow = ctx.builder.build_and(is_min, is_neg_one, "overflow")?;
+        if rem {
+            // INT_MIN % -1 is 0 in wasm, but undefined in LLVM
+            let rhs = ctx
+                .builder
+                .build_select(overflow, int_type.const_int(1, false), rhs, "")?
+                .into_int_value();
+            ctx.builder.build_int_signed_rem(lhs, rhs, "")?
+        } else {
+            gen_trap_if(ctx, exec_env_ptr, overflow, TrapKind::IntegerOverflow)?;
+            ctx.builder.build_int_signed_div(lhs, rhs, "")?
+        }
+    } else if rem {
+        ctx.builder.build_int_unsigned_rem(lhs, rhs, "")?
+    } else {
+        ctx.builder.build_int_unsigned_div(lhs, rhs, "")?
+    };
+    ctx.push(res.as_basic_value_enum());
+    Ok(())
+}
+
+/// Float-to-int conversion, trapping on NaN and values out of the range of `int_type`.
+pub fn compile_op_trunc<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    int_type: IntType<'a>,
+    signed: bool,
+) -> Result<()> {
+    let v = ctx.pop()?.into_float_value();
+    let float_type = v.get_type();
+
+    // NaN
+    let is_nan = ctx
+        .builder
+        .build_float_compare(FloatPredicate::UNO, v, v, "is_nan")?;
+    gen_trap_if(
+        ctx,
+        exec_env_ptr,
+        is_nan,
+        TrapKind::InvalidConversionToInteger,
+    )?;
+
+    // Out of range: the bounds are exclusive unless the minimum itself is representable
+    let bits = int_type.get_bit_width() as i32;
+    let (lower_pred, lower, upper) = if !signed {
+        (FloatPredicate::OGT, -1.0, 2f64.powi(bits))
+    } else if float_type == ctx.inkwell_types.f64_type && bits == 32 {
+        (FloatPredicate::OGT, -(2f64.powi(31)) - 1.0, 2f64.powi(31))
+    } else {
+        (
+            FloatPredicate::OGE,
+            -(2f64.powi(bits - 1)),
+            2f64.powi(bits - 1),
+        )
+    };
+    let above_lower =
+        ctx.builder
+            .build_float_compare(lower_pred, v, float_type.const_float(lower), "")?;
+    let below_upper = ctx.builder.build_float_compare(
+        FloatPredicate::OLT,
+        v,
+        float_type.const_float(upper),
+        "",
+    )?;
+    let in_range = ctx
+        .builder
+        .build_and(above_lower, below_upper, "in_range")?;
+    let out_of_range = ctx.builder.build_not(in_range, "out_of_range")?;
+    gen_trap_if(ctx, exec_env_ptr, out_of_range, TrapKind::IntegerOverflow)?;
+
+    let converted = if signed {
+        ctx.builder
+            .build_float_to_signed_int(v, int_type, "truncs")?
+    } else {
+        ctx.builder
+            .build_float_to_unsigned_int(v, int_type, "truncu")?
+    };
+    ctx.push(converted.as_basic_value_enum());
+    Ok(())
+}
+
+/// Zero-extend an i32 to i64. An i64 (e.g. an address of a 64-bit memory) is returned as it is.
+fn zext_to_i64<'a>(ctx: &Context<'a, '_>, value: IntValue<'a>) -> Result<IntValue<'a>> {
+    Ok(ctx
+        .builder
+        .build_int_z_extend_or_bit_cast(value, ctx.inkwell_types.i64_type, "")?)
+}
+
 fn resolve_pointer<'a>(
     ctx: &mut Context<'a, '_>,
     exec_env_ptr: &PointerValue<'a>,
+    mem: u32,
     offset: IntValue<'a>,
-) -> PointerValue<'a> {
-    let memory_base = gen_memory_base(ctx, exec_env_ptr).expect("error gen memory base");
+) -> Result<PointerValue<'a>> {
+    let memory_base = gen_memory_base(ctx, exec_env_ptr, mem)?;
     // calculate base + offset
     let dst_addr = unsafe {
         ctx.builder.build_gep(
@@ -1256,13 +1901,48 @@ fn resolve_pointer<'a>(
             &[offset],
             "resolved_addr",
         )
-    }
-    .expect("should build gep");
+    }?;
     // cast pointer value
-    ctx.builder
-        .build_bit_cast(dst_addr, ctx.inkwell_types.ptr_type, "bit_casted")
-        .expect("should build bit_cast")
-        .into_pointer_value()
+    Ok(ctx
+        .builder
+        .build_bit_cast(dst_addr, ctx.inkwell_types.ptr_type, "bit_casted")?
+        .into_pointer_value())
+}
+
+/// Get the pointer to the bytes accessed by a memory instruction, checking the bounds if necessary.
+pub(super) fn resolve_memarg_pointer<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    address_operand: IntValue<'a>,
+    memarg: &MemArg,
+    access_type: BasicTypeEnum<'a>,
+) -> Result<PointerValue<'a>> {
+    let address_operand_ex = zext_to_i64(ctx, address_operand)?;
+    let memarg_offset = ctx.inkwell_types.i64_type.const_int(memarg.offset, false);
+    let offset = ctx
+        .builder
+        .build_int_add(address_operand_ex, memarg_offset, "offset")?;
+
+    // check bounds
+    // Guard pages only cover memory 0 and cannot cover 64-bit memories
+    let memory64 = is_memory64(ctx, memarg.memory);
+    if !uses_guard_pages(ctx, memarg.memory) {
+        if memory64 {
+            // the effective address must not wrap around
+            let wrapped = ctx.builder.build_int_compare(
+                IntPredicate::ULT,
+                offset,
+                address_operand_ex,
+                "wrapped",
+            )?;
+            gen_trap_if(ctx, exec_env_ptr, wrapped, TrapKind::MemoryOutOfBounds)?;
+        }
+        let size = access_type.size_of().expect("should be sized type");
+        gen_bounds_check(ctx, exec_env_ptr, memarg.memory, offset, size)?;
+    }
+
+    // get actual virtual address
+    resolve_pointer(ctx, exec_env_ptr, memarg.memory, offset)
 }
 
 pub fn compile_op_load<'a>(
@@ -1275,45 +1955,32 @@ pub fn compile_op_load<'a>(
     require_extend: bool,
 ) -> Result<()> {
     // offset
-    let address_operand = ctx.pop().expect("stack empty").into_int_value();
-    let address_operand_ex = ctx
-        .builder
-        .build_int_z_extend(address_operand, ctx.inkwell_types.i64_type, "")
-        .expect("error build int z extend");
-    let memarg_offset = ctx.inkwell_types.i64_type.const_int(memarg.offset, false);
-    let offset = ctx
-        .builder
-        .build_int_add(address_operand_ex, memarg_offset, "offset")
-        .expect("error build int add");
-
-    // get actual virtual address
-    let dst_addr = resolve_pointer(ctx, exec_env_ptr, offset);
+    let address_operand = ctx.pop()?.into_int_value();
+    let dst_addr = resolve_memarg_pointer(ctx, exec_env_ptr, address_operand, memarg, load_type)?;
     // load value
-    let result = ctx
-        .builder
-        .build_load(load_type, dst_addr, "loaded")
-        .expect("error build load");
+    let result = ctx.builder.build_load(load_type, dst_addr, "loaded")?;
+    set_memory_access_metadata(
+        ctx,
+        result
+            .as_instruction_value()
+            .expect("should be instruction"),
+        memarg.memory,
+    );
 
     // push loaded value
     if require_extend {
         // extend value
         let extended_result = match signed {
-            true => ctx
-                .builder
-                .build_int_s_extend(
-                    result.into_int_value(),
-                    extended_type.into_int_type(),
-                    "loaded_extended",
-                )
-                .expect("error build int s extend"),
-            false => ctx
-                .builder
-                .build_int_z_extend(
-                    result.into_int_value(),
-                    extended_type.into_int_type(),
-                    "loaded_extended",
-                )
-                .expect("error build int z extend"),
+            true => ctx.builder.build_int_s_extend(
+                result.into_int_value(),
+                extended_type.into_int_type(),
+                "loaded_extended",
+            )?,
+            false => ctx.builder.build_int_z_extend(
+                result.into_int_value(),
+                extended_type.into_int_type(),
+                "loaded_extended",
+            )?,
         };
         ctx.push(extended_result.as_basic_value_enum());
     } else {
@@ -1330,40 +1997,23 @@ pub fn compile_op_store<'a>(
     require_narrow: bool,
 ) -> Result<()> {
     // value
-    let value = ctx.pop().expect("stack empty");
+    let value = ctx.pop()?;
 
     // offset
-    let address_operand = ctx.pop().expect("stack empty").into_int_value();
-    let address_operand_ex = ctx
-        .builder
-        .build_int_z_extend(address_operand, ctx.inkwell_types.i64_type, "")
-        .expect("error build int z extend");
-    let memarg_offset = ctx.inkwell_types.i64_type.const_int(memarg.offset, false);
-    let offset = ctx
-        .builder
-        .build_int_add(address_operand_ex, memarg_offset, "offset")
-        .expect("error build int add");
+    let address_operand = ctx.pop()?.into_int_value();
+    let dst_addr = resolve_memarg_pointer(ctx, exec_env_ptr, address_operand, memarg, store_type)?;
 
-    // get actual virtual address
-    let dst_addr = resolve_pointer(ctx, exec_env_ptr, offset);
-
-    if require_narrow {
-        let narrow_value = ctx
-            .builder
-            .build_int_truncate(
-                value.into_int_value(),
-                store_type.into_int_type(),
-                "narrow_value",
-            )
-            .expect("error build int truncate");
-        ctx.builder
-            .build_store(dst_addr, narrow_value)
-            .expect("should build store");
+    let store = if require_narrow {
+        let narrow_value = ctx.builder.build_int_truncate(
+            value.into_int_value(),
+            store_type.into_int_type(),
+            "narrow_value",
+        )?;
+        ctx.builder.build_store(dst_addr, narrow_value)?
     } else {
-        ctx.builder
-            .build_store(dst_addr, value)
-            .expect("should build store");
-    }
+        ctx.builder.build_store(dst_addr, value)?
+    };
+    set_memory_access_metadata(ctx, store, memarg.memory);
 
     Ok(())
 }
@@ -1,14 +1,14 @@
 use anyhow::{bail, Result};
-use inkwell::{
-    types::BasicTypeEnum,
-    values::{BasicValue, BasicValueEnum},
-    AddressSpace,
-};
-use wasmparser::{DataKind, DataSectionReader, GlobalSectionReader, Operator};
+use inkwell::{module::Linkage, AddressSpace};
+use wasmparser::{DataKind, DataSectionReader, GlobalSectionReader};
 
 use crate::{
-    compile::{compile_type::wasmty_to_llvmty, helper::gen_memory_base},
-    context::{Context, Global},
+    compile::{
+        compile_type::wasmty_to_llvmty,
+        const_expr::{gen_const_expr, gen_const_int_expr, is_const_value},
+        helper::{gen_bounds_check, gen_memory_base},
+    },
+    context::{Context, DataSegment, Global},
 };
 
 pub(super) fn compile_global_section(
@@ -23,57 +23,50 @@ pub(super) fn compile_global_section(
         let ty = wasmty_to_llvmty(ctx, &global.ty.content_type)?;
 
         // Get initial value
-        let init_expr_binary_reader = &mut global.init_expr.get_binary_reader();
-        let init_val: BasicValueEnum = match init_expr_binary_reader
-            .read_operator()
-            .expect("fail read_operator")
-        {
-            Operator::I32Const { value } => ty
-                .into_int_type()
-                .const_int(value as u64, false)
-                .as_basic_value_enum(),
-            Operator::I64Const { value } => ty
-                .into_int_type()
-                .const_int(value as u64, false)
-                .as_basic_value_enum(),
-            Operator::F32Const { value } => ty
-                .into_float_type()
-                .const_float(f32::from_bits(value.bits()).into())
-                .as_basic_value_enum(),
-            Operator::F64Const { value } => ty
-                .into_float_type()
-                .const_float(f64::from_bits(value.bits()))
-                .as_basic_value_enum(),
-            _ => {
-                bail!("Unsupposed Global const value");
-            }
-        };
+        let init_val = gen_const_expr(ctx, &global.init_expr)?;
+        if init_val.get_type() != ty {
+            bail!("GlobalSection: type mismatch of global {}", i);
+        }
+        let is_const = is_const_value(&init_val);
+
+        if !global.ty.mutable && is_const {
+            // declare as BasicValueEnum
+            ctx.globals.push(Global::Const { value: init_val });
+            continue;
+        }
 
-        // declare
+        // Declare GlobalValue
+        let global_value = ctx
+            .module
+            .add_global(ty, Some(AddressSpace::default()), &gname);
+        if is_const {
+            global_value.set_initializer(&init_val);
+        } else {
+            // The initial value depends on imported globals, so store it at instantiation
+            global_value.set_initializer(&ty.const_zero());
+        }
+        // Mutable globals are also reset at instantiation, since libraries can be
+        // instantiated again after `wanco_teardown`
+        if !is_const || global.ty.mutable {
+            ctx.builder
+                .build_store(global_value.as_pointer_value(), init_val)?;
+        }
         if global.ty.mutable {
-            // Declare GlobalValue
-            let global_value = ctx
-                .module
-                .add_global(ty, Some(AddressSpace::default()), &gname);
-            match init_val.get_type() {
-                BasicTypeEnum::IntType(..) => {
-                    global_value.set_initializer(&init_val.into_int_value());
-                }
-                BasicTypeEnum::FloatType(..) => {
-                    global_value.set_initializer(&init_val.into_float_value());
-                }
-                _ => {
-                    bail!("Unsupposed Global mutable value");
-                }
+            // wasi-threads instantiates the module for each thread,
+            // so every thread has its own copy of mutable globals.
+            if ctx.memories.iter().any(|memory| memory.shared) {
+                global_value.set_thread_local(true);
             }
             ctx.globals.push(Global::Mut {
                 ptr: global_value,
                 ty,
             });
-            global_value.set_initializer(&init_val);
         } else {
-            // declare as BasicValueEnum
-            ctx.globals.push(Global::Const { value: init_val });
+            global_value.set_linkage(Linkage::Private);
+            ctx.globals.push(Global::Immutable {
+                ptr: global_value,
+                ty,
+            });
         }
     }
     log::debug!("- declare {} globals", ctx.globals.len());
@@ -84,6 +77,15 @@ pub(super) fn compile_data_section(
     ctx: &mut Context<'_, '_>,
     data_segs: DataSectionReader,
 ) -> Result<()> {
+    let aot_main = ctx
+        .module
+        .get_function("aot_main")
+        .expect("should define aot_main");
+    let exec_env_ptr = aot_main
+        .get_first_param()
+        .expect("should have &exec_env")
+        .into_pointer_value();
+    ctx.current_fn = Some(aot_main);
     // Move position to aot_main %init
     ctx.builder
         .position_at_end(ctx.aot_init_block.expect("should define aot_main %init"));
@@ -96,84 +98,111 @@ pub(super) fn compile_data_section(
             data.range.start,
             data.range.end
         );
+
+        // Keep every segment as read-only data since passive ones are used by memory.init
+        let size = data.data.len();
+        log::debug!("- data size = {}", size);
+        let array_ty = ctx.inkwell_types.i8_type.array_type(size as u32);
+        let data_segment = ctx.module.add_global(
+            array_ty,
+            Some(AddressSpace::default()),
+            &format!("data_segment_{}", ctx.data_segments.len()),
+        );
+        let initializer = ctx.ictx.const_string(data.data, false);
+        data_segment.set_initializer(&initializer);
+        data_segment.set_constant(true);
+        data_segment.set_linkage(Linkage::Private);
+
         match data.kind {
             DataKind::Passive => {
-                log::error!("DataKind::Passive is not supported")
+                ctx.data_segments.push(DataSegment {
+                    global: data_segment,
+                    size,
+                    passive: true,
+                });
             }
             DataKind::Active {
-                memory_index: _,
+                memory_index,
                 offset_expr,
             } => {
-                // Make array from data
-                let size = data.data.len();
-                log::debug!("- data size = {}", size);
-                let array_ty = ctx.inkwell_types.i8_type.array_type(size as u32);
-                let data_segment_initializer = ctx.module.add_global(
-                    array_ty,
-                    Some(AddressSpace::default()),
-                    "global_memory_initializer",
-                );
-
-                // Initialize array
-                let mut data_intvalue = Vec::new();
-                for d in data.data {
-                    let d_intvalue = ctx.inkwell_types.i8_type.const_int(*d as u64, false);
-                    data_intvalue.push(d_intvalue);
-                }
-                log::debug!("- data_intvalue.len = {}", data_intvalue.len());
-                let initializer = ctx.inkwell_types.i8_type.const_array(&data_intvalue);
-                data_segment_initializer.set_initializer(&initializer);
+                // Active segments are dropped after instantiation
+                ctx.data_segments.push(DataSegment {
+                    global: data_segment,
+                    size,
+                    passive: false,
+                });
 
                 // Get offset from the base of the Linear Memory
-                let offset_op = offset_expr
-                    .get_binary_reader()
-                    .read_operator()
-                    .expect("failed to get data section offset");
-                let offset = match offset_op {
-                    Operator::I32Const { value } => value,
-                    _ => unreachable!("unsupported offset type"),
-                };
-                log::debug!("- offset = 0x{:x}", offset);
-                let offset_int = ctx.inkwell_types.i64_type.const_int(offset as u64, false);
-
-                // move position to aot_main init
-                ctx.builder
-                    .position_at_end(ctx.aot_init_block.expect("should define aot_init_block"));
-                let exec_env_ptr = ctx
-                    .module
-                    .get_function("aot_main")
-                    .expect("should define aot_main")
-                    .get_first_param()
-                    .expect("should have &exec_env")
-                    .into_pointer_value();
-                let memory_base =
-                    gen_memory_base(ctx, &exec_env_ptr).expect("should gen memory_base");
-                let memory_base_int = ctx
-                    .builder
-                    .build_ptr_to_int(memory_base, ctx.inkwell_types.i64_type, "memory_base_int")
-                    .expect("should build ptr to int");
+                // The offset is i64 for a 64-bit memory
+                let offset = gen_const_int_expr(ctx, &offset_expr)?;
+                let offset_int = ctx.builder.build_int_z_extend_or_bit_cast(
+                    offset,
+                    ctx.inkwell_types.i64_type,
+                    "offset",
+                )?;
+                log::debug!("- offset = {:?}", offset_int.get_zero_extended_constant());
+                // The offset may be given by an imported global, so the segment is checked
+                // to fit in the memory at instantiation
+                let size_int = ctx.inkwell_types.i64_type.const_int(size as u64, false);
+                gen_bounds_check(ctx, &exec_env_ptr, memory_index, offset_int, size_int)?;
+                let memory_base = gen_memory_base(ctx, &exec_env_ptr, memory_index)?;
+                let memory_base_int = ctx.builder.build_ptr_to_int(
+                    memory_base,
+                    ctx.inkwell_types.i64_type,
+                    "memory_base_int",
+                )?;
                 let dest_int =
                     ctx.builder
                         .build_int_add(memory_base_int, offset_int, "dest_int")?;
-                let dest_ptr = ctx
-                    .builder
-                    .build_int_to_ptr(dest_int, ctx.inkwell_types.ptr_type, "dest_ptr")
-                    .expect("should build int to ptr");
+                let dest_ptr = ctx.builder.build_int_to_ptr(
+                    dest_int,
+                    ctx.inkwell_types.ptr_type,
+                    "dest_ptr",
+                )?;
 
                 // Memcpy from initializer to Linear Memory
-                ctx.builder
-                    .build_memcpy(
-                        dest_ptr,
-                        1,
-                        data_segment_initializer.as_pointer_value(),
-                        1,
-                        ctx.inkwell_types
-                            .i64_type
-                            .const_int(data.data.len() as u64, false),
-                    )
-                    .expect("should build memcpy");
+                ctx.builder.build_memcpy(
+                    dest_ptr,
+                    1,
+                    data_segment.as_pointer_value(),
+                    1,
+                    size_int,
+                )?;
             }
         }
     }
+
+    // The bounds checks split the init block
+    ctx.aot_init_block = ctx.builder.get_insert_block();
+    ctx.current_fn = None;
     Ok(())
 }
+
+/// Declare the current sizes of data segments, which become 0 when they are dropped.
+/// This is always declared because lib-rt statically links it.
+pub(super) fn declare_data_segment_sizes(ctx: &mut Context<'_, '_>) {
+    let mut sizes = Vec::new();
+    for segment in &ctx.data_segments {
+        let size = if segment.passive { segment.size } else { 0 };
+        sizes.push(ctx.inkwell_types.i32_type.const_int(size as u64, false));
+    }
+    let data_segment_sizes = ctx.module.add_global(
+        ctx.inkwell_types.i32_type.array_type(sizes.len() as u32),
+        Some(AddressSpace::default()),
+        "DATA_SEGMENT_SIZES",
+    );
+    data_segment_sizes.set_initializer(&ctx.inkwell_types.i32_type.const_array(&sizes));
+    ctx.global_data_segment_sizes = Some(data_segment_sizes);
+
+    let num_data_segments = ctx.module.add_global(
+        ctx.inkwell_types.i32_type,
+        Some(AddressSpace::default()),
+        "NUM_DATA_SEGMENTS",
+    );
+    num_data_segments.set_initializer(
+        &ctx.inkwell_types
+            .i32_type
+            .const_int(sizes.len() as u64, false),
+    );
+    num_data_segments.set_constant(true);
+}
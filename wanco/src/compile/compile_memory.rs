@@ -1,27 +1,113 @@
-use anyhow::Result;
-use wasmparser::MemorySectionReader;
+use anyhow::{bail, Result};
+use wasmparser::{MemorySectionReader, MemoryType};
 
 use crate::context::Context;
 
+use super::helper::{get_extra_memories, uses_guard_pages};
+
 pub(super) fn compile_memory_section(
     ctx: &mut Context<'_, '_>,
     memories: MemorySectionReader,
 ) -> Result<()> {
-    // Set initial memory size
-    let mut size: u32 = 0;
-    for (i, memory) in memories.into_iter().enumerate() {
+    for memory in memories {
         let memory = memory?;
-        size += memory.initial as u32;
-        log::debug!("- memory[{}] = {:?}", i, memory);
+        log::debug!("- memory[{}] = {:?}", ctx.memories.len(), memory);
+        ctx.memories.push(memory);
     }
-    let init_memory_size =
-        ctx.module
-            .add_global(ctx.inkwell_types.i32_type, None, "INIT_MEMORY_SIZE");
-    init_memory_size.set_initializer(&ctx.inkwell_types.i32_type.const_int(size as u64, false));
-    init_memory_size.set_constant(true);
 
     // move position to aot_main %init
     ctx.builder
         .position_at_end(ctx.aot_init_block.expect("should define aot_main %init"));
     Ok(())
 }
+
+// Hard limits of the number of pages of 32-bit and 64-bit memories
+const MAX_PAGES_32: u64 = 1 << 16;
+const MAX_PAGES_64: u64 = 1 << 48;
+
+/// Get the maximum number of pages of the memory, which is the hard limit if not declared.
+fn max_pages(memory: &MemoryType) -> u64 {
+    let hard_limit = if memory.memory64 {
+        MAX_PAGES_64
+    } else {
+        MAX_PAGES_32
+    };
+    memory.maximum.map_or(hard_limit, |max| max.min(hard_limit))
+}
+
+/// Define the properties of the memories used by the runtime to allocate them.
+/// Memory 0 is pointed by ExecEnv, and the others are in EXTRA_MEMORIES.
+pub(super) fn declare_memories(ctx: &mut Context<'_, '_>) -> Result<()> {
+    if ctx.memories.iter().skip(1).any(|memory| memory.shared) {
+        bail!("Shared memory is only supported as memory 0");
+    }
+
+    // Set initial memory size of memory 0
+    let size = ctx.memories.first().map_or(0, |memory| memory.initial);
+    let init_memory_size =
+        ctx.module
+            .add_global(ctx.inkwell_types.i64_type, None, "INIT_MEMORY_SIZE");
+    init_memory_size.set_initializer(&ctx.inkwell_types.i64_type.const_int(size, false));
+    init_memory_size.set_constant(true);
+
+    // Set maximum memory size of memory 0
+    let max = ctx.memories.first().map_or(0, max_pages);
+    let max_memory_size =
+        ctx.module
+            .add_global(ctx.inkwell_types.i64_type, None, "MAX_MEMORY_SIZE");
+    max_memory_size.set_initializer(&ctx.inkwell_types.i64_type.const_int(max, false));
+    max_memory_size.set_constant(true);
+
+    // Tell the runtime to reserve guard pages around the linear memory
+    let use_guard_pages =
+        ctx.module
+            .add_global(ctx.inkwell_types.i32_type, None, "USE_GUARD_PAGES");
+    let guard_page = uses_guard_pages(ctx, 0);
+    use_guard_pages.set_initializer(
+        &ctx.inkwell_types
+            .i32_type
+            .const_int(guard_page as u64, false),
+    );
+    use_guard_pages.set_constant(true);
+
+    // A shared memory must not move while other threads access it
+    let shared_memory = ctx
+        .module
+        .add_global(ctx.inkwell_types.i32_type, None, "SHARED_MEMORY");
+    let shared = ctx.memories.first().is_some_and(|memory| memory.shared);
+    shared_memory.set_initializer(&ctx.inkwell_types.i32_type.const_int(shared as u64, false));
+    shared_memory.set_constant(true);
+
+    // The runtime allocates the other memories and sets their bases
+    let memory_type = ctx.memory_type.expect("should define memory_type");
+    let extra_memories: Vec<_> = ctx
+        .memories
+        .iter()
+        .skip(1)
+        .map(|memory| {
+            memory_type.const_named_struct(&[
+                ctx.inkwell_types.ptr_type.const_null().into(),
+                ctx.inkwell_types
+                    .i64_type
+                    .const_int(memory.initial, false)
+                    .into(),
+                ctx.inkwell_types
+                    .i64_type
+                    .const_int(max_pages(memory), false)
+                    .into(),
+            ])
+        })
+        .collect();
+    let global_extra_memories = get_extra_memories(ctx);
+    global_extra_memories.set_initializer(&memory_type.const_array(&extra_memories));
+    let num_extra_memories =
+        ctx.module
+            .add_global(ctx.inkwell_types.i32_type, None, "NUM_EXTRA_MEMORIES");
+    num_extra_memories.set_initializer(
+        &ctx.inkwell_types
+            .i32_type
+            .const_int(extra_memories.len() as u64, false),
+    );
+    num_extra_memories.set_constant(true);
+    Ok(())
+}
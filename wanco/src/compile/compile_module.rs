@@ -1,23 +1,37 @@
 use anyhow::{bail, Result};
 use inkwell::{
     attributes::Attribute,
-    values::{IntValue, PointerValue},
+    module::Linkage,
+    values::{FunctionValue, IntValue, PointerValue, StructValue},
     AddressSpace,
 };
 use wasmparser::{
     Chunk, Element, ElementItems, ElementKind, ElementSectionReader, ExportSectionReader,
-    FunctionSectionReader, ImportSectionReader, MemoryType, Operator, Parser, Payload,
-    SectionLimited, TableSectionReader, TypeRef,
+    FunctionSectionReader, ImportSectionReader, KnownCustom, Name, NameSectionReader, Parser,
+    Payload, SectionLimited, TableInit, TableSectionReader, TagSectionReader, TypeRef,
 };
 
 use crate::{
     compile::{
         compile_function::compile_function,
-        compile_global::{compile_data_section, compile_global_section},
-        compile_memory::compile_memory_section,
-        compile_type::compile_type_section,
+        compile_global::{
+            compile_data_section, compile_global_section, declare_data_segment_sizes,
+        },
+        compile_memory::{compile_memory_section, declare_memories},
+        compile_type::{compile_type_section, null_ref},
+        const_expr::gen_const_int_expr,
+        cr::restore::declare_restore_entries,
+        error::CompileError,
+        helper::{gen_table_base, gen_table_bounds_check, TAIL_CALL_CONV},
+        host::{
+            declare_host_function, declare_host_global, declare_host_table, gen_imports_header,
+            is_host_module,
+        },
+        library::gen_library_exports,
+        trap::TrapKind,
     },
-    context::{Context, Function},
+    context::{Context, ElementSegment, Export, Function, Table},
+    driver::Emit,
 };
 
 use super::synthesize::{finalize, initialize};
@@ -45,7 +59,7 @@ pub fn compile_module(mut data: &[u8], ctx: &mut Context) -> Result<()> {
             Chunk::NeedMoreData(_) => unreachable!(),
         };
 
-        //log::debug!("### {:?}", payload.as_ref().expect("fail get payload"));
+        //log::debug!("### {:?}", payload.as_ref()?);
         match payload {
             Payload::TypeSection(types) => {
                 log::debug!("TypeSection");
@@ -65,7 +79,11 @@ pub fn compile_module(mut data: &[u8], ctx: &mut Context) -> Result<()> {
             }
             Payload::TableSection(tables) => {
                 log::debug!("TableSection");
-                compile_table_section(tables)?;
+                compile_table_section(ctx, tables)?;
+            }
+            Payload::TagSection(tags) => {
+                log::debug!("TagSection");
+                compile_tag_section(ctx, tags)?;
             }
             Payload::GlobalSection(globals) => {
                 log::debug!("GlobalSection]");
@@ -75,6 +93,10 @@ pub fn compile_module(mut data: &[u8], ctx: &mut Context) -> Result<()> {
                 log::debug!("ExportSection");
                 compile_export_section(ctx, exports)?;
             }
+            Payload::StartSection { func, .. } => {
+                log::debug!("StartSection: func[{}]", func);
+                ctx.start_section_function_idx = Some(func);
+            }
             Payload::ElementSection(elements) => {
                 // parse later
                 elements_section = Some(elements);
@@ -86,9 +108,11 @@ pub fn compile_module(mut data: &[u8], ctx: &mut Context) -> Result<()> {
             Payload::CodeSectionEntry(_) => {
                 // parse later
             }
-            Payload::CustomSection(_c) => {
-                log::debug!("CustomSection");
-                log::debug!("TODO:")
+            Payload::CustomSection(custom) => {
+                log::debug!("CustomSection: {}", custom.name());
+                if let KnownCustom::Name(names) = custom.as_known() {
+                    compile_name_section(ctx, names)?;
+                }
             }
             Payload::Version { num, encoding, .. } => {
                 log::debug!("version:{}, encoding: {:?}", num, encoding);
@@ -117,16 +141,25 @@ pub fn compile_module(mut data: &[u8], ctx: &mut Context) -> Result<()> {
 
     log::info!("Compiling functions");
     declare_functions(ctx)?;
-    if let Some(elems) = elements_section {
-        compile_element_section(ctx, elems)?;
+    declare_memories(ctx)?;
+    let active_elements = match elements_section {
+        Some(elems) => compile_element_section(ctx, elems)?,
+        None => vec![],
+    };
+    // The list of tables is always declared because lib-rt statically links it
+    declare_tables(ctx, &active_elements)?;
+    if !ctx.config.no_restore && (ctx.config.enable_cr || ctx.config.legacy_cr) {
+        declare_restore_entries(ctx)?;
     }
+    declare_element_segment_sizes(ctx);
+    declare_data_segment_sizes(ctx);
 
     // pass
     let mut function_bodies = vec![];
     match code_section_data {
         Some(mut code_section_data) => {
             while let Chunk::Parsed { consumed, payload } =
-                parser.parse(code_section_data, false).expect("Error parse")
+                parser.parse(code_section_data, false)?
             {
                 code_section_data = &code_section_data[consumed..];
                 match payload {
@@ -145,13 +178,22 @@ pub fn compile_module(mut data: &[u8], ctx: &mut Context) -> Result<()> {
 
     ctx.current_function_idx = Some(ctx.num_imports);
     for body in function_bodies {
-        compile_function(ctx, body)?;
+        let offset = body.range().start;
+        compile_function(ctx, body)
+            .map_err(|e| CompileError::new(e, ctx.current_function_idx.unwrap(), None, offset))?;
         ctx.current_function_idx = Some(ctx.current_function_idx.unwrap() + 1);
     }
 
     ctx.current_fn = None;
     ctx.current_function_idx = None;
 
+    if ctx.config.emit != Emit::Exe {
+        gen_library_exports(ctx)?;
+    }
+    if ctx.config.imports_header.is_some() {
+        gen_imports_header(ctx);
+    }
+
     finalize(ctx)?;
 
     if ctx.config.enable_cr || ctx.config.legacy_cr {
@@ -161,37 +203,89 @@ pub fn compile_module(mut data: &[u8], ctx: &mut Context) -> Result<()> {
     Ok(())
 }
 
-fn compile_table_section(tables: TableSectionReader) -> Result<()> {
+fn compile_table_section(ctx: &mut Context<'_, '_>, tables: TableSectionReader) -> Result<()> {
     for (i, table) in tables.into_iter().enumerate() {
         let table = table?;
-        log::debug!("- table[{}] size={:?}", i, table.ty.initial);
+        log::debug!("- table[{}] {:?}", i, table.ty);
+        if !matches!(table.init, TableInit::RefNull) {
+            bail!("TableSection: Initializer expression Unsupported");
+        }
+        ctx.tables.push(Table {
+            ty: table.ty,
+            orig_name: None,
+            export_name: None,
+            global: None,
+        });
+    }
+    Ok(())
+}
+
+fn compile_tag_section(ctx: &mut Context<'_, '_>, tags: TagSectionReader) -> Result<()> {
+    for (i, tag) in tags.into_iter().enumerate() {
+        let tag = tag?;
+        log::debug!("- tag[{}] {:?}", i, tag);
+        ctx.tags.push(tag.func_type_idx);
     }
     Ok(())
 }
 
 fn compile_import_section(ctx: &mut Context<'_, '_>, imports: ImportSectionReader) -> Result<()> {
     assert!(ctx.functions.is_empty());
-    ctx.num_imports = imports.count();
     for import in imports {
         let import = import?;
         match import.ty {
             TypeRef::Func(ty) => {
-                let mut name = import.name.to_string();
-                if import.module == "wasi_snapshot_preview1" || import.module == "wasi_unstable" {
-                    name = format!("{}_{}", import.module, name);
-                }
+                let name = if is_host_module(import.module) {
+                    declare_host_function(ctx, import.module, import.name, ty)?
+                } else if import.module == "wasi" {
+                    // wasi-threads (see lib-rt/thread/thread.cc)
+                    if import.name != "thread-spawn" {
+                        bail!("Unknown import {}.{}", import.module, import.name);
+                    }
+                    "wasi_thread_spawn".to_string()
+                } else {
+                    format!("{}_{}", import.module, import.name)
+                };
                 ctx.functions.push(Function {
                     name,
                     type_idx: ty,
                     orig_name: Some((import.module.to_string(), import.name.to_string())),
+                    debug_name: None,
+                });
+            }
+            TypeRef::Memory(_) => {
+                bail!(
+                    "Imported memory {}.{} is not supported",
+                    import.module,
+                    import.name
+                );
+            }
+            TypeRef::Table(ty) => {
+                if !is_host_module(import.module) {
+                    bail!("Unknown import {}.{}", import.module, import.name);
+                }
+                ctx.tables.push(Table {
+                    ty,
+                    orig_name: Some((import.module.to_string(), import.name.to_string())),
+                    export_name: None,
+                    global: None,
                 });
             }
-            TypeRef::Memory(MemoryType {
-                memory64: false, ..
-            }) => {}
-            _ => bail!("Unimplemented import type: {:?}", import.ty),
+            TypeRef::Tag(ty) => {
+                // No other module can throw exceptions, so imported tags are module-local
+                ctx.tags.push(ty.func_type_idx);
+            }
+            TypeRef::Global(ty) => {
+                if !is_host_module(import.module) {
+                    bail!("Unknown import {}.{}", import.module, import.name);
+                }
+                let global = declare_host_global(ctx, import.module, import.name, &ty)?;
+                ctx.globals.push(global);
+            }
         }
     }
+    // Only imported functions precede defined functions in the function index space
+    ctx.num_imports = ctx.functions.len() as u32;
     log::info!("- declare {} functions", ctx.num_imports);
     Ok(())
 }
@@ -200,6 +294,11 @@ fn compile_export_section(ctx: &mut Context<'_, '_>, exports: ExportSectionReade
     for export in exports {
         log::debug!("ExportSection {:?}", export);
         let export = export?;
+        ctx.exports.push(Export {
+            name: export.name.to_string(),
+            kind: export.kind,
+            index: export.index,
+        });
         match export.kind {
             wasmparser::ExternalKind::Func => {
                 log::debug!("Export func[{}] = {}", export.name, export.index);
@@ -208,99 +307,439 @@ fn compile_export_section(ctx: &mut Context<'_, '_>, exports: ExportSectionReade
                     //ctx.functions[export.index as usize].0 = "wanco_start".to_string();
                     ctx.start_function_idx = Some(export.index);
                 }
+                if export.name == "_initialize" {
+                    ctx.initialize_function_idx = Some(export.index);
+                }
+                if export.name == "wasi_thread_start" {
+                    ctx.thread_start_function_idx = Some(export.index);
+                }
+            }
+            wasmparser::ExternalKind::Table => {
+                log::debug!("Export table[{}] = {}", export.index, export.name);
+                let Some(table) = ctx.tables.get_mut(export.index as usize) else {
+                    bail!("ExportSection: unknown table {}", export.index);
+                };
+                if table.export_name.is_some() {
+                    log::warn!("Table[{}] is exported more than once", export.index);
+                    continue;
+                }
+                table.export_name = Some(export.name.to_string());
             }
             _ => {
-                log::debug!("ExportSection: Exports other than function are not supported");
+                log::debug!(
+                    "ExportSection: Exports other than function and table are not supported"
+                );
             }
         }
     }
     Ok(())
 }
 
-fn compile_element_section(
-    ctx: &mut Context<'_, '_>,
+/// References written to a table at instantiation.
+/// The offset and the elements are LLVM constants unless they read globals determined at instantiation.
+struct ActiveElements<'a> {
+    table_index: u32,
+    offset: IntValue<'a>,
+    elems: Vec<IntValue<'a>>,
+}
+
+impl ActiveElements<'_> {
+    /// Get the offset and the elements if all of them are known at compile time.
+    fn as_constant(&self) -> Option<(usize, Vec<i32>)> {
+        let offset = self.offset.get_zero_extended_constant()? as usize;
+        let elems = self
+            .elems
+            .iter()
+            .map(|elem| elem.get_sign_extended_constant().map(|elem| elem as i32))
+            .collect::<Option<Vec<_>>>()?;
+        Some((offset, elems))
+    }
+}
+
+fn compile_element_section<'a>(
+    ctx: &mut Context<'a, '_>,
     elements: ElementSectionReader,
-) -> Result<()> {
+) -> Result<Vec<ActiveElements<'a>>> {
+    let mut active_elements = Vec::new();
     for element in elements {
         let element = element?;
-        match element.kind {
-            ElementKind::Active {
-                table_index,
-                offset_expr,
-            } => {
-                log::debug!("table[{:?}]", table_index);
-                // TODO: support multiple tables
-                // FIXME: not sure what to do if table_index is None
-                let table_index = table_index.unwrap_or(0);
-                assert_eq!(table_index, 0);
-
-                let offset_op = offset_expr
-                    .get_binary_reader()
-                    .read_operator()
-                    .expect("failed to get data section offset");
-                let offset = match offset_op {
-                    Operator::I32Const { value } => value,
-                    _other => unreachable!("unsupported offset type"),
-                };
-                match element.items {
-                    ElementItems::Functions(elems) => {
-                        // Declare function pointer array
-                        {
-                            let mut fpointers: Vec<PointerValue> = Vec::new();
-                            for f in ctx.function_values.iter() {
-                                fpointers.push(f.as_global_value().as_pointer_value());
-                            }
-                            let fptr_array = ctx
-                                .inkwell_types
-                                .ptr_type
-                                .array_type(fpointers.len() as u32);
-                            let global_fptr_array = ctx.module.add_global(
-                                fptr_array,
-                                Some(AddressSpace::default()),
-                                "GLOBAL_FPTR_ARRAY",
-                            );
-                            global_fptr_array.set_constant(true);
-                            let initializer = ctx.inkwell_types.ptr_type.const_array(&fpointers);
-                            global_fptr_array.set_initializer(&initializer);
-                            ctx.global_fptr_array = Some(global_fptr_array);
-                        }
-                        // Declare function table
-                        {
-                            let table_size = elems.count() + offset as u32;
-                            let idx_array_type = ctx.inkwell_types.i32_type.array_type(table_size);
-                            let global_table = ctx.module.add_global(
-                                idx_array_type,
-                                Some(AddressSpace::default()),
-                                "global_table",
-                            );
-                            ctx.global_table = Some(global_table);
-                            ctx.global_table_size = Some(table_size as usize);
-
-                            // Initialize function index array
-                            let mut fn_indices: Vec<IntValue> = Vec::new();
-                            for _ in 0..offset {
-                                fn_indices.push(ctx.inkwell_types.i32_type.const_int(0, false));
-                            }
-                            for (i, elem) in elems.into_iter().enumerate() {
-                                let elem = elem?;
-                                fn_indices
-                                    .push(ctx.inkwell_types.i32_type.const_int(elem as u64, false));
-                                log::debug!("- elem[{}] = Function[{}]", i + offset as usize, elem);
-                            }
-                            let initializer = ctx.inkwell_types.i32_type.const_array(&fn_indices);
-                            global_table.set_initializer(&initializer);
-                        }
-                    }
-                    ElementItems::Expressions { .. } => {
-                        bail!("ElementSection: Expressions item Unsupported");
+        let segment_idx = ctx.element_segments.len();
+        // References are function indices, and -1 means a null reference
+        let mut elems: Vec<IntValue> = Vec::new();
+        match element.items {
+            ElementItems::Functions(fn_indices) => {
+                for elem in fn_indices {
+                    let elem = elem?;
+                    if elem as usize >= ctx.functions.len() {
+                        bail!("ElementSection: unknown function {}", elem);
                     }
+                    elems.push(ctx.inkwell_types.i32_type.const_int(elem as u64, false));
+                }
+            }
+            ElementItems::Expressions(_, exprs) => {
+                for expr in exprs {
+                    elems.push(gen_const_int_expr(ctx, &expr?)?);
+                }
+            }
+        }
+        log::debug!("- elem_segment[{}] = {:?}", segment_idx, elems);
+
+        // Keep every segment as data since passive ones are used by table.init
+        let array_ty = ctx.inkwell_types.i32_type.array_type(elems.len() as u32);
+        let element_segment = ctx.module.add_global(
+            array_ty,
+            Some(AddressSpace::default()),
+            &format!("elem_segment_{}", segment_idx),
+        );
+        let mut refs: Vec<IntValue> = Vec::new();
+        for elem in &elems {
+            if elem.is_const() {
+                refs.push(*elem);
+            } else {
+                refs.push(null_ref(ctx));
+            }
+        }
+        element_segment.set_initializer(&ctx.inkwell_types.i32_type.const_array(&refs));
+        element_segment.set_linkage(Linkage::Private);
+        if elems.iter().all(|elem| elem.is_const()) {
+            element_segment.set_constant(true);
+        } else {
+            // Elements reading globals are written at instantiation
+            for (i, elem) in elems
+                .iter()
+                .enumerate()
+                .filter(|(_, elem)| !elem.is_const())
+            {
+                let elem_ptr = unsafe {
+                    ctx.builder.build_gep(
+                        array_ty,
+                        element_segment.as_pointer_value(),
+                        &[
+                            ctx.inkwell_types.i32_type.const_zero(),
+                            ctx.inkwell_types.i32_type.const_int(i as u64, false),
+                        ],
+                        "elem_ptr",
+                    )
+                }?;
+                ctx.builder.build_store(elem_ptr, *elem)?;
+            }
+        }
+        // Active and declarative segments are dropped after instantiation
+        ctx.element_segments.push(ElementSegment {
+            global: element_segment,
+            size: elems.len(),
+            passive: matches!(element.kind, ElementKind::Passive),
+        });
+
+        if let ElementKind::Active {
+            table_index,
+            offset_expr,
+        } = element.kind
+        {
+            // table_index is None if the segment uses the legacy encoding for table 0
+            let table_index = table_index.unwrap_or(0);
+            log::debug!("table[{:?}]", table_index);
+            if table_index as usize >= ctx.tables.len() {
+                bail!("ElementSection: unknown table {}", table_index);
+            }
+            let offset = gen_const_int_expr(ctx, &offset_expr)?;
+            log::debug!("- offset = {:?}", offset);
+            active_elements.push(ActiveElements {
+                table_index,
+                offset,
+                elems,
+            });
+        }
+    }
+    Ok(active_elements)
+}
+
+/// Declare the current sizes of element segments, which become 0 when they are dropped.
+/// This is always declared because lib-rt statically links it.
+fn declare_element_segment_sizes(ctx: &mut Context<'_, '_>) {
+    let mut sizes = Vec::new();
+    for segment in &ctx.element_segments {
+        let size = if segment.passive { segment.size } else { 0 };
+        sizes.push(ctx.inkwell_types.i32_type.const_int(size as u64, false));
+    }
+    let element_segment_sizes = ctx.module.add_global(
+        ctx.inkwell_types.i32_type.array_type(sizes.len() as u32),
+        Some(AddressSpace::default()),
+        "ELEM_SEGMENT_SIZES",
+    );
+    element_segment_sizes.set_initializer(&ctx.inkwell_types.i32_type.const_array(&sizes));
+    ctx.global_element_segment_sizes = Some(element_segment_sizes);
+
+    let num_element_segments = ctx.module.add_global(
+        ctx.inkwell_types.i32_type,
+        Some(AddressSpace::default()),
+        "NUM_ELEM_SEGMENTS",
+    );
+    num_element_segments.set_initializer(
+        &ctx.inkwell_types
+            .i32_type
+            .const_int(sizes.len() as u64, false),
+    );
+    num_element_segments.set_constant(true);
+}
+
+fn declare_tables<'a>(
+    ctx: &mut Context<'a, '_>,
+    active_elements: &[ActiveElements<'a>],
+) -> Result<()> {
+    // Declare function pointer array
+    // Indirect calls always use tailcc, so imported functions are called through thunks
+    let current_block = ctx.builder.get_insert_block();
+    let mut fpointers: Vec<PointerValue> = Vec::new();
+    for (function, f) in ctx.functions.iter().zip(ctx.function_values.iter()) {
+        let f = if function.orig_name.is_some() {
+            declare_import_thunk(ctx, &function.name, *f)?
+        } else {
+            *f
+        };
+        fpointers.push(f.as_global_value().as_pointer_value());
+    }
+    if let Some(block) = current_block {
+        ctx.builder.position_at_end(block);
+    }
+    let fptr_array = ctx
+        .inkwell_types
+        .ptr_type
+        .array_type(fpointers.len() as u32);
+    let global_fptr_array = ctx.module.add_global(
+        fptr_array,
+        Some(AddressSpace::default()),
+        "GLOBAL_FPTR_ARRAY",
+    );
+    global_fptr_array.set_constant(true);
+    let initializer = ctx.inkwell_types.ptr_type.const_array(&fpointers);
+    global_fptr_array.set_initializer(&initializer);
+    ctx.global_fptr_array = Some(global_fptr_array);
+
+    // Declare canonical type ids of functions for signature checks
+    let mut ftypes: Vec<IntValue> = Vec::new();
+    for f in ctx.functions.iter() {
+        let type_id = ctx.canonical_type_ids[f.type_idx as usize];
+        ftypes.push(ctx.inkwell_types.i32_type.const_int(type_id as u64, false));
+    }
+    let ftype_array = ctx.inkwell_types.i32_type.array_type(ftypes.len() as u32);
+    let global_ftype_array = ctx.module.add_global(
+        ftype_array,
+        Some(AddressSpace::default()),
+        "GLOBAL_FTYPE_ARRAY",
+    );
+    global_ftype_array.set_constant(true);
+    let initializer = ctx.inkwell_types.i32_type.const_array(&ftypes);
+    global_ftype_array.set_initializer(&initializer);
+    ctx.global_ftype_array = Some(global_ftype_array);
+
+    // Table entries are references, and -1 means a null entry
+    let mut table_entries: Vec<Vec<i32>> = ctx
+        .tables
+        .iter()
+        .map(|table| vec![-1; table.ty.initial as usize])
+        .collect();
+    // Imported tables and the tables whose elements are not known at compile time are
+    // initialized at instantiation. Later segments of such a table follow them to keep the order.
+    let mut initialized_at_instantiation: Vec<bool> = ctx
+        .tables
+        .iter()
+        .map(|table| table.orig_name.is_some())
+        .collect();
+    let mut instantiation_elements: Vec<&ActiveElements> = Vec::new();
+    for active in active_elements {
+        let i = active.table_index as usize;
+        match active.as_constant() {
+            Some((offset, elems)) if !initialized_at_instantiation[i] => {
+                if offset + elems.len() > table_entries[i].len() {
+                    bail!("ElementSection: out of bounds table access");
                 }
+                table_entries[i][offset..offset + elems.len()].copy_from_slice(&elems);
+            }
+            _ => {
+                initialized_at_instantiation[i] = true;
+                instantiation_elements.push(active);
+            }
+        }
+    }
+
+    // Declare tables
+    let table_type = ctx.table_type.expect("should define table_type");
+    for i in 0..ctx.tables.len() {
+        let table = &ctx.tables[i];
+        let global = if let Some((module, name)) = table.orig_name.clone() {
+            // Imported tables are defined by the host
+            if table.export_name.is_some() {
+                log::warn!(
+                    "Table[{}] is imported, so it is exported by its import symbol",
+                    i
+                );
             }
-            ElementKind::Declared => {
-                bail!("ElementSection: Declared kind Unsupported");
+            declare_host_table(ctx, &module, &name)
+        } else {
+            let table_entries = &table_entries[i];
+
+            // Declare the initial elements of the table
+            let init_array_type = ctx
+                .inkwell_types
+                .i32_type
+                .array_type(table_entries.len() as u32);
+            let table_init = ctx.module.add_global(
+                init_array_type,
+                Some(AddressSpace::default()),
+                &format!("table_{}_init", i),
+            );
+            let mut refs: Vec<IntValue> = Vec::new();
+            for entry in table_entries {
+                refs.push(ctx.inkwell_types.i32_type.const_int(*entry as u64, true));
             }
-            ElementKind::Passive => {
-                bail!("ElementSection: Passive kind Unsupported");
+            let initializer = ctx.inkwell_types.i32_type.const_array(&refs);
+            table_init.set_initializer(&initializer);
+            table_init.set_linkage(Linkage::Private);
+
+            // lib-rt moves the elements to its own storage when the table grows
+            let name = match &table.export_name {
+                Some(export_name) => format!("export_{}", export_name),
+                None => format!("table_{}", i),
+            };
+            let global = ctx
+                .module
+                .add_global(table_type, Some(AddressSpace::default()), &name);
+            let initializer = table_type.const_named_struct(&[
+                table_init.as_pointer_value().into(),
+                ctx.inkwell_types
+                    .i32_type
+                    .const_int(table_entries.len() as u64, false)
+                    .into(),
+            ]);
+            global.set_initializer(&initializer);
+            if table.export_name.is_none() {
+                global.set_linkage(Linkage::Private);
+            }
+            global
+        };
+        ctx.tables[i].global = Some(global);
+    }
+
+    // Declare the list of tables used by the runtime to checkpoint and restore them
+    let mut table_ptrs: Vec<PointerValue> = Vec::new();
+    for table in &ctx.tables {
+        table_ptrs.push(table.global.unwrap().as_pointer_value());
+    }
+    let tables = ctx.module.add_global(
+        ctx.inkwell_types
+            .ptr_type
+            .array_type(table_ptrs.len() as u32),
+        Some(AddressSpace::default()),
+        "TABLES",
+    );
+    tables.set_initializer(&ctx.inkwell_types.ptr_type.const_array(&table_ptrs));
+    tables.set_constant(true);
+    let num_tables = ctx.module.add_global(
+        ctx.inkwell_types.i32_type,
+        Some(AddressSpace::default()),
+        "NUM_TABLES",
+    );
+    num_tables.set_initializer(
+        &ctx.inkwell_types
+            .i32_type
+            .const_int(table_ptrs.len() as u64, false),
+    );
+    num_tables.set_constant(true);
+
+    // Declare the limits of the tables, which lib-rt checks against the sizes of imported tables
+    // A table without a maximum has -1
+    let i64_type = ctx.inkwell_types.i64_type;
+    let limits_type = ctx
+        .ictx
+        .struct_type(&[i64_type.into(), i64_type.into()], false);
+    let mut limits: Vec<StructValue> = Vec::new();
+    for table in &ctx.tables {
+        let max = table.ty.maximum.map_or(-1, |max| max as i64);
+        limits.push(limits_type.const_named_struct(&[
+            i64_type.const_int(table.ty.initial, false).into(),
+            i64_type.const_int(max as u64, true).into(),
+        ]));
+    }
+    let table_limits = ctx.module.add_global(
+        limits_type.array_type(limits.len() as u32),
+        Some(AddressSpace::default()),
+        "TABLE_LIMITS",
+    );
+    table_limits.set_initializer(&limits_type.const_array(&limits));
+    table_limits.set_constant(true);
+
+    if !instantiation_elements.is_empty() {
+        gen_init_tables(ctx, &instantiation_elements)?;
+    }
+    Ok(())
+}
+
+/// Write active elements to the tables at instantiation.
+fn gen_init_tables<'a>(
+    ctx: &mut Context<'a, '_>,
+    active_elements: &[&ActiveElements<'a>],
+) -> Result<()> {
+    let aot_main = ctx
+        .module
+        .get_function("aot_main")
+        .expect("should define aot_main");
+    let exec_env_ptr = aot_main
+        .get_first_param()
+        .expect("should have &exec_env")
+        .into_pointer_value();
+    ctx.current_fn = Some(aot_main);
+    ctx.builder
+        .position_at_end(ctx.aot_init_block.expect("should define aot_init_block"));
+
+    for active in active_elements {
+        let offset = ctx.builder.build_int_z_extend_or_bit_cast(
+            active.offset,
+            ctx.inkwell_types.i64_type,
+            "offset",
+        )?;
+        // All elements must fit in the table before any of them is written
+        gen_table_bounds_check(
+            ctx,
+            &exec_env_ptr,
+            active.table_index,
+            offset,
+            ctx.inkwell_types
+                .i64_type
+                .const_int(active.elems.len() as u64, false),
+            TrapKind::TableOutOfBounds,
+        )?;
+        let table_base = gen_table_base(ctx, active.table_index)?;
+        for (i, elem) in active.elems.iter().enumerate() {
+            let index = ctx.builder.build_int_add(
+                offset,
+                ctx.inkwell_types.i64_type.const_int(i as u64, false),
+                "index",
+            )?;
+            let elem_ptr = unsafe {
+                ctx.builder
+                    .build_gep(ctx.inkwell_types.i32_type, table_base, &[index], "elem_ptr")
+            }?;
+            ctx.builder.build_store(elem_ptr, *elem)?;
+        }
+    }
+
+    // The bounds checks split the init block
+    ctx.aot_init_block = ctx.builder.get_insert_block();
+    ctx.current_fn = None;
+    Ok(())
+}
+
+fn compile_name_section(ctx: &mut Context<'_, '_>, names: NameSectionReader) -> Result<()> {
+    for name in names {
+        // Skip malformed subsections as the name section is only for debugging
+        let Ok(Name::Function(map)) = name else {
+            continue;
+        };
+        for naming in map {
+            let naming = naming?;
+            if let Some(function) = ctx.functions.get_mut(naming.index as usize) {
+                function.debug_name = Some(naming.name.to_string());
             }
         }
     }
@@ -320,6 +759,7 @@ fn compile_function_section(
             name: fname,
             type_idx: sig,
             orig_name: None,
+            debug_name: None,
         });
     }
     ctx.num_functions = ctx.functions.len() as u32;
@@ -344,7 +784,14 @@ fn declare_functions(ctx: &mut Context<'_, '_>) -> Result<()> {
                 .create_enum_attribute(Attribute::get_named_enum_kind_id("noredzone"), 0);
             f.add_attribute(inkwell::attributes::AttributeLoc::Function, attr_noredzone);
 
-            // Add noinline attribute to the function since we need correct call stack when making a checkpoint
+            // Wasm functions use tailcc for return_call (see control::gen_tail_call).
+            // Imported functions are defined by the host, so they keep the C calling convention.
+            if function.orig_name.is_none() {
+                f.set_call_conventions(TAIL_CALL_CONV);
+            }
+
+            // Add noinline attribute to the function since we need correct call stack when making a checkpoint.
+            // This does not prevent tail calls, which drop the frame of the caller anyway.
             if ctx.config.enable_cr || ctx.config.legacy_cr {
                 let attr_noinline = ctx
                     .ictx
@@ -357,3 +804,27 @@ fn declare_functions(ctx: &mut Context<'_, '_>) -> Result<()> {
     }
     Ok(())
 }
+
+/// Define a wrapper of an imported function which has the calling convention of wasm functions.
+fn declare_import_thunk<'a>(
+    ctx: &Context<'a, '_>,
+    name: &str,
+    import: FunctionValue<'a>,
+) -> Result<FunctionValue<'a>> {
+    let thunk = ctx.module.add_function(
+        &format!("{}_thunk", name),
+        import.get_type(),
+        Some(Linkage::Private),
+    );
+    thunk.set_call_conventions(TAIL_CALL_CONV);
+    let block = ctx.ictx.append_basic_block(thunk, "entry");
+    ctx.builder.position_at_end(block);
+
+    let args: Vec<_> = thunk.get_param_iter().map(|param| param.into()).collect();
+    let call_site = ctx.builder.build_call(import, &args, "")?;
+    match call_site.try_as_basic_value().left() {
+        Some(ret) => ctx.builder.build_return(Some(&ret)),
+        None => ctx.builder.build_return(None),
+    }?;
+    Ok(thunk)
+}
@@ -1,6 +1,9 @@
 use anyhow::{bail, Result};
-use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType};
-use wasmparser::{CompositeType, TypeSectionReader, ValType};
+use inkwell::{
+    types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType, VectorType},
+    values::{BasicValue, BasicValueEnum, IntValue, VectorValue},
+};
+use wasmparser::{CompositeInnerType, FuncType, RefType, TypeSectionReader, ValType, V128};
 
 use crate::context::Context;
 
@@ -8,10 +11,11 @@ pub(super) fn compile_type_section(
     ctx: &mut Context<'_, '_>,
     types: TypeSectionReader,
 ) -> Result<()> {
+    let mut func_types: Vec<FuncType> = Vec::new();
     for entry in types {
         let subtypes: Vec<_> = entry?.into_types().collect();
         assert_eq!(subtypes.len(), 1);
-        let CompositeType::Func(ref func_type) = subtypes[0].composite_type else {
+        let CompositeInnerType::Func(ref func_type) = subtypes[0].composite_type.inner else {
             bail!("TypeSection: Unimplemented composite type: {:?}", subtypes);
         };
         log::debug!("- type: {:?}", func_type);
@@ -35,14 +39,28 @@ pub(super) fn compile_type_section(
                 return_llty.fn_type(&params_llty, false)
             }
             1 => {
-                let return_llty = wasmty_to_llvmty(ctx, &returns[0]).expect("convert return type");
+                let return_llty = wasmty_to_llvmty(ctx, &returns[0])?;
                 return_llty.fn_type(&params_llty, false)
             }
             _ => {
-                bail!("TypeSection: Unimplemented multiple return value");
+                // Multiple return values are lowered to a struct return
+                let mut returns_llty: Vec<BasicTypeEnum> = Vec::new();
+                for ret in returns.iter() {
+                    returns_llty.push(wasmty_to_llvmty(ctx, ret)?);
+                }
+                let return_llty = ctx.ictx.struct_type(&returns_llty, false);
+                return_llty.fn_type(&params_llty, false)
             }
         };
         ctx.signatures.push(sig);
+
+        // Canonicalize the type for signature checks of call_indirect
+        let canonical_type_id = match func_types.iter().position(|ty| ty == func_type) {
+            Some(id) => id,
+            None => func_types.len(),
+        };
+        ctx.canonical_type_ids.push(canonical_type_id as u32);
+        func_types.push(func_type.clone());
     }
     Ok(())
 }
@@ -56,6 +74,52 @@ pub(super) fn wasmty_to_llvmty<'a>(
         ValType::I64 => Ok(BasicTypeEnum::IntType(ctx.inkwell_types.i64_type)),
         ValType::F32 => Ok(BasicTypeEnum::FloatType(ctx.inkwell_types.f32_type)),
         ValType::F64 => Ok(BasicTypeEnum::FloatType(ctx.inkwell_types.f64_type)),
+        ValType::V128 => Ok(BasicTypeEnum::VectorType(ctx.inkwell_types.v128_type)),
+        // References are represented as i32 (see `null_ref`)
+        ValType::Ref(ty)
+            if *ty == RefType::FUNCREF || *ty == RefType::EXTERNREF || *ty == RefType::EXNREF =>
+        {
+            Ok(BasicTypeEnum::IntType(ctx.inkwell_types.i32_type))
+        }
         _ => bail!("Unimplemented ValType: {:?}", wasmty),
     }
 }
+
+/// Get the default value of a wasm type, which is used to initialize locals.
+pub(super) fn wasmty_default_value<'a>(
+    ctx: &Context<'a, '_>,
+    wasmty: &ValType,
+) -> Result<BasicValueEnum<'a>> {
+    match wasmty {
+        ValType::Ref(_) => Ok(null_ref(ctx).as_basic_value_enum()),
+        _ => Ok(wasmty_to_llvmty(ctx, wasmty)?.const_zero()),
+    }
+}
+
+/// Get the null reference.
+/// A funcref is a function index, an externref is a handle given by the host and
+/// an exnref is a handle of an exception in lib-rt, so all are represented as i32 and -1 means null.
+pub(super) fn null_ref<'a>(ctx: &Context<'a, '_>) -> IntValue<'a> {
+    ctx.inkwell_types.i32_type.const_all_ones()
+}
+
+/// Get the constant v128 value.
+pub(super) fn v128_const<'a>(ctx: &Context<'a, '_>, value: &V128) -> VectorValue<'a> {
+    let bytes = value.bytes();
+    let lo = u64::from_le_bytes(bytes[0..8].try_into().unwrap());
+    let hi = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+    VectorType::const_vector(&[
+        ctx.inkwell_types.i64_type.const_int(lo, false),
+        ctx.inkwell_types.i64_type.const_int(hi, false),
+    ])
+}
+
+/// Get the LLVM types of the wasm results of a function type.
+/// Multiple results are returned as a struct, so its fields are flattened.
+pub(super) fn llvm_result_types<'a>(fn_type: &FunctionType<'a>) -> Vec<BasicTypeEnum<'a>> {
+    match fn_type.get_return_type() {
+        None => vec![],
+        Some(BasicTypeEnum::StructType(ty)) => ty.get_field_types(),
+        Some(ty) => vec![ty],
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::{bail, Result};
+use inkwell::values::{BasicValue, BasicValueEnum, IntValue};
+use wasmparser::{ConstExpr, Operator};
+
+use crate::context::{Context, Global};
+
+use super::compile_type::{null_ref, v128_const};
+
+/// Evaluate a constant expression at the end of the init block of aot_main.
+/// The builder folds constant operands, so the result is an LLVM constant unless
+/// the expression reads a global whose value is determined at instantiation.
+pub(super) fn gen_const_expr<'a>(
+    ctx: &mut Context<'a, '_>,
+    expr: &ConstExpr,
+) -> Result<BasicValueEnum<'a>> {
+    ctx.builder
+        .position_at_end(ctx.aot_init_block.expect("should define aot_init_block"));
+
+    let mut stack: Vec<BasicValueEnum<'a>> = Vec::new();
+    let mut reader = expr.get_operators_reader();
+    loop {
+        let value = match reader.read()? {
+            Operator::I32Const { value } => ctx
+                .inkwell_types
+                .i32_type
+                .const_int(value as u64, false)
+                .as_basic_value_enum(),
+            Operator::I64Const { value } => ctx
+                .inkwell_types
+                .i64_type
+                .const_int(value as u64, false)
+                .as_basic_value_enum(),
+            Operator::F32Const { value } => ctx
+                .inkwell_types
+                .f32_type
+                .const_float(f32::from_bits(value.bits()).into())
+                .as_basic_value_enum(),
+            Operator::F64Const { value } => ctx
+                .inkwell_types
+                .f64_type
+                .const_float(f64::from_bits(value.bits()))
+                .as_basic_value_enum(),
+            Operator::V128Const { value } => v128_const(ctx, &value).as_basic_value_enum(),
+            Operator::RefNull { .. } => null_ref(ctx).as_basic_value_enum(),
+            Operator::RefFunc { function_index } => {
+                if function_index as usize >= ctx.functions.len() {
+                    bail!("Constant expression: unknown function {}", function_index);
+                }
+                ctx.inkwell_types
+                    .i32_type
+                    .const_int(function_index as u64, false)
+                    .as_basic_value_enum()
+            }
+            Operator::GlobalGet { global_index } => match ctx.globals.get(global_index as usize) {
+                Some(Global::Const { value }) => *value,
+                Some(Global::Mut { ptr, ty }) | Some(Global::Immutable { ptr, ty }) => ctx
+                    .builder
+                    .build_load(*ty, ptr.as_pointer_value(), "global")?,
+                None => bail!("Constant expression: unknown global {}", global_index),
+            },
+            // extended-const
+            Operator::I32Add | Operator::I64Add => {
+                let (lhs, rhs) = pop_int_operands(&mut stack)?;
+                ctx.builder
+                    .build_int_add(lhs, rhs, "add")?
+                    .as_basic_value_enum()
+            }
+            Operator::I32Sub | Operator::I64Sub => {
+                let (lhs, rhs) = pop_int_operands(&mut stack)?;
+                ctx.builder
+                    .build_int_sub(lhs, rhs, "sub")?
+                    .as_basic_value_enum()
+            }
+            Operator::I32Mul | Operator::I64Mul => {
+                let (lhs, rhs) = pop_int_operands(&mut stack)?;
+                ctx.builder
+                    .build_int_mul(lhs, rhs, "mul")?
+                    .as_basic_value_enum()
+            }
+            Operator::End => break,
+            other => bail!("Unsupported constant expression: {:?}", other),
+        };
+        stack.push(value);
+    }
+    match stack.as_slice() {
+        [value] => Ok(*value),
+        _ => bail!("Constant expression must produce exactly one value"),
+    }
+}
+
+/// Evaluate a constant expression of type i32 or i64, such as an offset or a reference.
+pub(super) fn gen_const_int_expr<'a>(
+    ctx: &mut Context<'a, '_>,
+    expr: &ConstExpr,
+) -> Result<IntValue<'a>> {
+    match gen_const_expr(ctx, expr)? {
+        BasicValueEnum::IntValue(value) => Ok(value),
+        other => bail!("Constant expression is not an integer: {:?}", other),
+    }
+}
+
+/// Whether a value is known at compile time.
+pub(super) fn is_const_value(value: &BasicValueEnum<'_>) -> bool {
+    match value {
+        BasicValueEnum::IntValue(v) => v.is_const(),
+        BasicValueEnum::FloatValue(v) => v.is_const(),
+        BasicValueEnum::VectorValue(v) => v.is_const(),
+        _ => false,
+    }
+}
+
+fn pop_int_operands<'a>(
+    stack: &mut Vec<BasicValueEnum<'a>>,
+) -> Result<(IntValue<'a>, IntValue<'a>)> {
+    let (Some(BasicValueEnum::IntValue(rhs)), Some(BasicValueEnum::IntValue(lhs))) =
+        (stack.pop(), stack.pop())
+    else {
+        bail!("Constant expression: type mismatch");
+    };
+    Ok((lhs, rhs))
+}
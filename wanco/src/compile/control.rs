@@ -2,17 +2,23 @@ use crate::context::Context;
 use anyhow::{bail, Result};
 use inkwell::{
     basic_block::BasicBlock,
-    types::BasicTypeEnum,
-    values::{BasicValue, BasicValueEnum, PhiValue, PointerValue},
+    types::{BasicTypeEnum, FunctionType, StructType},
+    values::{
+        BasicMetadataValueEnum, BasicValue, BasicValueEnum, CallSiteValue, FunctionValue, IntValue,
+        PhiValue, PointerValue,
+    },
 };
-use wasmparser::{BlockType, BrTable};
+use wasmparser::{BlockType, BrTable, Catch, TryTable};
 
 use super::{
-    compile_type::wasmty_to_llvmty,
+    compile_type::{llvm_result_types, null_ref, wasmty_to_llvmty},
     cr::{
         checkpoint::{gen_checkpoint_unwind, generate_stackmap},
         gen_migration_point, gen_restore_non_leaf,
     },
+    error::CompileErrorKind,
+    helper::{gen_reload_memory_sizes, gen_table_base, gen_table_bounds_check, TAIL_CALL_CONV},
+    trap::{gen_trap, gen_trap_if, TrapKind},
 };
 
 /// Holds the state of if-else.
@@ -22,6 +28,13 @@ pub enum IfElseState {
     Else,
 }
 
+/// Holds the state of try-catch.
+#[derive(Eq, PartialEq, Debug)]
+pub enum TryState {
+    Try,
+    Catch,
+}
+
 /// Holds the state of unreachable.
 #[derive(Eq, PartialEq, Debug)]
 pub enum UnreachableReason {
@@ -42,7 +55,7 @@ impl UnreachableReason {
     }
 }
 
-/// Holds the state of control instructions: block, loop, if-else.
+/// Holds the state of control instructions: block, loop, if-else, try, try_table.
 #[derive(Debug)]
 pub enum ControlFrame<'a> {
     Loop {
@@ -63,6 +76,31 @@ pub enum ControlFrame<'a> {
         if_end: BasicBlock<'a>,
         ifelse_state: IfElseState,
         end_phis: Vec<PhiValue<'a>>,
+        // block parameters passed to the else block again
+        params: Vec<BasicValueEnum<'a>>,
+        stack_size: usize,
+    },
+    /// Calls in the try block are invokes which unwind to `landing_pad`.
+    /// A checkpoint taken in the try block is restored at the call site, so the same
+    /// handler is active again after restore. The caught exception is referred to by
+    /// the handle in `slot`, which is saved as a local, and lib-rt saves the exception itself.
+    Try {
+        try_end: BasicBlock<'a>,
+        landing_pad: BasicBlock<'a>,
+        // matches the caught exception against the next catch clause (None after catch_all)
+        dispatch: Option<BasicBlock<'a>>,
+        try_state: TryState,
+        end_phis: Vec<PhiValue<'a>>,
+        // hidden local holding the handle of the caught exception
+        slot: PointerValue<'a>,
+        stack_size: usize,
+    },
+    /// Same as Block, but calls in it unwind to `landing_pad`,
+    /// which branches to the labels of the catch clauses.
+    TryTable {
+        next: BasicBlock<'a>,
+        landing_pad: BasicBlock<'a>,
+        end_phis: Vec<PhiValue<'a>>,
         stack_size: usize,
     },
 }
@@ -73,10 +111,67 @@ impl<'a> ControlFrame<'a> {
             ControlFrame::Loop { ref loop_body, .. } => loop_body,
             ControlFrame::Block { ref next, .. } => next,
             ControlFrame::IfElse { ref if_end, .. } => if_end,
+            ControlFrame::Try { ref try_end, .. } => try_end,
+            ControlFrame::TryTable { ref next, .. } => next,
         }
     }
 }
 
+/// Get the LLVM types of the parameters of a block.
+fn block_param_types<'a>(ctx: &Context<'a, '_>, blockty: &BlockType) -> Vec<BasicTypeEnum<'a>> {
+    match blockty {
+        BlockType::Empty | BlockType::Type(_) => vec![],
+        BlockType::FuncType(type_index) => {
+            let sig = ctx.signatures[*type_index as usize];
+            // Skip &exec_env
+            sig.get_param_types().into_iter().skip(1).collect()
+        }
+    }
+}
+
+/// Get the LLVM types of the results of a block.
+fn block_result_types<'a>(
+    ctx: &Context<'a, '_>,
+    blockty: &BlockType,
+) -> Result<Vec<BasicTypeEnum<'a>>> {
+    match blockty {
+        BlockType::Empty => Ok(vec![]),
+        BlockType::Type(valty) => Ok(vec![wasmty_to_llvmty(ctx, valty)?]),
+        BlockType::FuncType(type_index) => {
+            let sig = ctx.signatures[*type_index as usize];
+            Ok(llvm_result_types(&sig))
+        }
+    }
+}
+
+/// Get the stack size at the beginning of a block, excluding its parameters.
+fn block_stack_size(ctx: &Context<'_, '_>, blockty: &BlockType) -> Result<usize> {
+    let num_params = block_param_types(ctx, blockty).len();
+    let stack_size = ctx.current_frame_size();
+    if stack_size < num_params {
+        bail!(
+            "stack length too short for block parameters. Expected {} but found {}",
+            num_params,
+            stack_size
+        );
+    }
+    Ok(stack_size - num_params)
+}
+
+/// Build phis for the results of a block at the current position.
+fn gen_result_phis<'a>(
+    ctx: &mut Context<'a, '_>,
+    blockty: &BlockType,
+    name: &str,
+) -> Result<Vec<PhiValue<'a>>> {
+    let mut phis: Vec<PhiValue> = Vec::new();
+    for ty in block_result_types(ctx, blockty)? {
+        let phi = ctx.builder.build_phi(ty, name)?;
+        phis.push(phi);
+    }
+    Ok(phis)
+}
+
 pub fn gen_block(ctx: &mut Context<'_, '_>, blockty: &BlockType) -> Result<()> {
     let current_block = ctx.builder.get_insert_block().unwrap();
     let next_block = ctx.ictx.append_basic_block(
@@ -86,27 +181,15 @@ pub fn gen_block(ctx: &mut Context<'_, '_>, blockty: &BlockType) -> Result<()> {
 
     // Phi
     ctx.builder.position_at_end(next_block);
-    let mut phis: Vec<PhiValue> = Vec::new();
-    match blockty {
-        BlockType::Empty => {}
-        BlockType::Type(valty) => {
-            ctx.builder.position_at_end(next_block);
-            let phi = ctx
-                .builder
-                .build_phi(wasmty_to_llvmty(ctx, valty).unwrap(), "end_phi")
-                .expect("should build phi");
-            phis.push(phi);
-        }
-        BlockType::FuncType(..) => {
-            unreachable!("Unexpected FuncType");
-        }
-    }
+    let phis = gen_result_phis(ctx, blockty, "end_phi")?;
 
     ctx.builder.position_at_end(current_block);
+    // Block parameters are left on the stack
+    let stack_size = block_stack_size(ctx, blockty)?;
     ctx.control_frames.push(ControlFrame::Block {
         next: next_block,
         end_phis: phis,
-        stack_size: ctx.current_frame_size(),
+        stack_size,
     });
     Ok(())
 }
@@ -130,22 +213,23 @@ pub fn gen_loop<'a>(
     );
 
     // Phi
+    ctx.builder.position_at_end(body_block);
+    let mut body_phis: Vec<PhiValue> = Vec::new();
+    for ty in block_param_types(ctx, blockty) {
+        let phi = ctx.builder.build_phi(ty, "loop.body_phi")?;
+        body_phis.push(phi);
+    }
     ctx.builder.position_at_end(next_block);
-    let body_phis: Vec<PhiValue> = Vec::new();
-    let mut phis: Vec<PhiValue> = Vec::new();
-    match blockty {
-        BlockType::Empty => {}
-        BlockType::Type(valty) => {
-            ctx.builder.position_at_end(next_block);
-            let phi = ctx
-                .builder
-                .build_phi(wasmty_to_llvmty(ctx, valty).unwrap(), "loop.end_phi")
-                .expect("should build phi");
-            phis.push(phi);
-        }
-        BlockType::FuncType(..) => {
-            unreachable!("Unexpected FuncType");
-        }
+    let phis = gen_result_phis(ctx, blockty, "loop.end_phi")?;
+
+    // Loop parameters flow into the loop header
+    let stack_size = block_stack_size(ctx, blockty)?;
+    for phi in body_phis.iter().rev() {
+        let value = ctx.pop()?;
+        add_phi_incoming(phi, &value, current_block)?;
+    }
+    for phi in &body_phis {
+        ctx.push(phi.as_basic_value());
     }
 
     ctx.control_frames.push(ControlFrame::Loop {
@@ -153,19 +237,17 @@ pub fn gen_loop<'a>(
         loop_next: next_block,
         body_phis,
         end_phis: phis,
-        stack_size: ctx.current_frame_size(),
+        stack_size,
     });
 
     // Move to loop_body
     ctx.builder.position_at_end(current_block);
-    ctx.builder
-        .build_unconditional_branch(body_block)
-        .expect("should build unconditional branch");
+    ctx.builder.build_unconditional_branch(body_block)?;
     ctx.builder.position_at_end(body_block);
 
     // Generate migration point for loop
     if (ctx.config.enable_cr || ctx.config.legacy_cr) && !ctx.config.disable_loop_cr {
-        gen_migration_point(ctx, exec_env_ptr, locals).expect("fail to gen_migration_point");
+        gen_migration_point(ctx, exec_env_ptr, locals)?;
         ctx.num_migration_points += 1;
     }
 
@@ -194,47 +276,34 @@ pub fn gen_if(ctx: &mut Context<'_, '_>, blockty: &BlockType) -> Result<()> {
 
     // Phi
     ctx.builder.position_at_end(end_block);
-    let mut end_phis: Vec<PhiValue> = Vec::new();
-    match blockty {
-        BlockType::Empty => {}
-        BlockType::Type(valty) => {
-            ctx.builder.position_at_end(end_block);
-            let phi = ctx
-                .builder
-                .build_phi(wasmty_to_llvmty(ctx, valty).unwrap(), "if.end_phi")
-                .expect("should build phi");
-            end_phis.push(phi);
-        }
-        BlockType::FuncType(..) => {
-            bail!("Unexpected FuncType");
-        }
-    }
+    let end_phis = gen_result_phis(ctx, blockty, "if.end_phi")?;
 
-    // Reserve blocks
+    // Compare stack value vs zero
     ctx.builder.position_at_end(current_block);
+    let cond_value = ctx.pop()?.into_int_value();
+
+    // Reserve blocks
+    // Block parameters are left on the stack for the then block
+    let stack_size = block_stack_size(ctx, blockty)?;
+    let params = ctx.peekn(ctx.current_frame_size() - stack_size)?.to_vec();
     ctx.control_frames.push(ControlFrame::IfElse {
         if_then: then_block,
         if_else: else_block,
         if_end: end_block,
         ifelse_state: IfElseState::If,
         end_phis,
-        stack_size: ctx.current_frame_size(),
+        params,
+        stack_size,
     });
 
-    // Compare stack value vs zero
-    let cond_value = ctx.pop().expect("stack empty").into_int_value();
-    let cond_value = ctx
-        .builder
-        .build_int_compare(
-            inkwell::IntPredicate::NE,
-            cond_value,
-            ctx.inkwell_types.i32_type.const_int(0, false),
-            "",
-        )
-        .expect("should build int compare");
+    let cond_value = ctx.builder.build_int_compare(
+        inkwell::IntPredicate::NE,
+        cond_value,
+        ctx.inkwell_types.i32_type.const_int(0, false),
+        "",
+    )?;
     ctx.builder
-        .build_conditional_branch(cond_value, then_block, else_block)
-        .expect("should build conditional branch");
+        .build_conditional_branch(cond_value, then_block, else_block)?;
 
     // Jump to then block
     ctx.builder.position_at_end(then_block);
@@ -256,6 +325,8 @@ pub fn gen_else(ctx: &mut Context<'_, '_>) -> Result<()> {
             if_end,
             ifelse_state,
             end_phis,
+            params,
+            stack_size,
             ..
         } => {
             *ifelse_state = IfElseState::Else;
@@ -263,27 +334,30 @@ pub fn gen_else(ctx: &mut Context<'_, '_>) -> Result<()> {
             // Phi
             if ctx.unreachable_depth == 0 {
                 // Phi
-                for phi in end_phis {
+                for phi in end_phis.iter().rev() {
                     let value = ctx
                         .stack_frames
                         .last_mut()
                         .expect("frame empty")
                         .stack
                         .pop()
-                        .expect("stack empty");
-                    phi.add_incoming(&[(&value, current_block)]);
+                        .ok_or(CompileErrorKind::StackUnderflow)?;
+                    add_phi_incoming(phi, &value, current_block)?;
                 }
             }
 
             // Jump to merge block from current block
             if !ctx.unreachable_reason.is_jumped() {
-                ctx.builder
-                    .build_unconditional_branch(*if_end)
-                    .expect("should build unconditional branch");
+                ctx.builder.build_unconditional_branch(*if_end)?;
             }
 
             // Define else block
             ctx.builder.position_at_end(*if_else);
+
+            // Pass block parameters to the else block
+            let frame = ctx.stack_frames.last_mut().expect("frame empty");
+            frame.stack.truncate(*stack_size);
+            frame.stack.extend(params.iter());
         }
         _ => {
             unreachable!("Op Else with another ControlFrame");
@@ -291,7 +365,700 @@ pub fn gen_else(ctx: &mut Context<'_, '_>) -> Result<()> {
     }
     Ok(())
 }
-pub fn gen_br(ctx: &mut Context<'_, '_>, relative_depth: u32) -> Result<()> {
+
+pub fn gen_try<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    blockty: &BlockType,
+) -> Result<()> {
+    let current_block = ctx
+        .builder
+        .get_insert_block()
+        .expect("fail to get_insert_block");
+    let current_fn = ctx.current_fn.expect("fail to get current_fn");
+
+    // Create blocks
+    let landing_pad = ctx.ictx.append_basic_block(current_fn, "try.landing_pad");
+    let dispatch = ctx.ictx.append_basic_block(current_fn, "try.dispatch");
+    let end_block = ctx.ictx.append_basic_block(current_fn, "try.end");
+
+    // Phi
+    ctx.builder.position_at_end(end_block);
+    let end_phis = gen_result_phis(ctx, blockty, "try.end_phi")?;
+
+    // Nested try blocks use different slots
+    let depth = ctx
+        .control_frames
+        .iter()
+        .filter(|frame| matches!(frame, ControlFrame::Try { .. }))
+        .count();
+    let slot = ctx.exception_slots[depth];
+
+    // Landing pad: keep the handle of the caught exception in the slot
+    ctx.builder.position_at_end(landing_pad);
+    let handle = gen_landing_pad(ctx, exec_env_ptr)?;
+    // The exception previously caught by this slot is no longer referenced
+    gen_release_exception(ctx, exec_env_ptr, slot)?;
+    ctx.builder.build_store(slot, handle)?;
+    ctx.builder.build_unconditional_branch(dispatch)?;
+
+    ctx.builder.position_at_end(current_block);
+    // Block parameters are left on the stack
+    let stack_size = block_stack_size(ctx, blockty)?;
+    ctx.control_frames.push(ControlFrame::Try {
+        try_end: end_block,
+        landing_pad,
+        dispatch: Some(dispatch),
+        try_state: TryState::Try,
+        end_phis,
+        slot,
+        stack_size,
+    });
+    Ok(())
+}
+
+/// Generate `catch` if `tag_index` is given, otherwise `catch_all`.
+pub fn gen_catch<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    tag_index: Option<u32>,
+) -> Result<()> {
+    let current_block = ctx
+        .builder
+        .get_insert_block()
+        .expect("fail to get_insert_block");
+
+    let framelen = ctx.control_frames.len();
+    let ControlFrame::Try {
+        try_end,
+        dispatch,
+        try_state,
+        end_phis,
+        slot,
+        stack_size,
+        ..
+    } = &ctx.control_frames[framelen - 1]
+    else {
+        unreachable!("Op Catch with another ControlFrame");
+    };
+    let (try_end, slot, stack_size) = (*try_end, *slot, *stack_size);
+    let was_catch = *try_state == TryState::Catch;
+    let Some(dispatch) = *dispatch else {
+        bail!("catch clause after catch_all");
+    };
+
+    // Phi
+    if ctx.unreachable_depth == 0 {
+        for phi in end_phis.iter().rev() {
+            let value = ctx
+                .stack_frames
+                .last_mut()
+                .expect("frame empty")
+                .stack
+                .pop()
+                .ok_or(CompileErrorKind::StackUnderflow)?;
+            add_phi_incoming(phi, &value, current_block)?;
+        }
+    }
+
+    // Jump to merge block from the try block or the previous catch clause
+    if !ctx.unreachable_reason.is_jumped() {
+        if was_catch {
+            gen_release_exception(ctx, exec_env_ptr, slot)?;
+        }
+        ctx.builder.build_unconditional_branch(try_end)?;
+    }
+
+    // Match the tag of the caught exception
+    ctx.builder.position_at_end(dispatch);
+    let next_dispatch = match tag_index {
+        Some(tag_index) => {
+            let current_fn = ctx.current_fn.expect("fail to get current_fn");
+            let catch_block = ctx.ictx.append_basic_block(current_fn, "try.catch");
+            let next_dispatch = ctx.ictx.append_basic_block(current_fn, "try.dispatch");
+            let handle = gen_load_handle(ctx, slot)?;
+            let tag = ctx
+                .builder
+                .build_call(
+                    ctx.fn_exception_tag.expect("should define exception_tag"),
+                    &[exec_env_ptr.as_basic_value_enum().into(), handle.into()],
+                    "tag",
+                )?
+                .try_as_basic_value()
+                .left()
+                .expect("should return tag");
+            let matched = ctx.builder.build_int_compare(
+                inkwell::IntPredicate::EQ,
+                tag.into_int_value(),
+                ctx.inkwell_types
+                    .i32_type
+                    .const_int(tag_index as u64, false),
+                "matched",
+            )?;
+            ctx.builder
+                .build_conditional_branch(matched, catch_block, next_dispatch)?;
+            ctx.builder.position_at_end(catch_block);
+            Some(next_dispatch)
+        }
+        None => None,
+    };
+
+    // Push the payload of the exception
+    ctx.reset_stack(stack_size);
+    if let Some(tag_index) = tag_index {
+        let handle = gen_load_handle(ctx, slot)?;
+        for value in gen_load_payload(ctx, exec_env_ptr, handle, tag_index)? {
+            ctx.push(value);
+        }
+    }
+
+    let ControlFrame::Try {
+        dispatch,
+        try_state,
+        ..
+    } = &mut ctx.control_frames[framelen - 1]
+    else {
+        unreachable!("Op Catch with another ControlFrame");
+    };
+    *dispatch = next_dispatch;
+    *try_state = TryState::Catch;
+    Ok(())
+}
+
+pub fn gen_throw<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    tag_index: u32,
+) -> Result<()> {
+    let payload_type = tag_payload_type(ctx, tag_index);
+    let mut values: Vec<BasicValueEnum> = Vec::new();
+    for _ in 0..payload_type.count_fields() {
+        values.push(ctx.pop()?);
+    }
+    values.reverse();
+
+    // Allocate the exception and store the payload
+    let handle = ctx
+        .builder
+        .build_call(
+            ctx.fn_exception_new.expect("should define exception_new"),
+            &[
+                exec_env_ptr.as_basic_value_enum().into(),
+                ctx.inkwell_types
+                    .i32_type
+                    .const_int(tag_index as u64, false)
+                    .into(),
+                payload_type
+                    .size_of()
+                    .expect("should get size of payload")
+                    .into(),
+            ],
+            "handle",
+        )?
+        .try_as_basic_value()
+        .left()
+        .expect("should return handle")
+        .into_int_value();
+    if !values.is_empty() {
+        let payload = gen_exception_payload(ctx, exec_env_ptr, handle)?;
+        for (i, value) in values.iter().enumerate() {
+            let ptr =
+                ctx.builder
+                    .build_struct_gep(payload_type, payload, i as u32, "payload_field")?;
+            ctx.builder.build_store(ptr, *value)?;
+        }
+    }
+
+    let landing_pad = find_landing_pad(ctx, ctx.control_frames.len() - 1);
+    gen_throw_handle(ctx, exec_env_ptr, handle, landing_pad)?;
+    ctx.unreachable_depth += 1;
+    ctx.unreachable_reason = UnreachableReason::Unreachable;
+    Ok(())
+}
+
+pub fn gen_rethrow<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    relative_depth: u32,
+) -> Result<()> {
+    let frame = &ctx.control_frames[ctx.control_frames.len() - 1 - relative_depth as usize];
+    let ControlFrame::Try {
+        slot,
+        try_state: TryState::Catch,
+        ..
+    } = frame
+    else {
+        bail!("rethrow target is not a catch clause");
+    };
+    let slot = *slot;
+
+    let mut handle = gen_load_handle(ctx, slot)?;
+    let landing_pad = find_landing_pad(ctx, ctx.control_frames.len() - 1);
+    if landing_pad.is_some() {
+        // The slot keeps referring to the exception, so throw a copy of it
+        handle = ctx
+            .builder
+            .build_call(
+                ctx.fn_exception_clone
+                    .expect("should define exception_clone"),
+                &[exec_env_ptr.as_basic_value_enum().into(), handle.into()],
+                "handle",
+            )?
+            .try_as_basic_value()
+            .left()
+            .expect("should return handle")
+            .into_int_value();
+    }
+    gen_throw_handle(ctx, exec_env_ptr, handle, landing_pad)?;
+    ctx.unreachable_depth += 1;
+    ctx.unreachable_reason = UnreachableReason::Unreachable;
+    Ok(())
+}
+
+pub fn gen_delegate<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    relative_depth: u32,
+) -> Result<()> {
+    let current_block = ctx
+        .builder
+        .get_insert_block()
+        .expect("fail to get_insert_block");
+
+    // The label of delegate is resolved after popping the try block
+    let framelen = ctx.control_frames.len();
+    let landing_pad = find_landing_pad(ctx, framelen - 2 - relative_depth as usize);
+    let ControlFrame::Try { dispatch, slot, .. } = &mut ctx.control_frames[framelen - 1] else {
+        unreachable!("Op Delegate with another ControlFrame");
+    };
+    let slot = *slot;
+    if let Some(dispatch) = dispatch.take() {
+        ctx.builder.position_at_end(dispatch);
+        gen_forward_exception(ctx, exec_env_ptr, slot, landing_pad)?;
+    }
+
+    ctx.builder.position_at_end(current_block);
+    gen_end(ctx, exec_env_ptr)
+}
+
+pub fn gen_try_table<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    try_table: &TryTable,
+) -> Result<()> {
+    let current_block = ctx
+        .builder
+        .get_insert_block()
+        .expect("fail to get_insert_block");
+    let current_fn = ctx.current_fn.expect("fail to get current_fn");
+
+    // Create blocks
+    let landing_pad = ctx
+        .ictx
+        .append_basic_block(current_fn, "try_table.landing_pad");
+    let next_block = ctx.ictx.append_basic_block(current_fn, "try_table.next");
+
+    // Phi
+    ctx.builder.position_at_end(next_block);
+    let end_phis = gen_result_phis(ctx, &try_table.ty, "try_table.end_phi")?;
+
+    // Landing pad
+    ctx.builder.position_at_end(landing_pad);
+    let handle = gen_landing_pad(ctx, exec_env_ptr)?;
+    let tag = ctx
+        .builder
+        .build_call(
+            ctx.fn_exception_tag.expect("should define exception_tag"),
+            &[exec_env_ptr.as_basic_value_enum().into(), handle.into()],
+            "tag",
+        )?
+        .try_as_basic_value()
+        .left()
+        .expect("should return tag")
+        .into_int_value();
+
+    // Branch to the label of the first matching catch clause.
+    // The labels are resolved outside of the try_table block.
+    let mut caught_all = false;
+    for catch in &try_table.catches {
+        let (tag_index, label, is_ref) = match *catch {
+            Catch::One { tag, label } => (Some(tag), label, false),
+            Catch::OneRef { tag, label } => (Some(tag), label, true),
+            Catch::All { label } => (None, label, false),
+            Catch::AllRef { label } => (None, label, true),
+        };
+        let catch_block = ctx.ictx.append_basic_block(current_fn, "try_table.catch");
+        match tag_index {
+            Some(tag_index) => {
+                let next_dispatch = ctx
+                    .ictx
+                    .append_basic_block(current_fn, "try_table.dispatch");
+                let matched = ctx.builder.build_int_compare(
+                    inkwell::IntPredicate::EQ,
+                    tag,
+                    ctx.inkwell_types
+                        .i32_type
+                        .const_int(tag_index as u64, false),
+                    "matched",
+                )?;
+                ctx.builder
+                    .build_conditional_branch(matched, catch_block, next_dispatch)?;
+                ctx.builder.position_at_end(catch_block);
+                gen_catch_clause(ctx, exec_env_ptr, handle, Some(tag_index), label, is_ref)?;
+                ctx.builder.position_at_end(next_dispatch);
+            }
+            None => {
+                ctx.builder.build_unconditional_branch(catch_block)?;
+                ctx.builder.position_at_end(catch_block);
+                gen_catch_clause(ctx, exec_env_ptr, handle, None, label, is_ref)?;
+                caught_all = true;
+                break;
+            }
+        }
+    }
+    // No catch clause matches the exception, so throw it to the enclosing try
+    if !caught_all {
+        let landing_pad = find_landing_pad(ctx, ctx.control_frames.len() - 1);
+        gen_throw_handle(ctx, exec_env_ptr, handle, landing_pad)?;
+    }
+
+    ctx.builder.position_at_end(current_block);
+    // Block parameters are left on the stack
+    let stack_size = block_stack_size(ctx, &try_table.ty)?;
+    ctx.control_frames.push(ControlFrame::TryTable {
+        next: next_block,
+        landing_pad,
+        end_phis,
+        stack_size,
+    });
+    Ok(())
+}
+
+/// Branch to `label` with the payload of the caught exception, and the exnref if `is_ref`.
+fn gen_catch_clause<'a>(
+    ctx: &Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    handle: IntValue<'a>,
+    tag_index: Option<u32>,
+    label: u32,
+    is_ref: bool,
+) -> Result<()> {
+    let current_block = ctx
+        .builder
+        .get_insert_block()
+        .expect("fail to get_insert_block");
+    let mut values: Vec<BasicValueEnum> = Vec::new();
+    if let Some(tag_index) = tag_index {
+        values.extend(gen_load_payload(ctx, exec_env_ptr, handle, tag_index)?);
+    }
+    if is_ref {
+        values.push(handle.as_basic_value_enum());
+    } else {
+        // The exception is not referred to anymore
+        ctx.builder.build_call(
+            ctx.fn_exception_release
+                .expect("should define exception_release"),
+            &[exec_env_ptr.as_basic_value_enum().into(), handle.into()],
+            "",
+        )?;
+    }
+
+    let frame = &ctx.control_frames[ctx.control_frames.len() - 1 - label as usize];
+    let phis = match frame {
+        ControlFrame::Block { end_phis, .. } => end_phis,
+        ControlFrame::IfElse { end_phis, .. } => end_phis,
+        ControlFrame::Try { end_phis, .. } => end_phis,
+        ControlFrame::TryTable { end_phis, .. } => end_phis,
+        ControlFrame::Loop { body_phis, .. } => body_phis,
+    };
+    for (phi, value) in phis.iter().zip(values.iter()) {
+        add_phi_incoming(phi, value, current_block)?;
+    }
+    ctx.builder.build_unconditional_branch(*frame.br_dest())?;
+    Ok(())
+}
+
+pub fn gen_throw_ref<'a>(ctx: &mut Context<'a, '_>, exec_env_ptr: &PointerValue<'a>) -> Result<()> {
+    let exnref = ctx.pop()?.into_int_value();
+    let is_null = ctx.builder.build_int_compare(
+        inkwell::IntPredicate::EQ,
+        exnref,
+        null_ref(ctx),
+        "is_null",
+    )?;
+    gen_trap_if(ctx, exec_env_ptr, is_null, TrapKind::NullReference)?;
+
+    // The exnref may be thrown again, so throw a copy of it
+    let handle = ctx
+        .builder
+        .build_call(
+            ctx.fn_exception_clone
+                .expect("should define exception_clone"),
+            &[exec_env_ptr.as_basic_value_enum().into(), exnref.into()],
+            "handle",
+        )?
+        .try_as_basic_value()
+        .left()
+        .expect("should return handle")
+        .into_int_value();
+    let landing_pad = find_landing_pad(ctx, ctx.control_frames.len() - 1);
+    gen_throw_handle(ctx, exec_env_ptr, handle, landing_pad)?;
+    ctx.unreachable_depth += 1;
+    ctx.unreachable_reason = UnreachableReason::Unreachable;
+    Ok(())
+}
+
+/// Build a landing pad at the current position and get the handle of the caught exception.
+fn gen_landing_pad<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+) -> Result<IntValue<'a>> {
+    let current_fn = ctx.current_fn.expect("fail to get current_fn");
+    let personality = ctx.fn_personality.expect("should define personality");
+    current_fn.set_personality_function(personality);
+    let exception_type = ctx.ictx.struct_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+        ],
+        false,
+    );
+    let typeinfo = ctx
+        .global_exception_typeinfo
+        .expect("should define global_exception_typeinfo")
+        .as_pointer_value();
+    let landing_pad_value = ctx.builder.build_landing_pad(
+        exception_type,
+        personality,
+        &[typeinfo.into()],
+        false,
+        "exn",
+    )?;
+    let exn =
+        ctx.builder
+            .build_extract_value(landing_pad_value.into_struct_value(), 0, "exn_ptr")?;
+    let handle = ctx
+        .builder
+        .build_call(
+            ctx.fn_exception_catch
+                .expect("should define exception_catch"),
+            &[exec_env_ptr.as_basic_value_enum().into(), exn.into()],
+            "handle",
+        )?
+        .try_as_basic_value()
+        .left()
+        .expect("should return handle")
+        .into_int_value();
+    // The callee which threw the exception may have grown the memories
+    gen_reload_memory_sizes(ctx, exec_env_ptr)?;
+    Ok(handle)
+}
+
+/// Load the payload of an exception.
+fn gen_load_payload<'a>(
+    ctx: &Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    handle: IntValue<'a>,
+    tag_index: u32,
+) -> Result<Vec<BasicValueEnum<'a>>> {
+    let payload_type = tag_payload_type(ctx, tag_index);
+    if payload_type.count_fields() == 0 {
+        return Ok(vec![]);
+    }
+    let payload = gen_exception_payload(ctx, exec_env_ptr, handle)?;
+    let mut values = Vec::new();
+    for (i, ty) in payload_type.get_field_types().into_iter().enumerate() {
+        let ptr = ctx
+            .builder
+            .build_struct_gep(payload_type, payload, i as u32, "payload_field")?;
+        let value = ctx.builder.build_load(ty, ptr, "payload_value")?;
+        values.push(value);
+    }
+    Ok(values)
+}
+
+/// Get the landing pad of the innermost try block which encloses the control frame at `frame_index`.
+/// Returns None if the exception is thrown to the caller.
+fn find_landing_pad<'a>(ctx: &Context<'a, '_>, frame_index: usize) -> Option<BasicBlock<'a>> {
+    ctx.control_frames[..=frame_index]
+        .iter()
+        .rev()
+        .find_map(|frame| match frame {
+            ControlFrame::Try {
+                landing_pad,
+                try_state: TryState::Try,
+                ..
+            }
+            | ControlFrame::TryTable { landing_pad, .. } => Some(*landing_pad),
+            _ => None,
+        })
+}
+
+/// Callee of `gen_call_or_invoke`
+#[derive(Clone, Copy)]
+enum Callee<'a> {
+    Direct(FunctionValue<'a>),
+    Indirect(FunctionType<'a>, PointerValue<'a>),
+}
+
+impl Callee<'_> {
+    fn call_conventions(&self) -> u32 {
+        match self {
+            Callee::Direct(function) => function.get_call_conventions(),
+            // GLOBAL_FPTR_ARRAY holds thunks of imported functions (see declare_tables)
+            Callee::Indirect(..) => TAIL_CALL_CONV,
+        }
+    }
+}
+
+/// Build a call, or an invoke if the exceptions thrown by the callee are caught at `landing_pad`.
+fn gen_call_or_invoke<'a>(
+    ctx: &Context<'a, '_>,
+    callee: Callee<'a>,
+    args: &[BasicValueEnum<'a>],
+    landing_pad: Option<BasicBlock<'a>>,
+) -> Result<CallSiteValue<'a>> {
+    let call_site = match landing_pad {
+        None => {
+            let args: Vec<BasicMetadataValueEnum> = args.iter().map(|arg| (*arg).into()).collect();
+            match callee {
+                Callee::Direct(function) => ctx.builder.build_call(function, &args, ""),
+                Callee::Indirect(ty, ptr) => ctx.builder.build_indirect_call(ty, ptr, &args, ""),
+            }?
+        }
+        Some(landing_pad) => {
+            let current_fn = ctx.current_fn.expect("fail to get current_fn");
+            let then_block = ctx.ictx.append_basic_block(current_fn, "invoke.cont");
+            let call_site = match callee {
+                Callee::Direct(function) => {
+                    ctx.builder
+                        .build_invoke(function, args, then_block, landing_pad, "")
+                }
+                Callee::Indirect(ty, ptr) => {
+                    ctx.builder
+                        .build_indirect_invoke(ty, ptr, args, then_block, landing_pad, "")
+                }
+            }?;
+            ctx.builder.position_at_end(then_block);
+            call_site
+        }
+    };
+    call_site.set_call_convention(callee.call_conventions());
+    Ok(call_site)
+}
+
+/// Get the LLVM struct type of the payload of a tag.
+fn tag_payload_type<'a>(ctx: &Context<'a, '_>, tag_index: u32) -> StructType<'a> {
+    let sig = ctx.signatures[ctx.tags[tag_index as usize] as usize];
+    // Skip &exec_env
+    let fields: Vec<BasicTypeEnum> = sig.get_param_types().into_iter().skip(1).collect();
+    ctx.ictx.struct_type(&fields, false)
+}
+
+fn gen_load_handle<'a>(ctx: &Context<'a, '_>, slot: PointerValue<'a>) -> Result<IntValue<'a>> {
+    Ok(ctx
+        .builder
+        .build_load(ctx.inkwell_types.i32_type, slot, "handle")?
+        .into_int_value())
+}
+
+fn gen_exception_payload<'a>(
+    ctx: &Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    handle: IntValue<'a>,
+) -> Result<PointerValue<'a>> {
+    Ok(ctx
+        .builder
+        .build_call(
+            ctx.fn_exception_payload
+                .expect("should define exception_payload"),
+            &[exec_env_ptr.as_basic_value_enum().into(), handle.into()],
+            "payload",
+        )?
+        .try_as_basic_value()
+        .left()
+        .expect("should return payload")
+        .into_pointer_value())
+}
+
+/// Throw the exception of `handle`. This terminates the current basic block.
+fn gen_throw_handle<'a>(
+    ctx: &Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    handle: IntValue<'a>,
+    landing_pad: Option<BasicBlock<'a>>,
+) -> Result<()> {
+    let fn_rethrow = ctx
+        .fn_exception_rethrow
+        .expect("should define exception_rethrow");
+    gen_call_or_invoke(
+        ctx,
+        Callee::Direct(fn_rethrow),
+        &[exec_env_ptr.as_basic_value_enum(), handle.into()],
+        landing_pad,
+    )?;
+    ctx.builder.build_unreachable()?;
+    Ok(())
+}
+
+/// Throw the exception in `slot`, which is not caught by the try block, to `landing_pad`.
+fn gen_forward_exception<'a>(
+    ctx: &Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    slot: PointerValue<'a>,
+    landing_pad: Option<BasicBlock<'a>>,
+) -> Result<()> {
+    let handle = gen_load_handle(ctx, slot)?;
+    ctx.builder
+        .build_store(slot, ctx.inkwell_types.i32_type.const_all_ones())?;
+    gen_throw_handle(ctx, exec_env_ptr, handle, landing_pad)
+}
+
+/// Release the exception in `slot` and clear the slot.
+fn gen_release_exception<'a>(
+    ctx: &Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    slot: PointerValue<'a>,
+) -> Result<()> {
+    let handle = gen_load_handle(ctx, slot)?;
+    ctx.builder.build_call(
+        ctx.fn_exception_release
+            .expect("should define exception_release"),
+        &[exec_env_ptr.as_basic_value_enum().into(), handle.into()],
+        "",
+    )?;
+    ctx.builder
+        .build_store(slot, ctx.inkwell_types.i32_type.const_all_ones())?;
+    Ok(())
+}
+
+/// Release the exceptions caught by the catch clauses of the innermost `num_frames` frames.
+fn gen_release_exceptions<'a>(
+    ctx: &Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    num_frames: usize,
+) -> Result<()> {
+    for frame in ctx.control_frames.iter().rev().take(num_frames) {
+        if let ControlFrame::Try {
+            slot,
+            try_state: TryState::Catch,
+            ..
+        } = frame
+        {
+            gen_release_exception(ctx, exec_env_ptr, *slot)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn gen_br<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    relative_depth: u32,
+) -> Result<()> {
+    // Exceptions caught by the exited catch clauses are no longer referenced
+    gen_release_exceptions(ctx, exec_env_ptr, relative_depth as usize + 1)?;
+
     let current_block = ctx
         .builder
         .get_insert_block()
@@ -301,22 +1068,22 @@ pub fn gen_br(ctx: &mut Context<'_, '_>, relative_depth: u32) -> Result<()> {
     let phis = match frame {
         ControlFrame::Block { end_phis, .. } => end_phis,
         ControlFrame::IfElse { end_phis, .. } => end_phis,
+        ControlFrame::Try { end_phis, .. } => end_phis,
+        ControlFrame::TryTable { end_phis, .. } => end_phis,
         ControlFrame::Loop { body_phis, .. } => body_phis,
     };
-    for phi in phis {
+    for phi in phis.iter().rev() {
         let value = ctx
             .stack_frames
             .last_mut()
             .expect("frame empty")
             .stack
             .pop()
-            .expect("stack empty");
-        phi.add_incoming(&[(&value, current_block)]);
+            .ok_or(CompileErrorKind::StackUnderflow)?;
+        add_phi_incoming(phi, &value, current_block)?;
     }
 
-    ctx.builder
-        .build_unconditional_branch(*frame.br_dest())
-        .expect("should build unconditional branch");
+    ctx.builder.build_unconditional_branch(*frame.br_dest())?;
     ctx.unreachable_depth += 1;
     ctx.unreachable_reason = UnreachableReason::Br;
     Ok(())
@@ -336,27 +1103,26 @@ pub fn gen_brif(ctx: &mut Context<'_, '_>, relative_depth: u32) -> Result<()> {
         .expect("frame empty")
         .stack
         .pop()
-        .expect("stack empty");
-    let cond_value = ctx
-        .builder
-        .build_int_compare(
-            inkwell::IntPredicate::NE,
-            cond.into_int_value(),
-            ctx.inkwell_types.i32_type.const_int(0, false),
-            "",
-        )
-        .expect("should build int compare");
+        .ok_or(CompileErrorKind::StackUnderflow)?;
+    let cond_value = ctx.builder.build_int_compare(
+        inkwell::IntPredicate::NE,
+        cond.into_int_value(),
+        ctx.inkwell_types.i32_type.const_int(0, false),
+        "",
+    )?;
 
     // Phi
     let phis = match frame {
         ControlFrame::Block { end_phis, .. } => end_phis,
         ControlFrame::IfElse { end_phis, .. } => end_phis,
+        ControlFrame::Try { end_phis, .. } => end_phis,
+        ControlFrame::TryTable { end_phis, .. } => end_phis,
         ControlFrame::Loop { body_phis, .. } => body_phis,
     };
-    let values = ctx.peekn(phis.len()).expect("fail stack peekn");
+    let values = ctx.peekn(phis.len())?;
     for (i, phi) in phis.iter().enumerate().rev() {
         let value = values[i];
-        phi.add_incoming(&[(&value, current_block)]);
+        add_phi_incoming(phi, &value, current_block)?;
     }
 
     // Create else block
@@ -367,8 +1133,7 @@ pub fn gen_brif(ctx: &mut Context<'_, '_>, relative_depth: u32) -> Result<()> {
 
     // Branch
     ctx.builder
-        .build_conditional_branch(cond_value, *frame.br_dest(), else_block)
-        .expect("should build conditional branch");
+        .build_conditional_branch(cond_value, *frame.br_dest(), else_block)?;
     ctx.builder.position_at_end(else_block);
     Ok(())
 }
@@ -378,7 +1143,7 @@ pub fn gen_br_table(ctx: &mut Context<'_, '_>, targets: &BrTable) -> Result<()>
         .builder
         .get_insert_block()
         .expect("error get_insert_block");
-    let idx = ctx.pop().expect("stack empty");
+    let idx = ctx.pop()?;
 
     // default frame
     let default = targets.default();
@@ -388,44 +1153,47 @@ pub fn gen_br_table(ctx: &mut Context<'_, '_>, targets: &BrTable) -> Result<()>
     let phis = match default_frame {
         ControlFrame::Block { end_phis, .. } => end_phis,
         ControlFrame::IfElse { end_phis, .. } => end_phis,
+        ControlFrame::Try { end_phis, .. } => end_phis,
+        ControlFrame::TryTable { end_phis, .. } => end_phis,
         ControlFrame::Loop { body_phis, .. } => body_phis,
     };
-    let values = ctx.peekn(phis.len()).expect("fail stack peekn");
+    let values = ctx.peekn(phis.len())?;
     for (i, phi) in phis.iter().enumerate().rev() {
         let value = values[i];
         log::debug!("- add_incoming to {:?}", phi);
-        phi.add_incoming(&[(&value, current_block)]);
+        add_phi_incoming(phi, &value, current_block)?;
     }
 
     // cases
     let mut cases: Vec<_> = Vec::new();
     for (i, depth) in targets.targets().enumerate() {
-        let depth = depth.expect("fail to get depth");
+        let depth = depth?;
         let dest = &ctx.control_frames[ctx.control_frames.len() - 1 - depth as usize];
         let intv = ctx.inkwell_types.i32_type.const_int(i as u64, false);
         cases.push((intv, *dest.br_dest()));
         let phis = match dest {
             ControlFrame::Block { end_phis, .. } => end_phis,
             ControlFrame::IfElse { end_phis, .. } => end_phis,
+            ControlFrame::Try { end_phis, .. } => end_phis,
+            ControlFrame::TryTable { end_phis, .. } => end_phis,
             ControlFrame::Loop { body_phis, .. } => body_phis,
         };
-        let values = ctx.peekn(phis.len()).expect("fail stack peekn");
+        let values = ctx.peekn(phis.len())?;
         for (i, phi) in phis.iter().enumerate().rev() {
             let value = values[i];
-            phi.add_incoming(&[(&value, current_block)]);
+            add_phi_incoming(phi, &value, current_block)?;
             log::debug!("- add_incoming to {:?}", phi);
         }
     }
     // switch
     ctx.builder
-        .build_switch(idx.into_int_value(), *default_frame.br_dest(), &cases)
-        .expect("should build switch");
+        .build_switch(idx.into_int_value(), *default_frame.br_dest(), &cases)?;
     ctx.unreachable_depth += 1;
     ctx.unreachable_reason = UnreachableReason::Br;
     Ok(())
 }
 
-pub fn gen_end(ctx: &mut Context<'_, '_>) -> Result<()> {
+pub fn gen_end<'a>(ctx: &mut Context<'a, '_>, exec_env_ptr: &PointerValue<'a>) -> Result<()> {
     let current_block = ctx
         .builder
         .get_insert_block()
@@ -435,54 +1203,40 @@ pub fn gen_end(ctx: &mut Context<'_, '_>) -> Result<()> {
 
     if ctx.control_frames.is_empty() {
         // End of function
-        match ctx.unreachable_reason {
-            UnreachableReason::Unreachable | UnreachableReason::Return => {
-                ctx.builder.position_at_end(*frame.br_dest());
-                if current_fn.get_type().get_return_type().is_none() {
-                    ctx.builder.build_return(None).expect("should build return");
-                } else {
-                    let ret_ty = current_fn
-                        .get_type()
-                        .get_return_type()
-                        .expect("failed to get ret type");
-                    let dummy = ret_ty.const_zero();
-                    ctx.builder
-                        .build_return(Some(&dummy))
-                        .expect("should build return");
-                }
+        let phis = match frame {
+            ControlFrame::Block { ref end_phis, .. } => end_phis,
+            _ => {
+                unreachable!("Unexpected ControlFrame")
             }
-            UnreachableReason::Reachable | UnreachableReason::Br => {
-                ctx.builder
-                    .build_unconditional_branch(*frame.br_dest())
-                    .expect("should build unconditional branch");
-                ctx.builder.position_at_end(*frame.br_dest());
-                if current_fn.get_type().get_return_type().is_none() {
-                    ctx.builder.build_return(None).expect("should build return");
-                } else {
-                    let phis = match frame {
-                        ControlFrame::Block { ref end_phis, .. } => end_phis,
-                        _ => {
-                            unreachable!("Unexpected ControlFrame")
-                        }
-                    };
-                    assert!(!phis.is_empty());
-
-                    // Collect Phi
-                    if ctx.unreachable_reason == UnreachableReason::Reachable {
-                        for phi in phis {
-                            let value = ctx.pop().expect("stack empty");
-                            phi.add_incoming(&[(&value, current_block)]);
-                        }
-                    }
+        };
+        if ctx.unreachable_reason == UnreachableReason::Reachable {
+            // Collect Phi
+            for phi in phis.iter().rev() {
+                let value = ctx.pop()?;
+                add_phi_incoming(phi, &value, current_block)?;
+            }
+            ctx.builder.build_unconditional_branch(*frame.br_dest())?;
+        }
 
-                    // Return value
-                    // TODO: support multiple phis
-                    let value = phis[0].as_basic_value();
-                    ctx.builder
-                        .build_return(Some(&value))
-                        .expect("should build return");
+        ctx.builder.position_at_end(*frame.br_dest());
+        if phis.iter().any(|phi| phi.count_incoming() == 0) {
+            // The return block is never reached
+            for phi in phis {
+                phi.as_instruction().erase_from_basic_block();
+            }
+            match current_fn.get_type().get_return_type() {
+                Some(ret_ty) => {
+                    let dummy = ret_ty.const_zero();
+                    ctx.builder.build_return(Some(&dummy))?;
+                }
+                None => {
+                    ctx.builder.build_return(None)?;
                 }
             }
+        } else {
+            // Return value
+            let values: Vec<BasicValueEnum> = phis.iter().map(|phi| phi.as_basic_value()).collect();
+            gen_return_values(ctx, &values)?;
         }
     } else {
         // End of Block/IfElse/Loop
@@ -497,36 +1251,66 @@ pub fn gen_end(ctx: &mut Context<'_, '_>) -> Result<()> {
                 next,
                 end_phis,
                 stack_size,
+            }
+            | ControlFrame::TryTable {
+                next,
+                end_phis,
+                stack_size,
+                ..
             } => (next, end_phis, stack_size),
             ControlFrame::IfElse {
                 if_else,
                 if_end,
                 ifelse_state,
                 end_phis,
+                params,
                 stack_size,
                 ..
             } => {
                 // Case Else block doesn't exist
                 if ifelse_state == IfElseState::If {
                     ctx.builder.position_at_end(if_else);
-                    ctx.builder
-                        .build_unconditional_branch(if_end)
-                        .expect("should build unconditional branch");
+                    // Block parameters are passed through as results
+                    for (phi, param) in end_phis.iter().zip(params.iter()) {
+                        add_phi_incoming(phi, param, if_else)?;
+                    }
+                    ctx.builder.build_unconditional_branch(if_end)?;
                 }
                 (if_end, end_phis, stack_size)
             }
+            ControlFrame::Try {
+                try_end,
+                dispatch,
+                try_state,
+                end_phis,
+                slot,
+                stack_size,
+                ..
+            } => {
+                // The exception is released when the catch clause finishes
+                if try_state == TryState::Catch
+                    && ctx.unreachable_reason == UnreachableReason::Reachable
+                {
+                    gen_release_exception(ctx, exec_env_ptr, slot)?;
+                }
+                // No catch clause matches the exception, so throw it to the enclosing try
+                if let Some(dispatch) = dispatch {
+                    ctx.builder.position_at_end(dispatch);
+                    let landing_pad = find_landing_pad(ctx, ctx.control_frames.len() - 1);
+                    gen_forward_exception(ctx, exec_env_ptr, slot, landing_pad)?;
+                }
+                (try_end, end_phis, stack_size)
+            }
         };
         if ctx.unreachable_reason == UnreachableReason::Reachable {
             // Collect Phi
-            for phi in &end_phis {
-                let value = ctx.pop().expect("stack empty");
-                phi.add_incoming(&[(&value, current_block)]);
+            for phi in end_phis.iter().rev() {
+                let value = ctx.pop()?;
+                add_phi_incoming(phi, &value, current_block)?;
             }
             // Jump
             ctx.builder.position_at_end(current_block);
-            ctx.builder
-                .build_unconditional_branch(next)
-                .expect("should build unconditional branch");
+            ctx.builder.build_unconditional_branch(next)?;
         }
 
         ctx.builder.position_at_end(next);
@@ -559,25 +1343,19 @@ pub fn gen_call<'a>(
     let fn_called = ctx.function_values[callee_function_index as usize];
 
     if !ctx.config.no_restore && (ctx.config.enable_cr || ctx.config.legacy_cr) {
-        gen_restore_non_leaf(ctx, exec_env_ptr, locals, fn_called.get_params().len() - 1).unwrap();
+        gen_restore_non_leaf(ctx, exec_env_ptr, locals, fn_called.get_params().len() - 1)?;
     }
 
     let mut args: Vec<BasicValueEnum> = Vec::new();
     for _ in 0..(fn_called.get_params().len() - 1) {
-        args.push(ctx.pop().expect("stack empty"));
+        args.push(ctx.pop()?);
     }
     args.reverse();
     args.insert(0, exec_env_ptr.as_basic_value_enum());
 
     // call
-    let args = args
-        .iter()
-        .map(|arg| arg.as_basic_value_enum().into())
-        .collect::<Vec<_>>();
-    let call_site = ctx
-        .builder
-        .build_call(fn_called, &args, "")
-        .expect("should build call");
+    let landing_pad = find_landing_pad(ctx, ctx.control_frames.len() - 1);
+    let call_site = gen_call_or_invoke(ctx, Callee::Direct(fn_called), &args, landing_pad)?;
 
     if ctx.config.enable_cr {
         generate_stackmap(ctx, locals)?;
@@ -585,18 +1363,13 @@ pub fn gen_call<'a>(
 
     // Generate unwinding code for checkpoint
     if ctx.config.legacy_cr {
-        gen_checkpoint_unwind(ctx, exec_env_ptr, locals)
-            .expect("fail to gen_check_state_and_snapshot");
+        gen_checkpoint_unwind(ctx, exec_env_ptr, locals)?;
     }
 
-    if call_site.try_as_basic_value().is_left() {
-        ctx.push(
-            call_site
-                .try_as_basic_value()
-                .left()
-                .expect("fail translate call_site"),
-        );
-    }
+    // The callee may have grown the memories
+    gen_reload_memory_sizes(ctx, exec_env_ptr)?;
+
+    push_call_results(ctx, call_site)?;
     Ok(())
 }
 
@@ -607,8 +1380,6 @@ pub fn gen_call_indirect<'a>(
     type_index: u32,
     table_index: u32,
 ) -> Result<()> {
-    // TODO: support larger
-    assert_eq!(table_index, 0);
     let callee_type = ctx.signatures[type_index as usize];
 
     if !ctx.config.no_restore && (ctx.config.enable_cr || ctx.config.legacy_cr) {
@@ -617,62 +1388,24 @@ pub fn gen_call_indirect<'a>(
             exec_env_ptr,
             locals,
             callee_type.get_param_types().len(),
-        )
-        .unwrap();
+        )?;
     }
 
-    // Load function index
-    let idx = ctx.pop().expect("stack empty").into_int_value();
-    let fnidx_ptr = unsafe {
-        ctx.builder.build_gep(
-            ctx.inkwell_types.i32_type,
-            ctx.global_table
-                .expect("should define global_table")
-                .as_pointer_value(),
-            &[idx],
-            "fnidx_ptr",
-        )
-    }
-    .expect("should build gep");
-    let fnidx = ctx
-        .builder
-        .build_load(ctx.inkwell_types.i32_type, fnidx_ptr, "fnidx")
-        .expect("should build load");
-    let fptr_ptr = unsafe {
-        ctx.builder
-            .build_gep(
-                ctx.inkwell_types.ptr_type,
-                ctx.global_fptr_array
-                    .expect("should define global_fptr_array")
-                    .as_pointer_value(),
-                &[fnidx.into_int_value()],
-                "fptr",
-            )
-            .expect("should build gep")
-    };
-    let fptr = ctx
-        .builder
-        .build_load(ctx.inkwell_types.ptr_type, fptr_ptr, "fptr")
-        .expect("should build load");
+    let fptr = gen_load_indirect_callee(ctx, exec_env_ptr, type_index, table_index)?;
 
     // Generate restore point and get arguments for callee
     let mut args: Vec<BasicValueEnum> = Vec::new();
     for _ in 0..(callee_type.get_param_types().len() - 1) {
         // skip function index (stack top)
-        args.push(ctx.pop().expect("stack empty"));
+        args.push(ctx.pop()?);
     }
     args.reverse();
     args.insert(0, exec_env_ptr.as_basic_value_enum());
 
     // call and push result
-    let args = args
-        .iter()
-        .map(|arg| arg.as_basic_value_enum().into())
-        .collect::<Vec<_>>();
-    let call_site = ctx
-        .builder
-        .build_indirect_call(callee_type, fptr.into_pointer_value(), &args, "call_site")
-        .expect("should build indirect call");
+    let landing_pad = find_landing_pad(ctx, ctx.control_frames.len() - 1);
+    let call_site =
+        gen_call_or_invoke(ctx, Callee::Indirect(callee_type, fptr), &args, landing_pad)?;
 
     if ctx.config.enable_cr {
         generate_stackmap(ctx, locals)?;
@@ -680,73 +1413,289 @@ pub fn gen_call_indirect<'a>(
 
     // Generate unwinding code for checkpoint
     if ctx.config.legacy_cr {
-        gen_checkpoint_unwind(ctx, exec_env_ptr, locals)
-            .expect("fail to gen_check_state_and_snapshot");
+        gen_checkpoint_unwind(ctx, exec_env_ptr, locals)?;
     }
 
-    if call_site.try_as_basic_value().is_left() {
-        ctx.push(
-            call_site
-                .try_as_basic_value()
-                .left()
-                .expect("fail translate call_site"),
-        );
+    // The callee may have grown the memories
+    gen_reload_memory_sizes(ctx, exec_env_ptr)?;
+
+    push_call_results(ctx, call_site)?;
+
+    Ok(())
+}
+
+pub fn gen_return_call<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    locals: &mut [(PointerValue<'a>, BasicTypeEnum<'a>)],
+    callee_function_index: u32,
+) -> Result<()> {
+    let fn_called = ctx.function_values[callee_function_index as usize];
+
+    // Imported functions do not use tailcc, but they never call back into wasm,
+    // so an ordinary call followed by a return does not grow the stack either.
+    if fn_called.get_call_conventions() != TAIL_CALL_CONV {
+        gen_call(ctx, exec_env_ptr, locals, callee_function_index)?;
+        return gen_return(ctx, exec_env_ptr);
+    }
+
+    let mut args: Vec<BasicValueEnum> = Vec::new();
+    for _ in 0..(fn_called.get_params().len() - 1) {
+        args.push(ctx.pop()?);
+    }
+    args.reverse();
+    args.insert(0, exec_env_ptr.as_basic_value_enum());
+
+    gen_tail_call(ctx, exec_env_ptr, Callee::Direct(fn_called), &args)?;
+    Ok(())
+}
+
+pub fn gen_return_call_indirect<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    type_index: u32,
+    table_index: u32,
+) -> Result<()> {
+    let callee_type = ctx.signatures[type_index as usize];
+    let fptr = gen_load_indirect_callee(ctx, exec_env_ptr, type_index, table_index)?;
+
+    let mut args: Vec<BasicValueEnum> = Vec::new();
+    for _ in 0..(callee_type.get_param_types().len() - 1) {
+        args.push(ctx.pop()?);
     }
+    args.reverse();
+    args.insert(0, exec_env_ptr.as_basic_value_enum());
+
+    gen_tail_call(
+        ctx,
+        exec_env_ptr,
+        Callee::Indirect(callee_type, fptr),
+        &args,
+    )?;
+    Ok(())
+}
+
+/// Call a wasm function in tail position and return its results.
+///
+/// The call is a `tail` call of tailcc immediately followed by `ret`, which LLVM guarantees
+/// to lower to a jump reusing the current frame, just like `musttail`.
+/// (The C API of LLVM 17 cannot mark a call as `musttail`.)
+/// Since the frame of the current function no longer exists after the call:
+/// - it is never an invoke, because the handlers of this function do not catch exceptions
+///   thrown by the callee.
+/// - no restore point, stackmap or unwinding code is generated for it. A checkpoint taken in
+///   the callee does not contain this frame, so the callee returns straight to our caller
+///   after restore as well. When the restore point of our caller calls this function again,
+///   its restore dispatcher finds the frame of another function and jumps to it
+///   (see `gen_finalize_restore_dispatch`).
+/// - `noinline`, which is added for checkpoints, only prevents inlining and does not
+///   prevent the tail call.
+fn gen_tail_call<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    callee: Callee<'a>,
+    args: &[BasicValueEnum<'a>],
+) -> Result<()> {
+    gen_release_exceptions(ctx, exec_env_ptr, ctx.control_frames.len())?;
+
+    ctx.unreachable_depth += 1;
+    ctx.unreachable_reason = UnreachableReason::Return;
+
+    let call_site = gen_call_or_invoke(ctx, callee, args, None)?;
+    call_site.set_tail_call(true);
+    match call_site.try_as_basic_value().left() {
+        Some(ret) => ctx.builder.build_return(Some(&ret)),
+        None => ctx.builder.build_return(None),
+    }?;
+    Ok(())
+}
+
+/// Load the function pointer of the callee of `call_indirect` from the table.
+/// The table index is popped from the stack, and the element is checked to have the expected type.
+fn gen_load_indirect_callee<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    type_index: u32,
+    table_index: u32,
+) -> Result<PointerValue<'a>> {
+    // Check that the element is within the table
+    let idx = ctx.pop()?.into_int_value();
+    let idx_ex = ctx
+        .builder
+        .build_int_z_extend(idx, ctx.inkwell_types.i64_type, "")?;
+    gen_table_bounds_check(
+        ctx,
+        exec_env_ptr,
+        table_index,
+        idx_ex,
+        ctx.inkwell_types.i64_type.const_int(1, false),
+        TrapKind::UndefinedElement,
+    )?;
+
+    // Load function index
+    let table_base = gen_table_base(ctx, table_index)?;
+    let fnidx_ptr = unsafe {
+        ctx.builder
+            .build_gep(ctx.inkwell_types.i32_type, table_base, &[idx], "fnidx_ptr")
+    }?;
+    let fnidx = ctx
+        .builder
+        .build_load(ctx.inkwell_types.i32_type, fnidx_ptr, "fnidx")?;
+
+    // Check that the element is not null
+    let is_null = ctx.builder.build_int_compare(
+        inkwell::IntPredicate::EQ,
+        fnidx.into_int_value(),
+        ctx.inkwell_types.i32_type.const_all_ones(),
+        "is_null",
+    )?;
+    gen_trap_if(ctx, exec_env_ptr, is_null, TrapKind::UninitializedElement)?;
+
+    // Check the signature of the callee
+    let ftype_ptr = unsafe {
+        ctx.builder.build_gep(
+            ctx.inkwell_types.i32_type,
+            ctx.global_ftype_array
+                .expect("should define global_ftype_array")
+                .as_pointer_value(),
+            &[fnidx.into_int_value()],
+            "ftype_ptr",
+        )?
+    };
+    let ftype = ctx
+        .builder
+        .build_load(ctx.inkwell_types.i32_type, ftype_ptr, "ftype")?;
+    let expected_ftype = ctx
+        .inkwell_types
+        .i32_type
+        .const_int(ctx.canonical_type_ids[type_index as usize] as u64, false);
+    let type_mismatch = ctx.builder.build_int_compare(
+        inkwell::IntPredicate::NE,
+        ftype.into_int_value(),
+        expected_ftype,
+        "type_mismatch",
+    )?;
+    gen_trap_if(
+        ctx,
+        exec_env_ptr,
+        type_mismatch,
+        TrapKind::IndirectCallTypeMismatch,
+    )?;
+
+    let fptr_ptr = unsafe {
+        ctx.builder.build_gep(
+            ctx.inkwell_types.ptr_type,
+            ctx.global_fptr_array
+                .expect("should define global_fptr_array")
+                .as_pointer_value(),
+            &[fnidx.into_int_value()],
+            "fptr",
+        )?
+    };
+    let fptr = ctx
+        .builder
+        .build_load(ctx.inkwell_types.ptr_type, fptr_ptr, "fptr")?;
+    Ok(fptr.into_pointer_value())
+}
 
+/// Push the results of a call to the stack.
+/// Multiple results are returned as a struct, so its fields are pushed in order.
+fn push_call_results<'a>(ctx: &mut Context<'a, '_>, call_site: CallSiteValue<'a>) -> Result<()> {
+    let Some(ret) = call_site.try_as_basic_value().left() else {
+        return Ok(());
+    };
+    match ret {
+        BasicValueEnum::StructValue(ret) => {
+            for i in 0..ret.count_fields() {
+                let value = ctx.builder.build_extract_value(ret, i, "call_result")?;
+                ctx.push(value);
+            }
+        }
+        _ => ctx.push(ret),
+    }
     Ok(())
 }
 
 pub fn gen_drop(ctx: &mut Context<'_, '_>) -> Result<()> {
-    ctx.pop().expect("stack empty");
+    ctx.pop()?;
     Ok(())
 }
 
-pub fn gen_return(ctx: &mut Context<'_, '_>) -> Result<()> {
+pub fn gen_return<'a>(ctx: &mut Context<'a, '_>, exec_env_ptr: &PointerValue<'a>) -> Result<()> {
+    gen_release_exceptions(ctx, exec_env_ptr, ctx.control_frames.len())?;
+
     // Phi
     ctx.unreachable_depth += 1;
     ctx.unreachable_reason = UnreachableReason::Return;
 
     let current_fn = ctx.current_fn.expect("fail to get current_fn");
-    if current_fn.get_type().get_return_type().is_none() {
-        ctx.builder.build_return(None).expect("should build return");
-    } else {
-        // Return value
-        // TODO: support multiple phis
-        let ret = ctx.pop().expect("stack empty");
-        ctx.builder
-            .build_return(Some(&ret))
-            .expect("should build return");
+    let num_results = llvm_result_types(&current_fn.get_type()).len();
+    let mut values: Vec<BasicValueEnum> = Vec::new();
+    for _ in 0..num_results {
+        values.push(ctx.pop()?);
     }
+    values.reverse();
+    gen_return_values(ctx, &values)?;
 
     Ok(())
 }
 
+/// Return the values from the current function.
+/// Multiple values are returned as a struct.
+fn gen_return_values<'a>(ctx: &Context<'a, '_>, values: &[BasicValueEnum<'a>]) -> Result<()> {
+    match values {
+        [] => {
+            ctx.builder.build_return(None)?;
+        }
+        [value] => {
+            ctx.builder.build_return(Some(value))?;
+        }
+        _ => {
+            ctx.builder.build_aggregate_return(values)?;
+        }
+    }
+    Ok(())
+}
+
 pub fn gen_select(ctx: &mut Context<'_, '_>) -> Result<()> {
-    let v3 = ctx.pop().expect("stack empty");
-    let v2 = ctx.pop().expect("stack empty");
-    let v1 = ctx.pop().expect("stack empty");
-    let cond = ctx
-        .builder
-        .build_int_compare(
-            inkwell::IntPredicate::NE,
-            v3.into_int_value(),
-            ctx.inkwell_types.i32_type.const_zero(),
-            "",
-        )
-        .expect("should build int compare");
-    let res = ctx
-        .builder
-        .build_select(cond, v1, v2, "")
-        .expect("should build select");
+    let v3 = ctx.pop()?;
+    let v2 = ctx.pop()?;
+    let v1 = ctx.pop()?;
+    let cond = ctx.builder.build_int_compare(
+        inkwell::IntPredicate::NE,
+        v3.into_int_value(),
+        ctx.inkwell_types.i32_type.const_zero(),
+        "",
+    )?;
+    let res = ctx.builder.build_select(cond, v1, v2, "")?;
     ctx.push(res);
     Ok(())
 }
 
-pub fn gen_unreachable(ctx: &mut Context<'_, '_>) -> Result<()> {
+pub fn gen_unreachable<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+) -> Result<()> {
+    gen_trap(ctx, exec_env_ptr, TrapKind::Unreachable)?;
     ctx.unreachable_depth += 1;
     ctx.unreachable_reason = UnreachableReason::Unreachable;
-    ctx.builder
-        .build_unreachable()
-        .expect("should build unreachable");
+    Ok(())
+}
+
+/// Add an incoming value to a phi, checking that the value has the type of the phi.
+fn add_phi_incoming<'a>(
+    phi: &PhiValue<'a>,
+    value: &dyn BasicValue<'a>,
+    block: BasicBlock<'a>,
+) -> Result<()> {
+    let expected = phi.as_basic_value().get_type();
+    let found = value.as_basic_value_enum().get_type();
+    if expected != found {
+        bail!(CompileErrorKind::TypeMismatch {
+            expected: expected.to_string(),
+            found: found.to_string(),
+        });
+    }
+    phi.add_incoming(&[(value, block)]);
     Ok(())
 }
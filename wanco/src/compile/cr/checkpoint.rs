@@ -50,26 +50,8 @@ pub(crate) fn add_fn_store_globals<'a>(
             .add_function("store_globals", func_type, Some(Linkage::External));
     let bb = ctx.ictx.append_basic_block(fn_store_globals, "entry");
     ctx.builder.position_at_end(bb);
-    gen_store_globals(ctx, &exec_env_ptr).expect("should gen store globals");
-    ctx.builder.build_return(None).expect("should build return");
-    Ok(())
-}
-
-pub(crate) fn add_fn_store_table<'a>(
-    ctx: &mut Context<'a, '_>,
-    exec_env_ptr: PointerValue<'a>,
-) -> anyhow::Result<()> {
-    let func_type = ctx
-        .inkwell_types
-        .void_type
-        .fn_type(&[exec_env_ptr.get_type().into()], false);
-    let fn_store_table = ctx
-        .module
-        .add_function("store_table", func_type, Some(Linkage::External));
-    let bb = ctx.ictx.append_basic_block(fn_store_table, "entry");
-    ctx.builder.position_at_end(bb);
-    gen_store_table(ctx, &exec_env_ptr).expect("should gen store table");
-    ctx.builder.build_return(None).expect("should build return");
+    gen_store_globals(ctx, &exec_env_ptr)?;
+    ctx.builder.build_return(None)?;
     Ok(())
 }
 
@@ -79,19 +61,15 @@ fn gen_store_globals<'a>(ctx: &mut Context<'a, '_>, exec_env_ptr: &PointerValue<
     for global in &ctx.globals {
         let value = match global {
             Global::Const { value } => *value,
-            Global::Mut { ptr, ty } => {
-                let value = ctx
-                    .builder
-                    .build_load(*ty, ptr.as_pointer_value(), "")
-                    .expect("should build load");
+            Global::Mut { ptr, ty } | Global::Immutable { ptr, ty } => {
+                let value = ctx.builder.build_load(*ty, ptr.as_pointer_value(), "")?;
                 value
             }
         };
         globals.push(value);
     }
     for value in globals {
-        gen_push_global_value(ctx, exec_env_ptr, value)
-            .expect("should build push_global for const global");
+        gen_push_global_value(ctx, exec_env_ptr, value)?;
     }
     Ok(())
 }
@@ -103,44 +81,42 @@ fn gen_push_global_value<'a>(
 ) -> Result<()> {
     if value.get_type().is_int_type() {
         if value.get_type().into_int_type() == ctx.inkwell_types.i32_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_push_global_i32.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into(), value.into()],
-                    "",
-                )
-                .expect("should build call");
+            ctx.builder.build_call(
+                ctx.fn_push_global_i32.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into(), value.into()],
+                "",
+            )?;
         } else if value.get_type().into_int_type() == ctx.inkwell_types.i64_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_push_global_i64.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into(), value.into()],
-                    "",
-                )
-                .expect("should build call");
+            ctx.builder.build_call(
+                ctx.fn_push_global_i64.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into(), value.into()],
+                "",
+            )?;
         } else {
             bail!("Unsupported type {:?}", value);
         }
     } else if value.get_type().is_float_type() {
         if value.get_type().into_float_type() == ctx.inkwell_types.f32_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_push_global_f32.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into(), value.into()],
-                    "",
-                )
-                .expect("should build call");
+            ctx.builder.build_call(
+                ctx.fn_push_global_f32.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into(), value.into()],
+                "",
+            )?;
         } else if value.get_type().into_float_type() == ctx.inkwell_types.f64_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_push_global_f64.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into(), value.into()],
-                    "",
-                )
-                .expect("should build call");
+            ctx.builder.build_call(
+                ctx.fn_push_global_f64.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into(), value.into()],
+                "",
+            )?;
         } else {
             bail!("Unsupported type {:?}", value);
         }
+    } else if value.get_type().is_vector_type() {
+        ctx.builder.build_call(
+            ctx.fn_push_global_v128.unwrap(),
+            &[exec_env_ptr.as_basic_value_enum().into(), value.into()],
+            "",
+        )?;
     } else {
         bail!("Unsupported type {:?}", value);
     }
@@ -151,31 +127,12 @@ pub(crate) fn gen_store_table<'a>(
     ctx: &mut Context<'a, '_>,
     exec_env_ptr: &PointerValue<'a>,
 ) -> Result<()> {
-    let Some(global_table) = ctx.global_table else {
-        return Ok(());
-    };
-    for i in 0..ctx.global_table_size.unwrap() {
-        let elem_ptr = unsafe {
-            ctx.builder.build_gep(
-                ctx.inkwell_types.i32_type,
-                global_table.as_pointer_value(),
-                &[ctx.ictx.i32_type().const_int(i as u64, false)],
-                "fnidx_ptr",
-            )
-        }
-        .expect("should build gep");
-        let fnidx = ctx
-            .builder
-            .build_load(ctx.inkwell_types.i32_type, elem_ptr, "fnidx")
-            .expect("should build load");
-        ctx.builder
-            .build_call(
-                ctx.fn_push_table_index.unwrap(),
-                &[exec_env_ptr.as_basic_value_enum().into(), fnidx.into()],
-                "",
-            )
-            .expect("should build call");
-    }
+    // The table may have grown, so the runtime stores all of its current elements
+    ctx.builder.build_call(
+        ctx.fn_store_table.unwrap(),
+        &[exec_env_ptr.as_basic_value_enum().into()],
+        "",
+    )?;
     Ok(())
 }
 
@@ -196,12 +153,11 @@ pub(crate) fn gen_checkpoint_start<'a>(
         // we need to preserve the current stack fram.
         gen_return_default_value(ctx)?;
     } else if ctx.config.legacy_cr {
-        gen_set_migration_state(ctx, exec_env_ptr, MIGRATION_STATE_CHECKPOINT_CONTINUE)
-            .expect("fail to gen_set_migration_state");
-        gen_store_frame(ctx, exec_env_ptr, locals).expect("fail to gen_store_frame");
-        gen_store_stack(ctx, exec_env_ptr).expect("fail to gen_store_stack");
+        gen_set_migration_state(ctx, exec_env_ptr, MIGRATION_STATE_CHECKPOINT_CONTINUE)?;
+        gen_store_frame(ctx, exec_env_ptr, locals)?;
+        gen_store_stack(ctx, exec_env_ptr)?;
         // unwind a stack frame
-        gen_return_default_value(ctx).expect("fail to gen_return_default_value");
+        gen_return_default_value(ctx)?;
     }
     Ok(())
 }
@@ -220,15 +176,13 @@ pub fn gen_checkpoint_unwind<'a>(
         current_fn,
         &format!("non_leaf_op_{}_unwind.else", ctx.current_op.unwrap()),
     );
-    let cond = gen_compare_migration_state(ctx, exec_env_ptr, MIGRATION_STATE_CHECKPOINT_CONTINUE)
-        .expect("fail to gen_compare_migration_state");
+    let cond = gen_compare_migration_state(ctx, exec_env_ptr, MIGRATION_STATE_CHECKPOINT_CONTINUE)?;
     ctx.builder
-        .build_conditional_branch(cond.into_int_value(), then_bb, else_bb)
-        .expect("should build conditional branch");
+        .build_conditional_branch(cond.into_int_value(), then_bb, else_bb)?;
     ctx.builder.position_at_end(then_bb);
-    gen_store_frame(ctx, exec_env_ptr, locals).expect("fail to gen_store_frame");
-    gen_store_stack(ctx, exec_env_ptr).expect("fail to gen_store_stack");
-    gen_return_default_value(ctx).expect("fail to gen_return_default_value");
+    gen_store_frame(ctx, exec_env_ptr, locals)?;
+    gen_store_stack(ctx, exec_env_ptr)?;
+    gen_return_default_value(ctx)?;
     ctx.builder.position_at_end(else_bb);
     Ok(())
 }
@@ -247,33 +201,26 @@ fn gen_store_frame<'a>(
     }
 
     // Store a frame
-    ctx.builder
-        .build_call(
-            ctx.fn_push_frame.expect("should define push_frame"),
-            &[exec_env_ptr.as_basic_value_enum().into()],
-            "",
-        )
-        .expect("should build call");
+    ctx.builder.build_call(
+        ctx.fn_push_frame.expect("should define push_frame"),
+        &[exec_env_ptr.as_basic_value_enum().into()],
+        "",
+    )?;
     let pc = ctx.current_op.unwrap() as u64;
     let fn_index = ctx.current_function_idx.unwrap() as u64;
-    ctx.builder
-        .build_call(
-            ctx.fn_set_pc_to_frame.unwrap(),
-            &[
-                exec_env_ptr.as_basic_value_enum().into(),
-                ctx.inkwell_types.i32_type.const_int(fn_index, false).into(),
-                ctx.inkwell_types.i32_type.const_int(pc, false).into(),
-            ],
-            "",
-        )
-        .expect("should build call");
+    ctx.builder.build_call(
+        ctx.fn_set_pc_to_frame.unwrap(),
+        &[
+            exec_env_ptr.as_basic_value_enum().into(),
+            ctx.inkwell_types.i32_type.const_int(fn_index, false).into(),
+            ctx.inkwell_types.i32_type.const_int(pc, false).into(),
+        ],
+        "",
+    )?;
 
     for (ptr, ty) in locals {
-        let val = ctx
-            .builder
-            .build_load(ty.as_basic_type_enum(), *ptr, "")
-            .expect("should build load");
-        gen_add_local(ctx, exec_env_ptr, val).expect("should build push_local_T");
+        let val = ctx.builder.build_load(ty.as_basic_type_enum(), *ptr, "")?;
+        gen_add_local(ctx, exec_env_ptr, val)?;
     }
     Ok(())
 }
@@ -283,7 +230,7 @@ fn gen_store_stack<'a>(ctx: &mut Context<'a, '_>, exec_env_ptr: &PointerValue<'a
     let frame = ctx.stack_frames.last().expect("frame empty");
     let stack = frame.stack.clone();
     for value in stack.iter().rev() {
-        gen_push_stack(ctx, exec_env_ptr, *value).expect("should build push_T");
+        gen_push_stack(ctx, exec_env_ptr, *value)?;
     }
     Ok(())
 }
@@ -291,19 +238,26 @@ fn gen_store_stack<'a>(ctx: &mut Context<'a, '_>, exec_env_ptr: &PointerValue<'a
 fn gen_return_default_value(ctx: &mut Context<'_, '_>) -> Result<()> {
     let ret_type = ctx.current_fn.unwrap().get_type().get_return_type();
     let Some(ty) = ret_type else {
-        ctx.builder.build_return(None).expect("should build return");
+        ctx.builder.build_return(None)?;
         return Ok(());
     };
     match ty {
         BasicTypeEnum::IntType(ty) => {
             ctx.builder
-                .build_return(Some(&ty.const_zero().as_basic_value_enum()))
-                .expect("should build return");
+                .build_return(Some(&ty.const_zero().as_basic_value_enum()))?;
         }
         BasicTypeEnum::FloatType(ty) => {
             ctx.builder
-                .build_return(Some(&ty.const_zero().as_basic_value_enum()))
-                .expect("should build return");
+                .build_return(Some(&ty.const_zero().as_basic_value_enum()))?;
+        }
+        BasicTypeEnum::VectorType(ty) => {
+            ctx.builder
+                .build_return(Some(&ty.const_zero().as_basic_value_enum()))?;
+        }
+        // multiple return values
+        BasicTypeEnum::StructType(ty) => {
+            ctx.builder
+                .build_return(Some(&ty.const_zero().as_basic_value_enum()))?;
         }
         _ => unreachable!(),
     };
@@ -317,44 +271,42 @@ fn gen_add_local<'a>(
 ) -> Result<()> {
     if val.get_type().is_int_type() {
         if val.get_type().into_int_type() == ctx.inkwell_types.i32_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_push_local_i32.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
-                    "",
-                )
-                .expect("should build call");
+            ctx.builder.build_call(
+                ctx.fn_push_local_i32.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
+                "",
+            )?;
         } else if val.get_type().into_int_type() == ctx.inkwell_types.i64_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_push_local_i64.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
-                    "",
-                )
-                .expect("should build call");
+            ctx.builder.build_call(
+                ctx.fn_push_local_i64.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
+                "",
+            )?;
         } else {
             bail!("Unsupported type {:?}", val);
         }
     } else if val.get_type().is_float_type() {
         if val.get_type().into_float_type() == ctx.inkwell_types.f32_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_push_local_f32.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
-                    "",
-                )
-                .expect("should build call");
+            ctx.builder.build_call(
+                ctx.fn_push_local_f32.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
+                "",
+            )?;
         } else if val.get_type().into_float_type() == ctx.inkwell_types.f64_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_push_local_f64.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
-                    "",
-                )
-                .expect("should build call");
+            ctx.builder.build_call(
+                ctx.fn_push_local_f64.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
+                "",
+            )?;
         } else {
             bail!("Unsupported type {:?}", val);
         }
+    } else if val.get_type().is_vector_type() {
+        ctx.builder.build_call(
+            ctx.fn_push_local_v128.unwrap(),
+            &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
+            "",
+        )?;
     } else {
         bail!("Unsupported type {:?}", val);
     }
@@ -368,44 +320,42 @@ fn gen_push_stack<'a>(
 ) -> Result<()> {
     if val.get_type().is_int_type() {
         if val.get_type().into_int_type() == ctx.inkwell_types.i32_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_push_i32.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
-                    "",
-                )
-                .expect("should build call");
+            ctx.builder.build_call(
+                ctx.fn_push_i32.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
+                "",
+            )?;
         } else if val.get_type().into_int_type() == ctx.inkwell_types.i64_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_push_i64.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
-                    "",
-                )
-                .expect("should build call");
+            ctx.builder.build_call(
+                ctx.fn_push_i64.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
+                "",
+            )?;
         } else {
             bail!("Unsupported type {:?}", val);
         }
     } else if val.get_type().is_float_type() {
         if val.get_type().into_float_type() == ctx.inkwell_types.f32_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_push_f32.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
-                    "",
-                )
-                .expect("should build call");
+            ctx.builder.build_call(
+                ctx.fn_push_f32.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
+                "",
+            )?;
         } else if val.get_type().into_float_type() == ctx.inkwell_types.f64_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_push_f64.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
-                    "",
-                )
-                .expect("should build call");
+            ctx.builder.build_call(
+                ctx.fn_push_f64.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
+                "",
+            )?;
         } else {
             bail!("Unsupported type {:?}", val);
         }
+    } else if val.get_type().is_vector_type() {
+        ctx.builder.build_call(
+            ctx.fn_push_v128.unwrap(),
+            &[exec_env_ptr.as_basic_value_enum().into(), val.into()],
+            "",
+        )?;
     } else {
         bail!("Unsupported type {:?}", val);
     }
@@ -491,6 +441,10 @@ fn encode_llvm_type<'a>(ctx: &Context<'a, '_>, ty: &BasicTypeEnum<'a>) -> i32 {
                 unreachable!()
             }
         }
+        BasicTypeEnum::VectorType(ty) => {
+            assert_eq!(*ty, ctx.inkwell_types.v128_type);
+            4
+        }
         _ => unreachable!(),
     }
 }
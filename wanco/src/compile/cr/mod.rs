@@ -23,40 +23,31 @@ fn gen_migration_state<'a>(
     ctx: &mut Context<'a, '_>,
     exec_env_ptr: &PointerValue<'a>,
 ) -> Result<BasicValueEnum<'a>> {
-    let migration_state_ptr = ctx
-        .builder
-        .build_struct_gep(
-            ctx.exec_env_type.unwrap(),
-            *exec_env_ptr,
-            *ctx.exec_env_fields.get("migration_state").unwrap(),
-            "migration_state_ptr",
-        )
-        .expect("fail to build_struct_gep");
-    let migration_state = ctx
-        .builder
-        .build_load(
-            ctx.inkwell_types.i32_type,
-            migration_state_ptr,
-            "migration_state",
-        )
-        .expect("fail to build load");
+    let migration_state_ptr = ctx.builder.build_struct_gep(
+        ctx.exec_env_type.unwrap(),
+        *exec_env_ptr,
+        *ctx.exec_env_fields.get("migration_state").unwrap(),
+        "migration_state_ptr",
+    )?;
+    let migration_state = ctx.builder.build_load(
+        ctx.inkwell_types.i32_type,
+        migration_state_ptr,
+        "migration_state",
+    )?;
     let load_insn = migration_state.as_instruction_value().unwrap();
     load_insn.set_volatile(true).expect("fail to set_volatile");
-    let expect = ctx
-        .builder
-        .build_call(
-            ctx.inkwell_intrs.expect_i32,
-            &[
-                migration_state.as_basic_value_enum().into(),
-                ctx.inkwell_types
-                    .i32_type
-                    .const_int(0, false)
-                    .as_basic_value_enum()
-                    .into(),
-            ],
-            "",
-        )
-        .unwrap();
+    let expect = ctx.builder.build_call(
+        ctx.inkwell_intrs.expect_i32,
+        &[
+            migration_state.as_basic_value_enum().into(),
+            ctx.inkwell_types
+                .i32_type
+                .const_int(0, false)
+                .as_basic_value_enum()
+                .into(),
+        ],
+        "",
+    )?;
     Ok(expect.try_as_basic_value().left().unwrap())
 }
 
@@ -65,24 +56,20 @@ pub(super) fn gen_compare_migration_state<'a>(
     exec_env_ptr: &PointerValue<'a>,
     migration_state: i32,
 ) -> Result<BasicValueEnum<'a>> {
-    let current_migration_state =
-        gen_migration_state(ctx, exec_env_ptr).expect("fail to gen_migration_state");
+    let current_migration_state = gen_migration_state(ctx, exec_env_ptr)?;
 
     let migration_state = ctx
         .inkwell_types
         .i32_type
         .const_int(migration_state as u64, false);
-    let cmp = ctx
-        .builder
-        .build_int_compare(
-            inkwell::IntPredicate::EQ,
-            current_migration_state
-                .as_basic_value_enum()
-                .into_int_value(),
-            migration_state.as_basic_value_enum().into_int_value(),
-            "cmp_migration_state",
-        )
-        .expect("fail to build_int_compare");
+    let cmp = ctx.builder.build_int_compare(
+        inkwell::IntPredicate::EQ,
+        current_migration_state
+            .as_basic_value_enum()
+            .into_int_value(),
+        migration_state.as_basic_value_enum().into_int_value(),
+        "cmp_migration_state",
+    )?;
     Ok(cmp.as_basic_value_enum())
 }
 
@@ -91,22 +78,18 @@ fn gen_set_migration_state<'a>(
     exec_env_ptr: &PointerValue<'a>,
     migration_state: i32,
 ) -> Result<()> {
-    let migration_state_ptr = ctx
-        .builder
-        .build_struct_gep(
-            ctx.exec_env_type.unwrap(),
-            *exec_env_ptr,
-            *ctx.exec_env_fields.get("migration_state").unwrap(),
-            "migration_state_ptr",
-        )
-        .expect("fail to build_struct_gep");
+    let migration_state_ptr = ctx.builder.build_struct_gep(
+        ctx.exec_env_type.unwrap(),
+        *exec_env_ptr,
+        *ctx.exec_env_fields.get("migration_state").unwrap(),
+        "migration_state_ptr",
+    )?;
     let migration_state = ctx
         .inkwell_types
         .i32_type
         .const_int(migration_state as u64, false);
     ctx.builder
-        .build_store(migration_state_ptr, migration_state)
-        .expect("fail to build store");
+        .build_store(migration_state_ptr, migration_state)?;
     Ok(())
 }
 
@@ -127,19 +110,17 @@ pub(crate) fn gen_migration_point<'a>(
         &format!("chkpt_op_{}.else", ctx.current_op.unwrap()),
     );
 
-    let cond = gen_compare_migration_state(ctx, exec_env_ptr, MIGRATION_STATE_CHECKPOINT_START)
-        .expect("fail to gen_compare_migration_state")
+    let cond = gen_compare_migration_state(ctx, exec_env_ptr, MIGRATION_STATE_CHECKPOINT_START)?
         .into_int_value();
 
     ctx.builder
-        .build_conditional_branch(cond, chkpt_bb, chkpt_else_bb)
-        .unwrap();
+        .build_conditional_branch(cond, chkpt_bb, chkpt_else_bb)?;
 
     // checkpoint
     ctx.builder.position_at_end(chkpt_bb);
 
     // start unwinding
-    gen_checkpoint_start(ctx, exec_env_ptr, locals).expect("fail to gen_checkpoint");
+    gen_checkpoint_start(ctx, exec_env_ptr, locals)?;
 
     // restore (create new bb)
     if !ctx.config.no_restore {
@@ -148,7 +129,7 @@ pub(crate) fn gen_migration_point<'a>(
             &format!("restore_op_{}.end", ctx.current_op.unwrap()),
         );
         ctx.builder.position_at_end(chkpt_else_bb);
-        ctx.builder.build_unconditional_branch(phi_bb).unwrap();
+        ctx.builder.build_unconditional_branch(phi_bb)?;
 
         gen_restore_point(
             ctx,
@@ -157,7 +138,7 @@ pub(crate) fn gen_migration_point<'a>(
             0,
             &phi_bb,
             &ctx.builder.get_insert_block().unwrap(),
-        );
+        )?;
 
         ctx.builder.position_at_end(phi_bb);
     } else {
@@ -177,7 +158,7 @@ pub(crate) fn gen_restore_non_leaf<'a>(
         ctx.current_fn.unwrap(),
         &format!("non_leaf_op_{}_restore.end", ctx.current_op.unwrap()),
     );
-    ctx.builder.build_unconditional_branch(phi_bb).unwrap();
+    ctx.builder.build_unconditional_branch(phi_bb)?;
 
     gen_restore_point(
         ctx,
@@ -186,7 +167,7 @@ pub(crate) fn gen_restore_non_leaf<'a>(
         skip_stack_top,
         &phi_bb,
         &original_bb,
-    );
+    )?;
     ctx.builder.position_at_end(phi_bb);
     Ok(())
 }
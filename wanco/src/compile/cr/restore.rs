@@ -1,11 +1,16 @@
 use anyhow::{bail, Result};
 use inkwell::{
     basic_block::BasicBlock,
-    types::BasicTypeEnum,
-    values::{AnyValue, BasicValue, CallSiteValue, PointerValue},
+    module::Linkage,
+    types::{BasicType, BasicTypeEnum, FunctionType},
+    values::{AnyValue, BasicMetadataValueEnum, BasicValue, CallSiteValue, PointerValue},
+    AddressSpace, IntPredicate,
 };
 
-use crate::context::{Context, Global};
+use crate::{
+    compile::helper::TAIL_CALL_CONV,
+    context::{Context, Global},
+};
 
 use super::{
     gen_compare_migration_state, MAX_LOCALS_STORE, MAX_STACK_STORE, MIGRATION_STATE_RESTORE,
@@ -18,11 +23,9 @@ pub(crate) fn gen_restore_dispatch<'a>(
     let current_fn = ctx.current_fn.unwrap();
     let dispatch_bb = ctx.ictx.append_basic_block(current_fn, "restore.dispatch");
     let norestore_bb = ctx.ictx.append_basic_block(current_fn, "main");
-    let cond = gen_compare_migration_state(ctx, exec_env_ptr, MIGRATION_STATE_RESTORE)
-        .expect("fail to gen_compare_migration_state");
+    let cond = gen_compare_migration_state(ctx, exec_env_ptr, MIGRATION_STATE_RESTORE)?;
     ctx.builder
-        .build_conditional_branch(cond.into_int_value(), dispatch_bb, norestore_bb)
-        .expect("should build conditional branch");
+        .build_conditional_branch(cond.into_int_value(), dispatch_bb, norestore_bb)?;
 
     // dispatch_bb is generated in gen_finalize_restore_dispatch
 
@@ -41,27 +44,160 @@ pub(crate) fn gen_finalize_restore_dispatch<'a>(
         .ictx
         .append_basic_block(current_fn, "dispatch.unreachable");
     ctx.builder.position_at_end(unreachable_bb);
-    ctx.builder
-        .build_unreachable()
-        .expect("should build unreachable");
+    ctx.builder.build_unreachable()?;
 
     ctx.builder
         .position_at_end(ctx.restore_dispatch_bb.unwrap());
-    let op_index = ctx
+    gen_dispatch_tail_callee(ctx, exec_env_ptr)?;
+    let op_index = ctx.builder.build_call(
+        ctx.fn_get_pc_from_frame.unwrap(),
+        &[exec_env_ptr.as_basic_value_enum().into()],
+        "op_index",
+    )?;
+    ctx.builder.build_switch(
+        op_index.as_any_value_enum().into_int_value(),
+        unreachable_bb,
+        &ctx.restore_dispatch_cases,
+    )?;
+    Ok(())
+}
+
+/// Jump to the function which owns the next frame if it is not the current function.
+/// It happens when the current function has made a tail call, which leaves no frame of
+/// the current function in the checkpoint (see control::gen_tail_call).
+/// The function restores its locals, including the arguments, from the frame.
+fn gen_dispatch_tail_callee<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+) -> Result<()> {
+    let current_fn = ctx.current_fn.unwrap();
+    let fn_index = ctx
         .builder
         .build_call(
-            ctx.fn_get_pc_from_frame.unwrap(),
+            ctx.fn_get_fn_index_from_frame.unwrap(),
             &[exec_env_ptr.as_basic_value_enum().into()],
-            "op_index",
-        )
-        .expect("should build call");
+            "fn_index",
+        )?
+        .as_any_value_enum()
+        .into_int_value();
+    let is_tail_callee = ctx.builder.build_int_compare(
+        IntPredicate::NE,
+        fn_index,
+        ctx.inkwell_types
+            .i32_type
+            .const_int(ctx.current_function_idx.unwrap() as u64, false),
+        "is_tail_callee",
+    )?;
+    let tail_callee_bb = ctx
+        .ictx
+        .append_basic_block(current_fn, "dispatch.tail_callee");
+    let own_frame_bb = ctx
+        .ictx
+        .append_basic_block(current_fn, "dispatch.own_frame");
     ctx.builder
-        .build_switch(
-            op_index.as_any_value_enum().into_int_value(),
-            unreachable_bb,
-            &ctx.restore_dispatch_cases,
+        .build_conditional_branch(is_tail_callee, tail_callee_bb, own_frame_bb)?;
+
+    ctx.builder.position_at_end(tail_callee_bb);
+    let entry_ptr = unsafe {
+        ctx.builder.build_gep(
+            ctx.inkwell_types.ptr_type,
+            ctx.global_restore_entry_array
+                .expect("should define global_restore_entry_array")
+                .as_pointer_value(),
+            &[fn_index],
+            "restore_entry_ptr",
         )
-        .expect("should build switch");
+    }?;
+    let entry = ctx
+        .builder
+        .build_load(ctx.inkwell_types.ptr_type, entry_ptr, "restore_entry")?
+        .into_pointer_value();
+    // Functions in a chain of tail calls have the same results
+    let call_site = ctx.builder.build_indirect_call(
+        restore_entry_type(ctx, current_fn.get_type()),
+        entry,
+        &[exec_env_ptr.as_basic_value_enum().into()],
+        "",
+    )?;
+    call_site.set_call_convention(TAIL_CALL_CONV);
+    call_site.set_tail_call(true);
+    gen_return_call_results(ctx, call_site)?;
+
+    ctx.builder.position_at_end(own_frame_bb);
+    Ok(())
+}
+
+/// Return the results of a call from a restore entry or a restore dispatch.
+/// The callee has the same results as the caller, so multiple results are returned
+/// as the struct of the callee without being split (see control::gen_return_values).
+fn gen_return_call_results<'a>(ctx: &Context<'a, '_>, call_site: CallSiteValue<'a>) -> Result<()> {
+    match call_site.try_as_basic_value().left() {
+        Some(ret) => ctx.builder.build_return(Some(&ret))?,
+        None => ctx.builder.build_return(None)?,
+    };
+    Ok(())
+}
+
+/// Get the type of the restore entry of a function, which only takes &exec_env.
+fn restore_entry_type<'a>(ctx: &Context<'a, '_>, fn_type: FunctionType<'a>) -> FunctionType<'a> {
+    let params = [ctx.inkwell_types.ptr_type.into()];
+    match fn_type.get_return_type() {
+        Some(ty) => ty.fn_type(&params, false),
+        None => ctx.inkwell_types.void_type.fn_type(&params, false),
+    }
+}
+
+/// Define the restore entries of functions and the array of them indexed by function index.
+/// A restore entry calls the function with dummy arguments, which are overwritten by
+/// the restored locals. Imported functions have no entry.
+pub(crate) fn declare_restore_entries(ctx: &mut Context<'_, '_>) -> Result<()> {
+    let current_block = ctx.builder.get_insert_block();
+    let mut entries: Vec<PointerValue> = Vec::new();
+    for (i, (function, f)) in ctx
+        .functions
+        .iter()
+        .zip(ctx.function_values.iter())
+        .enumerate()
+    {
+        if function.orig_name.is_some() {
+            entries.push(ctx.inkwell_types.ptr_type.const_null());
+            continue;
+        }
+        let entry = ctx.module.add_function(
+            &format!("restore_entry_{}", i),
+            restore_entry_type(ctx, f.get_type()),
+            Some(Linkage::Private),
+        );
+        entry.set_call_conventions(TAIL_CALL_CONV);
+        let block = ctx.ictx.append_basic_block(entry, "entry");
+        ctx.builder.position_at_end(block);
+
+        let mut args: Vec<BasicMetadataValueEnum> = vec![entry
+            .get_first_param()
+            .expect("should have &exec_env as the first param")
+            .into()];
+        for ty in f.get_type().get_param_types().into_iter().skip(1) {
+            args.push(ty.const_zero().into());
+        }
+        let call_site = ctx.builder.build_call(*f, &args, "")?;
+        call_site.set_call_convention(TAIL_CALL_CONV);
+        call_site.set_tail_call(true);
+        gen_return_call_results(ctx, call_site)?;
+        entries.push(entry.as_global_value().as_pointer_value());
+    }
+    if let Some(block) = current_block {
+        ctx.builder.position_at_end(block);
+    }
+
+    let entry_array = ctx.module.add_global(
+        ctx.inkwell_types.ptr_type.array_type(entries.len() as u32),
+        Some(AddressSpace::default()),
+        "GLOBAL_RESTORE_ENTRY_ARRAY",
+    );
+    entry_array.set_constant(true);
+    entry_array.set_linkage(Linkage::Private);
+    entry_array.set_initializer(&ctx.inkwell_types.ptr_type.const_array(&entries));
+    ctx.global_restore_entry_array = Some(entry_array);
     Ok(())
 }
 
@@ -75,13 +211,13 @@ pub(crate) fn gen_restore_point<'a>(
     skip_stack_top: usize,
     phi_bb: &BasicBlock<'a>,
     original_bb: &BasicBlock<'a>,
-) {
+) -> Result<()> {
     let nlocals = locals.len();
     let nstack = ctx.stack_frames.last().unwrap().stack.len();
     if nlocals > MAX_LOCALS_STORE || nstack > MAX_STACK_STORE {
         log::warn!("Too large frame to checkpoint/restore, skipped");
         log::warn!("nlocals: {}, nstack: {}", nlocals, nstack);
-        return;
+        return Ok(());
     }
 
     let current_fn = ctx.current_fn.unwrap();
@@ -104,7 +240,7 @@ pub(crate) fn gen_restore_point<'a>(
         &restore_start_bb,
         phi_bb,
         original_bb,
-    );
+    )
 }
 
 // Return the last basic block of the restore process.
@@ -118,34 +254,31 @@ fn gen_restore_wasm_stack<'a>(
     restore_start_bb: &BasicBlock<'a>,
     phi_bb: &BasicBlock<'a>,
     original_bb: &BasicBlock<'a>,
-) {
+) -> Result<()> {
     // Restore a frame (locals)
     ctx.builder.position_at_end(*restore_start_bb);
     let mut restored_locals = Vec::new();
     for (_, ty) in locals.iter() {
-        let cs = gen_restore_local(ctx, exec_env_ptr, *ty).expect("should build pop_front_local_T");
+        let cs = gen_restore_local(ctx, exec_env_ptr, *ty)?;
         restored_locals.push(cs);
     }
     // Add store nodes
     for i in 0..restored_locals.len() {
         let restored_value = &restored_locals[i].try_as_basic_value().left().unwrap();
         let (local_ptr, _) = &locals[i];
-        ctx.builder
-            .build_store(*local_ptr, *restored_value)
-            .expect("should build store");
+        ctx.builder.build_store(*local_ptr, *restored_value)?;
     }
 
     // Store stack values
     ctx.builder.position_at_end(*restore_start_bb);
-    let frame = ctx.stack_frames.last().expect("frame empty");
+    let frame = ctx.stack_frames.last().unwrap();
     let stack = frame.stack.clone();
 
     let mut restored_stack = Vec::new();
     for i in 0..stack.len() {
         let value_type = stack[i].get_type();
         if i < stack.len() - skip_stack_top {
-            let cs = gen_restore_stack_value(ctx, exec_env_ptr, value_type)
-                .expect("should build push_T")
+            let cs = gen_restore_stack_value(ctx, exec_env_ptr, value_type)?
                 .try_as_basic_value()
                 .left()
                 .unwrap();
@@ -158,17 +291,13 @@ fn gen_restore_wasm_stack<'a>(
     }
 
     // call pop_front_frame
-    ctx.builder
-        .build_call(
-            ctx.fn_pop_front_frame.unwrap(),
-            &[exec_env_ptr.as_basic_value_enum().into()],
-            "",
-        )
-        .expect("should build call");
+    ctx.builder.build_call(
+        ctx.fn_pop_front_frame.unwrap(),
+        &[exec_env_ptr.as_basic_value_enum().into()],
+        "",
+    )?;
 
-    ctx.builder
-        .build_unconditional_branch(*phi_bb)
-        .expect("should build unconditional branch");
+    ctx.builder.build_unconditional_branch(*phi_bb)?;
 
     // Add phi nodes for restored stack values
     ctx.builder.position_at_end(*phi_bb);
@@ -177,14 +306,12 @@ fn gen_restore_wasm_stack<'a>(
         let stack_value = stack[i];
 
         let ty = stack_value.get_type();
-        let phi = ctx
-            .builder
-            .build_phi(ty, &format!("stack_value_{}", i))
-            .expect("should build phi");
+        let phi = ctx.builder.build_phi(ty, &format!("stack_value_{}", i))?;
         phi.add_incoming(&[(&stack_value, *original_bb)]);
         phi.add_incoming(&[(restored_value, *restore_start_bb)]);
         ctx.stack_frames.last_mut().unwrap().stack[i] = phi.as_basic_value();
     }
+    Ok(())
 }
 
 fn gen_restore_stack_value<'a>(
@@ -194,44 +321,42 @@ fn gen_restore_stack_value<'a>(
 ) -> Result<CallSiteValue<'a>> {
     let cs = if ty.is_int_type() {
         if ty.into_int_type() == ctx.inkwell_types.i32_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_pop_i32.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into()],
-                    "",
-                )
-                .expect("should build call")
+            ctx.builder.build_call(
+                ctx.fn_pop_i32.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into()],
+                "",
+            )?
         } else if ty.into_int_type() == ctx.inkwell_types.i64_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_pop_i64.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into()],
-                    "",
-                )
-                .expect("should build call")
+            ctx.builder.build_call(
+                ctx.fn_pop_i64.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into()],
+                "",
+            )?
         } else {
             bail!("Unsupported type {:?}", ty);
         }
     } else if ty.is_float_type() {
         if ty.into_float_type() == ctx.inkwell_types.f32_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_pop_f32.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into()],
-                    "",
-                )
-                .expect("should build call")
+            ctx.builder.build_call(
+                ctx.fn_pop_f32.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into()],
+                "",
+            )?
         } else if ty.into_float_type() == ctx.inkwell_types.f64_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_pop_f64.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into()],
-                    "",
-                )
-                .expect("should build call")
+            ctx.builder.build_call(
+                ctx.fn_pop_f64.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into()],
+                "",
+            )?
         } else {
             bail!("Unsupported type {:?}", ty)
         }
+    } else if ty.is_vector_type() {
+        ctx.builder.build_call(
+            ctx.fn_pop_v128.unwrap(),
+            &[exec_env_ptr.as_basic_value_enum().into()],
+            "",
+        )?
     } else {
         bail!("Unsupported type {:?}", ty)
     };
@@ -245,44 +370,42 @@ fn gen_restore_local<'a>(
 ) -> Result<CallSiteValue<'a>> {
     let cs = if ty.is_int_type() {
         if ty.into_int_type() == ctx.inkwell_types.i32_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_pop_front_local_i32.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into()],
-                    "",
-                )
-                .expect("should build call")
+            ctx.builder.build_call(
+                ctx.fn_pop_front_local_i32.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into()],
+                "",
+            )?
         } else if ty.into_int_type() == ctx.inkwell_types.i64_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_pop_front_local_i64.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into()],
-                    "",
-                )
-                .expect("should build call")
+            ctx.builder.build_call(
+                ctx.fn_pop_front_local_i64.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into()],
+                "",
+            )?
         } else {
             bail!("Unsupported type {:?}", ty);
         }
     } else if ty.is_float_type() {
         if ty.into_float_type() == ctx.inkwell_types.f32_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_pop_front_local_f32.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into()],
-                    "",
-                )
-                .expect("should build call")
+            ctx.builder.build_call(
+                ctx.fn_pop_front_local_f32.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into()],
+                "",
+            )?
         } else if ty.into_float_type() == ctx.inkwell_types.f64_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_pop_front_local_f64.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into()],
-                    "",
-                )
-                .expect("should build call")
+            ctx.builder.build_call(
+                ctx.fn_pop_front_local_f64.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into()],
+                "",
+            )?
         } else {
             bail!("Unsupported type {:?}", ty)
         }
+    } else if ty.is_vector_type() {
+        ctx.builder.build_call(
+            ctx.fn_pop_front_local_v128.unwrap(),
+            &[exec_env_ptr.as_basic_value_enum().into()],
+            "",
+        )?
     } else {
         bail!("Unsupported type {:?}", ty)
     };
@@ -296,11 +419,9 @@ pub(crate) fn gen_restore_globals<'a>(
     let current_fn = ctx.current_fn.unwrap();
     let then_bb = ctx.ictx.append_basic_block(current_fn, "restore.then");
     let else_bb = ctx.ictx.append_basic_block(current_fn, "restore.else");
-    let cond = gen_compare_migration_state(ctx, exec_env_ptr, MIGRATION_STATE_RESTORE)
-        .expect("fail to gen_compare_migration_state");
+    let cond = gen_compare_migration_state(ctx, exec_env_ptr, MIGRATION_STATE_RESTORE)?;
     ctx.builder
-        .build_conditional_branch(cond.into_int_value(), then_bb, else_bb)
-        .expect("should build conditional branch");
+        .build_conditional_branch(cond.into_int_value(), then_bb, else_bb)?;
     ctx.builder.position_at_end(then_bb);
 
     // add globals
@@ -308,22 +429,20 @@ pub(crate) fn gen_restore_globals<'a>(
         match global {
             Global::Const { value } => {
                 let ty = value.get_type();
-                gen_restore_global(ctx, exec_env_ptr, ty)
-                    .expect("should build pop_front_global for mut global");
+                gen_restore_global(ctx, exec_env_ptr, ty)?;
+            }
+            // Immutable globals are initialized again at instantiation
+            Global::Immutable { ty, .. } => {
+                gen_restore_global(ctx, exec_env_ptr, *ty)?;
             }
             Global::Mut { ptr, ty } => {
-                let v = gen_restore_global(ctx, exec_env_ptr, *ty)
-                    .expect("should build pop_front_global for mut global");
+                let v = gen_restore_global(ctx, exec_env_ptr, *ty)?;
                 let v = v.try_as_basic_value().left().unwrap();
-                ctx.builder
-                    .build_store(ptr.as_pointer_value(), v)
-                    .expect("should build load");
+                ctx.builder.build_store(ptr.as_pointer_value(), v)?;
             }
         };
     }
-    ctx.builder
-        .build_unconditional_branch(else_bb)
-        .expect("should build unconditonal branch");
+    ctx.builder.build_unconditional_branch(else_bb)?;
     // Move back to else bb
     ctx.builder.position_at_end(else_bb);
     Ok(())
@@ -336,44 +455,42 @@ fn gen_restore_global<'a>(
 ) -> Result<CallSiteValue<'a>> {
     let cs = if ty.is_int_type() {
         if ty.into_int_type() == ctx.inkwell_types.i32_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_pop_front_global_i32.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into()],
-                    "",
-                )
-                .expect("should build call")
+            ctx.builder.build_call(
+                ctx.fn_pop_front_global_i32.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into()],
+                "",
+            )?
         } else if ty.into_int_type() == ctx.inkwell_types.i64_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_pop_front_global_i64.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into()],
-                    "",
-                )
-                .expect("should build call")
+            ctx.builder.build_call(
+                ctx.fn_pop_front_global_i64.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into()],
+                "",
+            )?
         } else {
             bail!("Unsupported type {:?}", ty);
         }
     } else if ty.is_float_type() {
         if ty.into_float_type() == ctx.inkwell_types.f32_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_pop_front_global_f32.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into()],
-                    "",
-                )
-                .expect("should build call")
+            ctx.builder.build_call(
+                ctx.fn_pop_front_global_f32.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into()],
+                "",
+            )?
         } else if ty.into_float_type() == ctx.inkwell_types.f64_type {
-            ctx.builder
-                .build_call(
-                    ctx.fn_pop_front_global_f64.unwrap(),
-                    &[exec_env_ptr.as_basic_value_enum().into()],
-                    "",
-                )
-                .expect("should build call")
+            ctx.builder.build_call(
+                ctx.fn_pop_front_global_f64.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into()],
+                "",
+            )?
         } else {
             bail!("Unsupported type {:?}", ty)
         }
+    } else if ty.is_vector_type() {
+        ctx.builder.build_call(
+            ctx.fn_pop_front_global_v128.unwrap(),
+            &[exec_env_ptr.as_basic_value_enum().into()],
+            "",
+        )?
     } else {
         bail!("Unsupported type {:?}", ty)
     };
@@ -384,46 +501,25 @@ pub(crate) fn gen_restore_table<'a>(
     ctx: &mut Context<'a, '_>,
     exec_env_ptr: &PointerValue<'a>,
 ) -> Result<()> {
-    let Some(global_table) = ctx.global_table else {
+    if ctx.tables.is_empty() {
         return Ok(());
-    };
+    }
     let current_fn = ctx.current_fn.unwrap();
     let then_bb = ctx.ictx.append_basic_block(current_fn, "restore.then");
     let else_bb = ctx.ictx.append_basic_block(current_fn, "restore.else");
-    let cond = gen_compare_migration_state(ctx, exec_env_ptr, MIGRATION_STATE_RESTORE)
-        .expect("fail to gen_compare_migration_state");
+    let cond = gen_compare_migration_state(ctx, exec_env_ptr, MIGRATION_STATE_RESTORE)?;
     ctx.builder
-        .build_conditional_branch(cond.into_int_value(), then_bb, else_bb)
-        .expect("should build conditional branch");
+        .build_conditional_branch(cond.into_int_value(), then_bb, else_bb)?;
     ctx.builder.position_at_end(then_bb);
 
-    for i in 0..ctx.global_table_size.unwrap() {
-        let elem_ptr = unsafe {
-            ctx.builder.build_gep(
-                ctx.inkwell_types.i32_type,
-                global_table.as_pointer_value(),
-                &[ctx.ictx.i32_type().const_int(i as u64, false)],
-                "fnidx_ptr",
-            )
-        }
-        .expect("should build gep");
-        let value = ctx
-            .builder
-            .build_call(
-                ctx.fn_pop_front_table_index.unwrap(),
-                &[exec_env_ptr.as_basic_value_enum().into()],
-                "",
-            )
-            .expect("should build call");
-        let value = value.as_any_value_enum().into_int_value();
-        ctx.builder
-            .build_store(elem_ptr, value)
-            .expect("should build store");
-    }
+    // The runtime resizes the table to the checkpointed size
+    ctx.builder.build_call(
+        ctx.fn_restore_table.unwrap(),
+        &[exec_env_ptr.as_basic_value_enum().into()],
+        "",
+    )?;
 
-    ctx.builder
-        .build_unconditional_branch(else_bb)
-        .expect("should build unconditonal branch");
+    ctx.builder.build_unconditional_branch(else_bb)?;
     ctx.builder.position_at_end(else_bb);
     Ok(())
 }
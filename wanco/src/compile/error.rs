@@ -0,0 +1,63 @@
+use inkwell::builder::BuilderError;
+
+/// Kind of an error in compiling a function.
+#[derive(Debug, thiserror::Error)]
+pub enum CompileErrorKind {
+    #[error("unsupported operator")]
+    UnsupportedOperator,
+    #[error("stack underflow")]
+    StackUnderflow,
+    #[error("type mismatch: expected {expected}, found {found}")]
+    TypeMismatch { expected: String, found: String },
+    #[error("failed to build LLVM IR: {0}")]
+    Builder(#[from] BuilderError),
+    #[error("{0:#}")]
+    Other(anyhow::Error),
+}
+
+impl From<anyhow::Error> for CompileErrorKind {
+    /// Recover the kind of an error raised through `anyhow`.
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<CompileErrorKind>() {
+            Ok(kind) => return kind,
+            Err(e) => e,
+        };
+        match e.downcast::<BuilderError>() {
+            Ok(e) => CompileErrorKind::Builder(e),
+            Err(e) => CompileErrorKind::Other(e),
+        }
+    }
+}
+
+/// Error in compiling a function, located by the operator being compiled.
+#[derive(Debug, thiserror::Error)]
+#[error("{kind} at offset 0x{offset:x} in function {function_index}")]
+pub struct CompileError {
+    pub function_index: u32,
+    /// Index of the operator in the function (see `Context::current_op`),
+    /// or None if the error is not in an operator.
+    pub op_index: Option<u32>,
+    /// Byte offset of the operator, or of the function body if the error is not in an operator
+    pub offset: usize,
+    pub kind: CompileErrorKind,
+}
+
+impl CompileError {
+    /// Locate an error in compiling a function, unless it has already been located.
+    pub(super) fn new(
+        e: anyhow::Error,
+        function_index: u32,
+        op_index: Option<u32>,
+        offset: usize,
+    ) -> Self {
+        match e.downcast::<CompileError>() {
+            Ok(e) => e,
+            Err(e) => CompileError {
+                function_index,
+                op_index,
+                offset,
+                kind: e.into(),
+            },
+        }
+    }
+}
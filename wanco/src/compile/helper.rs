@@ -1,8 +1,27 @@
-use crate::context::Context;
-use anyhow::Result;
-use inkwell::values::{
-    BasicMetadataValueEnum, BasicValue, BasicValueEnum, FunctionValue, PointerValue,
+use crate::{
+    context::{Context, MemoryCache},
+    driver::BoundsChecks,
 };
+use anyhow::{bail, Result};
+use inkwell::{
+    values::{
+        BasicMetadataValueEnum, BasicValue, BasicValueEnum, FunctionValue, GlobalValue, IntValue,
+        PointerValue,
+    },
+    AddressSpace,
+};
+
+use super::{
+    alias::{set_invariant_load_metadata, set_memory_state_metadata},
+    trap::{gen_trap_if, TrapKind},
+};
+
+/// Size of a wasm page in bytes.
+pub const PAGE_SIZE: u64 = 65536;
+
+/// LLVM calling convention of wasm functions (`tailcc`).
+/// Calls in tail position are guaranteed to reuse the frame of the caller under it.
+pub const TAIL_CALL_CONV: u32 = 18;
 
 pub fn gen_llvm_intrinsic<'a>(
     ctx: &mut Context<'a, '_>,
@@ -11,8 +30,7 @@ pub fn gen_llvm_intrinsic<'a>(
 ) -> Result<()> {
     let res = ctx
         .builder
-        .build_call(function, args, "")
-        .expect("should build call to llvm intrinsic")
+        .build_call(function, args, "")?
         .try_as_basic_value()
         .left()
         .expect("should be basic value");
@@ -30,91 +48,324 @@ pub fn gen_exec_env_field_ptr<'a>(
         .exec_env_fields
         .get(field_name)
         .expect("should define field");
+    let field_ptr =
+        ctx.builder
+            .build_struct_gep(exec_env_type, *exec_env_ptr, field_idx, field_name)?;
+    Ok(field_ptr)
+}
+
+/// Get the pointer to a field of a memory other than memory 0 in EXTRA_MEMORIES.
+fn gen_extra_memory_field_ptr<'a>(
+    ctx: &mut Context<'a, '_>,
+    mem: u32,
+    field_idx: u32,
+) -> Result<PointerValue<'a>> {
+    if mem as usize >= ctx.memories.len() {
+        bail!("Unknown memory {}", mem);
+    }
+    let memory_type = ctx.memory_type.expect("should define memory_type");
+    let extra_memories = get_extra_memories(ctx);
+    let memory_ptr = unsafe {
+        ctx.builder.build_gep(
+            memory_type,
+            extra_memories.as_pointer_value(),
+            &[ctx.inkwell_types.i32_type.const_int(mem as u64 - 1, false)],
+            "memory_ptr",
+        )
+    }?;
     let field_ptr = ctx
         .builder
-        .build_struct_gep(exec_env_type, *exec_env_ptr, field_idx, field_name)
-        .expect("should build struct gep");
+        .build_struct_gep(memory_type, memory_ptr, field_idx, "")?;
     Ok(field_ptr)
 }
 
+/// Get EXTRA_MEMORIES, which holds the base and the size of the memories other than memory 0.
+/// It is declared once the memories are known, and the initializer is set by `declare_memories`.
+pub fn get_extra_memories<'a>(ctx: &mut Context<'a, '_>) -> GlobalValue<'a> {
+    if let Some(extra_memories) = ctx.global_extra_memories {
+        return extra_memories;
+    }
+    let memory_type = ctx.memory_type.expect("should define memory_type");
+    let num_extra_memories = ctx.memories.len().saturating_sub(1);
+    let extra_memories = ctx.module.add_global(
+        memory_type.array_type(num_extra_memories as u32),
+        Some(AddressSpace::default()),
+        "EXTRA_MEMORIES",
+    );
+    ctx.global_extra_memories = Some(extra_memories);
+    extra_memories
+}
+
+/// Get the base address of the memory.
 pub fn gen_memory_base<'a>(
     ctx: &mut Context<'a, '_>,
     exec_env_ptr: &PointerValue<'a>,
+    mem: u32,
 ) -> Result<PointerValue<'a>> {
-    let memory_base_ptr = gen_exec_env_field_ptr(ctx, exec_env_ptr, "memory_base")
-        .expect("should gen memory_base ptr");
-    let memory_base = ctx
-        .builder
-        .build_load(ctx.inkwell_types.ptr_type, memory_base_ptr, "memory_base")
-        .expect("should build load");
+    if let Some(cache) = ctx.memory_caches.get(mem as usize) {
+        return Ok(cache.base);
+    }
+    gen_load_memory_base(ctx, exec_env_ptr, mem)
+}
+
+/// Load the base address of the memory from ExecEnv or EXTRA_MEMORIES.
+/// The base never moves after instantiation, so the load is invariant.
+fn gen_load_memory_base<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    mem: u32,
+) -> Result<PointerValue<'a>> {
+    let memory_base_ptr = if mem == 0 {
+        gen_exec_env_field_ptr(ctx, exec_env_ptr, "memory_base")?
+    } else {
+        gen_extra_memory_field_ptr(ctx, mem, 0)?
+    };
+    let memory_base =
+        ctx.builder
+            .build_load(ctx.inkwell_types.ptr_type, memory_base_ptr, "memory_base")?;
+    let load = memory_base
+        .as_instruction_value()
+        .expect("should be instruction");
+    set_memory_state_metadata(ctx, load);
+    set_invariant_load_metadata(ctx, load);
     Ok(memory_base.into_pointer_value())
 }
 
+/// Get the number of pages of the memory as i64.
 pub fn gen_memory_size<'a>(
     ctx: &mut Context<'a, '_>,
     exec_env_ptr: &PointerValue<'a>,
+    mem: u32,
 ) -> Result<BasicValueEnum<'a>> {
-    let memory_size_ptr = gen_exec_env_field_ptr(ctx, exec_env_ptr, "memory_size")
-        .expect("should gen memory_size ptr");
-    let memory_size = ctx
-        .builder
-        .build_load(ctx.inkwell_types.i32_type, memory_size_ptr, "memory_size")
-        .expect("should build load");
+    if let Some(size_cache) = ctx
+        .memory_caches
+        .get(mem as usize)
+        .and_then(|cache| cache.size)
+    {
+        let memory_size =
+            ctx.builder
+                .build_load(ctx.inkwell_types.i64_type, size_cache, "memory_size")?;
+        return Ok(memory_size);
+    }
+    gen_load_memory_size(ctx, exec_env_ptr, mem)
+}
+
+/// Load the number of pages of the memory from ExecEnv or EXTRA_MEMORIES.
+fn gen_load_memory_size<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    mem: u32,
+) -> Result<BasicValueEnum<'a>> {
+    let memory_size_ptr = if mem == 0 {
+        gen_exec_env_field_ptr(ctx, exec_env_ptr, "memory_size")?
+    } else {
+        gen_extra_memory_field_ptr(ctx, mem, 1)?
+    };
+    let memory_size =
+        ctx.builder
+            .build_load(ctx.inkwell_types.i64_type, memory_size_ptr, "memory_size")?;
+    set_memory_state_metadata(
+        ctx,
+        memory_size
+            .as_instruction_value()
+            .expect("should be instruction"),
+    );
     Ok(memory_size)
 }
 
+/// Cache the bases and the sizes of the memories at the current position, which must be
+/// in the entry block of the current function so that the caches dominate every access.
+pub fn gen_memory_caches<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+) -> Result<()> {
+    ctx.memory_caches.clear();
+    let mut caches = Vec::new();
+    for mem in 0..ctx.memories.len() as u32 {
+        let base = gen_load_memory_base(ctx, exec_env_ptr, mem)?;
+        let size = if ctx.memories[mem as usize].shared {
+            None
+        } else {
+            let size_cache = ctx
+                .builder
+                .build_alloca(ctx.inkwell_types.i64_type, "memory_size_cache")?;
+            let size = gen_load_memory_size(ctx, exec_env_ptr, mem)?;
+            ctx.builder.build_store(size_cache, size)?;
+            Some(size_cache)
+        };
+        caches.push(MemoryCache { base, size });
+    }
+    ctx.memory_caches = caches;
+    Ok(())
+}
+
+/// Reload the cached sizes of the memories, which may have been grown by a callee.
+pub fn gen_reload_memory_sizes<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+) -> Result<()> {
+    for mem in 0..ctx.memory_caches.len() {
+        if let Some(size_cache) = ctx.memory_caches[mem].size {
+            let size = gen_load_memory_size(ctx, exec_env_ptr, mem as u32)?;
+            ctx.builder.build_store(size_cache, size)?;
+        }
+    }
+    Ok(())
+}
+
+/// Check if the memory is indexed by i64 (memory64 proposal).
+pub fn is_memory64(ctx: &Context<'_, '_>, mem: u32) -> bool {
+    ctx.memories
+        .get(mem as usize)
+        .is_some_and(|memory| memory.memory64)
+}
+
+/// Check if out-of-bounds accesses to the memory are caught by guard pages.
+/// Guard pages only cover memory 0, and cannot cover a 64-bit memory.
+pub fn uses_guard_pages(ctx: &Context<'_, '_>, mem: u32) -> bool {
+    ctx.config.bounds_checks == BoundsChecks::GuardPage && mem == 0 && !is_memory64(ctx, mem)
+}
+
+/// Trap unless `size` bytes from `offset` (both i64) are inside the memory.
+pub fn gen_bounds_check<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    mem: u32,
+    offset: IntValue<'a>,
+    size: IntValue<'a>,
+) -> Result<()> {
+    let end = ctx.builder.build_int_add(offset, size, "access_end")?;
+    // 64-bit offsets may wrap around
+    let wrapped =
+        ctx.builder
+            .build_int_compare(inkwell::IntPredicate::ULT, end, offset, "wrapped")?;
+    let memory_size = gen_memory_size(ctx, exec_env_ptr, mem)?.into_int_value();
+    let memory_bytes = ctx.builder.build_int_mul(
+        memory_size,
+        ctx.inkwell_types.i64_type.const_int(PAGE_SIZE, false),
+        "memory_bytes",
+    )?;
+    let out_of_bounds = ctx.builder.build_int_compare(
+        inkwell::IntPredicate::UGT,
+        end,
+        memory_bytes,
+        "out_of_bounds",
+    )?;
+    let out_of_bounds = ctx.builder.build_or(out_of_bounds, wrapped, "")?;
+
+    gen_trap_if(
+        ctx,
+        exec_env_ptr,
+        out_of_bounds,
+        TrapKind::MemoryOutOfBounds,
+    )
+}
+
+pub fn gen_table_field_ptr<'a>(
+    ctx: &mut Context<'a, '_>,
+    table: u32,
+    field_idx: u32,
+) -> Result<PointerValue<'a>> {
+    let table_type = ctx.table_type.expect("should define table_type");
+    let Some(table_ptr) = ctx
+        .tables
+        .get(table as usize)
+        .and_then(|table| table.global)
+    else {
+        bail!("Unknown table {}", table);
+    };
+    let field_ptr =
+        ctx.builder
+            .build_struct_gep(table_type, table_ptr.as_pointer_value(), field_idx, "")?;
+    Ok(field_ptr)
+}
+
+pub fn gen_table_base<'a>(ctx: &mut Context<'a, '_>, table: u32) -> Result<PointerValue<'a>> {
+    let table_base_ptr = gen_table_field_ptr(ctx, table, 0)?;
+    let table_base =
+        ctx.builder
+            .build_load(ctx.inkwell_types.ptr_type, table_base_ptr, "table_base")?;
+    Ok(table_base.into_pointer_value())
+}
+
+pub fn gen_table_size<'a>(ctx: &mut Context<'a, '_>, table: u32) -> Result<IntValue<'a>> {
+    let table_size_ptr = gen_table_field_ptr(ctx, table, 1)?;
+    let table_size =
+        ctx.builder
+            .build_load(ctx.inkwell_types.i32_type, table_size_ptr, "table_size")?;
+    Ok(table_size.into_int_value())
+}
+
+/// Trap with `kind` unless `size` elements from `offset` (both i64) are inside the table.
+pub fn gen_table_bounds_check<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    table: u32,
+    offset: IntValue<'a>,
+    size: IntValue<'a>,
+    kind: TrapKind,
+) -> Result<()> {
+    let end = ctx.builder.build_int_add(offset, size, "access_end")?;
+    let table_size = gen_table_size(ctx, table)?;
+    let table_size = ctx
+        .builder
+        .build_int_z_extend(table_size, ctx.inkwell_types.i64_type, "")?;
+    let out_of_bounds = ctx.builder.build_int_compare(
+        inkwell::IntPredicate::UGT,
+        end,
+        table_size,
+        "out_of_bounds",
+    )?;
+    gen_trap_if(ctx, exec_env_ptr, out_of_bounds, kind)
+}
+
 pub fn gen_and(ctx: &mut Context<'_, '_>) -> Result<()> {
-    let (v1, v2) = ctx.pop2();
+    let (v1, v2) = ctx.pop2()?;
     let res = ctx
         .builder
-        .build_and(v1.into_int_value(), v2.into_int_value(), "")
-        .expect("should build and");
+        .build_and(v1.into_int_value(), v2.into_int_value(), "")?;
     ctx.push(res.as_basic_value_enum());
     Ok(())
 }
 
 pub fn gen_or(ctx: &mut Context<'_, '_>) -> Result<()> {
-    let (v1, v2) = ctx.pop2();
+    let (v1, v2) = ctx.pop2()?;
     let res = ctx
         .builder
-        .build_or(v1.into_int_value(), v2.into_int_value(), "")
-        .expect("should build or");
+        .build_or(v1.into_int_value(), v2.into_int_value(), "")?;
     ctx.push(res.as_basic_value_enum());
     Ok(())
 }
 
 pub fn gen_xor(ctx: &mut Context<'_, '_>) -> Result<()> {
-    let (v1, v2) = ctx.pop2();
+    let (v1, v2) = ctx.pop2()?;
     let res = ctx
         .builder
-        .build_xor(v1.into_int_value(), v2.into_int_value(), "")
-        .expect("should build xor");
+        .build_xor(v1.into_int_value(), v2.into_int_value(), "")?;
     ctx.push(res.as_basic_value_enum());
     Ok(())
 }
 
 pub fn gen_shl(ctx: &mut Context<'_, '_>) -> Result<()> {
-    let (v1, v2) = ctx.pop2();
+    let (v1, v2) = ctx.pop2()?;
     let res = ctx
         .builder
-        .build_left_shift(v1.into_int_value(), v2.into_int_value(), "")
-        .expect("should build shl");
+        .build_left_shift(v1.into_int_value(), v2.into_int_value(), "")?;
     ctx.push(res.as_basic_value_enum());
     Ok(())
 }
 
 pub fn gen_shr(ctx: &mut Context<'_, '_>, sign_extend: bool) -> Result<()> {
-    let (v1, v2) = ctx.pop2();
-    let res = ctx
-        .builder
-        .build_right_shift(v1.into_int_value(), v2.into_int_value(), sign_extend, "")
-        .expect("should build shr");
+    let (v1, v2) = ctx.pop2()?;
+    let res =
+        ctx.builder
+            .build_right_shift(v1.into_int_value(), v2.into_int_value(), sign_extend, "")?;
     ctx.push(res.as_basic_value_enum());
     Ok(())
 }
 
 pub fn gen_rotl(ctx: &mut Context<'_, '_>, if_32bit: bool) -> Result<()> {
-    let (v1, v2) = ctx.pop2();
+    let (v1, v2) = ctx.pop2()?;
     let (v1, v2) = (v1.into_int_value(), v2.into_int_value());
     let mask = if if_32bit {
         ctx.inkwell_types.i32_type.const_int(31u64, false)
@@ -136,7 +387,7 @@ pub fn gen_rotl(ctx: &mut Context<'_, '_>, if_32bit: bool) -> Result<()> {
 }
 
 pub fn gen_rotr(ctx: &mut Context<'_, '_>, if_32bit: bool) -> Result<()> {
-    let (v1, v2) = ctx.pop2();
+    let (v1, v2) = ctx.pop2()?;
     let (v1, v2) = (v1.into_int_value(), v2.into_int_value());
     let mask = if if_32bit {
         ctx.inkwell_types.i32_type.const_int(31u64, false)
@@ -158,32 +409,24 @@ pub fn gen_rotr(ctx: &mut Context<'_, '_>, if_32bit: bool) -> Result<()> {
 }
 
 pub fn gen_float_compare(ctx: &mut Context<'_, '_>, cond: inkwell::FloatPredicate) -> Result<()> {
-    let v2 = ctx.pop().expect("stack empty").into_float_value();
-    let v1 = ctx.pop().expect("stack empty").into_float_value();
-    let cond = ctx
-        .builder
-        .build_float_compare(cond, v1, v2, "")
-        .expect("should build float compare");
+    let v2 = ctx.pop()?.into_float_value();
+    let v1 = ctx.pop()?.into_float_value();
+    let cond = ctx.builder.build_float_compare(cond, v1, v2, "")?;
     let result = ctx
         .builder
-        .build_int_z_extend(cond, ctx.inkwell_types.i32_type, "")
-        .expect("should build int z extend");
+        .build_int_z_extend(cond, ctx.inkwell_types.i32_type, "")?;
     ctx.push(result.as_basic_value_enum());
 
     Ok(())
 }
 
 pub fn gen_int_compare(ctx: &mut Context<'_, '_>, cond: inkwell::IntPredicate) -> Result<()> {
-    let v2 = ctx.pop().expect("stack empty").into_int_value();
-    let v1 = ctx.pop().expect("stack empty").into_int_value();
-    let cond = ctx
-        .builder
-        .build_int_compare(cond, v1, v2, "")
-        .expect("should build int compare");
+    let v2 = ctx.pop()?.into_int_value();
+    let v1 = ctx.pop()?.into_int_value();
+    let cond = ctx.builder.build_int_compare(cond, v1, v2, "")?;
     let result = ctx
         .builder
-        .build_int_z_extend(cond, ctx.inkwell_types.i32_type, "")
-        .expect("should build int z extend");
+        .build_int_z_extend(cond, ctx.inkwell_types.i32_type, "")?;
     ctx.push(result.as_basic_value_enum());
 
     Ok(())
@@ -0,0 +1,201 @@
+use anyhow::{bail, Result};
+use inkwell::{module::Linkage, types::BasicTypeEnum, values::GlobalValue, AddressSpace};
+use wasmparser::GlobalType;
+
+use crate::context::{Context, Global};
+
+use super::{
+    compile_type::{llvm_result_types, wasmty_to_llvmty},
+    library::c_type,
+};
+
+const HEADER_PROLOGUE: &str = r#"// Generated by wanco. Do not edit.
+//
+// Host imports of the module. Define them in C/C++ objects or archives and
+// pass them to wanco with --host. The symbols encode the signatures, so a
+// mismatched definition is reported as an undefined symbol at link time.
+// funcref and externref values are int32_t, and -1 means null.
+#pragma once
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct ExecEnv wanco_exec_env_t;
+typedef int64_t wanco_v128_t __attribute__((vector_size(16)));
+// The size of a table must be within the limits of its import.
+typedef struct {
+  int32_t *elements;
+  int32_t size;
+} wanco_table_t;
+"#;
+
+const HEADER_EPILOGUE: &str = r#"
+#ifdef __cplusplus
+}
+#endif
+"#;
+
+/// Modules whose imports are implemented by the runtime rather than by the host.
+pub(super) fn is_host_module(module: &str) -> bool {
+    !matches!(module, "wasi_snapshot_preview1" | "wasi_unstable" | "wasi")
+}
+
+/// Declare a host function and get its symbol.
+/// The symbol is `wanco_import_<module><name>_<params>_<results>`.
+pub(super) fn declare_host_function(
+    ctx: &mut Context<'_, '_>,
+    module: &str,
+    name: &str,
+    type_idx: u32,
+) -> Result<String> {
+    let fn_type = ctx.signatures[type_idx as usize];
+    // Skip &exec_env
+    let param_types: Vec<BasicTypeEnum> = fn_type.get_param_types().into_iter().skip(1).collect();
+    let results = llvm_result_types(&fn_type);
+    if results.len() > 1 {
+        bail!(
+            "Host function {}.{} cannot return multiple values",
+            module,
+            name
+        );
+    }
+
+    let symbol = format!(
+        "wanco_import_{}{}_{}_{}",
+        mangle_name(module),
+        mangle_name(name),
+        signature_code(ctx, &param_types)?,
+        signature_code(ctx, &results)?
+    );
+
+    let mut c_params = vec!["wanco_exec_env_t *env".to_string()];
+    for (i, ty) in param_types.iter().enumerate() {
+        c_params.push(format!("{} arg{}", c_type(ctx, *ty)?, i));
+    }
+    let c_result = match results.first() {
+        Some(ty) => c_type(ctx, *ty)?,
+        None => "void",
+    };
+    ctx.host_imports.push(format!(
+        "// function {:?} {:?}\n{} {}({});",
+        module,
+        name,
+        c_result,
+        symbol,
+        c_params.join(", ")
+    ));
+    Ok(symbol)
+}
+
+/// Declare a host global as an external global variable.
+/// The symbol is `wanco_import_<module><name>_<type>`, and the type of a mutable global is prefixed by `m`.
+pub(super) fn declare_host_global<'a>(
+    ctx: &mut Context<'a, '_>,
+    module: &str,
+    name: &str,
+    global_type: &GlobalType,
+) -> Result<Global<'a>> {
+    if global_type.shared {
+        bail!("Shared global {}.{} is not supported", module, name);
+    }
+    let ty = wasmty_to_llvmty(ctx, &global_type.content_type)?;
+    let symbol = format!(
+        "wanco_import_{}{}_{}{}",
+        mangle_name(module),
+        mangle_name(name),
+        if global_type.mutable { "m" } else { "" },
+        signature_code(ctx, &[ty])?
+    );
+
+    let ptr = ctx
+        .module
+        .add_global(ty, Some(AddressSpace::default()), &symbol);
+    ctx.host_imports.push(format!(
+        "// global {:?} {:?}\nextern {}{} {};",
+        module,
+        name,
+        if global_type.mutable { "" } else { "const " },
+        c_type(ctx, ty)?,
+        symbol
+    ));
+    if global_type.mutable {
+        Ok(Global::Mut { ptr, ty })
+    } else {
+        ptr.set_constant(true);
+        Ok(Global::Immutable { ptr, ty })
+    }
+}
+
+/// Declare a host table as an external `wanco_table_t`.
+/// The symbol is `wanco_import_<module><name>_t`.
+pub(super) fn declare_host_table<'a>(
+    ctx: &mut Context<'a, '_>,
+    module: &str,
+    name: &str,
+) -> GlobalValue<'a> {
+    let table_type = ctx.table_type.expect("should define table_type");
+    let symbol = format!(
+        "wanco_import_{}{}_t",
+        mangle_name(module),
+        mangle_name(name)
+    );
+    let global = ctx
+        .module
+        .add_global(table_type, Some(AddressSpace::default()), &symbol);
+    global.set_linkage(Linkage::External);
+    ctx.host_imports.push(format!(
+        "// table {:?} {:?}\nextern wanco_table_t {};",
+        module, name, symbol
+    ));
+    global
+}
+
+/// Generate the C header which declares the host imports.
+pub(super) fn gen_imports_header(ctx: &mut Context<'_, '_>) {
+    let mut header = HEADER_PROLOGUE.to_string();
+    for declaration in &ctx.host_imports {
+        header.push('\n');
+        header.push_str(declaration);
+        header.push('\n');
+    }
+    header.push_str(HEADER_EPILOGUE);
+    ctx.imports_header = Some(header);
+}
+
+/// Mangle a module or field name so that concatenated names never collide.
+/// C identifiers which do not start with a digit are prefixed by their length,
+/// and other names are hex-encoded and prefixed by `x<length>_`.
+fn mangle_name(name: &str) -> String {
+    let is_identifier = name.bytes().next().is_some_and(|b| !b.is_ascii_digit())
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_');
+    if is_identifier {
+        format!("{}{}", name.len(), name)
+    } else {
+        let hex: String = name.bytes().map(|b| format!("{:02x}", b)).collect();
+        format!("x{}_{}", hex.len(), hex)
+    }
+}
+
+/// Encode types as i (i32 and references), l (i64), f (f32), d (f64) and V (v128).
+/// An empty list is v.
+fn signature_code(ctx: &Context<'_, '_>, types: &[BasicTypeEnum<'_>]) -> Result<String> {
+    if types.is_empty() {
+        return Ok("v".to_string());
+    }
+    let mut code = String::new();
+    for ty in types {
+        let c = match ty {
+            BasicTypeEnum::IntType(ty) if *ty == ctx.inkwell_types.i32_type => 'i',
+            BasicTypeEnum::IntType(ty) if *ty == ctx.inkwell_types.i64_type => 'l',
+            BasicTypeEnum::FloatType(ty) if *ty == ctx.inkwell_types.f32_type => 'f',
+            BasicTypeEnum::FloatType(ty) if *ty == ctx.inkwell_types.f64_type => 'd',
+            BasicTypeEnum::VectorType(..) => 'V',
+            _ => bail!("Unsupported type of a host import: {:?}", ty),
+        };
+        code.push(c);
+    }
+    Ok(code)
+}
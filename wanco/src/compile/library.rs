@@ -0,0 +1,334 @@
+use std::fmt::Write;
+
+use anyhow::{bail, Result};
+use inkwell::{
+    types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType},
+    values::{BasicValueEnum, FunctionValue, GlobalValue},
+};
+use wasmparser::ExternalKind;
+
+use crate::context::{Context, Global};
+
+use super::{
+    compile_type::llvm_result_types,
+    helper::{gen_memory_base, gen_memory_size, PAGE_SIZE},
+};
+
+const HEADER_PROLOGUE: &str = r#"// Generated by wanco. Do not edit.
+//
+// The module is instantiated at most once at a time since its globals and
+// tables are in static storage. Traps terminate the process.
+// funcref and externref values are int32_t, and -1 means null.
+#pragma once
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct ExecEnv wanco_exec_env_t;
+typedef int64_t wanco_v128_t __attribute__((vector_size(16)));
+
+// Instantiate the module: allocate the linear memory, then run the start
+// section and `_initialize`. `_start` is not run, but it can be called through
+// its export. argc and argv are passed to WASI.
+// Returns NULL if the module is already instantiated or the instantiation
+// throws an exception.
+wanco_exec_env_t *wanco_init(int argc, char **argv);
+// Release the memories and reset the globals and tables, so that the module
+// can be instantiated again.
+void wanco_teardown(wanco_exec_env_t *env);
+// Exported functions do not propagate wasm exceptions to the caller. If one
+// throws, it returns zero (or leaves multiple results unwritten), and this
+// returns 1 until the next call of this function in the same thread.
+// Returns 0 otherwise.
+int32_t wanco_uncaught_exception(wanco_exec_env_t *env);
+"#;
+
+const HEADER_EPILOGUE: &str = r#"
+#ifdef __cplusplus
+}
+#endif
+"#;
+
+/// Define the C ABI entry points of the exported functions, memories and globals,
+/// and generate the C header which declares them along with the runtime API.
+pub(super) fn gen_library_exports(ctx: &mut Context<'_, '_>) -> Result<()> {
+    let mut header = HEADER_PROLOGUE.to_string();
+    for i in 0..ctx.exports.len() {
+        let name = ctx.exports[i].name.clone();
+        let index = ctx.exports[i].index;
+        let symbol = c_identifier(&name);
+        match ctx.exports[i].kind {
+            ExternalKind::Func => {
+                writeln!(header, "\n// function {:?}", name)?;
+                gen_function_export(ctx, &mut header, &format!("wanco_export_{}", symbol), index)?;
+            }
+            ExternalKind::Memory => {
+                writeln!(header, "\n// memory {:?}", name)?;
+                gen_memory_export(ctx, &mut header, &symbol, index)?;
+            }
+            ExternalKind::Global => {
+                writeln!(header, "\n// global {:?}", name)?;
+                gen_global_export(ctx, &mut header, &symbol, index)?;
+            }
+            _ => {
+                log::warn!("Export {:?} is not exposed in the library", name);
+            }
+        }
+    }
+    header.push_str(HEADER_EPILOGUE);
+    ctx.c_header = Some(header);
+    Ok(())
+}
+
+/// Define an exported function which calls the wasm function.
+/// Multiple results are written to the pointers following the arguments.
+fn gen_function_export(
+    ctx: &mut Context<'_, '_>,
+    header: &mut String,
+    symbol: &str,
+    index: u32,
+) -> Result<()> {
+    let function = ctx.function_values[index as usize];
+    let fn_type = function.get_type();
+    let param_types = fn_type.get_param_types();
+    let results = llvm_result_types(&fn_type);
+
+    let mut params: Vec<BasicMetadataTypeEnum> =
+        param_types.iter().map(|ty| (*ty).into()).collect();
+    if results.len() > 1 {
+        params.extend(
+            results
+                .iter()
+                .map(|_| BasicMetadataTypeEnum::from(ctx.inkwell_types.ptr_type)),
+        );
+    }
+    let export_type = match results.as_slice() {
+        [ty] => ty.fn_type(&params, false),
+        _ => ctx.inkwell_types.void_type.fn_type(&params, false),
+    };
+    let export = add_export_function(ctx, symbol, export_type)?;
+
+    // The function is invoked so that exceptions never unwind through the C frames of the host
+    let args: Vec<BasicValueEnum> = export.get_param_iter().take(param_types.len()).collect();
+    let then_block = ctx.ictx.append_basic_block(export, "invoke.cont");
+    let catch_block = ctx.ictx.append_basic_block(export, "catch");
+    let call_site = ctx
+        .builder
+        .build_invoke(function, &args, then_block, catch_block, "")?;
+    call_site.set_call_convention(function.get_call_conventions());
+    ctx.builder.position_at_end(then_block);
+    match (results.len(), call_site.try_as_basic_value().left()) {
+        (1, Some(ret)) => ctx.builder.build_return(Some(&ret)),
+        (0, _) => ctx.builder.build_return(None),
+        (_, Some(ret)) => {
+            let ret = ret.into_struct_value();
+            for (i, ptr) in export.get_param_iter().skip(param_types.len()).enumerate() {
+                let value = ctx.builder.build_extract_value(ret, i as u32, "result")?;
+                ctx.builder.build_store(ptr.into_pointer_value(), value)?;
+            }
+            ctx.builder.build_return(None)
+        }
+        _ => unreachable!("function with results should return a value"),
+    }?;
+
+    // Report the exception to lib-rt and return zero, or leave multiple results unwritten
+    ctx.builder.position_at_end(catch_block);
+    let personality = ctx.fn_personality.expect("should define personality");
+    export.set_personality_function(personality);
+    let exception_type = ctx.ictx.struct_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+        ],
+        false,
+    );
+    let typeinfo = ctx
+        .global_exception_typeinfo
+        .expect("should define global_exception_typeinfo")
+        .as_pointer_value();
+    let landing_pad_value = ctx.builder.build_landing_pad(
+        exception_type,
+        personality,
+        &[typeinfo.into()],
+        false,
+        "exn",
+    )?;
+    let exn =
+        ctx.builder
+            .build_extract_value(landing_pad_value.into_struct_value(), 0, "exn_ptr")?;
+    let exec_env_ptr = export.get_first_param().expect("should have &exec_env");
+    ctx.builder.build_call(
+        ctx.fn_exception_uncaught
+            .expect("should define exception_uncaught"),
+        &[exec_env_ptr.into(), exn.into()],
+        "",
+    )?;
+    match results.as_slice() {
+        [ty] => ctx.builder.build_return(Some(&ty.const_zero())),
+        _ => ctx.builder.build_return(None),
+    }?;
+
+    // Skip &exec_env
+    let mut c_params = vec!["wanco_exec_env_t *env".to_string()];
+    for (i, ty) in param_types.iter().skip(1).enumerate() {
+        c_params.push(format!("{} arg{}", c_type(ctx, *ty)?, i));
+    }
+    let c_result = match results.as_slice() {
+        [ty] => c_type(ctx, *ty)?,
+        _ => {
+            for (i, ty) in results.iter().enumerate() {
+                c_params.push(format!("{} *result{}", c_type(ctx, *ty)?, i));
+            }
+            "void"
+        }
+    };
+    writeln!(header, "{} {}({});", c_result, symbol, c_params.join(", "))?;
+    Ok(())
+}
+
+/// Define the accessors of the base address and the size in bytes of an exported memory.
+fn gen_memory_export(
+    ctx: &mut Context<'_, '_>,
+    header: &mut String,
+    symbol: &str,
+    index: u32,
+) -> Result<()> {
+    let ptr_type = ctx.inkwell_types.ptr_type;
+    let i64_type = ctx.inkwell_types.i64_type;
+
+    let base_symbol = format!("wanco_memory_base_{}", symbol);
+    let base = add_export_function(
+        ctx,
+        &base_symbol,
+        ptr_type.fn_type(&[ptr_type.into()], false),
+    )?;
+    let exec_env_ptr = base.get_first_param().unwrap().into_pointer_value();
+    let memory_base = gen_memory_base(ctx, &exec_env_ptr, index)?;
+    ctx.builder.build_return(Some(&memory_base))?;
+    writeln!(header, "uint8_t *{}(wanco_exec_env_t *env);", base_symbol)?;
+
+    let size_symbol = format!("wanco_memory_size_{}", symbol);
+    let size = add_export_function(
+        ctx,
+        &size_symbol,
+        i64_type.fn_type(&[ptr_type.into()], false),
+    )?;
+    let exec_env_ptr = size.get_first_param().unwrap().into_pointer_value();
+    let pages = gen_memory_size(ctx, &exec_env_ptr, index)?.into_int_value();
+    let bytes = ctx
+        .builder
+        .build_int_mul(pages, i64_type.const_int(PAGE_SIZE, false), "bytes")?;
+    ctx.builder.build_return(Some(&bytes))?;
+    writeln!(header, "uint64_t {}(wanco_exec_env_t *env);", size_symbol)?;
+    Ok(())
+}
+
+/// Define the getter of an exported global, and the setter if it is mutable.
+fn gen_global_export(
+    ctx: &mut Context<'_, '_>,
+    header: &mut String,
+    symbol: &str,
+    index: u32,
+) -> Result<()> {
+    let ptr_type = ctx.inkwell_types.ptr_type;
+    let get_symbol = format!("wanco_global_get_{}", symbol);
+    match ctx.globals[index as usize] {
+        Global::Const { value } => {
+            let ty = value.get_type();
+            add_export_function(ctx, &get_symbol, ty.fn_type(&[ptr_type.into()], false))?;
+            ctx.builder.build_return(Some(&value))?;
+            writeln!(
+                header,
+                "{} {}(wanco_exec_env_t *env);",
+                c_type(ctx, ty)?,
+                get_symbol
+            )?;
+        }
+        Global::Immutable { ptr, ty } => {
+            gen_global_getter(ctx, header, &get_symbol, ptr, ty)?;
+        }
+        Global::Mut { ptr, ty } => {
+            gen_global_getter(ctx, header, &get_symbol, ptr, ty)?;
+
+            let set_symbol = format!("wanco_global_set_{}", symbol);
+            let set_type = ctx
+                .inkwell_types
+                .void_type
+                .fn_type(&[ptr_type.into(), ty.into()], false);
+            let set = add_export_function(ctx, &set_symbol, set_type)?;
+            let value = set.get_nth_param(1).unwrap();
+            ctx.builder.build_store(ptr.as_pointer_value(), value)?;
+            ctx.builder.build_return(None)?;
+            writeln!(
+                header,
+                "void {}(wanco_exec_env_t *env, {} value);",
+                set_symbol,
+                c_type(ctx, ty)?
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Define the getter of a global which is loaded from the pointer.
+fn gen_global_getter<'a>(
+    ctx: &Context<'a, '_>,
+    header: &mut String,
+    get_symbol: &str,
+    ptr: GlobalValue<'a>,
+    ty: BasicTypeEnum<'a>,
+) -> Result<()> {
+    let ptr_type = ctx.inkwell_types.ptr_type;
+    add_export_function(ctx, get_symbol, ty.fn_type(&[ptr_type.into()], false))?;
+    let value = ctx
+        .builder
+        .build_load(ty, ptr.as_pointer_value(), "value")?;
+    ctx.builder.build_return(Some(&value))?;
+    writeln!(
+        header,
+        "{} {}(wanco_exec_env_t *env);",
+        c_type(ctx, ty)?,
+        get_symbol
+    )?;
+    Ok(())
+}
+
+/// Add an exported function and move the builder to its entry.
+fn add_export_function<'a>(
+    ctx: &Context<'a, '_>,
+    symbol: &str,
+    fn_type: FunctionType<'a>,
+) -> Result<FunctionValue<'a>> {
+    if ctx.module.get_function(symbol).is_some() {
+        bail!(
+            "Exports conflict with each other as the C symbol {}",
+            symbol
+        );
+    }
+    let function = ctx.module.add_function(symbol, fn_type, None);
+    let block = ctx.ictx.append_basic_block(function, "entry");
+    ctx.builder.position_at_end(block);
+    Ok(function)
+}
+
+/// Convert an export name to a C identifier by replacing invalid characters with `_`.
+fn c_identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+pub(super) fn c_type(ctx: &Context<'_, '_>, ty: BasicTypeEnum<'_>) -> Result<&'static str> {
+    let c_type = match ty {
+        BasicTypeEnum::IntType(ty) if ty == ctx.inkwell_types.i32_type => "int32_t",
+        BasicTypeEnum::IntType(ty) if ty == ctx.inkwell_types.i64_type => "int64_t",
+        BasicTypeEnum::FloatType(ty) if ty == ctx.inkwell_types.f32_type => "float",
+        BasicTypeEnum::FloatType(ty) if ty == ctx.inkwell_types.f64_type => "double",
+        BasicTypeEnum::VectorType(..) => "wanco_v128_t",
+        _ => bail!("Unsupported type in the C header: {:?}", ty),
+    };
+    Ok(c_type)
+}
@@ -1,12 +1,23 @@
+mod alias;
+mod atomic;
 mod compile_function;
 mod compile_global;
 mod compile_memory;
 mod compile_module;
 mod compile_type;
+mod const_expr;
 pub mod control;
 pub mod cr;
+mod error;
 pub mod helper;
+mod host;
+mod library;
+mod simd;
 pub mod stackmap;
 mod synthesize;
+pub mod trap;
+mod validate;
 
 pub use compile_module::compile_module;
+pub use error::{CompileError, CompileErrorKind};
+pub use validate::validate_module;
@@ -0,0 +1,1172 @@
+use anyhow::{anyhow, Result};
+use inkwell::{
+    builder::{Builder, BuilderError},
+    intrinsics::Intrinsic,
+    types::{BasicType, BasicTypeEnum, VectorType},
+    values::{BasicMetadataValueEnum, BasicValue, BasicValueEnum, PointerValue, VectorValue},
+    FloatPredicate, IntPredicate,
+};
+use wasmparser::{MemArg, Operator};
+
+use crate::{
+    compile::{
+        alias::set_memory_access_metadata, compile_function::resolve_memarg_pointer,
+        compile_type::v128_const,
+    },
+    context::Context,
+};
+
+/// Compile the instructions of the SIMD proposal.
+/// Returns false if the operator is not a vector instruction.
+pub(super) fn compile_op_simd<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    op: &Operator,
+) -> Result<bool> {
+    let v128 = ctx.inkwell_types.v128_type;
+    let i8x16 = ctx.inkwell_types.i8x16_type;
+    let i16x8 = ctx.inkwell_types.i16x8_type;
+    let i32x4 = ctx.inkwell_types.i32x4_type;
+    let i64x2 = ctx.inkwell_types.i64x2_type;
+    let f32x4 = ctx.inkwell_types.f32x4_type;
+    let f64x2 = ctx.inkwell_types.f64x2_type;
+
+    match op {
+        /******************************
+          Memory instructions
+        ******************************/
+        Operator::V128Load { memarg } => {
+            let v = gen_load(ctx, exec_env_ptr, memarg, v128.as_basic_type_enum())?;
+            ctx.push(v);
+        }
+        Operator::V128Load8x8S { memarg } => {
+            gen_load_extend(ctx, exec_env_ptr, memarg, i16x8, true)?;
+        }
+        Operator::V128Load8x8U { memarg } => {
+            gen_load_extend(ctx, exec_env_ptr, memarg, i16x8, false)?;
+        }
+        Operator::V128Load16x4S { memarg } => {
+            gen_load_extend(ctx, exec_env_ptr, memarg, i32x4, true)?;
+        }
+        Operator::V128Load16x4U { memarg } => {
+            gen_load_extend(ctx, exec_env_ptr, memarg, i32x4, false)?;
+        }
+        Operator::V128Load32x2S { memarg } => {
+            gen_load_extend(ctx, exec_env_ptr, memarg, i64x2, true)?;
+        }
+        Operator::V128Load32x2U { memarg } => {
+            gen_load_extend(ctx, exec_env_ptr, memarg, i64x2, false)?;
+        }
+        Operator::V128Load8Splat { memarg } => {
+            gen_load_splat(ctx, exec_env_ptr, memarg, i8x16)?;
+        }
+        Operator::V128Load16Splat { memarg } => {
+            gen_load_splat(ctx, exec_env_ptr, memarg, i16x8)?;
+        }
+        Operator::V128Load32Splat { memarg } => {
+            gen_load_splat(ctx, exec_env_ptr, memarg, i32x4)?;
+        }
+        Operator::V128Load64Splat { memarg } => {
+            gen_load_splat(ctx, exec_env_ptr, memarg, i64x2)?;
+        }
+        Operator::V128Load32Zero { memarg } => {
+            gen_load_zero(ctx, exec_env_ptr, memarg, i32x4)?;
+        }
+        Operator::V128Load64Zero { memarg } => {
+            gen_load_zero(ctx, exec_env_ptr, memarg, i64x2)?;
+        }
+        Operator::V128Load8Lane { memarg, lane } => {
+            gen_load_lane(ctx, exec_env_ptr, memarg, i8x16, *lane)?;
+        }
+        Operator::V128Load16Lane { memarg, lane } => {
+            gen_load_lane(ctx, exec_env_ptr, memarg, i16x8, *lane)?;
+        }
+        Operator::V128Load32Lane { memarg, lane } => {
+            gen_load_lane(ctx, exec_env_ptr, memarg, i32x4, *lane)?;
+        }
+        Operator::V128Load64Lane { memarg, lane } => {
+            gen_load_lane(ctx, exec_env_ptr, memarg, i64x2, *lane)?;
+        }
+        Operator::V128Store { memarg } => {
+            let v = ctx.pop()?;
+            gen_store(ctx, exec_env_ptr, memarg, v)?;
+        }
+        Operator::V128Store8Lane { memarg, lane } => {
+            gen_store_lane(ctx, exec_env_ptr, memarg, i8x16, *lane)?;
+        }
+        Operator::V128Store16Lane { memarg, lane } => {
+            gen_store_lane(ctx, exec_env_ptr, memarg, i16x8, *lane)?;
+        }
+        Operator::V128Store32Lane { memarg, lane } => {
+            gen_store_lane(ctx, exec_env_ptr, memarg, i32x4, *lane)?;
+        }
+        Operator::V128Store64Lane { memarg, lane } => {
+            gen_store_lane(ctx, exec_env_ptr, memarg, i64x2, *lane)?;
+        }
+        /******************************
+          Lane instructions
+        ******************************/
+        Operator::V128Const { value } => {
+            let v = v128_const(ctx, value);
+            ctx.push(v.as_basic_value_enum());
+        }
+        Operator::I8x16Shuffle { lanes } => {
+            let (v1, v2) = pop2_vector(ctx, i8x16)?;
+            let mask = const_lanes(ctx, lanes.iter().map(|lane| *lane as u32));
+            let res = ctx.builder.build_shuffle_vector(v1, v2, mask, "shuffle")?;
+            push_vector(ctx, res)?;
+        }
+        Operator::I8x16Swizzle => {
+            gen_swizzle(ctx)?;
+        }
+        Operator::I8x16ExtractLaneS { lane } => {
+            gen_extract_lane(ctx, i8x16, *lane, Some(true))?;
+        }
+        Operator::I8x16ExtractLaneU { lane } => {
+            gen_extract_lane(ctx, i8x16, *lane, Some(false))?;
+        }
+        Operator::I16x8ExtractLaneS { lane } => {
+            gen_extract_lane(ctx, i16x8, *lane, Some(true))?;
+        }
+        Operator::I16x8ExtractLaneU { lane } => {
+            gen_extract_lane(ctx, i16x8, *lane, Some(false))?;
+        }
+        Operator::I32x4ExtractLane { lane } => {
+            gen_extract_lane(ctx, i32x4, *lane, None)?;
+        }
+        Operator::I64x2ExtractLane { lane } => {
+            gen_extract_lane(ctx, i64x2, *lane, None)?;
+        }
+        Operator::F32x4ExtractLane { lane } => {
+            gen_extract_lane(ctx, f32x4, *lane, None)?;
+        }
+        Operator::F64x2ExtractLane { lane } => {
+            gen_extract_lane(ctx, f64x2, *lane, None)?;
+        }
+        Operator::I8x16ReplaceLane { lane } => {
+            gen_replace_lane(ctx, i8x16, *lane)?;
+        }
+        Operator::I16x8ReplaceLane { lane } => {
+            gen_replace_lane(ctx, i16x8, *lane)?;
+        }
+        Operator::I32x4ReplaceLane { lane } => {
+            gen_replace_lane(ctx, i32x4, *lane)?;
+        }
+        Operator::I64x2ReplaceLane { lane } => {
+            gen_replace_lane(ctx, i64x2, *lane)?;
+        }
+        Operator::F32x4ReplaceLane { lane } => {
+            gen_replace_lane(ctx, f32x4, *lane)?;
+        }
+        Operator::F64x2ReplaceLane { lane } => {
+            gen_replace_lane(ctx, f64x2, *lane)?;
+        }
+        Operator::I8x16Splat => {
+            gen_splat_op(ctx, i8x16)?;
+        }
+        Operator::I16x8Splat => {
+            gen_splat_op(ctx, i16x8)?;
+        }
+        Operator::I32x4Splat => {
+            gen_splat_op(ctx, i32x4)?;
+        }
+        Operator::I64x2Splat => {
+            gen_splat_op(ctx, i64x2)?;
+        }
+        Operator::F32x4Splat => {
+            gen_splat_op(ctx, f32x4)?;
+        }
+        Operator::F64x2Splat => {
+            gen_splat_op(ctx, f64x2)?;
+        }
+        /******************************
+          Comparison instructions
+        ******************************/
+        Operator::I8x16Eq => gen_int_compare(ctx, i8x16, IntPredicate::EQ)?,
+        Operator::I8x16Ne => gen_int_compare(ctx, i8x16, IntPredicate::NE)?,
+        Operator::I8x16LtS => gen_int_compare(ctx, i8x16, IntPredicate::SLT)?,
+        Operator::I8x16LtU => gen_int_compare(ctx, i8x16, IntPredicate::ULT)?,
+        Operator::I8x16GtS => gen_int_compare(ctx, i8x16, IntPredicate::SGT)?,
+        Operator::I8x16GtU => gen_int_compare(ctx, i8x16, IntPredicate::UGT)?,
+        Operator::I8x16LeS => gen_int_compare(ctx, i8x16, IntPredicate::SLE)?,
+        Operator::I8x16LeU => gen_int_compare(ctx, i8x16, IntPredicate::ULE)?,
+        Operator::I8x16GeS => gen_int_compare(ctx, i8x16, IntPredicate::SGE)?,
+        Operator::I8x16GeU => gen_int_compare(ctx, i8x16, IntPredicate::UGE)?,
+        Operator::I16x8Eq => gen_int_compare(ctx, i16x8, IntPredicate::EQ)?,
+        Operator::I16x8Ne => gen_int_compare(ctx, i16x8, IntPredicate::NE)?,
+        Operator::I16x8LtS => gen_int_compare(ctx, i16x8, IntPredicate::SLT)?,
+        Operator::I16x8LtU => gen_int_compare(ctx, i16x8, IntPredicate::ULT)?,
+        Operator::I16x8GtS => gen_int_compare(ctx, i16x8, IntPredicate::SGT)?,
+        Operator::I16x8GtU => gen_int_compare(ctx, i16x8, IntPredicate::UGT)?,
+        Operator::I16x8LeS => gen_int_compare(ctx, i16x8, IntPredicate::SLE)?,
+        Operator::I16x8LeU => gen_int_compare(ctx, i16x8, IntPredicate::ULE)?,
+        Operator::I16x8GeS => gen_int_compare(ctx, i16x8, IntPredicate::SGE)?,
+        Operator::I16x8GeU => gen_int_compare(ctx, i16x8, IntPredicate::UGE)?,
+        Operator::I32x4Eq => gen_int_compare(ctx, i32x4, IntPredicate::EQ)?,
+        Operator::I32x4Ne => gen_int_compare(ctx, i32x4, IntPredicate::NE)?,
+        Operator::I32x4LtS => gen_int_compare(ctx, i32x4, IntPredicate::SLT)?,
+        Operator::I32x4LtU => gen_int_compare(ctx, i32x4, IntPredicate::ULT)?,
+        Operator::I32x4GtS => gen_int_compare(ctx, i32x4, IntPredicate::SGT)?,
+        Operator::I32x4GtU => gen_int_compare(ctx, i32x4, IntPredicate::UGT)?,
+        Operator::I32x4LeS => gen_int_compare(ctx, i32x4, IntPredicate::SLE)?,
+        Operator::I32x4LeU => gen_int_compare(ctx, i32x4, IntPredicate::ULE)?,
+        Operator::I32x4GeS => gen_int_compare(ctx, i32x4, IntPredicate::SGE)?,
+        Operator::I32x4GeU => gen_int_compare(ctx, i32x4, IntPredicate::UGE)?,
+        Operator::I64x2Eq => gen_int_compare(ctx, i64x2, IntPredicate::EQ)?,
+        Operator::I64x2Ne => gen_int_compare(ctx, i64x2, IntPredicate::NE)?,
+        Operator::I64x2LtS => gen_int_compare(ctx, i64x2, IntPredicate::SLT)?,
+        Operator::I64x2GtS => gen_int_compare(ctx, i64x2, IntPredicate::SGT)?,
+        Operator::I64x2LeS => gen_int_compare(ctx, i64x2, IntPredicate::SLE)?,
+        Operator::I64x2GeS => gen_int_compare(ctx, i64x2, IntPredicate::SGE)?,
+        Operator::F32x4Eq => gen_float_compare(ctx, f32x4, i32x4, FloatPredicate::OEQ)?,
+        Operator::F32x4Ne => gen_float_compare(ctx, f32x4, i32x4, FloatPredicate::UNE)?,
+        Operator::F32x4Lt => gen_float_compare(ctx, f32x4, i32x4, FloatPredicate::OLT)?,
+        Operator::F32x4Gt => gen_float_compare(ctx, f32x4, i32x4, FloatPredicate::OGT)?,
+        Operator::F32x4Le => gen_float_compare(ctx, f32x4, i32x4, FloatPredicate::OLE)?,
+        Operator::F32x4Ge => gen_float_compare(ctx, f32x4, i32x4, FloatPredicate::OGE)?,
+        Operator::F64x2Eq => gen_float_compare(ctx, f64x2, i64x2, FloatPredicate::OEQ)?,
+        Operator::F64x2Ne => gen_float_compare(ctx, f64x2, i64x2, FloatPredicate::UNE)?,
+        Operator::F64x2Lt => gen_float_compare(ctx, f64x2, i64x2, FloatPredicate::OLT)?,
+        Operator::F64x2Gt => gen_float_compare(ctx, f64x2, i64x2, FloatPredicate::OGT)?,
+        Operator::F64x2Le => gen_float_compare(ctx, f64x2, i64x2, FloatPredicate::OLE)?,
+        Operator::F64x2Ge => gen_float_compare(ctx, f64x2, i64x2, FloatPredicate::OGE)?,
+        /******************************
+          Bitwise instructions
+        ******************************/
+        Operator::V128Not => gen_unop(ctx, v128, |b, v| b.build_not(v, "v128not"))?,
+        Operator::V128And => gen_binop(ctx, v128, |b, v1, v2| b.build_and(v1, v2, "v128and"))?,
+        Operator::V128AndNot => gen_binop(ctx, v128, |b, v1, v2| {
+            let not_v2 = b.build_not(v2, "not_v2")?;
+            b.build_and(v1, not_v2, "v128andnot")
+        })?,
+        Operator::V128Or => gen_binop(ctx, v128, |b, v1, v2| b.build_or(v1, v2, "v128or"))?,
+        Operator::V128Xor => gen_binop(ctx, v128, |b, v1, v2| b.build_xor(v1, v2, "v128xor"))?,
+        Operator::V128Bitselect => {
+            let mask = pop_vector(ctx, v128)?;
+            let (v1, v2) = pop2_vector(ctx, v128)?;
+            let selected1 = ctx.builder.build_and(v1, mask, "selected1")?;
+            let not_mask = ctx.builder.build_not(mask, "not_mask")?;
+            let selected2 = ctx.builder.build_and(v2, not_mask, "selected2")?;
+            let res = ctx.builder.build_or(selected1, selected2, "bitselect")?;
+            push_vector(ctx, res)?;
+        }
+        Operator::V128AnyTrue => {
+            let v = ctx.pop()?;
+            let i128_type = ctx.ictx.i128_type();
+            let v = ctx
+                .builder
+                .build_bit_cast(v, i128_type, "")?
+                .into_int_value();
+            let any_true = ctx.builder.build_int_compare(
+                IntPredicate::NE,
+                v,
+                i128_type.const_zero(),
+                "any_true",
+            )?;
+            let res = ctx
+                .builder
+                .build_int_z_extend(any_true, ctx.inkwell_types.i32_type, "")?;
+            ctx.push(res.as_basic_value_enum());
+        }
+        /******************************
+          Integer instructions
+        ******************************/
+        Operator::I8x16Abs => gen_int_abs(ctx, i8x16)?,
+        Operator::I16x8Abs => gen_int_abs(ctx, i16x8)?,
+        Operator::I32x4Abs => gen_int_abs(ctx, i32x4)?,
+        Operator::I64x2Abs => gen_int_abs(ctx, i64x2)?,
+        Operator::I8x16Neg => gen_unop(ctx, i8x16, |b, v| b.build_int_neg(v, "neg"))?,
+        Operator::I16x8Neg => gen_unop(ctx, i16x8, |b, v| b.build_int_neg(v, "neg"))?,
+        Operator::I32x4Neg => gen_unop(ctx, i32x4, |b, v| b.build_int_neg(v, "neg"))?,
+        Operator::I64x2Neg => gen_unop(ctx, i64x2, |b, v| b.build_int_neg(v, "neg"))?,
+        Operator::I8x16Popcnt => gen_unary_intrinsic(ctx, "llvm.ctpop", i8x16)?,
+        Operator::I8x16AllTrue => gen_all_true(ctx, i8x16)?,
+        Operator::I16x8AllTrue => gen_all_true(ctx, i16x8)?,
+        Operator::I32x4AllTrue => gen_all_true(ctx, i32x4)?,
+        Operator::I64x2AllTrue => gen_all_true(ctx, i64x2)?,
+        Operator::I8x16Bitmask => gen_bitmask(ctx, i8x16)?,
+        Operator::I16x8Bitmask => gen_bitmask(ctx, i16x8)?,
+        Operator::I32x4Bitmask => gen_bitmask(ctx, i32x4)?,
+        Operator::I64x2Bitmask => gen_bitmask(ctx, i64x2)?,
+        Operator::I8x16NarrowI16x8S => gen_narrow(ctx, i16x8, i8x16, true)?,
+        Operator::I8x16NarrowI16x8U => gen_narrow(ctx, i16x8, i8x16, false)?,
+        Operator::I16x8NarrowI32x4S => gen_narrow(ctx, i32x4, i16x8, true)?,
+        Operator::I16x8NarrowI32x4U => gen_narrow(ctx, i32x4, i16x8, false)?,
+        Operator::I16x8ExtendLowI8x16S => gen_extend(ctx, i8x16, i16x8, false, true)?,
+        Operator::I16x8ExtendHighI8x16S => gen_extend(ctx, i8x16, i16x8, true, true)?,
+        Operator::I16x8ExtendLowI8x16U => gen_extend(ctx, i8x16, i16x8, false, false)?,
+        Operator::I16x8ExtendHighI8x16U => gen_extend(ctx, i8x16, i16x8, true, false)?,
+        Operator::I32x4ExtendLowI16x8S => gen_extend(ctx, i16x8, i32x4, false, true)?,
+        Operator::I32x4ExtendHighI16x8S => gen_extend(ctx, i16x8, i32x4, true, true)?,
+        Operator::I32x4ExtendLowI16x8U => gen_extend(ctx, i16x8, i32x4, false, false)?,
+        Operator::I32x4ExtendHighI16x8U => gen_extend(ctx, i16x8, i32x4, true, false)?,
+        Operator::I64x2ExtendLowI32x4S => gen_extend(ctx, i32x4, i64x2, false, true)?,
+        Operator::I64x2ExtendHighI32x4S => gen_extend(ctx, i32x4, i64x2, true, true)?,
+        Operator::I64x2ExtendLowI32x4U => gen_extend(ctx, i32x4, i64x2, false, false)?,
+        Operator::I64x2ExtendHighI32x4U => gen_extend(ctx, i32x4, i64x2, true, false)?,
+        Operator::I8x16Shl => gen_shift(ctx, i8x16, Shift::Shl)?,
+        Operator::I8x16ShrS => gen_shift(ctx, i8x16, Shift::ShrS)?,
+        Operator::I8x16ShrU => gen_shift(ctx, i8x16, Shift::ShrU)?,
+        Operator::I16x8Shl => gen_shift(ctx, i16x8, Shift::Shl)?,
+        Operator::I16x8ShrS => gen_shift(ctx, i16x8, Shift::ShrS)?,
+        Operator::I16x8ShrU => gen_shift(ctx, i16x8, Shift::ShrU)?,
+        Operator::I32x4Shl => gen_shift(ctx, i32x4, Shift::Shl)?,
+        Operator::I32x4ShrS => gen_shift(ctx, i32x4, Shift::ShrS)?,
+        Operator::I32x4ShrU => gen_shift(ctx, i32x4, Shift::ShrU)?,
+        Operator::I64x2Shl => gen_shift(ctx, i64x2, Shift::Shl)?,
+        Operator::I64x2ShrS => gen_shift(ctx, i64x2, Shift::ShrS)?,
+        Operator::I64x2ShrU => gen_shift(ctx, i64x2, Shift::ShrU)?,
+        Operator::I8x16Add => gen_binop(ctx, i8x16, |b, v1, v2| b.build_int_add(v1, v2, "add"))?,
+        Operator::I16x8Add => gen_binop(ctx, i16x8, |b, v1, v2| b.build_int_add(v1, v2, "add"))?,
+        Operator::I32x4Add => gen_binop(ctx, i32x4, |b, v1, v2| b.build_int_add(v1, v2, "add"))?,
+        Operator::I64x2Add => gen_binop(ctx, i64x2, |b, v1, v2| b.build_int_add(v1, v2, "add"))?,
+        Operator::I8x16Sub => gen_binop(ctx, i8x16, |b, v1, v2| b.build_int_sub(v1, v2, "sub"))?,
+        Operator::I16x8Sub => gen_binop(ctx, i16x8, |b, v1, v2| b.build_int_sub(v1, v2, "sub"))?,
+        Operator::I32x4Sub => gen_binop(ctx, i32x4, |b, v1, v2| b.build_int_sub(v1, v2, "sub"))?,
+        Operator::I64x2Sub => gen_binop(ctx, i64x2, |b, v1, v2| b.build_int_sub(v1, v2, "sub"))?,
+        Operator::I16x8Mul => gen_binop(ctx, i16x8, |b, v1, v2| b.build_int_mul(v1, v2, "mul"))?,
+        Operator::I32x4Mul => gen_binop(ctx, i32x4, |b, v1, v2| b.build_int_mul(v1, v2, "mul"))?,
+        Operator::I64x2Mul => gen_binop(ctx, i64x2, |b, v1, v2| b.build_int_mul(v1, v2, "mul"))?,
+        Operator::I8x16AddSatS => gen_binary_intrinsic(ctx, "llvm.sadd.sat", i8x16)?,
+        Operator::I8x16AddSatU => gen_binary_intrinsic(ctx, "llvm.uadd.sat", i8x16)?,
+        Operator::I8x16SubSatS => gen_binary_intrinsic(ctx, "llvm.ssub.sat", i8x16)?,
+        Operator::I8x16SubSatU => gen_binary_intrinsic(ctx, "llvm.usub.sat", i8x16)?,
+        Operator::I16x8AddSatS => gen_binary_intrinsic(ctx, "llvm.sadd.sat", i16x8)?,
+        Operator::I16x8AddSatU => gen_binary_intrinsic(ctx, "llvm.uadd.sat", i16x8)?,
+        Operator::I16x8SubSatS => gen_binary_intrinsic(ctx, "llvm.ssub.sat", i16x8)?,
+        Operator::I16x8SubSatU => gen_binary_intrinsic(ctx, "llvm.usub.sat", i16x8)?,
+        Operator::I8x16MinS => gen_binary_intrinsic(ctx, "llvm.smin", i8x16)?,
+        Operator::I8x16MinU => gen_binary_intrinsic(ctx, "llvm.umin", i8x16)?,
+        Operator::I8x16MaxS => gen_binary_intrinsic(ctx, "llvm.smax", i8x16)?,
+        Operator::I8x16MaxU => gen_binary_intrinsic(ctx, "llvm.umax", i8x16)?,
+        Operator::I16x8MinS => gen_binary_intrinsic(ctx, "llvm.smin", i16x8)?,
+        Operator::I16x8MinU => gen_binary_intrinsic(ctx, "llvm.umin", i16x8)?,
+        Operator::I16x8MaxS => gen_binary_intrinsic(ctx, "llvm.smax", i16x8)?,
+        Operator::I16x8MaxU => gen_binary_intrinsic(ctx, "llvm.umax", i16x8)?,
+        Operator::I32x4MinS => gen_binary_intrinsic(ctx, "llvm.smin", i32x4)?,
+        Operator::I32x4MinU => gen_binary_intrinsic(ctx, "llvm.umin", i32x4)?,
+        Operator::I32x4MaxS => gen_binary_intrinsic(ctx, "llvm.smax", i32x4)?,
+        Operator::I32x4MaxU => gen_binary_intrinsic(ctx, "llvm.umax", i32x4)?,
+        Operator::I8x16AvgrU => gen_avgr_u(ctx, i8x16)?,
+        Operator::I16x8AvgrU => gen_avgr_u(ctx, i16x8)?,
+        Operator::I16x8Q15MulrSatS => gen_q15mulr_sat_s(ctx)?,
+        Operator::I16x8ExtAddPairwiseI8x16S => gen_ext_add_pairwise(ctx, i8x16, i16x8, true)?,
+        Operator::I16x8ExtAddPairwiseI8x16U => gen_ext_add_pairwise(ctx, i8x16, i16x8, false)?,
+        Operator::I32x4ExtAddPairwiseI16x8S => gen_ext_add_pairwise(ctx, i16x8, i32x4, true)?,
+        Operator::I32x4ExtAddPairwiseI16x8U => gen_ext_add_pairwise(ctx, i16x8, i32x4, false)?,
+        Operator::I16x8ExtMulLowI8x16S => gen_ext_mul(ctx, i8x16, i16x8, false, true)?,
+        Operator::I16x8ExtMulHighI8x16S => gen_ext_mul(ctx, i8x16, i16x8, true, true)?,
+        Operator::I16x8ExtMulLowI8x16U => gen_ext_mul(ctx, i8x16, i16x8, false, false)?,
+        Operator::I16x8ExtMulHighI8x16U => gen_ext_mul(ctx, i8x16, i16x8, true, false)?,
+        Operator::I32x4ExtMulLowI16x8S => gen_ext_mul(ctx, i16x8, i32x4, false, true)?,
+        Operator::I32x4ExtMulHighI16x8S => gen_ext_mul(ctx, i16x8, i32x4, true, true)?,
+        Operator::I32x4ExtMulLowI16x8U => gen_ext_mul(ctx, i16x8, i32x4, false, false)?,
+        Operator::I32x4ExtMulHighI16x8U => gen_ext_mul(ctx, i16x8, i32x4, true, false)?,
+        Operator::I64x2ExtMulLowI32x4S => gen_ext_mul(ctx, i32x4, i64x2, false, true)?,
+        Operator::I64x2ExtMulHighI32x4S => gen_ext_mul(ctx, i32x4, i64x2, true, true)?,
+        Operator::I64x2ExtMulLowI32x4U => gen_ext_mul(ctx, i32x4, i64x2, false, false)?,
+        Operator::I64x2ExtMulHighI32x4U => gen_ext_mul(ctx, i32x4, i64x2, true, false)?,
+        Operator::I32x4DotI16x8S => gen_dot(ctx)?,
+        /******************************
+          Floating-point instructions
+        ******************************/
+        Operator::F32x4Ceil => gen_unary_intrinsic(ctx, "llvm.ceil", f32x4)?,
+        Operator::F64x2Ceil => gen_unary_intrinsic(ctx, "llvm.ceil", f64x2)?,
+        Operator::F32x4Floor => gen_unary_intrinsic(ctx, "llvm.floor", f32x4)?,
+        Operator::F64x2Floor => gen_unary_intrinsic(ctx, "llvm.floor", f64x2)?,
+        Operator::F32x4Trunc => gen_unary_intrinsic(ctx, "llvm.trunc", f32x4)?,
+        Operator::F64x2Trunc => gen_unary_intrinsic(ctx, "llvm.trunc", f64x2)?,
+        Operator::F32x4Nearest => gen_unary_intrinsic(ctx, "llvm.nearbyint", f32x4)?,
+        Operator::F64x2Nearest => gen_unary_intrinsic(ctx, "llvm.nearbyint", f64x2)?,
+        Operator::F32x4Abs => gen_unary_intrinsic(ctx, "llvm.fabs", f32x4)?,
+        Operator::F64x2Abs => gen_unary_intrinsic(ctx, "llvm.fabs", f64x2)?,
+        Operator::F32x4Sqrt => gen_unary_intrinsic(ctx, "llvm.sqrt", f32x4)?,
+        Operator::F64x2Sqrt => gen_unary_intrinsic(ctx, "llvm.sqrt", f64x2)?,
+        Operator::F32x4Neg => gen_unop(ctx, f32x4, |b, v| b.build_float_neg(v, "fneg"))?,
+        Operator::F64x2Neg => gen_unop(ctx, f64x2, |b, v| b.build_float_neg(v, "fneg"))?,
+        Operator::F32x4Add => gen_binop(ctx, f32x4, |b, v1, v2| b.build_float_add(v1, v2, "fadd"))?,
+        Operator::F64x2Add => gen_binop(ctx, f64x2, |b, v1, v2| b.build_float_add(v1, v2, "fadd"))?,
+        Operator::F32x4Sub => gen_binop(ctx, f32x4, |b, v1, v2| b.build_float_sub(v1, v2, "fsub"))?,
+        Operator::F64x2Sub => gen_binop(ctx, f64x2, |b, v1, v2| b.build_float_sub(v1, v2, "fsub"))?,
+        Operator::F32x4Mul => gen_binop(ctx, f32x4, |b, v1, v2| b.build_float_mul(v1, v2, "fmul"))?,
+        Operator::F64x2Mul => gen_binop(ctx, f64x2, |b, v1, v2| b.build_float_mul(v1, v2, "fmul"))?,
+        Operator::F32x4Div => gen_binop(ctx, f32x4, |b, v1, v2| b.build_float_div(v1, v2, "fdiv"))?,
+        Operator::F64x2Div => gen_binop(ctx, f64x2, |b, v1, v2| b.build_float_div(v1, v2, "fdiv"))?,
+        Operator::F32x4Min => gen_float_min_max(ctx, f32x4, i32x4, true)?,
+        Operator::F64x2Min => gen_float_min_max(ctx, f64x2, i64x2, true)?,
+        Operator::F32x4Max => gen_float_min_max(ctx, f32x4, i32x4, false)?,
+        Operator::F64x2Max => gen_float_min_max(ctx, f64x2, i64x2, false)?,
+        Operator::F32x4PMin => gen_float_pmin_pmax(ctx, f32x4, true)?,
+        Operator::F64x2PMin => gen_float_pmin_pmax(ctx, f64x2, true)?,
+        Operator::F32x4PMax => gen_float_pmin_pmax(ctx, f32x4, false)?,
+        Operator::F64x2PMax => gen_float_pmin_pmax(ctx, f64x2, false)?,
+        /******************************
+          Conversion instructions
+        ******************************/
+        Operator::I32x4TruncSatF32x4S => {
+            let v = pop_vector(ctx, f32x4)?;
+            let res = gen_vector_intrinsic(ctx, "llvm.fptosi.sat", &[i32x4, f32x4], &[v.into()])?;
+            push_vector(ctx, res)?;
+        }
+        Operator::I32x4TruncSatF32x4U => {
+            let v = pop_vector(ctx, f32x4)?;
+            let res = gen_vector_intrinsic(ctx, "llvm.fptoui.sat", &[i32x4, f32x4], &[v.into()])?;
+            push_vector(ctx, res)?;
+        }
+        Operator::I32x4TruncSatF64x2SZero => gen_trunc_sat_f64x2_zero(ctx, "llvm.fptosi.sat")?,
+        Operator::I32x4TruncSatF64x2UZero => gen_trunc_sat_f64x2_zero(ctx, "llvm.fptoui.sat")?,
+        Operator::F32x4ConvertI32x4S => {
+            let v = pop_vector(ctx, i32x4)?;
+            let res = ctx.builder.build_signed_int_to_float(v, f32x4, "convert")?;
+            push_vector(ctx, res)?;
+        }
+        Operator::F32x4ConvertI32x4U => {
+            let v = pop_vector(ctx, i32x4)?;
+            let res = ctx
+                .builder
+                .build_unsigned_int_to_float(v, f32x4, "convert")?;
+            push_vector(ctx, res)?;
+        }
+        Operator::F64x2ConvertLowI32x4S | Operator::F64x2ConvertLowI32x4U => {
+            let v = pop_vector(ctx, i32x4)?;
+            let low = gen_half(ctx, v, false)?;
+            let res = if matches!(op, Operator::F64x2ConvertLowI32x4S) {
+                ctx.builder
+                    .build_signed_int_to_float(low, f64x2, "convert")?
+            } else {
+                ctx.builder
+                    .build_unsigned_int_to_float(low, f64x2, "convert")?
+            };
+            push_vector(ctx, res)?;
+        }
+        Operator::F32x4DemoteF64x2Zero => {
+            let v = pop_vector(ctx, f64x2)?;
+            let f32x2 = ctx.inkwell_types.f32_type.vec_type(2);
+            let demoted = ctx.builder.build_float_trunc(v, f32x2, "demoted")?;
+            let res = gen_concat(ctx, demoted, f32x2.const_zero())?;
+            push_vector(ctx, res)?;
+        }
+        Operator::F64x2PromoteLowF32x4 => {
+            let v = pop_vector(ctx, f32x4)?;
+            let low = gen_half(ctx, v, false)?;
+            let res = ctx.builder.build_float_ext(low, f64x2, "promoted")?;
+            push_vector(ctx, res)?;
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+enum Shift {
+    Shl,
+    ShrS,
+    ShrU,
+}
+
+/// Pop a v128 value and interpret it as the given lane type.
+fn pop_vector<'a>(ctx: &mut Context<'a, '_>, ty: VectorType<'a>) -> Result<VectorValue<'a>> {
+    let v = ctx.pop()?;
+    Ok(ctx.builder.build_bit_cast(v, ty, "")?.into_vector_value())
+}
+
+fn pop2_vector<'a>(
+    ctx: &mut Context<'a, '_>,
+    ty: VectorType<'a>,
+) -> Result<(VectorValue<'a>, VectorValue<'a>)> {
+    let v2 = pop_vector(ctx, ty)?;
+    let v1 = pop_vector(ctx, ty)?;
+    Ok((v1, v2))
+}
+
+/// Push a vector of any lane type as a v128 value.
+fn push_vector<'a>(ctx: &mut Context<'a, '_>, v: VectorValue<'a>) -> Result<()> {
+    let v = ctx
+        .builder
+        .build_bit_cast(v, ctx.inkwell_types.v128_type, "")?;
+    ctx.push(v);
+    Ok(())
+}
+
+fn gen_unop<'a>(
+    ctx: &mut Context<'a, '_>,
+    ty: VectorType<'a>,
+    f: impl FnOnce(&Builder<'a>, VectorValue<'a>) -> Result<VectorValue<'a>, BuilderError>,
+) -> Result<()> {
+    let v = pop_vector(ctx, ty)?;
+    let res = f(&ctx.builder, v)?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+fn gen_binop<'a>(
+    ctx: &mut Context<'a, '_>,
+    ty: VectorType<'a>,
+    f: impl FnOnce(
+        &Builder<'a>,
+        VectorValue<'a>,
+        VectorValue<'a>,
+    ) -> Result<VectorValue<'a>, BuilderError>,
+) -> Result<()> {
+    let (v1, v2) = pop2_vector(ctx, ty)?;
+    let res = f(&ctx.builder, v1, v2)?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+/// Call an overloaded LLVM intrinsic.
+fn gen_vector_intrinsic<'a>(
+    ctx: &Context<'a, '_>,
+    name: &str,
+    types: &[VectorType<'a>],
+    args: &[BasicMetadataValueEnum<'a>],
+) -> Result<VectorValue<'a>> {
+    let types: Vec<BasicTypeEnum> = types.iter().map(|ty| ty.as_basic_type_enum()).collect();
+    let function = Intrinsic::find(name)
+        .expect("should find intrinsic")
+        .get_declaration(ctx.module, &types)
+        .expect("should declare intrinsic");
+    Ok(ctx
+        .builder
+        .build_call(function, args, "")?
+        .try_as_basic_value()
+        .left()
+        .expect("should be basic value")
+        .into_vector_value())
+}
+
+fn gen_unary_intrinsic<'a>(
+    ctx: &mut Context<'a, '_>,
+    name: &str,
+    ty: VectorType<'a>,
+) -> Result<()> {
+    let v = pop_vector(ctx, ty)?;
+    let res = gen_vector_intrinsic(ctx, name, &[ty], &[v.into()])?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+fn gen_binary_intrinsic<'a>(
+    ctx: &mut Context<'a, '_>,
+    name: &str,
+    ty: VectorType<'a>,
+) -> Result<()> {
+    let (v1, v2) = pop2_vector(ctx, ty)?;
+    let res = gen_vector_intrinsic(ctx, name, &[ty], &[v1.into(), v2.into()])?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+/// Build a constant vector of lane indices, which is used as a shuffle mask.
+fn const_lanes<'a>(ctx: &Context<'a, '_>, lanes: impl Iterator<Item = u32>) -> VectorValue<'a> {
+    let lanes: Vec<_> = lanes
+        .map(|lane| ctx.inkwell_types.i32_type.const_int(lane as u64, false))
+        .collect();
+    VectorType::const_vector(&lanes)
+}
+
+/// Build a vector with the same lanes as the given value.
+fn gen_splat<'a>(
+    ctx: &Context<'a, '_>,
+    value: BasicValueEnum<'a>,
+    ty: VectorType<'a>,
+) -> Result<VectorValue<'a>> {
+    let v = ctx.builder.build_insert_element(
+        ty.get_poison(),
+        value,
+        ctx.inkwell_types.i32_type.const_zero(),
+        "",
+    )?;
+    let mask = const_lanes(ctx, std::iter::repeat_n(0, ty.get_size() as usize));
+    Ok(ctx
+        .builder
+        .build_shuffle_vector(v, ty.get_poison(), mask, "splat")?)
+}
+
+fn gen_splat_int<'a>(value: u64, ty: VectorType<'a>) -> VectorValue<'a> {
+    let lane = ty.get_element_type().into_int_type().const_int(value, true);
+    let lanes = vec![lane; ty.get_size() as usize];
+    VectorType::const_vector(&lanes)
+}
+
+/// Get the low or high half lanes of a vector.
+fn gen_half<'a>(ctx: &Context<'a, '_>, v: VectorValue<'a>, high: bool) -> Result<VectorValue<'a>> {
+    let size = v.get_type().get_size();
+    let start = if high { size / 2 } else { 0 };
+    let mask = const_lanes(ctx, start..start + size / 2);
+    Ok(ctx
+        .builder
+        .build_shuffle_vector(v, v.get_type().get_poison(), mask, "half")?)
+}
+
+/// Concatenate the lanes of two vectors.
+fn gen_concat<'a>(
+    ctx: &Context<'a, '_>,
+    v1: VectorValue<'a>,
+    v2: VectorValue<'a>,
+) -> Result<VectorValue<'a>> {
+    let size = v1.get_type().get_size();
+    let mask = const_lanes(ctx, 0..size * 2);
+    Ok(ctx.builder.build_shuffle_vector(v1, v2, mask, "concat")?)
+}
+
+/// Get the lane type with the same number of lanes as the given integer vector type.
+fn resize_lanes<'a>(ctx: &Context<'a, '_>, ty: VectorType<'a>, bits: u32) -> VectorType<'a> {
+    ctx.ictx.custom_width_int_type(bits).vec_type(ty.get_size())
+}
+
+/// Load a value from the linear memory. SIMD loads are not necessarily aligned.
+fn gen_load<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    memarg: &MemArg,
+    ty: BasicTypeEnum<'a>,
+) -> Result<BasicValueEnum<'a>> {
+    let address_operand = ctx.pop()?.into_int_value();
+    let ptr = resolve_memarg_pointer(ctx, exec_env_ptr, address_operand, memarg, ty)?;
+    let v = ctx.builder.build_load(ty, ptr, "loaded")?;
+    let load = v.as_instruction_value().expect("should be instruction");
+    load.set_alignment(1).map_err(|e| anyhow!(e))?;
+    set_memory_access_metadata(ctx, load, memarg.memory);
+    Ok(v)
+}
+
+fn gen_store<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    memarg: &MemArg,
+    value: BasicValueEnum<'a>,
+) -> Result<()> {
+    let address_operand = ctx.pop()?.into_int_value();
+    let ptr = resolve_memarg_pointer(ctx, exec_env_ptr, address_operand, memarg, value.get_type())?;
+    let store = ctx.builder.build_store(ptr, value)?;
+    store.set_alignment(1).map_err(|e| anyhow!(e))?;
+    set_memory_access_metadata(ctx, store, memarg.memory);
+    Ok(())
+}
+
+/// Load half of a v128 value and extend the lanes to the given type.
+fn gen_load_extend<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    memarg: &MemArg,
+    ty: VectorType<'a>,
+    signed: bool,
+) -> Result<()> {
+    let bits = ty.get_element_type().into_int_type().get_bit_width();
+    let load_type = resize_lanes(ctx, ty, bits / 2);
+    let v = gen_load(ctx, exec_env_ptr, memarg, load_type.as_basic_type_enum())?;
+    let res = if signed {
+        ctx.builder
+            .build_int_s_extend(v.into_vector_value(), ty, "loaded_extended")?
+    } else {
+        ctx.builder
+            .build_int_z_extend(v.into_vector_value(), ty, "loaded_extended")?
+    };
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+fn gen_load_splat<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    memarg: &MemArg,
+    ty: VectorType<'a>,
+) -> Result<()> {
+    let v = gen_load(ctx, exec_env_ptr, memarg, ty.get_element_type())?;
+    let res = gen_splat(ctx, v, ty)?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+/// Load a value into the first lane and fill the other lanes with zero.
+fn gen_load_zero<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    memarg: &MemArg,
+    ty: VectorType<'a>,
+) -> Result<()> {
+    let v = gen_load(ctx, exec_env_ptr, memarg, ty.get_element_type())?;
+    let res = ctx.builder.build_insert_element(
+        ty.const_zero(),
+        v,
+        ctx.inkwell_types.i32_type.const_zero(),
+        "",
+    )?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+fn gen_load_lane<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    memarg: &MemArg,
+    ty: VectorType<'a>,
+    lane: u8,
+) -> Result<()> {
+    let vector = pop_vector(ctx, ty)?;
+    let v = gen_load(ctx, exec_env_ptr, memarg, ty.get_element_type())?;
+    let res = ctx.builder.build_insert_element(
+        vector,
+        v,
+        ctx.inkwell_types.i32_type.const_int(lane as u64, false),
+        "",
+    )?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+fn gen_store_lane<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    memarg: &MemArg,
+    ty: VectorType<'a>,
+    lane: u8,
+) -> Result<()> {
+    let vector = pop_vector(ctx, ty)?;
+    let v = ctx.builder.build_extract_element(
+        vector,
+        ctx.inkwell_types.i32_type.const_int(lane as u64, false),
+        "lane",
+    )?;
+    gen_store(ctx, exec_env_ptr, memarg, v)
+}
+
+/// Extract a lane, extending it to i32 if `signed` is given.
+fn gen_extract_lane<'a>(
+    ctx: &mut Context<'a, '_>,
+    ty: VectorType<'a>,
+    lane: u8,
+    signed: Option<bool>,
+) -> Result<()> {
+    let v = pop_vector(ctx, ty)?;
+    let res = ctx.builder.build_extract_element(
+        v,
+        ctx.inkwell_types.i32_type.const_int(lane as u64, false),
+        "lane",
+    )?;
+    let res = match signed {
+        Some(true) => ctx
+            .builder
+            .build_int_s_extend(res.into_int_value(), ctx.inkwell_types.i32_type, "")?
+            .as_basic_value_enum(),
+        Some(false) => ctx
+            .builder
+            .build_int_z_extend(res.into_int_value(), ctx.inkwell_types.i32_type, "")?
+            .as_basic_value_enum(),
+        None => res,
+    };
+    ctx.push(res);
+    Ok(())
+}
+
+/// Pop a scalar value and convert it to the lane type, truncating i32 to i8 or i16.
+fn pop_lane_value<'a>(ctx: &mut Context<'a, '_>, ty: VectorType<'a>) -> Result<BasicValueEnum<'a>> {
+    let v = ctx.pop()?;
+    let lane_type = ty.get_element_type();
+    if lane_type.is_int_type() && lane_type.into_int_type().get_bit_width() < 32 {
+        Ok(ctx
+            .builder
+            .build_int_truncate(v.into_int_value(), lane_type.into_int_type(), "")?
+            .as_basic_value_enum())
+    } else {
+        Ok(v)
+    }
+}
+
+fn gen_replace_lane<'a>(ctx: &mut Context<'a, '_>, ty: VectorType<'a>, lane: u8) -> Result<()> {
+    let v = pop_lane_value(ctx, ty)?;
+    let vector = pop_vector(ctx, ty)?;
+    let res = ctx.builder.build_insert_element(
+        vector,
+        v,
+        ctx.inkwell_types.i32_type.const_int(lane as u64, false),
+        "",
+    )?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+fn gen_splat_op<'a>(ctx: &mut Context<'a, '_>, ty: VectorType<'a>) -> Result<()> {
+    let v = pop_lane_value(ctx, ty)?;
+    let res = gen_splat(ctx, v, ty)?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+/// Select lanes of the first operand by the indices in the second operand.
+/// Out of range indices select zero.
+fn gen_swizzle(ctx: &mut Context<'_, '_>) -> Result<()> {
+    let i8x16 = ctx.inkwell_types.i8x16_type;
+    let (v, indices) = pop2_vector(ctx, i8x16)?;
+    let i8_type = ctx.inkwell_types.i8_type;
+    let mut res = i8x16.const_zero();
+    for i in 0..16 {
+        let lane = ctx.inkwell_types.i32_type.const_int(i, false);
+        let index = ctx
+            .builder
+            .build_extract_element(indices, lane, "index")?
+            .into_int_value();
+        let in_range = ctx.builder.build_int_compare(
+            IntPredicate::ULT,
+            index,
+            i8_type.const_int(16, false),
+            "in_range",
+        )?;
+        // extractelement with an out of range index is poison
+        let masked_index =
+            ctx.builder
+                .build_and(index, i8_type.const_int(15, false), "masked_index")?;
+        let selected = ctx
+            .builder
+            .build_extract_element(v, masked_index, "selected")?;
+        let selected =
+            ctx.builder
+                .build_select(in_range, selected, i8_type.const_zero().into(), "")?;
+        res = ctx.builder.build_insert_element(res, selected, lane, "")?;
+    }
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+/// Compare lanes and set all bits of the lanes where the condition holds.
+fn gen_int_compare<'a>(
+    ctx: &mut Context<'a, '_>,
+    ty: VectorType<'a>,
+    pred: IntPredicate,
+) -> Result<()> {
+    let (v1, v2) = pop2_vector(ctx, ty)?;
+    let cond = ctx.builder.build_int_compare(pred, v1, v2, "")?;
+    let res = ctx.builder.build_int_s_extend(cond, ty, "")?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+fn gen_float_compare<'a>(
+    ctx: &mut Context<'a, '_>,
+    ty: VectorType<'a>,
+    int_ty: VectorType<'a>,
+    pred: FloatPredicate,
+) -> Result<()> {
+    let (v1, v2) = pop2_vector(ctx, ty)?;
+    let cond = ctx.builder.build_float_compare(pred, v1, v2, "")?;
+    let res = ctx.builder.build_int_s_extend(cond, int_ty, "")?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+fn gen_int_abs<'a>(ctx: &mut Context<'a, '_>, ty: VectorType<'a>) -> Result<()> {
+    let v = pop_vector(ctx, ty)?;
+    // The absolute value of the minimum value is the minimum value itself
+    let is_int_min_poison = ctx.inkwell_types.bool_type.const_zero();
+    let res = gen_vector_intrinsic(
+        ctx,
+        "llvm.abs",
+        &[ty],
+        &[v.into(), is_int_min_poison.into()],
+    )?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+/// Gather the condition of each lane into a bitmask.
+fn gen_lane_mask<'a>(
+    ctx: &Context<'a, '_>,
+    ty: VectorType<'a>,
+    v: VectorValue<'a>,
+    pred: IntPredicate,
+) -> Result<inkwell::values::IntValue<'a>> {
+    let cond = ctx
+        .builder
+        .build_int_compare(pred, v, ty.const_zero(), "")?;
+    let mask_type = ctx.ictx.custom_width_int_type(ty.get_size());
+    Ok(ctx
+        .builder
+        .build_bit_cast(cond, mask_type, "lane_mask")?
+        .into_int_value())
+}
+
+fn gen_all_true<'a>(ctx: &mut Context<'a, '_>, ty: VectorType<'a>) -> Result<()> {
+    let v = pop_vector(ctx, ty)?;
+    let mask = gen_lane_mask(ctx, ty, v, IntPredicate::NE)?;
+    let all_true = ctx.builder.build_int_compare(
+        IntPredicate::EQ,
+        mask,
+        mask.get_type().const_all_ones(),
+        "all_true",
+    )?;
+    let res = ctx
+        .builder
+        .build_int_z_extend(all_true, ctx.inkwell_types.i32_type, "")?;
+    ctx.push(res.as_basic_value_enum());
+    Ok(())
+}
+
+fn gen_bitmask<'a>(ctx: &mut Context<'a, '_>, ty: VectorType<'a>) -> Result<()> {
+    let v = pop_vector(ctx, ty)?;
+    // The bitmask consists of the sign bits of the lanes
+    let mask = gen_lane_mask(ctx, ty, v, IntPredicate::SLT)?;
+    let res = ctx
+        .builder
+        .build_int_z_extend(mask, ctx.inkwell_types.i32_type, "bitmask")?;
+    ctx.push(res.as_basic_value_enum());
+    Ok(())
+}
+
+/// Narrow the lanes of two vectors with saturation.
+fn gen_narrow<'a>(
+    ctx: &mut Context<'a, '_>,
+    from_ty: VectorType<'a>,
+    to_ty: VectorType<'a>,
+    signed: bool,
+) -> Result<()> {
+    let (v1, v2) = pop2_vector(ctx, from_ty)?;
+    let v = gen_concat(ctx, v1, v2)?;
+    let wide_ty = v.get_type();
+    let bits = to_ty.get_element_type().into_int_type().get_bit_width();
+    // The input lanes are always signed
+    let (min, max) = if signed {
+        (-(1i64 << (bits - 1)) as u64, (1u64 << (bits - 1)) - 1)
+    } else {
+        (0, (1u64 << bits) - 1)
+    };
+    let min = gen_splat_int(min, wide_ty);
+    let max = gen_splat_int(max, wide_ty);
+    let v = gen_vector_intrinsic(ctx, "llvm.smax", &[wide_ty], &[v.into(), min.into()])?;
+    let v = gen_vector_intrinsic(ctx, "llvm.smin", &[wide_ty], &[v.into(), max.into()])?;
+    let res = ctx.builder.build_int_truncate(v, to_ty, "narrowed")?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+fn gen_extend_lanes<'a>(
+    ctx: &Context<'a, '_>,
+    v: VectorValue<'a>,
+    ty: VectorType<'a>,
+    signed: bool,
+) -> Result<VectorValue<'a>> {
+    let extended = if signed {
+        ctx.builder.build_int_s_extend(v, ty, "extended")?
+    } else {
+        ctx.builder.build_int_z_extend(v, ty, "extended")?
+    };
+    Ok(extended)
+}
+
+/// Extend the low or high half lanes to the twice wider lanes.
+fn gen_extend<'a>(
+    ctx: &mut Context<'a, '_>,
+    from_ty: VectorType<'a>,
+    to_ty: VectorType<'a>,
+    high: bool,
+    signed: bool,
+) -> Result<()> {
+    let v = pop_vector(ctx, from_ty)?;
+    let half = gen_half(ctx, v, high)?;
+    let res = gen_extend_lanes(ctx, half, to_ty, signed)?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+fn gen_shift<'a>(ctx: &mut Context<'a, '_>, ty: VectorType<'a>, shift: Shift) -> Result<()> {
+    let count = ctx.pop()?.into_int_value();
+    let v = pop_vector(ctx, ty)?;
+    // The shift count is taken modulo the lane width
+    let lane_type = ty.get_element_type().into_int_type();
+    let count = ctx.builder.build_and(
+        count,
+        ctx.inkwell_types
+            .i32_type
+            .const_int(lane_type.get_bit_width() as u64 - 1, false),
+        "",
+    )?;
+    let count = ctx.builder.build_int_cast(count, lane_type, "")?;
+    let count = gen_splat(ctx, count.as_basic_value_enum(), ty)?;
+    let res = match shift {
+        Shift::Shl => ctx.builder.build_left_shift(v, count, "shl"),
+        Shift::ShrS => ctx.builder.build_right_shift(v, count, true, "shr_s"),
+        Shift::ShrU => ctx.builder.build_right_shift(v, count, false, "shr_u"),
+    }?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+/// Unsigned rounding average, which is computed in the twice wider lanes.
+fn gen_avgr_u<'a>(ctx: &mut Context<'a, '_>, ty: VectorType<'a>) -> Result<()> {
+    let (v1, v2) = pop2_vector(ctx, ty)?;
+    let bits = ty.get_element_type().into_int_type().get_bit_width();
+    let wide_ty = resize_lanes(ctx, ty, bits * 2);
+    let v1 = gen_extend_lanes(ctx, v1, wide_ty, false)?;
+    let v2 = gen_extend_lanes(ctx, v2, wide_ty, false)?;
+    let sum = ctx.builder.build_int_add(v1, v2, "sum")?;
+    let sum = ctx
+        .builder
+        .build_int_add(sum, gen_splat_int(1, wide_ty), "sum")?;
+    let avg = ctx
+        .builder
+        .build_right_shift(sum, gen_splat_int(1, wide_ty), false, "avg")?;
+    let res = ctx.builder.build_int_truncate(avg, ty, "")?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+/// Q15 multiplication with rounding and saturation.
+fn gen_q15mulr_sat_s(ctx: &mut Context<'_, '_>) -> Result<()> {
+    let i16x8 = ctx.inkwell_types.i16x8_type;
+    let i32x8 = ctx.inkwell_types.i32_type.vec_type(8);
+    let (v1, v2) = pop2_vector(ctx, i16x8)?;
+    let v1 = gen_extend_lanes(ctx, v1, i32x8, true)?;
+    let v2 = gen_extend_lanes(ctx, v2, i32x8, true)?;
+    let product = ctx.builder.build_int_mul(v1, v2, "product")?;
+    let product = ctx
+        .builder
+        .build_int_add(product, gen_splat_int(0x4000, i32x8), "")?;
+    let product = ctx
+        .builder
+        .build_right_shift(product, gen_splat_int(15, i32x8), true, "")?;
+    // Only -0x8000 * -0x8000 overflows
+    let max = gen_splat_int(0x7fff, i32x8);
+    let product = gen_vector_intrinsic(ctx, "llvm.smin", &[i32x8], &[product.into(), max.into()])?;
+    let res = ctx.builder.build_int_truncate(product, i16x8, "")?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+/// Add the adjacent lanes in the twice wider lanes.
+fn gen_ext_add_pairwise<'a>(
+    ctx: &mut Context<'a, '_>,
+    from_ty: VectorType<'a>,
+    to_ty: VectorType<'a>,
+    signed: bool,
+) -> Result<()> {
+    let v = pop_vector(ctx, from_ty)?;
+    let (even, odd) = gen_even_odd(ctx, v)?;
+    let even = gen_extend_lanes(ctx, even, to_ty, signed)?;
+    let odd = gen_extend_lanes(ctx, odd, to_ty, signed)?;
+    let res = ctx.builder.build_int_add(even, odd, "")?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+/// Split a vector into the even lanes and the odd lanes.
+fn gen_even_odd<'a>(
+    ctx: &Context<'a, '_>,
+    v: VectorValue<'a>,
+) -> Result<(VectorValue<'a>, VectorValue<'a>)> {
+    let size = v.get_type().get_size();
+    let poison = v.get_type().get_poison();
+    let even_mask = const_lanes(ctx, (0..size).step_by(2));
+    let odd_mask = const_lanes(ctx, (1..size).step_by(2));
+    let even = ctx
+        .builder
+        .build_shuffle_vector(v, poison, even_mask, "even")?;
+    let odd = ctx
+        .builder
+        .build_shuffle_vector(v, poison, odd_mask, "odd")?;
+    Ok((even, odd))
+}
+
+/// Multiply the low or high half lanes in the twice wider lanes.
+fn gen_ext_mul<'a>(
+    ctx: &mut Context<'a, '_>,
+    from_ty: VectorType<'a>,
+    to_ty: VectorType<'a>,
+    high: bool,
+    signed: bool,
+) -> Result<()> {
+    let (v1, v2) = pop2_vector(ctx, from_ty)?;
+    let v1 = gen_half(ctx, v1, high)?;
+    let v2 = gen_half(ctx, v2, high)?;
+    let v1 = gen_extend_lanes(ctx, v1, to_ty, signed)?;
+    let v2 = gen_extend_lanes(ctx, v2, to_ty, signed)?;
+    let res = ctx.builder.build_int_mul(v1, v2, "")?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+fn gen_dot(ctx: &mut Context<'_, '_>) -> Result<()> {
+    let i16x8 = ctx.inkwell_types.i16x8_type;
+    let i32x8 = ctx.inkwell_types.i32_type.vec_type(8);
+    let (v1, v2) = pop2_vector(ctx, i16x8)?;
+    let v1 = gen_extend_lanes(ctx, v1, i32x8, true)?;
+    let v2 = gen_extend_lanes(ctx, v2, i32x8, true)?;
+    let product = ctx.builder.build_int_mul(v1, v2, "product")?;
+    let (even, odd) = gen_even_odd(ctx, product)?;
+    let res = ctx.builder.build_int_add(even, odd, "")?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+/// Wasm min and max propagate NaN and order -0 below +0.
+fn gen_float_min_max<'a>(
+    ctx: &mut Context<'a, '_>,
+    ty: VectorType<'a>,
+    int_ty: VectorType<'a>,
+    is_min: bool,
+) -> Result<()> {
+    let (v1, v2) = pop2_vector(ctx, ty)?;
+    let build_compare = |pred| ctx.builder.build_float_compare(pred, v1, v2, "");
+    let unordered = build_compare(FloatPredicate::UNO)?;
+    let lt = build_compare(FloatPredicate::OLT)?;
+    let gt = build_compare(FloatPredicate::OGT)?;
+
+    // Equal lanes differ only in the sign of zero
+    let i1 = ctx
+        .builder
+        .build_bit_cast(v1, int_ty, "")?
+        .into_vector_value();
+    let i2 = ctx
+        .builder
+        .build_bit_cast(v2, int_ty, "")?
+        .into_vector_value();
+    let equal = if is_min {
+        ctx.builder.build_or(i1, i2, "")
+    } else {
+        ctx.builder.build_and(i1, i2, "")
+    }?;
+    let equal = ctx.builder.build_bit_cast(equal, ty, "")?;
+
+    let (first, second) = if is_min { (lt, gt) } else { (gt, lt) };
+    let res = ctx
+        .builder
+        .build_select(second, v2.as_basic_value_enum(), equal, "")?;
+    let res = ctx
+        .builder
+        .build_select(first, v1.as_basic_value_enum(), res, "")?;
+    // Adding NaN gives a quiet NaN
+    let nan = ctx.builder.build_float_add(v1, v2, "nan")?;
+    let res = ctx
+        .builder
+        .build_select(unordered, nan.as_basic_value_enum(), res, "")?
+        .into_vector_value();
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+/// Pseudo-min and pseudo-max, which are defined as `b < a ? b : a` and `a < b ? b : a`.
+fn gen_float_pmin_pmax<'a>(
+    ctx: &mut Context<'a, '_>,
+    ty: VectorType<'a>,
+    is_min: bool,
+) -> Result<()> {
+    let (v1, v2) = pop2_vector(ctx, ty)?;
+    let (lhs, rhs) = if is_min { (v2, v1) } else { (v1, v2) };
+    let cond = ctx
+        .builder
+        .build_float_compare(FloatPredicate::OLT, lhs, rhs, "")?;
+    let res = ctx
+        .builder
+        .build_select(cond, v2, v1, "")?
+        .into_vector_value();
+    push_vector(ctx, res)?;
+    Ok(())
+}
+
+fn gen_trunc_sat_f64x2_zero(ctx: &mut Context<'_, '_>, name: &str) -> Result<()> {
+    let f64x2 = ctx.inkwell_types.f64x2_type;
+    let i32x2 = ctx.inkwell_types.i32_type.vec_type(2);
+    let v = pop_vector(ctx, f64x2)?;
+    let truncated = gen_vector_intrinsic(ctx, name, &[i32x2, f64x2], &[v.into()])?;
+    let res = gen_concat(ctx, truncated, i32x2.const_zero())?;
+    push_vector(ctx, res)?;
+    Ok(())
+}
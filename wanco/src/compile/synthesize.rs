@@ -1,13 +1,21 @@
 use std::collections::HashMap;
 
-use anyhow::bail;
-use inkwell::{module::Linkage, types::BasicType, values::BasicValue};
+use inkwell::{
+    attributes::{Attribute, AttributeLoc},
+    basic_block::BasicBlock,
+    module::Linkage,
+    types::BasicType,
+    values::{BasicValue, PointerValue},
+    IntPredicate,
+};
 
-use crate::context::Context;
+use crate::{context::Context, driver::Emit};
 
 use super::cr::{
-    checkpoint::{add_fn_store_globals, add_fn_store_table, gen_store_globals_and_table},
+    checkpoint::{add_fn_store_globals, gen_store_globals_and_table},
+    gen_compare_migration_state,
     restore::{gen_restore_globals, gen_restore_table},
+    MIGRATION_STATE_CHECKPOINT_CONTINUE, MIGRATION_STATE_RESTORE,
 };
 
 pub fn initialize(ctx: &mut Context<'_, '_>) -> anyhow::Result<()> {
@@ -22,7 +30,7 @@ pub fn initialize(ctx: &mut Context<'_, '_>) -> anyhow::Result<()> {
     let exec_env_type = ctx.ictx.struct_type(
         &[
             ctx.inkwell_types.ptr_type.into(),
-            ctx.inkwell_types.i32_type.into(),
+            ctx.inkwell_types.i64_type.into(),
             ctx.inkwell_types.i32_type.into(),
             ctx.inkwell_types.i32_type.into(),
             ctx.inkwell_types.ptr_type.into(),
@@ -32,6 +40,29 @@ pub fn initialize(ctx: &mut Context<'_, '_>) -> anyhow::Result<()> {
     ctx.exec_env_type = Some(exec_env_type);
     ctx.exec_env_fields = exec_env_fields;
 
+    // Define Table struct (elements, size)
+    // See lib-rt/aot.h for the type definition
+    let table_type = ctx.ictx.struct_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+        ],
+        false,
+    );
+    ctx.table_type = Some(table_type);
+
+    // Define Memory struct (base, size, max)
+    // See lib-rt/aot.h for the type definition
+    let memory_type = ctx.ictx.struct_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i64_type.into(),
+            ctx.inkwell_types.i64_type.into(),
+        ],
+        false,
+    );
+    ctx.memory_type = Some(memory_type);
+
     // Define aot_main function
     let aot_main_fn_type = ctx.inkwell_types.void_type.fn_type(
         &[ctx.inkwell_types.ptr_type.as_basic_type_enum().into()],
@@ -49,20 +80,225 @@ pub fn initialize(ctx: &mut Context<'_, '_>) -> anyhow::Result<()> {
     // Move position to aot_main %entry
     ctx.builder.position_at_end(aot_entry_block);
     // br %init
-    ctx.builder
-        .build_unconditional_branch(aot_init_block)
-        .expect("should build unconditional branch (entry -> init)");
+    ctx.builder.build_unconditional_branch(aot_init_block)?;
 
     // Declare memory_grow function
-    let fn_type_memory_grow = ctx
-        .inkwell_types
-        .i32_type
-        .fn_type(&[ctx.inkwell_types.i32_type.into()], false);
+    // The delta and the result are i64 regardless of the index type of the memory
+    let fn_type_memory_grow = ctx.inkwell_types.i64_type.fn_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+            ctx.inkwell_types.i64_type.into(),
+        ],
+        false,
+    );
     let fn_memory_grow = ctx
         .module
         .add_function("memory_grow", fn_type_memory_grow, None);
     ctx.fn_memory_grow = Some(fn_memory_grow);
 
+    // Declare trap function
+    let fn_type_trap = ctx.inkwell_types.void_type.fn_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+        ],
+        false,
+    );
+    let fn_trap = ctx.module.add_function("trap", fn_type_trap, None);
+    for attr in ["noreturn", "cold"] {
+        let attr = ctx
+            .ictx
+            .create_enum_attribute(Attribute::get_named_enum_kind_id(attr), 0);
+        fn_trap.add_attribute(AttributeLoc::Function, attr);
+    }
+    ctx.fn_trap = Some(fn_trap);
+
+    // Declare table functions
+    let fn_type_table_grow = ctx.inkwell_types.i32_type.fn_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+            ctx.inkwell_types.i64_type.into(),
+        ],
+        false,
+    );
+    ctx.fn_table_grow = Some(
+        ctx.module
+            .add_function("table_grow", fn_type_table_grow, None),
+    );
+    let fn_type_table_fill = ctx.inkwell_types.void_type.fn_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+        ],
+        false,
+    );
+    ctx.fn_table_fill = Some(
+        ctx.module
+            .add_function("table_fill", fn_type_table_fill, None),
+    );
+
+    // Declare wait and notify functions
+    let fn_type_memory_atomic_notify = ctx.inkwell_types.i32_type.fn_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+        ],
+        false,
+    );
+    ctx.fn_memory_atomic_notify = Some(ctx.module.add_function(
+        "memory_atomic_notify",
+        fn_type_memory_atomic_notify,
+        None,
+    ));
+    let fn_type_memory_atomic_wait32 = ctx.inkwell_types.i32_type.fn_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+            ctx.inkwell_types.i64_type.into(),
+        ],
+        false,
+    );
+    ctx.fn_memory_atomic_wait32 = Some(ctx.module.add_function(
+        "memory_atomic_wait32",
+        fn_type_memory_atomic_wait32,
+        None,
+    ));
+    let fn_type_memory_atomic_wait64 = ctx.inkwell_types.i32_type.fn_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i64_type.into(),
+            ctx.inkwell_types.i64_type.into(),
+        ],
+        false,
+    );
+    ctx.fn_memory_atomic_wait64 = Some(ctx.module.add_function(
+        "memory_atomic_wait64",
+        fn_type_memory_atomic_wait64,
+        None,
+    ));
+
+    // Declare exception functions (see lib-rt/exception/exception.cc)
+    let fn_type_exception_new = ctx.inkwell_types.i32_type.fn_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+            ctx.inkwell_types.i64_type.into(),
+        ],
+        false,
+    );
+    ctx.fn_exception_new = Some(ctx.module.add_function(
+        "exception_new",
+        fn_type_exception_new,
+        None,
+    ));
+    let fn_type_exception_rethrow = ctx.inkwell_types.void_type.fn_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+        ],
+        false,
+    );
+    let fn_exception_rethrow =
+        ctx.module
+            .add_function("exception_rethrow", fn_type_exception_rethrow, None);
+    let noreturn = ctx
+        .ictx
+        .create_enum_attribute(Attribute::get_named_enum_kind_id("noreturn"), 0);
+    fn_exception_rethrow.add_attribute(AttributeLoc::Function, noreturn);
+    ctx.fn_exception_rethrow = Some(fn_exception_rethrow);
+    let fn_type_exception_handle = ctx.inkwell_types.i32_type.fn_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+        ],
+        false,
+    );
+    ctx.fn_exception_clone = Some(ctx.module.add_function(
+        "exception_clone",
+        fn_type_exception_handle,
+        None,
+    ));
+    ctx.fn_exception_tag = Some(ctx.module.add_function(
+        "exception_tag",
+        fn_type_exception_handle,
+        None,
+    ));
+    let fn_type_exception_catch = ctx.inkwell_types.i32_type.fn_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.ptr_type.into(),
+        ],
+        false,
+    );
+    ctx.fn_exception_catch = Some(ctx.module.add_function(
+        "exception_catch",
+        fn_type_exception_catch,
+        None,
+    ));
+    let fn_type_exception_payload = ctx.inkwell_types.ptr_type.fn_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+        ],
+        false,
+    );
+    ctx.fn_exception_payload = Some(ctx.module.add_function(
+        "exception_payload",
+        fn_type_exception_payload,
+        None,
+    ));
+    let fn_type_exception_release = ctx.inkwell_types.void_type.fn_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+        ],
+        false,
+    );
+    ctx.fn_exception_release = Some(ctx.module.add_function(
+        "exception_release",
+        fn_type_exception_release,
+        None,
+    ));
+    // Exported functions catch exceptions instead of unwinding into the host
+    let fn_type_exception_uncaught = ctx.inkwell_types.void_type.fn_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.ptr_type.into(),
+        ],
+        false,
+    );
+    ctx.fn_exception_uncaught = Some(ctx.module.add_function(
+        "exception_uncaught",
+        fn_type_exception_uncaught,
+        None,
+    ));
+    // Wasm exceptions are C++ exceptions thrown by lib-rt
+    let fn_type_personality = ctx.inkwell_types.i32_type.fn_type(&[], true);
+    ctx.fn_personality = Some(ctx.module.add_function(
+        "__gxx_personality_v0",
+        fn_type_personality,
+        None,
+    ));
+    // typeinfo for wanco::WasmException
+    let global_exception_typeinfo = ctx.module.add_global(
+        ctx.inkwell_types.ptr_type,
+        None,
+        "_ZTIN5wanco13WasmExceptionE",
+    );
+    global_exception_typeinfo.set_linkage(Linkage::External);
+    global_exception_typeinfo.set_constant(true);
+    ctx.global_exception_typeinfo = Some(global_exception_typeinfo);
+
     load_api(ctx);
     Ok(())
 }
@@ -71,7 +307,7 @@ pub fn load_api(ctx: &mut Context<'_, '_>) {
     let exec_env_ptr_type = ctx.inkwell_types.ptr_type;
     // Checkpoint related
     // FIXME: We should only add these functions if we are using checkpointing
-    // However, lib-rt statically links fn_store_globals and fn_store_table
+    // However, lib-rt statically links fn_store_globals
     if true || ctx.config.enable_cr || ctx.config.legacy_cr {
         // checkpoint api
         let fn_type_start_checkpoint = ctx
@@ -142,6 +378,15 @@ pub fn load_api(ctx: &mut Context<'_, '_>) {
             fn_type_push_local_f64,
             Some(Linkage::External),
         ));
+        let fn_type_push_local_v128 = ctx.inkwell_types.void_type.fn_type(
+            &[exec_env_ptr_type.into(), ctx.inkwell_types.v128_type.into()],
+            false,
+        );
+        ctx.fn_push_local_v128 = Some(ctx.module.add_function(
+            "push_local_v128",
+            fn_type_push_local_v128,
+            Some(Linkage::External),
+        ));
         let fn_type_push_i32 = ctx.inkwell_types.void_type.fn_type(
             &[exec_env_ptr_type.into(), ctx.inkwell_types.i32_type.into()],
             false,
@@ -178,6 +423,15 @@ pub fn load_api(ctx: &mut Context<'_, '_>) {
             fn_type_push_f64,
             Some(Linkage::External),
         ));
+        let fn_type_push_v128 = ctx.inkwell_types.void_type.fn_type(
+            &[exec_env_ptr_type.into(), ctx.inkwell_types.v128_type.into()],
+            false,
+        );
+        ctx.fn_push_v128 = Some(ctx.module.add_function(
+            "push_v128",
+            fn_type_push_v128,
+            Some(Linkage::External),
+        ));
         let fn_type_push_global_i32 = ctx.inkwell_types.void_type.fn_type(
             &[exec_env_ptr_type.into(), ctx.inkwell_types.i32_type.into()],
             false,
@@ -214,13 +468,22 @@ pub fn load_api(ctx: &mut Context<'_, '_>) {
             fn_type_push_global_f64,
             Some(Linkage::External),
         ));
-        let fn_type_push_table_index = ctx.inkwell_types.void_type.fn_type(
-            &[exec_env_ptr_type.into(), ctx.inkwell_types.i32_type.into()],
+        let fn_type_push_global_v128 = ctx.inkwell_types.void_type.fn_type(
+            &[exec_env_ptr_type.into(), ctx.inkwell_types.v128_type.into()],
             false,
         );
-        ctx.fn_push_table_index = Some(ctx.module.add_function(
-            "push_table_index",
-            fn_type_push_table_index,
+        ctx.fn_push_global_v128 = Some(ctx.module.add_function(
+            "push_global_v128",
+            fn_type_push_global_v128,
+            Some(Linkage::External),
+        ));
+        let fn_type_store_table = ctx
+            .inkwell_types
+            .void_type
+            .fn_type(&[exec_env_ptr_type.into()], false);
+        ctx.fn_store_table = Some(ctx.module.add_function(
+            "store_table",
+            fn_type_store_table,
             Some(Linkage::External),
         ));
 
@@ -243,6 +506,11 @@ pub fn load_api(ctx: &mut Context<'_, '_>) {
             fn_type_get_pc_from_frame,
             Some(Linkage::External),
         ));
+        ctx.fn_get_fn_index_from_frame = Some(ctx.module.add_function(
+            "get_fn_index_from_frame",
+            fn_type_get_pc_from_frame,
+            Some(Linkage::External),
+        ));
         let fn_type_frame_is_empty = ctx
             .inkwell_types
             .bool_type
@@ -289,6 +557,15 @@ pub fn load_api(ctx: &mut Context<'_, '_>) {
             fn_type_pop_front_local_f64,
             Some(Linkage::External),
         ));
+        let fn_type_pop_front_local_v128 = ctx
+            .inkwell_types
+            .v128_type
+            .fn_type(&[exec_env_ptr_type.into()], false);
+        ctx.fn_pop_front_local_v128 = Some(ctx.module.add_function(
+            "pop_front_local_v128",
+            fn_type_pop_front_local_v128,
+            Some(Linkage::External),
+        ));
         // stack
         let fn_type_pop_i32 = ctx
             .inkwell_types
@@ -326,6 +603,15 @@ pub fn load_api(ctx: &mut Context<'_, '_>) {
             fn_type_pop_f64,
             Some(Linkage::External),
         ));
+        let fn_type_pop_v128 = ctx
+            .inkwell_types
+            .v128_type
+            .fn_type(&[exec_env_ptr_type.into()], false);
+        ctx.fn_pop_v128 = Some(ctx.module.add_function(
+            "pop_v128",
+            fn_type_pop_v128,
+            Some(Linkage::External),
+        ));
         // globals
         let fn_type_pop_front_global_i32 = ctx
             .inkwell_types
@@ -363,14 +649,23 @@ pub fn load_api(ctx: &mut Context<'_, '_>) {
             fn_type_pop_front_global_f64,
             Some(Linkage::External),
         ));
+        let fn_type_pop_front_global_v128 = ctx
+            .inkwell_types
+            .v128_type
+            .fn_type(&[exec_env_ptr_type.into()], false);
+        ctx.fn_pop_front_global_v128 = Some(ctx.module.add_function(
+            "pop_front_global_v128",
+            fn_type_pop_front_global_v128,
+            Some(Linkage::External),
+        ));
         // table
-        let fn_type_pop_front_table_index = ctx
+        let fn_type_restore_table = ctx
             .inkwell_types
-            .i32_type
+            .void_type
             .fn_type(&[exec_env_ptr_type.into()], false);
-        ctx.fn_pop_front_table_index = Some(ctx.module.add_function(
-            "pop_front_table_index",
-            fn_type_pop_front_table_index,
+        ctx.fn_restore_table = Some(ctx.module.add_function(
+            "restore_table",
+            fn_type_restore_table,
             Some(Linkage::External),
         ));
     }
@@ -392,8 +687,7 @@ pub fn finalize(ctx: &mut Context<'_, '_>) -> anyhow::Result<()> {
         .position_at_end(ctx.aot_init_block.expect("should move to aot_init_block"));
     // br %main
     ctx.builder
-        .build_unconditional_branch(ctx.aot_main_block.expect("should define aot_main_block"))
-        .expect("should build unconditional branch (init -> main)");
+        .build_unconditional_branch(ctx.aot_main_block.expect("should define aot_main_block"))?;
 
     // Move position to aot_main %init
     ctx.builder
@@ -401,30 +695,196 @@ pub fn finalize(ctx: &mut Context<'_, '_>) -> anyhow::Result<()> {
 
     // restore globals
     if !ctx.config.no_restore && (ctx.config.enable_cr || ctx.config.legacy_cr) {
-        gen_restore_globals(ctx, &exec_env_ptr).expect("should gen restore globals");
-        gen_restore_table(ctx, &exec_env_ptr).expect("should gen restore table");
+        gen_restore_globals(ctx, &exec_env_ptr)?;
+        gen_restore_table(ctx, &exec_env_ptr)?;
+    }
+
+    // Run the start section as a part of instantiation
+    let end_block = ctx.ictx.append_basic_block(aot_main, "end");
+    if let Some(start_section_idx) = ctx.start_section_function_idx {
+        gen_call_start_section(ctx, &exec_env_ptr, start_section_idx, end_block)?;
     }
 
-    // Call the start WASM function
-    let Some(start_idx) = ctx.start_function_idx else {
-        bail!("start function not defined");
+    // Call the entry point: `_start` of commands or `_initialize` of reactors.
+    // Libraries only initialize the module, and `_start` is called through its export.
+    let start_function_idx = match ctx.config.emit {
+        Emit::Exe => ctx.start_function_idx,
+        Emit::Staticlib | Emit::Sharedlib => None,
     };
-    let start_fn = ctx.function_values[start_idx as usize];
-    ctx.builder
-        .build_call(start_fn, &[exec_env_ptr.as_basic_value_enum().into()], "")
-        .expect("should build call");
+    match (start_function_idx, ctx.initialize_function_idx) {
+        (Some(idx), _) | (None, Some(idx)) => gen_call_function(ctx, &exec_env_ptr, idx)?,
+        (None, None) if ctx.config.emit == Emit::Exe => {
+            log::warn!("Neither _start nor _initialize is exported")
+        }
+        (None, None) => (),
+    }
+    ctx.builder.build_unconditional_branch(end_block)?;
+    ctx.builder.position_at_end(end_block);
 
     // checkpoint globals (legacy)
     if ctx.config.legacy_cr {
         gen_store_globals_and_table(ctx, &exec_env_ptr)?;
     }
 
-    ctx.builder.build_return(None).expect("should build return");
+    ctx.builder.build_return(None)?;
 
-    // add functions to checkpoint globals and table
-    // We always add these functions because lib-rt statically links them
+    // add a function to checkpoint globals
+    // We always add this function because lib-rt statically links it
     add_fn_store_globals(ctx, exec_env_ptr)?;
-    add_fn_store_table(ctx, exec_env_ptr)?;
 
+    add_function_names(ctx);
+
+    if let Some(thread_start_idx) = ctx.thread_start_function_idx {
+        add_fn_thread_start(ctx, thread_start_idx)?;
+    }
+
+    Ok(())
+}
+
+/// Call a wasm function which only takes &exec_env.
+fn gen_call_function<'a>(
+    ctx: &Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    idx: u32,
+) -> anyhow::Result<()> {
+    let function = ctx.function_values[idx as usize];
+    ctx.builder
+        .build_call(function, &[exec_env_ptr.as_basic_value_enum().into()], "")?
+        .set_call_convention(function.get_call_conventions());
+    Ok(())
+}
+
+/// Call the function of the start section, and then move to the block of the entry point.
+/// When restoring, the start section has already run unless the checkpoint was taken in it.
+/// If a checkpoint is taken in it (legacy), the entry point is skipped to unwind to `end_block`.
+fn gen_call_start_section<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    start_section_idx: u32,
+    end_block: BasicBlock<'a>,
+) -> anyhow::Result<()> {
+    let aot_main = ctx.current_fn.expect("should define aot_main");
+    let call_block = ctx.ictx.append_basic_block(aot_main, "start_section");
+    let entry_point_block = ctx.ictx.append_basic_block(aot_main, "entry_point");
+
+    if !ctx.config.no_restore && (ctx.config.enable_cr || ctx.config.legacy_cr) {
+        let is_restore = gen_compare_migration_state(ctx, exec_env_ptr, MIGRATION_STATE_RESTORE)?;
+        let check_frame_block = ctx
+            .ictx
+            .append_basic_block(aot_main, "start_section.check_frame");
+        ctx.builder.build_conditional_branch(
+            is_restore.into_int_value(),
+            check_frame_block,
+            call_block,
+        )?;
+
+        ctx.builder.position_at_end(check_frame_block);
+        let fn_index = ctx
+            .builder
+            .build_call(
+                ctx.fn_get_fn_index_from_frame.unwrap(),
+                &[exec_env_ptr.as_basic_value_enum().into()],
+                "fn_index",
+            )?
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let in_start_section = ctx.builder.build_int_compare(
+            IntPredicate::EQ,
+            fn_index,
+            ctx.inkwell_types
+                .i32_type
+                .const_int(start_section_idx as u64, false),
+            "in_start_section",
+        )?;
+        ctx.builder
+            .build_conditional_branch(in_start_section, call_block, entry_point_block)?;
+    } else {
+        ctx.builder.build_unconditional_branch(call_block)?;
+    }
+
+    ctx.builder.position_at_end(call_block);
+    gen_call_function(ctx, exec_env_ptr, start_section_idx)?;
+    if ctx.config.legacy_cr {
+        let is_unwinding =
+            gen_compare_migration_state(ctx, exec_env_ptr, MIGRATION_STATE_CHECKPOINT_CONTINUE)?;
+        ctx.builder.build_conditional_branch(
+            is_unwinding.into_int_value(),
+            end_block,
+            entry_point_block,
+        )?;
+    } else {
+        ctx.builder.build_unconditional_branch(entry_point_block)?;
+    }
+
+    ctx.builder.position_at_end(entry_point_block);
+    Ok(())
+}
+
+/// Define the entry of threads spawned by `wasi_thread_spawn` (see lib-rt/thread/thread.cc).
+/// It calls the exported `wasi_thread_start(tid, start_arg)`.
+fn add_fn_thread_start(ctx: &mut Context<'_, '_>, thread_start_idx: u32) -> anyhow::Result<()> {
+    let fn_type = ctx.inkwell_types.void_type.fn_type(
+        &[
+            ctx.inkwell_types.ptr_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+            ctx.inkwell_types.i32_type.into(),
+        ],
+        false,
+    );
+    let thread_start = ctx
+        .module
+        .add_function("aot_wasi_thread_start", fn_type, None);
+    let block = ctx.ictx.append_basic_block(thread_start, "entry");
+    ctx.builder.position_at_end(block);
+
+    let args: Vec<_> = thread_start
+        .get_param_iter()
+        .map(|param| param.into())
+        .collect();
+    let thread_start_fn = ctx.function_values[thread_start_idx as usize];
+    ctx.builder
+        .build_call(thread_start_fn, &args, "")?
+        .set_call_convention(thread_start_fn.get_call_conventions());
+    ctx.builder.build_return(None)?;
     Ok(())
 }
+
+/// Define the table of function names used by the runtime to print wasm backtraces.
+/// Functions without a name in the name section are null.
+fn add_function_names(ctx: &mut Context<'_, '_>) {
+    let mut names = Vec::new();
+    for (i, function) in ctx.functions.iter().enumerate() {
+        let Some(debug_name) = &function.debug_name else {
+            names.push(ctx.inkwell_types.ptr_type.const_null());
+            continue;
+        };
+        let name = ctx.ictx.const_string(debug_name.as_bytes(), true);
+        let global = ctx
+            .module
+            .add_global(name.get_type(), None, &format!("function_name_{}", i));
+        global.set_initializer(&name);
+        global.set_constant(true);
+        global.set_linkage(Linkage::Private);
+        names.push(global.as_pointer_value());
+    }
+
+    let function_names = ctx.module.add_global(
+        ctx.inkwell_types.ptr_type.array_type(names.len() as u32),
+        None,
+        "FUNCTION_NAMES",
+    );
+    function_names.set_initializer(&ctx.inkwell_types.ptr_type.const_array(&names));
+    function_names.set_constant(true);
+
+    let num_functions = ctx
+        .module
+        .add_global(ctx.inkwell_types.i32_type, None, "NUM_FUNCTIONS");
+    num_functions.set_initializer(
+        &ctx.inkwell_types
+            .i32_type
+            .const_int(names.len() as u64, false),
+    );
+    num_functions.set_constant(true);
+}
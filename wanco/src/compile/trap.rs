@@ -0,0 +1,78 @@
+use anyhow::Result;
+use inkwell::values::{BasicValue, IntValue, PointerValue};
+
+use crate::{compile::stackmap, context::Context};
+
+/// Kinds of traps passed to the runtime trap entry.
+/// This must be kept in sync with `TrapKind` in lib-rt/trap/trap.h.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum TrapKind {
+    MemoryOutOfBounds = 0,
+    IntegerDivideByZero = 1,
+    IntegerOverflow = 2,
+    InvalidConversionToInteger = 3,
+    Unreachable = 4,
+    IndirectCallTypeMismatch = 5,
+    UndefinedElement = 6,
+    UninitializedElement = 7,
+    TableOutOfBounds = 8,
+    UnalignedAtomic = 9,
+    NullReference = 10,
+    AtomicWaitNonShared = 11,
+}
+
+/// Call the runtime trap entry. The current block is terminated.
+pub fn gen_trap<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    kind: TrapKind,
+) -> Result<()> {
+    // Record the location of the trap so that the runtime can print a wasm backtrace
+    if let (Some(func_idx), Some(insn_offset)) = (ctx.current_function_idx, ctx.current_op) {
+        let stackmap_id = stackmap::stackmap_id(func_idx, insn_offset);
+        ctx.builder.build_call(
+            ctx.inkwell_intrs.experimental_stackmap,
+            &[
+                ctx.inkwell_types
+                    .i64_type
+                    .const_int(stackmap_id, false)
+                    .into(),
+                ctx.inkwell_types.i32_type.const_zero().into(),
+            ],
+            "",
+        )?;
+    }
+    ctx.builder.build_call(
+        ctx.fn_trap.expect("should define fn_trap"),
+        &[
+            exec_env_ptr.as_basic_value_enum().into(),
+            ctx.inkwell_types
+                .i32_type
+                .const_int(kind as u64, false)
+                .into(),
+        ],
+        "",
+    )?;
+    ctx.builder.build_unreachable()?;
+    Ok(())
+}
+
+/// Trap if `cond` is true, and continue in a new block otherwise.
+pub fn gen_trap_if<'a>(
+    ctx: &mut Context<'a, '_>,
+    exec_env_ptr: &PointerValue<'a>,
+    cond: IntValue<'a>,
+    kind: TrapKind,
+) -> Result<()> {
+    let current_fn = ctx.current_fn.expect("fail to get current_fn");
+    let trap_bb = ctx.ictx.append_basic_block(current_fn, "trap");
+    let ok_bb = ctx.ictx.append_basic_block(current_fn, "trap.else");
+    ctx.builder.build_conditional_branch(cond, trap_bb, ok_bb)?;
+
+    ctx.builder.position_at_end(trap_bb);
+    gen_trap(ctx, exec_env_ptr, kind)?;
+
+    ctx.builder.position_at_end(ok_bb);
+    Ok(())
+}
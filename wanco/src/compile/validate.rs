@@ -0,0 +1,277 @@
+use std::{collections::HashSet, fmt::Write};
+
+use anyhow::{anyhow, Result};
+use wasmparser::{
+    for_each_operator, BinaryReaderError, CompositeInnerType, FuncValidatorAllocations,
+    FunctionBody, ImportSectionReader, MemorySectionReader, MemoryType, Parser, Payload, TypeRef,
+    TypeSectionReader, ValidPayload, Validator, VisitOperator, WasmFeatures,
+};
+
+use super::host::is_host_module;
+
+/// Proposals implemented by wanco.
+/// Keep in sync with `SUPPORTED_PROPOSALS`.
+const SUPPORTED_FEATURES: WasmFeatures = WasmFeatures::MUTABLE_GLOBAL
+    .union(WasmFeatures::SATURATING_FLOAT_TO_INT)
+    .union(WasmFeatures::SIGN_EXTENSION)
+    .union(WasmFeatures::REFERENCE_TYPES)
+    .union(WasmFeatures::MULTI_VALUE)
+    .union(WasmFeatures::BULK_MEMORY)
+    .union(WasmFeatures::SIMD)
+    .union(WasmFeatures::THREADS)
+    .union(WasmFeatures::TAIL_CALL)
+    .union(WasmFeatures::FLOATS)
+    .union(WasmFeatures::MULTI_MEMORY)
+    .union(WasmFeatures::EXCEPTIONS)
+    .union(WasmFeatures::LEGACY_EXCEPTIONS)
+    .union(WasmFeatures::MEMORY64)
+    .union(WasmFeatures::EXTENDED_CONST);
+
+/// Proposals of the instructions implemented by wanco, as named by `for_each_operator`.
+const SUPPORTED_PROPOSALS: &[&str] = &[
+    "mvp",
+    "saturating_float_to_int",
+    "sign_extension",
+    "reference_types",
+    "bulk_memory",
+    "simd",
+    "threads",
+    "tail_call",
+    "exceptions",
+    "legacy_exceptions",
+];
+
+/// Validate the module and check that wanco supports everything it uses before generating any code.
+/// All the problems found are reported together with their byte offsets.
+pub fn validate_module(wasm: &[u8]) -> Result<()> {
+    let mut validator = Validator::new_with_features(SUPPORTED_FEATURES);
+    let mut diagnostics = Diagnostics::default();
+    let mut allocs = FuncValidatorAllocations::default();
+    // Number of results of each type, or None for types other than functions
+    let mut num_results: Vec<Option<usize>> = Vec::new();
+    let mut num_memories = 0;
+    // Errors in sections make the validator inconsistent, so it is not used after them
+    let mut module_is_valid = true;
+
+    for payload in Parser::new(0).parse_all(wasm) {
+        let payload = match payload {
+            Ok(payload) => payload,
+            Err(e) => {
+                diagnostics.push_error(&e);
+                break;
+            }
+        };
+        let checked = match &payload {
+            Payload::TypeSection(types) => check_type_section(types.clone(), &mut num_results),
+            Payload::ImportSection(imports) => check_import_section(
+                imports.clone(),
+                &num_results,
+                &mut num_memories,
+                &mut diagnostics,
+            ),
+            Payload::MemorySection(memories) => {
+                check_memory_section(memories.clone(), &mut num_memories, &mut diagnostics)
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = checked {
+            diagnostics.push_error(&e);
+        }
+
+        if module_is_valid {
+            match validator.payload(&payload) {
+                Ok(ValidPayload::Func(func, body)) => {
+                    let mut func = func.into_validator(std::mem::take(&mut allocs));
+                    if let Err(e) = func.validate(&body) {
+                        diagnostics.push_error(&e);
+                    }
+                    allocs = func.into_allocations();
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    diagnostics.push_error(&e);
+                    module_is_valid = false;
+                }
+            }
+        }
+
+        // The validator stops at the first error of a function,
+        // so every body is also checked for instructions of unsupported proposals
+        if let Payload::CodeSectionEntry(body) = &payload {
+            if let Err(e) = check_proposals(body, &mut diagnostics) {
+                diagnostics.push_error(&e);
+            }
+        }
+    }
+
+    diagnostics.into_result()
+}
+
+/// Problems found in a module, which are sorted by their offsets.
+#[derive(Default)]
+struct Diagnostics {
+    entries: Vec<(usize, String)>,
+    offsets: HashSet<usize>,
+}
+
+impl Diagnostics {
+    fn push(&mut self, offset: usize, message: String) {
+        self.entries.push((offset, message));
+        self.offsets.insert(offset);
+    }
+
+    fn is_reported(&self, offset: usize) -> bool {
+        self.offsets.contains(&offset)
+    }
+
+    fn push_error(&mut self, e: &BinaryReaderError) {
+        self.push(e.offset(), e.message().to_string());
+    }
+
+    fn into_result(mut self) -> Result<()> {
+        if self.entries.is_empty() {
+            return Ok(());
+        }
+        self.entries.sort_by_key(|(offset, _)| *offset);
+        let mut message = String::from("The module is invalid or uses unsupported features:");
+        for (offset, entry) in &self.entries {
+            write!(message, "\n  0x{:x}: {}", offset, entry)?;
+        }
+        Err(anyhow!(message))
+    }
+}
+
+fn check_type_section(
+    types: TypeSectionReader,
+    num_results: &mut Vec<Option<usize>>,
+) -> Result<(), BinaryReaderError> {
+    for rec_group in types {
+        for subtype in rec_group?.into_types() {
+            num_results.push(match &subtype.composite_type.inner {
+                CompositeInnerType::Func(func_type) => Some(func_type.results().len()),
+                _ => None,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn check_import_section(
+    imports: ImportSectionReader,
+    num_results: &[Option<usize>],
+    num_memories: &mut u32,
+    diagnostics: &mut Diagnostics,
+) -> Result<(), BinaryReaderError> {
+    for import in imports.into_iter_with_offsets() {
+        let (offset, import) = import?;
+        match import.ty {
+            TypeRef::Func(ty) if is_host_module(import.module) => {
+                if matches!(num_results.get(ty as usize), Some(Some(n)) if *n > 1) {
+                    diagnostics.push(
+                        offset,
+                        format!(
+                            "host function {}.{} cannot return multiple values",
+                            import.module, import.name
+                        ),
+                    );
+                }
+            }
+            // wasi-threads (see lib-rt/thread/thread.cc)
+            TypeRef::Func(_) if import.module == "wasi" && import.name != "thread-spawn" => {
+                diagnostics.push(
+                    offset,
+                    format!("unknown import {}.{}", import.module, import.name),
+                );
+            }
+            TypeRef::Global(_) | TypeRef::Table(_) if !is_host_module(import.module) => {
+                diagnostics.push(
+                    offset,
+                    format!("unknown import {}.{}", import.module, import.name),
+                );
+            }
+            // The runtime allocates every memory, so the host cannot provide one
+            TypeRef::Memory(ty) => {
+                diagnostics.push(
+                    offset,
+                    format!(
+                        "imported memory {}.{} is not supported",
+                        import.module, import.name
+                    ),
+                );
+                check_memory(&ty, offset, num_memories, diagnostics);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn check_memory_section(
+    memories: MemorySectionReader,
+    num_memories: &mut u32,
+    diagnostics: &mut Diagnostics,
+) -> Result<(), BinaryReaderError> {
+    for memory in memories.into_iter_with_offsets() {
+        let (offset, memory) = memory?;
+        check_memory(&memory, offset, num_memories, diagnostics);
+    }
+    Ok(())
+}
+
+fn check_memory(
+    memory: &MemoryType,
+    offset: usize,
+    num_memories: &mut u32,
+    diagnostics: &mut Diagnostics,
+) {
+    // The runtime shares memory 0 between threads through ExecEnv (see lib-rt/aot.h)
+    if memory.shared && *num_memories != 0 {
+        diagnostics.push(
+            offset,
+            format!("shared memory {} is not supported", num_memories),
+        );
+    }
+    *num_memories += 1;
+}
+
+/// Report the instructions of unsupported proposals in the body without validating it.
+/// Offsets already reported by the validator are skipped.
+fn check_proposals(
+    body: &FunctionBody,
+    diagnostics: &mut Diagnostics,
+) -> Result<(), BinaryReaderError> {
+    let mut operators = body.get_operators_reader()?;
+    while !operators.eof() {
+        let offset = operators.original_position();
+        let proposal = operators.visit_operator(&mut ProposalVisitor)?;
+        if !SUPPORTED_PROPOSALS.contains(&proposal) && !diagnostics.is_reported(offset) {
+            diagnostics.push(
+                offset,
+                format!(
+                    "instructions of the {} proposal are not supported",
+                    proposal
+                ),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Get the proposal which defines an instruction.
+struct ProposalVisitor;
+
+macro_rules! define_visit_proposal {
+    ($( @$proposal:ident $op:ident $({ $($arg:ident: $argty:ty),* })? => $visit:ident)*) => {
+        $(
+            fn $visit(&mut self $($(, _: $argty)*)?) -> &'static str {
+                stringify!($proposal)
+            }
+        )*
+    };
+}
+
+impl<'a> VisitOperator<'a> for ProposalVisitor {
+    type Output = &'static str;
+
+    for_each_operator!(define_visit_proposal);
+}
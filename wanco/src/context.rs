@@ -6,11 +6,16 @@ use inkwell::{
     context::Context as InkwellContext,
     module::Module,
     types::{BasicTypeEnum, FunctionType, StructType},
-    values::{BasicValueEnum, FunctionValue, GlobalValue, IntValue},
+    values::{BasicValueEnum, FunctionValue, GlobalValue, IntValue, PointerValue},
 };
 
+use wasmparser::{ExternalKind, MemoryType, TableType};
+
 use crate::{
-    compile::control::{ControlFrame, UnreachableReason},
+    compile::{
+        control::{ControlFrame, UnreachableReason},
+        CompileErrorKind,
+    },
     driver::Args,
     inkwell::{init_inkwell, InkwellIntrinsics, InkwellTypes},
 };
@@ -26,6 +31,18 @@ pub enum Global<'a> {
     Const {
         value: BasicValueEnum<'a>,
     },
+    /// Immutable global whose value is determined at instantiation, such as an imported one
+    Immutable {
+        ptr: GlobalValue<'a>,
+        ty: BasicTypeEnum<'a>,
+    },
+}
+
+/// An export of the module
+pub struct Export {
+    pub name: String,
+    pub kind: ExternalKind,
+    pub index: u32,
 }
 
 pub struct StackFrame<'a> {
@@ -43,6 +60,41 @@ pub struct Function {
     pub type_idx: u32,
     // (module, name)
     pub orig_name: Option<(String, String)>,
+    // name in the name section
+    pub debug_name: Option<String>,
+}
+
+pub struct Table<'a> {
+    pub ty: TableType,
+    // (module, name)
+    pub orig_name: Option<(String, String)>,
+    // name in the export section
+    pub export_name: Option<String>,
+    /// Pointer to the table struct (see lib-rt/aot.h)
+    pub global: Option<GlobalValue<'a>>,
+}
+
+/// Base and size of a memory cached in the current function (see `--hoist-memory-loads`)
+pub struct MemoryCache<'a> {
+    /// Loaded once in the entry block since the base never moves
+    pub base: PointerValue<'a>,
+    /// Alloca reloaded after calls and memory.grow, or None if the memory is shared
+    /// since other threads may grow it at any time
+    pub size: Option<PointerValue<'a>>,
+}
+
+pub struct DataSegment<'a> {
+    /// Read-only bytes of the segment
+    pub global: GlobalValue<'a>,
+    pub size: usize,
+    pub passive: bool,
+}
+
+pub struct ElementSegment<'a> {
+    /// Read-only references of the segment
+    pub global: GlobalValue<'a>,
+    pub size: usize,
+    pub passive: bool,
 }
 
 pub struct Context<'a, 'b> {
@@ -62,24 +114,72 @@ pub struct Context<'a, 'b> {
     pub aot_main_block: Option<BasicBlock<'a>>,
 
     pub fn_memory_grow: Option<FunctionValue<'a>>,
-    pub global_table: Option<GlobalValue<'a>>,
-    pub global_table_size: Option<usize>,
+    pub fn_trap: Option<FunctionValue<'a>>,
+    pub fn_table_grow: Option<FunctionValue<'a>>,
+    pub fn_table_fill: Option<FunctionValue<'a>>,
+    pub fn_memory_atomic_notify: Option<FunctionValue<'a>>,
+    pub fn_memory_atomic_wait32: Option<FunctionValue<'a>>,
+    pub fn_memory_atomic_wait64: Option<FunctionValue<'a>>,
+    pub fn_exception_new: Option<FunctionValue<'a>>,
+    pub fn_exception_rethrow: Option<FunctionValue<'a>>,
+    pub fn_exception_clone: Option<FunctionValue<'a>>,
+    pub fn_exception_catch: Option<FunctionValue<'a>>,
+    pub fn_exception_tag: Option<FunctionValue<'a>>,
+    pub fn_exception_payload: Option<FunctionValue<'a>>,
+    pub fn_exception_release: Option<FunctionValue<'a>>,
+    pub fn_exception_uncaught: Option<FunctionValue<'a>>,
+    pub fn_personality: Option<FunctionValue<'a>>,
+    /// C++ typeinfo of exceptions thrown by lib-rt (see lib-rt/exception)
+    pub global_exception_typeinfo: Option<GlobalValue<'a>>,
     pub global_fptr_array: Option<GlobalValue<'a>>,
+    /// Entries to restore functions by index (see cr::restore::declare_restore_entries)
+    pub global_restore_entry_array: Option<GlobalValue<'a>>,
+    pub global_ftype_array: Option<GlobalValue<'a>>,
 
     pub exec_env_type: Option<StructType<'a>>,
     pub exec_env_fields: HashMap<&'static str, u32>,
+    pub table_type: Option<StructType<'a>>,
+    /// Type of the memories other than memory 0 (see lib-rt/aot.h)
+    pub memory_type: Option<StructType<'a>>,
+    /// Bases and sizes of the memories other than memory 0 (memory 0 is in exec_env)
+    pub global_extra_memories: Option<GlobalValue<'a>>,
 
     // module info
     pub signatures: Vec<FunctionType<'a>>,
+    // Structurally equivalent types share the same id
+    pub canonical_type_ids: Vec<u32>,
     /// List of (function, index)
     pub functions: Vec<Function>,
     pub function_values: Vec<FunctionValue<'a>>,
     pub num_functions: u32,
+    /// `_start` exported by commands
     pub start_function_idx: Option<u32>,
+    /// `_initialize` exported by reactors
+    pub initialize_function_idx: Option<u32>,
+    /// Function of the start section, which runs during instantiation
+    pub start_section_function_idx: Option<u32>,
+    /// `wasi_thread_start` exported for wasi-threads
+    pub thread_start_function_idx: Option<u32>,
+    pub exports: Vec<Export>,
+    /// C header declaring the exports of a library (see compile::library)
+    pub c_header: Option<String>,
+    /// C declarations of the host imports (see compile::host)
+    pub host_imports: Vec<String>,
+    /// C header declaring the host imports
+    pub imports_header: Option<String>,
+    pub tables: Vec<Table<'a>>,
+    /// Memories are allocated by the runtime, which cannot import them
+    pub memories: Vec<MemoryType>,
+    /// Type indices of the tags
+    pub tags: Vec<u32>,
 
     pub num_imports: u32,
 
     pub globals: Vec<Global<'a>>,
+    pub data_segments: Vec<DataSegment<'a>>,
+    pub global_data_segment_sizes: Option<GlobalValue<'a>>,
+    pub element_segments: Vec<ElementSegment<'a>>,
+    pub global_element_segment_sizes: Option<GlobalValue<'a>>,
 
     // builder state
     pub current_function_idx: Option<u32>,
@@ -90,6 +190,10 @@ pub struct Context<'a, 'b> {
     pub stack_frames: Vec<StackFrame<'a>>,
     pub unreachable_depth: u32,
     pub unreachable_reason: UnreachableReason,
+    /// Hidden locals holding the handles of caught exceptions, one per nesting level of try
+    pub exception_slots: Vec<PointerValue<'a>>,
+    /// Cached bases and sizes of the memories, empty unless `--hoist-memory-loads` is given
+    pub memory_caches: Vec<MemoryCache<'a>>,
 
     // checkpoint related
     pub fn_start_checkpoint: Option<FunctionValue<'a>>,
@@ -99,32 +203,39 @@ pub struct Context<'a, 'b> {
     pub fn_push_local_i64: Option<FunctionValue<'a>>,
     pub fn_push_local_f32: Option<FunctionValue<'a>>,
     pub fn_push_local_f64: Option<FunctionValue<'a>>,
+    pub fn_push_local_v128: Option<FunctionValue<'a>>,
     pub fn_push_i32: Option<FunctionValue<'a>>,
     pub fn_push_i64: Option<FunctionValue<'a>>,
     pub fn_push_f32: Option<FunctionValue<'a>>,
     pub fn_push_f64: Option<FunctionValue<'a>>,
+    pub fn_push_v128: Option<FunctionValue<'a>>,
     pub fn_push_global_i32: Option<FunctionValue<'a>>,
     pub fn_push_global_i64: Option<FunctionValue<'a>>,
     pub fn_push_global_f32: Option<FunctionValue<'a>>,
     pub fn_push_global_f64: Option<FunctionValue<'a>>,
-    pub fn_push_table_index: Option<FunctionValue<'a>>,
+    pub fn_push_global_v128: Option<FunctionValue<'a>>,
+    pub fn_store_table: Option<FunctionValue<'a>>,
     // restore related
     pub fn_pop_front_frame: Option<FunctionValue<'a>>,
     pub fn_frame_is_empty: Option<FunctionValue<'a>>,
     pub fn_get_pc_from_frame: Option<FunctionValue<'a>>,
+    pub fn_get_fn_index_from_frame: Option<FunctionValue<'a>>,
     pub fn_pop_front_local_i32: Option<FunctionValue<'a>>,
     pub fn_pop_front_local_i64: Option<FunctionValue<'a>>,
     pub fn_pop_front_local_f32: Option<FunctionValue<'a>>,
     pub fn_pop_front_local_f64: Option<FunctionValue<'a>>,
+    pub fn_pop_front_local_v128: Option<FunctionValue<'a>>,
     pub fn_pop_i32: Option<FunctionValue<'a>>,
     pub fn_pop_i64: Option<FunctionValue<'a>>,
     pub fn_pop_f32: Option<FunctionValue<'a>>,
     pub fn_pop_f64: Option<FunctionValue<'a>>,
+    pub fn_pop_v128: Option<FunctionValue<'a>>,
     pub fn_pop_front_global_i32: Option<FunctionValue<'a>>,
     pub fn_pop_front_global_i64: Option<FunctionValue<'a>>,
     pub fn_pop_front_global_f32: Option<FunctionValue<'a>>,
     pub fn_pop_front_global_f64: Option<FunctionValue<'a>>,
-    pub fn_pop_front_table_index: Option<FunctionValue<'a>>,
+    pub fn_pop_front_global_v128: Option<FunctionValue<'a>>,
+    pub fn_restore_table: Option<FunctionValue<'a>>,
 
     // restore related builder state
     pub restore_dispatch_bb: Option<BasicBlock<'a>>,
@@ -154,19 +265,53 @@ impl<'a> Context<'a, '_> {
             aot_init_block: None,
             aot_main_block: None,
             fn_memory_grow: None,
+            fn_trap: None,
+            fn_table_grow: None,
+            fn_table_fill: None,
+            fn_memory_atomic_notify: None,
+            fn_memory_atomic_wait32: None,
+            fn_memory_atomic_wait64: None,
+            fn_exception_new: None,
+            fn_exception_rethrow: None,
+            fn_exception_clone: None,
+            fn_exception_catch: None,
+            fn_exception_tag: None,
+            fn_exception_payload: None,
+            fn_exception_release: None,
+            fn_exception_uncaught: None,
+            fn_personality: None,
+            global_exception_typeinfo: None,
             exec_env_type: None,
             exec_env_fields: HashMap::new(),
+            table_type: None,
+            memory_type: None,
+            global_extra_memories: None,
             global_fptr_array: None,
-            global_table: None,
-            global_table_size: None,
+            global_restore_entry_array: None,
+            global_ftype_array: None,
 
             signatures: Vec::new(),
+            canonical_type_ids: Vec::new(),
             functions: Vec::new(),
             function_values: Vec::new(),
             num_functions: 0,
             start_function_idx: None,
+            initialize_function_idx: None,
+            start_section_function_idx: None,
+            thread_start_function_idx: None,
+            exports: Vec::new(),
+            c_header: None,
+            host_imports: Vec::new(),
+            imports_header: None,
+            tables: Vec::new(),
+            memories: Vec::new(),
+            tags: Vec::new(),
             num_imports: 0,
             globals: Vec::new(),
+            data_segments: Vec::new(),
+            global_data_segment_sizes: None,
+            element_segments: Vec::new(),
+            global_element_segment_sizes: None,
 
             current_function_idx: None,
             current_fn: None,
@@ -175,6 +320,8 @@ impl<'a> Context<'a, '_> {
             stack_frames: Vec::new(),
             unreachable_depth: 0,
             unreachable_reason: UnreachableReason::Reachable,
+            exception_slots: Vec::new(),
+            memory_caches: Vec::new(),
 
             fn_start_checkpoint: None,
             fn_push_frame: None,
@@ -183,32 +330,39 @@ impl<'a> Context<'a, '_> {
             fn_push_local_i64: None,
             fn_push_local_f32: None,
             fn_push_local_f64: None,
+            fn_push_local_v128: None,
             fn_push_i32: None,
             fn_push_i64: None,
             fn_push_f32: None,
             fn_push_f64: None,
+            fn_push_v128: None,
             fn_push_global_i64: None,
             fn_push_global_i32: None,
             fn_push_global_f32: None,
             fn_push_global_f64: None,
-            fn_push_table_index: None,
+            fn_push_global_v128: None,
+            fn_store_table: None,
 
             fn_get_pc_from_frame: None,
+            fn_get_fn_index_from_frame: None,
             fn_frame_is_empty: None,
             fn_pop_front_frame: None,
             fn_pop_front_local_i32: None,
             fn_pop_front_local_i64: None,
             fn_pop_front_local_f32: None,
             fn_pop_front_local_f64: None,
+            fn_pop_front_local_v128: None,
             fn_pop_i32: None,
             fn_pop_i64: None,
             fn_pop_f32: None,
             fn_pop_f64: None,
+            fn_pop_v128: None,
             fn_pop_front_global_i32: None,
             fn_pop_front_global_i64: None,
             fn_pop_front_global_f32: None,
             fn_pop_front_global_f64: None,
-            fn_pop_front_table_index: None,
+            fn_pop_front_global_v128: None,
+            fn_restore_table: None,
 
             restore_dispatch_bb: None,
             restore_dispatch_cases: Vec::new(),
@@ -224,28 +378,26 @@ impl<'a> Context<'a, '_> {
     }
 
     /// Pop a value from the current stack frame
-    pub fn pop(&mut self) -> Option<BasicValueEnum<'a>> {
+    pub fn pop(&mut self) -> Result<BasicValueEnum<'a>> {
         let frame = self.stack_frames.last_mut().expect("frame empty");
-        frame.stack.pop()
+        match frame.stack.pop() {
+            Some(value) => Ok(value),
+            None => bail!(CompileErrorKind::StackUnderflow),
+        }
     }
 
     /// Pop two values from the current stack frame
-    pub fn pop2(&mut self) -> (BasicValueEnum<'a>, BasicValueEnum<'a>) {
-        let frame = self.stack_frames.last_mut().expect("frame empty");
-        let v2 = frame.stack.pop().expect("stack empty");
-        let v1 = frame.stack.pop().expect("stack empty");
-        (v1, v2)
+    pub fn pop2(&mut self) -> Result<(BasicValueEnum<'a>, BasicValueEnum<'a>)> {
+        let v2 = self.pop()?;
+        let v1 = self.pop()?;
+        Ok((v1, v2))
     }
 
     /// Peek n values from the stack bottom
     pub fn peekn(&self, n: usize) -> Result<&[BasicValueEnum<'a>]> {
         let frame = self.stack_frames.last().expect("frame empty");
         if frame.stack.len() < n {
-            bail!(
-                "stack length too short. Expected {} but found {}",
-                n,
-                frame.stack.len()
-            );
+            bail!(CompileErrorKind::StackUnderflow);
         }
         let index = frame.stack.len() - n;
         Ok(&frame.stack[index..])
@@ -1,10 +1,15 @@
 use anyhow::{anyhow, Context as _, Result};
 use clap::Parser;
 use inkwell::{object_file::ObjectFile, targets};
-use std::path::{self, Path, PathBuf};
+use std::{
+    fmt::Write as _,
+    io::Write as _,
+    path::{self, Path, PathBuf},
+};
+use wasmparser::{BinaryReader, WasmFeatures};
 
 use crate::{
-    compile::{self, stackmap},
+    compile::{self, stackmap, CompileError},
     context::Context,
 };
 
@@ -32,6 +37,28 @@ impl std::fmt::Display for OptimizationLevel {
     }
 }
 
+/// Strategy for checking linear-memory accesses.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BoundsChecks {
+    /// Compare every access against the memory size and branch to a trap.
+    #[default]
+    Explicit,
+    /// Reserve a large virtual region and trap on faults in its guard pages.
+    GuardPage,
+}
+
+/// Kind of the output file.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Emit {
+    /// An executable which runs `_start`.
+    #[default]
+    Exe,
+    /// A static library which exposes the exports with the C ABI.
+    Staticlib,
+    /// A shared library which exposes the exports with the C ABI.
+    Sharedlib,
+}
+
 #[derive(Debug, Clone, Parser, Default)]
 pub struct Args {
     pub input_file: path::PathBuf,
@@ -44,6 +71,18 @@ pub struct Args {
     #[arg(short)]
     pub compile_only: bool,
 
+    /// Kind of the output. Libraries come with a C header declaring the exports.
+    #[arg(long, value_enum, default_value = "exe")]
+    pub emit: Emit,
+
+    /// Link a C/C++ object or archive which defines host imports. Can be repeated.
+    #[arg(long = "host", value_name = "FILE")]
+    pub host_files: Vec<PathBuf>,
+
+    /// Write a C header declaring the host imports of the module.
+    #[arg(long, value_name = "FILE")]
+    pub imports_header: Option<PathBuf>,
+
     /// Enable LTO.
     #[arg(long, default_value = "false")]
     pub lto: bool,
@@ -74,6 +113,15 @@ pub struct Args {
     #[arg(long)]
     pub disable_loop_cr: bool,
 
+    /// How to check out-of-bounds linear-memory accesses.
+    #[arg(long, value_enum, default_value = "explicit")]
+    pub bounds_checks: BoundsChecks,
+
+    /// Load the bases and the sizes of memories once per function, and reload the sizes
+    /// only after calls and memory.grow.
+    #[arg(long)]
+    pub hoist_memory_loads: bool,
+
     /// Optimization level.
     #[arg(short = 'O', value_enum, default_value = "1")]
     pub optimization: OptimizationLevel,
@@ -106,11 +154,19 @@ pub fn check_config(args: &Args) -> bool {
         log::error!("Cannot specify both --enable-cr and --legacy-cr");
         return false;
     }
+    if args.emit != Emit::Exe && (args.enable_cr || args.legacy_cr) {
+        log::error!("Checkpoint/restore is not supported for libraries");
+        return false;
+    }
     true
 }
 
 struct AotWasmModule<'a> {
     module: inkwell::module::Module<'a>,
+    /// C header declaring the exports of a library
+    c_header: Option<String>,
+    /// C header declaring the host imports
+    imports_header: Option<String>,
 }
 
 impl<'a> AotWasmModule<'a> {
@@ -119,9 +175,21 @@ impl<'a> AotWasmModule<'a> {
         let builder = ictx.create_builder();
 
         let mut ctx = Context::new(args, ictx, &module, builder);
-        compile::compile_module(wasm, &mut ctx)?;
-
-        Ok(Self { module })
+        if let Err(e) = compile::compile_module(wasm, &mut ctx) {
+            let description = match e.downcast_ref::<CompileError>() {
+                Some(error) => describe_compile_error(&ctx, wasm, error)?,
+                None => return Err(e),
+            };
+            return Err(e.context(description));
+        }
+        let c_header = ctx.c_header.take();
+        let imports_header = ctx.imports_header.take();
+
+        Ok(Self {
+            module,
+            c_header,
+            imports_header,
+        })
     }
 
     fn write_llvm_object(&self, args: &Args, out_tmpdir: bool) -> Result<PathBuf> {
@@ -183,8 +251,16 @@ impl<'a> AotWasmModule<'a> {
     }
 
     fn link_with_runtime(&self, args: &Args) -> Result<PathBuf> {
-        let exe_path = args.output_file.clone().unwrap_or("a.out".to_owned());
+        let default_path = match args.emit {
+            Emit::Exe => "a.out",
+            Emit::Staticlib => "libwasm.a",
+            Emit::Sharedlib => "libwasm.so",
+        };
+        let exe_path = args.output_file.clone().unwrap_or(default_path.to_owned());
         let exe_path = Path::new(&exe_path).to_path_buf();
+        if args.emit == Emit::Staticlib {
+            return self.archive_with_runtime(args, exe_path);
+        }
 
         // write .bc file
         let tmp_ll_path = self.write_llvm_asm(args, true)?;
@@ -200,17 +276,33 @@ impl<'a> AotWasmModule<'a> {
         let mut cmd = std::process::Command::new(clangxx);
         let cmd = cmd
             .arg(tmp_ll_path)
+            .args(&args.host_files)
             .arg(format!("{}/libwanco_rt.a", library_path))
             .arg(format!("{}/libwanco_wasi.a", library_path))
             .arg("-g")
             .arg("-o")
             .arg(&exe_path)
-            .arg("-no-pie")
             .arg(format!("-{}", args.optimization));
+        match args.emit {
+            Emit::Exe => {
+                cmd.arg("-no-pie");
+            }
+            // The library API is not referenced by the module, so pull it from the runtime explicitly
+            Emit::Sharedlib => {
+                cmd.arg("-shared")
+                    .arg("-fPIC")
+                    .arg("-Wl,--undefined=wanco_init")
+                    .arg("-Wl,--undefined=wanco_teardown");
+            }
+            Emit::Staticlib => unreachable!(),
+        }
 
         // link protobuf to the exe
         cmd.arg("-lprotobuf");
 
+        // link pthread to the exe for wasi-threads
+        cmd.arg("-lpthread");
+
         // link libunwind to the exe
         let triple = get_target_machine(args).unwrap().get_triple();
         let triple = triple.as_str().to_str().unwrap();
@@ -252,6 +344,121 @@ impl<'a> AotWasmModule<'a> {
 
         Ok(exe_path)
     }
+
+    /// Bundle the module and the runtime libraries into a static library.
+    /// Users link it with the dependencies of the runtime (protobuf, libunwind, libelf and pthread).
+    fn archive_with_runtime(&self, args: &Args, lib_path: PathBuf) -> Result<PathBuf> {
+        let tmp_ll_path = self.write_llvm_asm(args, true)?;
+        let obj_path = tmp_ll_path.with_extension("o");
+
+        let clangxx = args.clang_path.clone().unwrap_or("clang++-17".to_owned());
+        let library_path = args
+            .library_path
+            .clone()
+            .unwrap_or("/usr/local/lib".to_owned());
+        let mut cmd = std::process::Command::new(clangxx);
+        let cmd = cmd
+            .arg("-c")
+            .arg(&tmp_ll_path)
+            .arg("-g")
+            .arg("-fPIC")
+            .arg("-o")
+            .arg(&obj_path)
+            .arg(format!("-{}", args.optimization));
+        if let Some(ref target) = args.target {
+            cmd.arg(format!("--target={}", target));
+        }
+        log::info!("{:?}", cmd);
+        let o = cmd
+            .output()
+            .map_err(|e| anyhow!(e.to_string()))
+            .context("Failed to compile the module")?;
+        if !o.status.success() {
+            let cc_stderr = String::from_utf8(o.stderr).unwrap();
+            return Err(anyhow!("Failed to compile the module: {}", cc_stderr));
+        }
+
+        // Merge the host files and the runtime archives with an MRI script of ar
+        let _ = std::fs::remove_file(&lib_path);
+        let mut script = format!(
+            "CREATE {}\nADDMOD {}\n",
+            lib_path.display(),
+            obj_path.display()
+        );
+        for host_file in &args.host_files {
+            let command = match host_file.extension() {
+                Some(ext) if ext == "a" => "ADDLIB",
+                _ => "ADDMOD",
+            };
+            writeln!(script, "{} {}", command, host_file.display())?;
+        }
+        writeln!(
+            script,
+            "ADDLIB {}/libwanco_rt.a\nADDLIB {}/libwanco_wasi.a\nSAVE\nEND",
+            library_path, library_path
+        )?;
+        let mut ar = std::process::Command::new("ar")
+            .arg("-M")
+            .stdin(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .context("Failed to run ar")?;
+        ar.stdin
+            .take()
+            .expect("should open stdin of ar")
+            .write_all(script.as_bytes())?;
+        let o = ar.wait_with_output()?;
+        if !o.status.success() {
+            let ar_stderr = String::from_utf8(o.stderr).unwrap();
+            return Err(anyhow!("Failed to archive object files: {}", ar_stderr));
+        }
+        log::info!("Archived to {}", lib_path.display());
+
+        Ok(lib_path)
+    }
+
+    fn write_imports_header(&self, path: &Path) -> Result<()> {
+        let imports_header = self
+            .imports_header
+            .as_ref()
+            .expect("should generate C header of imports");
+        std::fs::write(path, imports_header)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        log::info!("Writing C header of imports to {}", path.display());
+        Ok(())
+    }
+
+    fn write_c_header(&self, lib_path: &Path) -> Result<PathBuf> {
+        let path = lib_path.with_extension("h");
+        let c_header = self.c_header.as_ref().expect("should generate C header");
+        std::fs::write(&path, c_header)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        log::info!("Writing C header to {}", path.display());
+        Ok(path)
+    }
+}
+
+/// Describe an error in compiling a function with the name of the function in the name section
+/// and the disassembled operator.
+fn describe_compile_error(ctx: &Context, wasm: &[u8], error: &CompileError) -> Result<String> {
+    let mut description = format!("Failed to compile function {}", error.function_index);
+    let function = ctx.functions.get(error.function_index as usize);
+    if let Some(name) = function.and_then(|function| function.debug_name.as_ref()) {
+        write!(description, " ({})", name)?;
+    }
+    write!(description, ": {}", error.kind)?;
+    if let Some(op_index) = error.op_index {
+        let mut reader =
+            BinaryReader::new(&wasm[error.offset..], error.offset, WasmFeatures::all());
+        if let Ok(op) = reader.read_operator() {
+            write!(
+                description,
+                "\n  op[{}] at offset 0x{:x}: {:?}",
+                op_index, error.offset, op
+            )?;
+        }
+    }
+    Ok(description)
 }
 
 fn dump_stackmap(obj: &ObjectFile) -> Result<()> {
@@ -282,9 +489,15 @@ fn dump_stackmap(obj: &ObjectFile) -> Result<()> {
 }
 
 pub fn compile_and_link(wasm: &[u8], args: &Args) -> Result<()> {
+    compile::validate_module(wasm)?;
+
     let ictx = inkwell::context::Context::create();
     let aot_module = AotWasmModule::compile(&ictx, wasm, args.clone())?;
 
+    if let Some(ref path) = args.imports_header {
+        aot_module.write_imports_header(path)?;
+    }
+
     if false {
         let obj = aot_module.write_object_to_memory(args)?;
         dump_stackmap(&obj)?;
@@ -301,6 +514,10 @@ pub fn compile_and_link(wasm: &[u8], args: &Args) -> Result<()> {
     let exe_path = aot_module.link_with_runtime(args)?;
     log::info!("Linked to {}", exe_path.display());
 
+    if args.emit != Emit::Exe {
+        aot_module.write_c_header(&exe_path)?;
+    }
+
     Ok(())
 }
 
@@ -354,3 +571,46 @@ fn get_target_machine(args: &Args) -> Result<targets::TargetMachine, String> {
         .create_target_machine(&triple, &cpu, &features, opt_level, reloc_mode, code_model)
         .ok_or("failed to get target machine".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::CompileErrorKind;
+
+    #[test]
+    fn test_stack_underflow() {
+        // Validation rejects the module, so the code generator is called directly
+        let wasm = wat::parse_str(
+            r#"
+            (module
+              (func $underflow (export "_start")
+                i32.const 1
+                i32.add
+                drop
+              )
+            )
+            "#,
+        )
+        .unwrap();
+        assert!(compile::validate_module(&wasm).is_err());
+
+        let ictx = inkwell::context::Context::create();
+        let err = match AotWasmModule::compile(&ictx, &wasm, Args::default()) {
+            Ok(_) => panic!("should fail to compile"),
+            Err(e) => e,
+        };
+        let error = err
+            .downcast_ref::<CompileError>()
+            .expect("should be a compile error");
+        assert!(matches!(error.kind, CompileErrorKind::StackUnderflow));
+        assert_eq!(error.function_index, 0);
+        // i32.const, i32.add
+        assert_eq!(error.op_index, Some(1));
+
+        // The function is named after the name section, and the operator is disassembled
+        let message = err.to_string();
+        assert!(message.contains("(underflow)"));
+        assert!(message.contains("stack underflow"));
+        assert!(message.contains("I32Add"));
+    }
+}
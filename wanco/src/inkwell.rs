@@ -11,6 +11,14 @@ pub struct InkwellTypes<'ctx> {
     pub f32_type: FloatType<'ctx>,
     pub f64_type: FloatType<'ctx>,
     pub ptr_type: PointerType<'ctx>,
+    // v128 is represented as <2 x i64> like __m128i, and bitcasted to the lane types
+    pub v128_type: VectorType<'ctx>,
+    pub i8x16_type: VectorType<'ctx>,
+    pub i16x8_type: VectorType<'ctx>,
+    pub i32x4_type: VectorType<'ctx>,
+    pub i64x2_type: VectorType<'ctx>,
+    pub f32x4_type: VectorType<'ctx>,
+    pub f64x2_type: VectorType<'ctx>,
 }
 
 /// Basic insts of inkwell.
@@ -41,6 +49,14 @@ pub struct InkwellIntrinsics<'ctx> {
     pub copysign_f32: FunctionValue<'ctx>,
     pub copysign_f64: FunctionValue<'ctx>,
     pub expect_i32: FunctionValue<'ctx>,
+    pub fptosi_sat_i32_f32: FunctionValue<'ctx>,
+    pub fptosi_sat_i32_f64: FunctionValue<'ctx>,
+    pub fptosi_sat_i64_f32: FunctionValue<'ctx>,
+    pub fptosi_sat_i64_f64: FunctionValue<'ctx>,
+    pub fptoui_sat_i32_f32: FunctionValue<'ctx>,
+    pub fptoui_sat_i32_f64: FunctionValue<'ctx>,
+    pub fptoui_sat_i64_f32: FunctionValue<'ctx>,
+    pub fptoui_sat_i64_f64: FunctionValue<'ctx>,
 
     pub experimental_stackmap: FunctionValue<'ctx>,
 }
@@ -60,6 +76,13 @@ pub fn init_inkwell<'a>(
 
     let ptr_type = ctx.ptr_type(AddressSpace::default());
 
+    let i8x16_type = i8_type.vec_type(16);
+    let i16x8_type = i16_type.vec_type(8);
+    let i32x4_type = i32_type.vec_type(4);
+    let i64x2_type = i64_type.vec_type(2);
+    let f32x4_type = f32_type.vec_type(4);
+    let f64x2_type = f64_type.vec_type(2);
+
     let bool_type_meta: BasicMetadataTypeEnum = bool_type.into();
     //let i8_type_meta: BasicMetadataTypeEnum = i8_type.into();
     //let i16_type_meta: BasicMetadataTypeEnum = i16_type.into();
@@ -76,6 +99,10 @@ pub fn init_inkwell<'a>(
     let f32_f32 = f32_type.fn_type(&[f32_type_meta], false);
     let f32f32_f32 = f32_type.fn_type(&[f32_type_meta, f32_type_meta], false);
     let f64f64_f64 = f64_type.fn_type(&[f64_type_meta, f64_type_meta], false);
+    let f32_i32 = i32_type.fn_type(&[f32_type_meta], false);
+    let f64_i32 = i32_type.fn_type(&[f64_type_meta], false);
+    let f32_i64 = i64_type.fn_type(&[f32_type_meta], false);
+    let f64_i64 = i64_type.fn_type(&[f64_type_meta], false);
 
     let ctlz_i32 = module.add_function("llvm.ctlz.i32", i32bool_i32, None);
     let ctlz_i64 = module.add_function("llvm.ctlz.i64", i64bool_i64, None);
@@ -101,6 +128,14 @@ pub fn init_inkwell<'a>(
     let maxnum_f64 = module.add_function("llvm.maxnum.f64", f64f64_f64, None);
     let copysign_f32 = module.add_function("llvm.copysign.f32", f32f32_f32, None);
     let copysign_f64 = module.add_function("llvm.copysign.f64", f64f64_f64, None);
+    let fptosi_sat_i32_f32 = module.add_function("llvm.fptosi.sat.i32.f32", f32_i32, None);
+    let fptosi_sat_i32_f64 = module.add_function("llvm.fptosi.sat.i32.f64", f64_i32, None);
+    let fptosi_sat_i64_f32 = module.add_function("llvm.fptosi.sat.i64.f32", f32_i64, None);
+    let fptosi_sat_i64_f64 = module.add_function("llvm.fptosi.sat.i64.f64", f64_i64, None);
+    let fptoui_sat_i32_f32 = module.add_function("llvm.fptoui.sat.i32.f32", f32_i32, None);
+    let fptoui_sat_i32_f64 = module.add_function("llvm.fptoui.sat.i32.f64", f64_i32, None);
+    let fptoui_sat_i64_f32 = module.add_function("llvm.fptoui.sat.i64.f32", f32_i64, None);
+    let fptoui_sat_i64_f64 = module.add_function("llvm.fptoui.sat.i64.f64", f64_i64, None);
     let expect_i32 = module.add_function(
         "llvm.expect.i32",
         i32_type.fn_type(&[i32_type_meta, i32_type_meta], false),
@@ -124,6 +159,13 @@ pub fn init_inkwell<'a>(
             f32_type,
             f64_type,
             ptr_type,
+            v128_type: i64x2_type,
+            i8x16_type,
+            i16x8_type,
+            i32x4_type,
+            i64x2_type,
+            f32x4_type,
+            f64x2_type,
         },
         InkwellIntrinsics {
             ctlz_i32,
@@ -151,6 +193,14 @@ pub fn init_inkwell<'a>(
             copysign_f32,
             copysign_f64,
             expect_i32,
+            fptosi_sat_i32_f32,
+            fptosi_sat_i32_f64,
+            fptosi_sat_i64_f32,
+            fptosi_sat_i64_f64,
+            fptoui_sat_i32_f32,
+            fptoui_sat_i32_f64,
+            fptoui_sat_i64_f32,
+            fptoui_sat_i64_f64,
 
             experimental_stackmap,
         },
@@ -4,4 +4,5 @@ mod context;
 mod driver;
 mod inkwell;
 
-pub use driver::{run_compiler, Args};
+pub use compile::{CompileError, CompileErrorKind};
+pub use driver::{run_compiler, Args, BoundsChecks, Emit};
@@ -0,0 +1,16 @@
+// Host imports of checkpoint.wat
+#include "imports.h"
+#include <signal.h>
+#include <stdio.h>
+
+// The runtime takes a checkpoint at the next migration point on SIGUSR1
+// (see SIGCHKPT in lib-rt/aot.h)
+void wanco_import_3env18request_checkpoint_v_v(wanco_exec_env_t *env) {
+  raise(SIGUSR1);
+}
+
+void wanco_import_3env5check_i_v(wanco_exec_env_t *env, int32_t arg0) {
+  static int count = 0;
+  count++;
+  printf("%s %d\n", arg0 ? "Test Passed" : "Test Failed", count);
+}
@@ -0,0 +1,28 @@
+;; Checkpoint taken in a callee with multiple results (see checkpoint.c)
+(module
+  (import "env" "request_checkpoint" (func $request_checkpoint))
+  (import "env" "check" (func $check (param i32)))
+
+  ;; The checkpoint is taken at the loop header after the request
+  (func $pair (param $x i64) (result i32 i64)
+    (local $i i32)
+    (call $request_checkpoint)
+    (loop $l
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br_if $l (i32.lt_u (local.get $i) (i32.const 3)))
+    )
+    (i32.add (i32.wrap_i64 (local.get $x)) (local.get $i))
+    (i64.mul (local.get $x) (i64.const 2))
+  )
+
+  (func (export "_start")
+    (local $b i64)
+    ;; The value below the results is restored from the frame of the caller
+    (i32.const 100)
+    (call $pair (i64.const 20))
+    (local.set $b)
+    (i32.add)
+    (call $check (i32.eq (i32.const 123)))
+    (call $check (i64.eq (local.get $b) (i64.const 40)))
+  )
+)
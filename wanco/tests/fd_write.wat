@@ -13,12 +13,12 @@
 
   ;; Debug function
   (func $printHello
-    (call $fd_write
+    (drop (call $fd_write
             (i32.const 1) ;; file_descriptor - 1 for stdout
             (i32.const 0) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
             (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
             (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
-    )
+    ))
   )
 
   (func (export "_start")
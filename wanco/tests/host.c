@@ -0,0 +1,27 @@
+// Host imports of host.wat
+#include "imports.h"
+#include <stdio.h>
+
+int32_t wanco_import_3env7counter_mi = 10;
+const int64_t wanco_import_3env4base_l = 42;
+const int32_t wanco_import_3env13__memory_base_i = 1024;
+const int32_t wanco_import_3env12__table_base_i = 2;
+
+static int32_t funcs[3] = {-1, 3, -1};
+wanco_table_t wanco_import_3env5funcs_t = {funcs, 3};
+
+int32_t wanco_import_3env3add_ii_i(wanco_exec_env_t *env, int32_t arg0,
+                                   int32_t arg1) {
+  return arg0 + arg1;
+}
+
+int64_t wanco_import_x14_6d792d686f73745twice_l_l(wanco_exec_env_t *env,
+                                                    int64_t arg0) {
+  return arg0 * 2;
+}
+
+void wanco_import_3env5check_i_v(wanco_exec_env_t *env, int32_t arg0) {
+  static int count = 0;
+  count++;
+  printf("%s %d\n", arg0 ? "Test Passed" : "Test Failed", count);
+}
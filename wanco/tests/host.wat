@@ -0,0 +1,39 @@
+;; Module importing functions, globals and a table from the host (see host.c)
+(module
+  (import "env" "add" (func $add (param i32 i32) (result i32)))
+  (import "my-host" "twice" (func $twice (param i64) (result i64)))
+  (import "env" "check" (func $check (param i32)))
+  (import "env" "counter" (global $counter (mut i32)))
+  (import "env" "base" (global $base i64))
+  (import "env" "__memory_base" (global $__memory_base i32))
+  (import "env" "__table_base" (global $__table_base i32))
+  (import "env" "funcs" (table $funcs 2 8 funcref))
+
+  ;; Constant expressions reading imported globals are evaluated at instantiation
+  (global $derived i64 (i64.mul (global.get $base) (i64.const 2)))
+  (global $next (mut i32) (i32.add (global.get $__memory_base) (i32.const 1)))
+  (data (global.get $__memory_base) "\2a")
+  (memory 1)
+  (table $local 4 funcref)
+  (elem (table $local) (global.get $__table_base) func $nine)
+
+  (type $ret_i32 (func (result i32)))
+  (func $nine (result i32) (i32.const 9))
+
+  (func (export "_start")
+    (call $check (i32.eq (call $add (i32.const 1) (i32.const 2)) (i32.const 3)))
+    (call $check (i64.eq (call $twice (global.get $base)) (i64.const 84)))
+    ;; The host sees the update
+    (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+    (call $check (i32.eq (global.get $counter) (i32.const 11)))
+    (call $check (i32.eq (memory.size) (i32.const 1)))
+    (call $check (i64.eq (global.get $derived) (i64.const 84)))
+    (call $check (i32.eq (global.get $next) (i32.const 1025)))
+    (call $check (i32.eq (i32.load8_u (global.get $__memory_base)) (i32.const 42)))
+    (call $check
+      (i32.eq (call_indirect $local (type $ret_i32) (global.get $__table_base)) (i32.const 9)))
+    ;; The host sets $nine (function 3) to the element 1
+    (call $check (i32.eq (table.size $funcs) (i32.const 3)))
+    (call $check (i32.eq (call_indirect $funcs (type $ret_i32) (i32.const 1)) (i32.const 9)))
+  )
+)
@@ -0,0 +1,8 @@
+;; Imports `add` with a signature different from the definition in host.c
+(module
+  (import "env" "add" (func $add (param i64 i64) (result i64)))
+
+  (func (export "_start")
+    (drop (call $add (i64.const 1) (i64.const 2)))
+  )
+)
@@ -0,0 +1,60 @@
+// Calls the library compiled from library.wat
+#include "libwanco_library.h"
+#include <stdio.h>
+
+#define CHECK(cond)                                                            \
+  if (!(cond)) {                                                               \
+    printf("Test Failed: %s\n", #cond);                                        \
+    return 1;                                                                  \
+  }
+
+int main(void) {
+  wanco_exec_env_t *env = wanco_init(0, NULL);
+  CHECK(env != NULL);
+  CHECK(wanco_init(0, NULL) == NULL);
+
+  CHECK(wanco_export_add(env, 1, 2) == 3);
+
+  int64_t quot, rem;
+  wanco_export_divmod(env, 17, 5, &quot, &rem);
+  CHECK(quot == 3 && rem == 2);
+
+  CHECK(wanco_export_check_positive(env, 5) == 5);
+  CHECK(wanco_uncaught_exception(env) == 0);
+  CHECK(wanco_export_check_positive(env, -1) == 0);
+  CHECK(wanco_uncaught_exception(env) == 1);
+  CHECK(wanco_uncaught_exception(env) == 0);
+
+  CHECK(wanco_global_get_answer(env) == 42);
+  CHECK(wanco_global_get_counter(env) == 10);
+  wanco_export_bump_counter(env);
+  CHECK(wanco_global_get_counter(env) == 17);
+  wanco_global_set_counter(env, 0);
+  CHECK(wanco_global_get_counter(env) == 0);
+  wanco_export__start(env);
+  CHECK(wanco_global_get_counter(env) == -1);
+  wanco_global_set_counter(env, 0);
+
+  CHECK(wanco_memory_size_memory(env) == 65536);
+  uint8_t *memory = wanco_memory_base_memory(env);
+  CHECK(memory[0] == 7);
+  memory[0] = 3;
+  wanco_export_bump_counter(env);
+  CHECK(wanco_global_get_counter(env) == 3);
+
+  CHECK(wanco_global_get_instances(env) == 1);
+  CHECK(wanco_export_table_size(env) == 2);
+  wanco_teardown(env);
+
+  // The module is instantiated again from its initial state
+  env = wanco_init(0, NULL);
+  CHECK(env != NULL);
+  CHECK(wanco_global_get_instances(env) == 1);
+  CHECK(wanco_export_table_size(env) == 2);
+  CHECK(wanco_global_get_counter(env) == 10);
+  CHECK(wanco_memory_base_memory(env)[0] == 7);
+  wanco_teardown(env);
+
+  printf("Test Passed\n");
+  return 0;
+}
@@ -0,0 +1,46 @@
+;; Library exposing functions, a memory and globals to C (see library.c)
+(module
+  (memory (export "memory") 1)
+  (global $counter (export "counter") (mut i32) (i32.const 0))
+  (global (export "answer") i64 (i64.const 42))
+  ;; reset by wanco_teardown along with the table
+  (global $instances (export "instances") (mut i32) (i32.const 0))
+  (table $t 1 funcref)
+  (tag $e (param i32))
+
+  (func (export "_initialize")
+    (global.set $counter (i32.const 10))
+    (i32.store (i32.const 0) (i32.const 7))
+    (global.set $instances (i32.add (global.get $instances) (i32.const 1)))
+    (drop (table.grow $t (ref.null func) (i32.const 1)))
+  )
+
+  ;; Only called through its export, not by wanco_init
+  (func (export "_start")
+    (global.set $counter (i32.const -1))
+  )
+
+  (func (export "table-size") (result i32)
+    (table.size $t)
+  )
+
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))
+  )
+
+  (func (export "divmod") (param i64 i64) (result i64 i64)
+    (i64.div_u (local.get 0) (local.get 1))
+    (i64.rem_u (local.get 0) (local.get 1))
+  )
+
+  ;; The exception is reported by wanco_uncaught_exception
+  (func (export "check-positive") (param i32) (result i32)
+    (if (i32.le_s (local.get 0) (i32.const 0))
+      (then (throw $e (local.get 0))))
+    (local.get 0)
+  )
+
+  (func (export "bump-counter")
+    (global.set $counter (i32.add (global.get $counter) (i32.load (i32.const 0))))
+  )
+)
@@ -0,0 +1,91 @@
+use std::{path::PathBuf, process::Command};
+
+use wanco::*;
+
+const HOST_SOURCE: &str = "tests/checkpoint.c";
+
+fn run_test(test_name: &str, args: Args) {
+    let _ = env_logger::builder().try_init();
+
+    let input_file = PathBuf::from("tests")
+        .join("checkpoint")
+        .with_extension("wat");
+    let out_dir = std::path::PathBuf::from("/tmp").join(format!("wanco_checkpoint_{}", test_name));
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let exe = out_dir.join("main");
+
+    // Compile checkpoint.c against the header of the imports
+    let header_args = Args {
+        input_file: input_file.clone(),
+        output_file: Some(out_dir.join("checkpoint").to_str().unwrap().to_owned()),
+        compile_only: true,
+        imports_header: Some(out_dir.join("imports.h")),
+        ..Default::default()
+    };
+    if let Err(e) = run_compiler(&header_args) {
+        panic!("Could not compile {:?} ({})", &header_args.input_file, e);
+    }
+    let host_obj = out_dir.join("checkpoint_imports.o");
+    let status = Command::new("clang-17")
+        .arg("-c")
+        .arg(HOST_SOURCE)
+        .arg("-I")
+        .arg(&out_dir)
+        .arg("-o")
+        .arg(&host_obj)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // Compile
+    let args = Args {
+        input_file,
+        output_file: Some(exe.to_str().unwrap().to_owned()),
+        host_files: vec![host_obj],
+        ..args
+    };
+    if let Err(e) = run_compiler(&args) {
+        panic!("Could not compile {:?} ({})", &args.input_file, e);
+    }
+
+    // Execute until the checkpoint
+    let output = Command::new(&exe).current_dir(&out_dir).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("Test Passed"));
+    assert!(!stdout.contains("Test Failed"));
+    assert!(output.status.success());
+
+    // Restore and execute the rest
+    let output = Command::new(&exe)
+        .current_dir(&out_dir)
+        .arg("--restore")
+        .arg("checkpoint.pb")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Test Passed 2"));
+    assert!(!stdout.contains("Test Failed"));
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_checkpoint_multi_value_callee() {
+    run_test(
+        "multi_value_callee",
+        Args {
+            enable_cr: true,
+            ..Default::default()
+        },
+    );
+}
+
+#[test]
+fn test_legacy_checkpoint_multi_value_callee() {
+    run_test(
+        "legacy_multi_value_callee",
+        Args {
+            legacy_cr: true,
+            ..Default::default()
+        },
+    );
+}
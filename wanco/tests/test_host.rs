@@ -0,0 +1,81 @@
+use std::{path::PathBuf, process::Command};
+
+use wanco::*;
+
+const HOST_SOURCE: &str = "tests/host.c";
+
+/// Compile host.c against the header of the imports of host.wat.
+fn build_host(out_dir: &PathBuf) -> PathBuf {
+    let header = out_dir.join("imports.h");
+    let args = Args {
+        input_file: PathBuf::from("tests").join("host").with_extension("wat"),
+        output_file: Some(out_dir.join("host").to_str().unwrap().to_owned()),
+        compile_only: true,
+        imports_header: Some(header),
+        ..Default::default()
+    };
+    if let Err(e) = run_compiler(&args) {
+        panic!("Could not compile {:?} ({})", &args.input_file, e);
+    }
+    let host_obj = out_dir.join("host_imports.o");
+    let status = Command::new("clang-17")
+        .arg("-c")
+        .arg(HOST_SOURCE)
+        .arg("-I")
+        .arg(out_dir)
+        .arg("-o")
+        .arg(&host_obj)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    host_obj
+}
+
+#[test]
+fn test_host_imports() {
+    let _ = env_logger::builder().try_init();
+
+    let out_dir = std::path::PathBuf::from("/tmp").join("wanco_host");
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let host_obj = build_host(&out_dir);
+    let exe = out_dir.join("main");
+
+    // Compile
+    let args = Args {
+        input_file: PathBuf::from("tests").join("host").with_extension("wat"),
+        output_file: Some(exe.to_str().unwrap().to_owned()),
+        host_files: vec![host_obj],
+        ..Default::default()
+    };
+    if let Err(e) = run_compiler(&args) {
+        panic!("Could not compile {:?} ({})", &args.input_file, e);
+    }
+    // Execute
+    let output = Command::new(exe).output().unwrap();
+
+    // Assert
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Test Passed 10"));
+    assert!(!stdout.contains("Test Failed"));
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_host_signature_mismatch() {
+    let _ = env_logger::builder().try_init();
+
+    let out_dir = std::path::PathBuf::from("/tmp").join("wanco_host_mismatch");
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let host_obj = build_host(&out_dir);
+
+    let args = Args {
+        input_file: PathBuf::from("tests")
+            .join("host_mismatch")
+            .with_extension("wat"),
+        output_file: Some(out_dir.join("main").to_str().unwrap().to_owned()),
+        host_files: vec![host_obj],
+        ..Default::default()
+    };
+    let err = run_compiler(&args).expect_err("should fail to link");
+    assert!(err.to_string().contains("wanco_import_3env3add_ll_l"));
+}
@@ -0,0 +1,47 @@
+use std::{path::PathBuf, process::Command};
+
+use wanco::*;
+
+#[test]
+fn test_sharedlib() {
+    let _ = env_logger::builder().try_init();
+
+    let path = PathBuf::from("tests").join("library").with_extension("wat");
+    let out_dir = std::path::PathBuf::from("/tmp").join("wanco_library");
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let lib = out_dir.join("libwanco_library.so");
+    let exe = out_dir.join("main");
+
+    // Compile the library and libwanco_library.h
+    let args = Args {
+        input_file: path,
+        output_file: Some(lib.to_str().unwrap().to_owned()),
+        emit: Emit::Sharedlib,
+        ..Default::default()
+    };
+    if let Err(e) = run_compiler(&args) {
+        panic!("Could not compile {:?} ({})", &args.input_file, e);
+    }
+    // Build the C program calling the library
+    let status = Command::new("clang-17")
+        .arg("tests/library.c")
+        .arg("-I")
+        .arg(&out_dir)
+        .arg("-L")
+        .arg(&out_dir)
+        .arg("-lwanco_library")
+        .arg(format!("-Wl,-rpath,{}", out_dir.display()))
+        .arg("-o")
+        .arg(&exe)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    // Execute
+    let output = Command::new(exe).output().unwrap();
+
+    // Assert
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Test Passed"));
+    assert!(!stdout.contains("Test Failed"));
+    assert!(output.status.success());
+}
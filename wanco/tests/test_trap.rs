@@ -0,0 +1,213 @@
+use std::{path::PathBuf, process::Command};
+
+use wanco::*;
+
+const TEST_DIR: &str = "tests/trap/";
+
+// The exit status of a trap is 100 + the kind of the trap (see lib-rt/trap/trap.h)
+macro_rules! trap_test {
+    ($name:ident, $file:literal, $message:literal, $status:literal) => {
+        trap_test!($name, $file, $message, $status, Args::default());
+    };
+    ($name:ident, $file:literal, $message:literal, $status:literal, $args:expr) => {
+        #[test]
+        fn $name() {
+            run_test(stringify!($name), $file, $message, $status, $args);
+        }
+    };
+}
+
+fn run_test(test_name: &str, file: &str, message: &str, status: i32, args: Args) {
+    let _ = env_logger::builder().try_init();
+
+    let path = PathBuf::from(TEST_DIR).join(file).with_extension("wat");
+    // Traps caught by guard pages are reported from the signal handler without a backtrace
+    let has_backtrace = args.bounds_checks != BoundsChecks::GuardPage;
+    let tmp_filename = format!("wanco_trap_{}", test_name);
+    let exe = std::path::PathBuf::from("/tmp").join(tmp_filename);
+
+    // Compile
+    let args = Args {
+        input_file: path,
+        output_file: Some(exe.to_str().unwrap().to_owned()),
+        ..args
+    };
+    if let Err(e) = run_compiler(&args) {
+        panic!("Could not compile {:?} ({})", &args.input_file, e);
+    }
+    // Execute
+    let output = Command::new(exe).output().unwrap();
+
+    // Assert
+    // The module prints "Test Passed" before it traps
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stdout.contains("Test Passed"));
+    assert!(!stdout.contains("Test Failed"));
+    assert!(stderr.contains(&format!("wasm trap: {}", message)));
+    assert_eq!(stderr.contains("Wasm backtrace:"), has_backtrace);
+    assert_eq!(output.status.code(), Some(status));
+}
+
+trap_test!(
+    memory_out_of_bounds,
+    "memory_out_of_bounds",
+    "memory access out of bounds",
+    100
+);
+trap_test!(
+    memory_out_of_bounds_guard_page,
+    "memory_out_of_bounds",
+    "memory access out of bounds",
+    100,
+    Args {
+        bounds_checks: BoundsChecks::GuardPage,
+        ..Default::default()
+    }
+);
+trap_test!(
+    memory_grown_by_callee,
+    "memory_grown_by_callee",
+    "memory access out of bounds",
+    100
+);
+trap_test!(
+    memory_grown_by_callee_hoisted,
+    "memory_grown_by_callee",
+    "memory access out of bounds",
+    100,
+    Args {
+        hoist_memory_loads: true,
+        ..Default::default()
+    }
+);
+trap_test!(
+    memory_fill_out_of_bounds,
+    "memory_fill_out_of_bounds",
+    "memory access out of bounds",
+    100
+);
+trap_test!(
+    memory_init_out_of_bounds,
+    "memory_init_out_of_bounds",
+    "memory access out of bounds",
+    100
+);
+trap_test!(
+    memory64_out_of_bounds,
+    "memory64_out_of_bounds",
+    "memory access out of bounds",
+    100
+);
+trap_test!(
+    int_divide_by_zero,
+    "int_divide_by_zero",
+    "integer divide by zero",
+    101
+);
+trap_test!(int_overflow, "int_overflow", "integer overflow", 102);
+trap_test!(trunc_overflow, "trunc_overflow", "integer overflow", 102);
+trap_test!(
+    invalid_conversion,
+    "invalid_conversion",
+    "invalid conversion to integer",
+    103
+);
+trap_test!(unreachable, "unreachable", "unreachable executed", 104);
+trap_test!(
+    indirect_call_type_mismatch,
+    "indirect_call_type_mismatch",
+    "indirect call type mismatch",
+    105
+);
+trap_test!(
+    undefined_element,
+    "undefined_element",
+    "undefined element",
+    106
+);
+trap_test!(
+    uninitialized_element,
+    "uninitialized_element",
+    "uninitialized element",
+    107
+);
+trap_test!(
+    table_out_of_bounds,
+    "table_out_of_bounds",
+    "out of bounds table access",
+    108
+);
+trap_test!(
+    table_init_out_of_bounds,
+    "table_init_out_of_bounds",
+    "out of bounds table access",
+    108
+);
+trap_test!(
+    unaligned_atomic,
+    "unaligned_atomic",
+    "unaligned atomic",
+    109
+);
+trap_test!(
+    null_exception_reference,
+    "null_exception_reference",
+    "null reference",
+    110
+);
+trap_test!(
+    atomic_wait_non_shared,
+    "atomic_wait_non_shared",
+    "atomic wait on non-shared memory",
+    111
+);
+
+#[test]
+fn unreachable_backtrace() {
+    let _ = env_logger::builder().try_init();
+
+    let exe = std::path::PathBuf::from("/tmp").join("wanco_trap_unreachable_backtrace");
+    let args = Args {
+        input_file: PathBuf::from(TEST_DIR).join("unreachable.wat"),
+        output_file: Some(exe.to_str().unwrap().to_owned()),
+        ..Default::default()
+    };
+    if let Err(e) = run_compiler(&args) {
+        panic!("Could not compile {:?} ({})", &args.input_file, e);
+    }
+    let output = Command::new(exe).output().unwrap();
+
+    // The innermost frame comes first
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let level2 = stderr
+        .find("(level2)")
+        .expect("level2 should be in backtrace");
+    let level1 = stderr
+        .find("(level1)")
+        .expect("level1 should be in backtrace");
+    assert!(level2 < level1);
+}
+
+#[test]
+fn data_segment_out_of_bounds() {
+    let _ = env_logger::builder().try_init();
+
+    let exe = std::path::PathBuf::from("/tmp").join("wanco_trap_data_segment_out_of_bounds");
+    let args = Args {
+        input_file: PathBuf::from(TEST_DIR).join("data_segment_out_of_bounds.wat"),
+        output_file: Some(exe.to_str().unwrap().to_owned()),
+        ..Default::default()
+    };
+    if let Err(e) = run_compiler(&args) {
+        panic!("Could not compile {:?} ({})", &args.input_file, e);
+    }
+    let output = Command::new(exe).output().unwrap();
+
+    // The instantiation traps before the entry point runs
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!stdout.contains("Test Failed"));
+    assert!(stderr.contains("wasm trap: memory access out of bounds"));
+    assert_eq!(output.status.code(), Some(100));
+}
@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use wanco::*;
+
+#[test]
+fn test_unsupported_features() {
+    let _ = env_logger::builder().try_init();
+
+    let args = Args {
+        input_file: PathBuf::from("tests")
+            .join("unsupported")
+            .with_extension("wat"),
+        output_file: Some("/tmp/wanco_unsupported".to_owned()),
+        ..Default::default()
+    };
+    let err = run_compiler(&args).expect_err("should reject the module");
+    let message = err.to_string();
+    // Every problem is reported at once, in the order of the offsets
+    let expected = [
+        "0x21: host function env.pair cannot return multiple values",
+        "0x2c: imported memory env.memory is not supported",
+        "0x43: shared memory 1 is not supported",
+        "0x5d: relaxed SIMD support is not enabled",
+        "0x65: type mismatch: expected i32, found i64",
+        "0x7a: relaxed SIMD support is not enabled",
+    ];
+    let mut rest = message.as_str();
+    for entry in expected {
+        let Some(position) = rest.find(entry) else {
+            panic!("{:?} is not reported in order:\n{}", entry, message);
+        };
+        rest = &rest[position + entry.len()..];
+    }
+}
@@ -3,6 +3,8 @@ use std::{path::PathBuf, process::Command};
 use wanco::*;
 
 const TEST_DIR: &str = "tests/wasker/";
+/// Host file defining the globals of the spectest module
+const SPECTEST_SOURCE: &str = "tests/wasker/spectest.c";
 
 macro_rules! ident_to_str {
     ($ident:ident) => {
@@ -17,24 +19,46 @@ macro_rules! wasker_test {
     ($name:ident) => {
         #[test]
         fn $name() {
-            run_test(ident_to_str!($name));
+            run_test(ident_to_str!($name), false);
+        }
+    };
+    ($name:ident, spectest) => {
+        #[test]
+        fn $name() {
+            run_test(ident_to_str!($name), true);
         }
     };
 }
 
-fn run_test(test_name: &str) {
+fn run_test(test_name: &str, spectest: bool) {
     let _ = env_logger::builder().try_init();
 
     let path = PathBuf::from(TEST_DIR)
         .join(test_name)
         .with_extension("wat");
     let tmp_filename = format!("wanco_wasker_{}", test_name);
-    let exe = std::path::PathBuf::from("/tmp").join(tmp_filename);
+    let exe = std::path::PathBuf::from("/tmp").join(&tmp_filename);
+
+    let mut host_files = Vec::new();
+    if spectest {
+        let host_obj =
+            std::path::PathBuf::from("/tmp").join(format!("{}_spectest.o", tmp_filename));
+        let status = Command::new("clang-17")
+            .arg("-c")
+            .arg(SPECTEST_SOURCE)
+            .arg("-o")
+            .arg(&host_obj)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        host_files.push(host_obj);
+    }
 
     // Compile
     let args = Args {
         input_file: path,
         output_file: Some(exe.to_str().unwrap().to_owned()),
+        host_files,
         ..Default::default()
     };
     if let Err(e) = run_compiler(&args) {
@@ -56,26 +80,44 @@ fn run_test(test_name: &str) {
 wasker_test!(address32);
 wasker_test!(address64);
 wasker_test!(align);
+wasker_test!(atomic);
 wasker_test!(block);
+wasker_test!(block_param);
 wasker_test!(br);
 wasker_test!(br_if);
 wasker_test!(br_table);
 wasker_test!(bulk);
 wasker_test!(call);
 wasker_test!(call_indirect);
+wasker_test!(const_expr, spectest);
 wasker_test!(convert);
 wasker_test!(endianness);
 wasker_test!(example);
+wasker_test!(exception);
 wasker_test!(r#f64);
 wasker_test!(f64_bitwise);
 wasker_test!(f64_cmp);
 wasker_test!(r#i64);
 wasker_test!(local_get);
+wasker_test!(memory64);
 wasker_test!(memory_copy);
 wasker_test!(memory_fill);
+wasker_test!(memory_grow_limit);
+wasker_test!(memory_init);
 wasker_test!(memory_size);
+wasker_test!(multi_memory);
+wasker_test!(multi_table);
+wasker_test!(multi_value);
 wasker_test!(r#if);
 wasker_test!(r#loop);
+wasker_test!(reactor);
+wasker_test!(ref_types);
 wasker_test!(r#return);
 wasker_test!(select);
+wasker_test!(simd);
+wasker_test!(start);
 wasker_test!(switch);
+wasker_test!(table_init, spectest);
+wasker_test!(tail_call);
+wasker_test!(trunc_sat);
+wasker_test!(try_table);
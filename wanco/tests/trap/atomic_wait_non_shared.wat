@@ -0,0 +1,35 @@
+;; Waiting on a non-shared memory traps instead of blocking forever
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "Test Failed\n")
+
+  (func $printSuccess
+    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 128)))
+  )
+
+  (func $printFail
+    (drop (call $fd_write (i32.const 1) (i32.const 8) (i32.const 1) (i32.const 128)))
+  )
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 12))
+    (call $printSuccess)
+    ;; The value matches, so this would block without a timeout
+    (drop (memory.atomic.wait32 (i32.const 256) (i32.const 0) (i64.const -1)))
+    ;; should not reach here
+    (call $printFail)
+  )
+)
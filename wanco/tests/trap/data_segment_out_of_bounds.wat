@@ -0,0 +1,22 @@
+;; Test that an active data segment which does not fit in the memory traps at instantiation
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  (data (i32.const 16) "Test Failed\n")
+  ;; The last byte is out of the memory
+  (data (i32.add (i32.const 65534) (i32.const 1)) "ab")
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; Never reached since the instantiation fails
+    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 128)))
+  )
+)
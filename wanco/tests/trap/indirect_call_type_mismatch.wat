@@ -0,0 +1,44 @@
+;; Test that call_indirect with a wrong signature traps
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "Test Failed\n")
+
+  (func $printSuccess
+    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 128)))
+  )
+
+  (func $printFail
+    (drop (call $fd_write (i32.const 1) (i32.const 8) (i32.const 1) (i32.const 128)))
+  )
+
+  (type $id (func (param i32) (result i32)))
+  (type $same (func (param i32) (result i32)))
+  (type $other (func (param i64)))
+  (table 2 funcref)
+  (elem (i32.const 0) $identity)
+
+  (func $identity (type $id) (local.get 0))
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 12))
+    (call $printSuccess)
+    ;; a structurally equivalent type is accepted
+    (drop (call_indirect (type $same) (i32.const 5) (i32.const 0)))
+    (call_indirect (type $other) (i64.const 5) (i32.const 0))
+    ;; should not reach here
+    (call $printFail)
+  )
+)
@@ -0,0 +1,36 @@
+;; Test that signed division overflow traps
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "Test Failed\n")
+
+  (func $printSuccess
+    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 128)))
+  )
+
+  (func $printFail
+    (drop (call $fd_write (i32.const 1) (i32.const 8) (i32.const 1) (i32.const 128)))
+  )
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 12))
+    (call $printSuccess)
+    ;; INT_MIN % -1 does not trap
+    (drop (i32.rem_s (i32.const 0x80000000) (i32.const -1)))
+    (drop (i64.div_s (i64.const 0x8000000000000000) (i64.const -1)))
+    ;; should not reach here
+    (call $printFail)
+  )
+)
@@ -0,0 +1,21 @@
+;; Uses constructs and proposals which wanco does not support
+(module
+  (import "env" "pair" (func $pair (result i32 i32)))
+  (import "env" "memory" (memory 1))
+
+  (memory 1 1 shared)
+
+  ;; relaxed-simd proposal
+  (func $madd (param v128 v128 v128) (result v128)
+    (f32x4.relaxed_madd (local.get 0) (local.get 1) (local.get 2))
+  )
+
+  ;; type mismatch
+  (func $mismatch (result i32)
+    (i64.const 0)
+  )
+
+  (func (export "_start")
+    (drop (i32x4.relaxed_trunc_f32x4_s (v128.const i32x4 0 0 0 0)))
+  )
+)
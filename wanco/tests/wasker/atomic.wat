@@ -0,0 +1,90 @@
+;; Test atomic instructions on a shared memory
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1 1 shared)
+
+  ;; Store the Hello World (null terminated) string at byte offset 0
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "#Test Failed\n")
+
+  ;; Debug function
+  (func $printSuccess
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 0) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $printFail
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 8) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $assert_test_i32 (param $expected i32) (param $result i32)
+    local.get $expected
+    local.get $result
+    i32.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 13))
+    ;; loads and stores
+    (i32.atomic.store (i32.const 256) (i32.const 0x12345678))
+    (call $assert_test_i32 (i32.atomic.load (i32.const 256)) (i32.const 0x12345678))
+    (call $assert_test_i32 (i32.atomic.load8_u (i32.const 256)) (i32.const 0x78))
+    (call $assert_test_i32 (i32.atomic.load16_u (i32.const 258)) (i32.const 0x1234))
+    (i64.atomic.store32 (i32.const 264) (i64.const -1))
+    (call $assert_test_i32 (i32.wrap_i64 (i64.atomic.load (i32.const 264))) (i32.const -1))
+    (call $assert_test_i32 (i32.wrap_i64 (i64.shr_u (i64.atomic.load (i32.const 264)) (i64.const 32))) (i32.const 0))
+    ;; read-modify-write returns the old value
+    (i32.atomic.store (i32.const 256) (i32.const 10))
+    (call $assert_test_i32 (i32.atomic.rmw.add (i32.const 256) (i32.const 5)) (i32.const 10))
+    (call $assert_test_i32 (i32.atomic.rmw.sub (i32.const 256) (i32.const 3)) (i32.const 15))
+    (call $assert_test_i32 (i32.atomic.rmw.and (i32.const 256) (i32.const 6)) (i32.const 12))
+    (call $assert_test_i32 (i32.atomic.rmw.or (i32.const 256) (i32.const 1)) (i32.const 4))
+    (call $assert_test_i32 (i32.atomic.rmw.xor (i32.const 256) (i32.const 7)) (i32.const 5))
+    (call $assert_test_i32 (i32.atomic.rmw.xchg (i32.const 256) (i32.const 0x1ff)) (i32.const 2))
+    (call $assert_test_i32 (i32.atomic.rmw8.add_u (i32.const 256) (i32.const 1)) (i32.const 0xff))
+    (call $assert_test_i32 (i32.atomic.load (i32.const 256)) (i32.const 0x100))
+    (call $assert_test_i32 (i32.wrap_i64 (i64.atomic.rmw.add (i32.const 264) (i64.const 1))) (i32.const -1))
+    (call $assert_test_i32 (i32.wrap_i64 (i64.atomic.load (i32.const 264))) (i32.const 0))
+    ;; compare-exchange stores only if the value matches
+    (call $assert_test_i32 (i32.atomic.rmw.cmpxchg (i32.const 256) (i32.const 0) (i32.const 1)) (i32.const 0x100))
+    (call $assert_test_i32 (i32.atomic.load (i32.const 256)) (i32.const 0x100))
+    (call $assert_test_i32 (i32.atomic.rmw.cmpxchg (i32.const 256) (i32.const 0x100) (i32.const 1)) (i32.const 0x100))
+    (call $assert_test_i32 (i32.atomic.load (i32.const 256)) (i32.const 1))
+    (call $assert_test_i32 (i32.atomic.rmw16.cmpxchg_u (i32.const 256) (i32.const 0x10001) (i32.const 2)) (i32.const 1))
+    (call $assert_test_i32 (i32.atomic.load (i32.const 256)) (i32.const 2))
+    (atomic.fence)
+    ;; wait returns "not-equal" (1) if the value differs and "timed-out" (2) after the timeout
+    (call $assert_test_i32 (memory.atomic.wait32 (i32.const 256) (i32.const 0) (i64.const -1)) (i32.const 1))
+    (call $assert_test_i32 (memory.atomic.wait32 (i32.const 256) (i32.const 2) (i64.const 0)) (i32.const 2))
+    (call $assert_test_i32 (memory.atomic.wait64 (i32.const 264) (i64.const 0) (i64.const 1000)) (i32.const 2))
+    ;; no thread is waiting
+    (call $assert_test_i32 (memory.atomic.notify (i32.const 256) (i32.const 1)) (i32.const 0))
+  )
+)
@@ -0,0 +1,137 @@
+;; Test blocks with parameters
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  ;; Store the Hello World (null terminated) string at byte offset 0
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "#Test Failed\n")
+
+  ;; Debug function
+  (func $printSuccess
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 0) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $printFail
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 8) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $assert_test_i32 (param $expected i32) (param $result i32)
+    local.get $expected
+    local.get $result
+    i32.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (func $assert_test_i64 (param $expected i64) (param $result i64)
+    local.get $expected
+    local.get $result
+    i64.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (func $block-param (result i32)
+    (i32.const 10)
+    (block (param i32) (result i32)
+      (i32.const 3)
+      (i32.sub)
+    )
+  )
+  (func $block-params-br (param i32) (result i32)
+    (i32.const 1) (i32.const 2)
+    (block (param i32 i32) (result i32)
+      (i32.add)
+      (br_if 0 (local.get 0))
+      (i32.const 100)
+      (i32.add)
+    )
+  )
+  (func $if-param (param i32) (result i32)
+    (i32.const 5)
+    (if (param i32) (result i32) (local.get 0)
+      (then (i32.const 2) (i32.mul))
+      (else (i32.const 2) (i32.sub))
+    )
+  )
+  (func $if-param-no-else (param i32) (result i32)
+    (i32.const 5)
+    (if (param i32) (result i32) (local.get 0)
+      (then (i32.const 2) (i32.mul))
+    )
+  )
+  ;; sum 1..n using a loop parameter as the accumulator
+  (func $loop-param (param $n i32) (result i32)
+    (i32.const 0)
+    (loop $l (param i32) (result i32)
+      (local.get $n)
+      (i32.add)
+      (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+      (br_if $l (local.get $n))
+    )
+  )
+  ;; fibonacci using two loop parameters
+  (func $loop-params (param $n i32) (result i64)
+    (i64.const 0) (i64.const 1)
+    (loop $l (param i64 i64) (result i64)
+      (if (param i64 i64) (result i64) (i32.eqz (local.get $n))
+        (then (drop))
+        (else
+          (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+          (call $fib-step)
+          (br $l)
+        )
+      )
+    )
+  )
+  (func $fib-step (param i64 i64) (result i64 i64)
+    (local.get 1)
+    (i64.add (local.get 0) (local.get 1))
+  )
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 13))
+    (call $assert_test_i32 (call $block-param) (i32.const 7))
+    (call $assert_test_i32 (call $block-params-br (i32.const 1)) (i32.const 3))
+    (call $assert_test_i32 (call $block-params-br (i32.const 0)) (i32.const 103))
+    (call $assert_test_i32 (call $if-param (i32.const 1)) (i32.const 10))
+    (call $assert_test_i32 (call $if-param (i32.const 0)) (i32.const 3))
+    (call $assert_test_i32 (call $if-param-no-else (i32.const 1)) (i32.const 10))
+    (call $assert_test_i32 (call $if-param-no-else (i32.const 0)) (i32.const 5))
+    (call $assert_test_i32 (call $loop-param (i32.const 10)) (i32.const 55))
+    (call $assert_test_i64 (call $loop-params (i32.const 10)) (i64.const 55))
+  )
+)
@@ -744,6 +744,7 @@
   )
 
   (type $sig (func (param i32 i32 i32) (result i32)))
+  (table funcref (elem $f))
   (func $as-call_indirect-first (result i32)
     (block (result i32)
       (call_indirect (type $sig)
@@ -0,0 +1,83 @@
+;; Test extended constant expressions in globals, data and element segments
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+  ;; Constant expressions can read imported globals (see spectest.c)
+  (import "spectest" "global_i32" (global $a i32))
+  (import "spectest" "global_i64" (global $a64 i64))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  ;; Store the Hello World (null terminated) string at byte offset 0
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "#Test Failed\n")
+
+  ;; Debug function
+  (func $printSuccess
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 0) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $printFail
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 8) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $assert_test_i32 (param $expected i32) (param $result i32)
+    local.get $expected
+    local.get $result
+    i32.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  ;; 666 + 2 * 3
+  (global $b i32 (i32.add (global.get $a) (i32.mul (i32.const 2) (i32.const 3))))
+  (global $c (mut i64) (i64.sub (global.get $a64) (i64.const 624)))
+  (global $f funcref (ref.func $seven))
+  (global $n externref (ref.null extern))
+
+  ;; data at 64
+  (data (i32.sub (global.get $a) (i32.const 602)) "\2a")
+
+  (table $t 4 funcref)
+  ;; active segment at 2
+  (elem (table $t) (i32.sub (global.get $a) (i32.const 664)) func $seven)
+  (elem $exprs funcref (ref.func $seven))
+
+  (type $ret_i32 (func (result i32)))
+  (func $seven (result i32) (i32.const 7))
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 13))
+    (call $assert_test_i32 (global.get $b) (i32.const 672))
+    (call $assert_test_i32 (i64.eq (global.get $c) (i64.const 42)) (i32.const 1))
+    (call $assert_test_i32 (i32.load8_u (i32.const 64)) (i32.const 42))
+    (call $assert_test_i32 (call_indirect $t (type $ret_i32) (i32.const 2)) (i32.const 7))
+    (table.init $t $exprs (i32.const 0) (i32.const 0) (i32.const 1))
+    (call $assert_test_i32 (call_indirect $t (type $ret_i32) (i32.const 0)) (i32.const 7))
+    (call $assert_test_i32 (ref.is_null (global.get $n)) (i32.const 1))
+  )
+)
@@ -0,0 +1,159 @@
+;; Test throw, catch, catch_all, rethrow and delegate of the exception handling proposal
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  ;; Store the Hello World (null terminated) string at byte offset 0
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "#Test Failed\n")
+
+  ;; Debug function
+  (func $printSuccess
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 0) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $printFail
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 8) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $assert_test_i32 (param $expected i32) (param $result i32)
+    local.get $expected
+    local.get $result
+    i32.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (tag $e_i32 (param i32))
+  (tag $e_pair (param i64 f64))
+  (tag $e_empty)
+
+  (func $throw_i32 (param i32)
+    (throw $e_i32 (local.get 0))
+  )
+
+  ;; the exception passes through a function without try
+  (func $propagate (param i32)
+    (call $throw_i32 (local.get 0))
+  )
+
+  (func $catch_callee (result i32)
+    try (result i32)
+      (call $propagate (i32.const 42))
+      i32.const 0
+    catch $e_i32
+    end
+  )
+
+  ;; the value below the try block is kept
+  (func $catch_pair (result i32)
+    i32.const 100
+    try (result i64 f64)
+      i64.const 7
+      f64.const 2.5
+      throw $e_pair
+    catch $e_pair
+    end
+    i32.trunc_f64_s
+    i64.extend_i32_s
+    i64.add
+    i32.wrap_i64
+    i32.add
+  )
+
+  (func $catch_all (result i32)
+    try (result i32)
+      throw $e_empty
+    catch $e_i32
+    catch_all
+      i32.const 3
+    end
+  )
+
+  (func $rethrow (result i32)
+    try (result i32)
+      try
+        (call $throw_i32 (i32.const 5))
+      catch_all
+        rethrow 0
+      end
+      i32.const 0
+    catch $e_i32
+      i32.const 1
+      i32.add
+    end
+  )
+
+  (func $delegate (result i32)
+    try (result i32)
+      try
+        (call $throw_i32 (i32.const 8))
+      delegate 0
+      i32.const 0
+    catch $e_i32
+    end
+  )
+
+  (func $br_out_of_catch (result i32)
+    block $out (result i32)
+      try
+        (throw $e_i32 (i32.const 11))
+      catch $e_i32
+        br $out
+      end
+      i32.const 0
+    end
+  )
+
+  (func $catch_in_loop (result i32)
+    (local $i i32)
+    (local $sum i32)
+    loop $l
+      try
+        (call $throw_i32 (local.get $i))
+      catch $e_i32
+        (local.set $sum (i32.add (local.get $sum)))
+      end
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br_if $l (i32.lt_u (local.get $i) (i32.const 5)))
+    end
+    (local.get $sum)
+  )
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 13))
+    (call $assert_test_i32 (call $catch_callee) (i32.const 42))
+    (call $assert_test_i32 (call $catch_pair) (i32.const 109))
+    (call $assert_test_i32 (call $catch_all) (i32.const 3))
+    (call $assert_test_i32 (call $rethrow) (i32.const 6))
+    (call $assert_test_i32 (call $delegate) (i32.const 8))
+    (call $assert_test_i32 (call $br_out_of_catch) (i32.const 11))
+    (call $assert_test_i32 (call $catch_in_loop) (i32.const 10))
+  )
+)
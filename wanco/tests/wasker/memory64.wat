@@ -0,0 +1,71 @@
+;; Test a 64-bit linear memory
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+
+  ;; Define a single page 64-bit memory of 64KB.
+  (memory $0 i64 1)
+
+  ;; Store the Hello World (null terminated) string at byte offset 0
+  (data (i64.const 16) "Test Passed\n")
+  (data (i64.const 32) "#Test Failed\n")
+
+  ;; Debug function
+  (func $printSuccess
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 0) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $printFail
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 8) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $assert_test_i32 (param $expected i32) (param $result i32)
+    local.get $expected
+    local.get $result
+    i32.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i64.const 0) (i32.const 16))
+    (i32.store (i64.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i64.const 8) (i32.const 32))
+    (i32.store (i64.const 12) (i32.const 13))
+    ;; addresses and offsets are 64-bit
+    (i64.store offset=256 (i64.const 8) (i64.const 0x1122334455667788))
+    (call $assert_test_i32 (i32.load (i64.const 264)) (i32.const 0x55667788))
+    (call $assert_test_i32 (i32.load8_u offset=7 (i64.const 264)) (i32.const 0x11))
+    ;; memory.size and memory.grow take and return i64
+    (call $assert_test_i32 (i64.eq (memory.size) (i64.const 1)) (i32.const 1))
+    (call $assert_test_i32 (i64.eq (memory.grow (i64.const 2)) (i64.const 1)) (i32.const 1))
+    (call $assert_test_i32 (i64.eq (memory.size) (i64.const 3)) (i32.const 1))
+    (call $assert_test_i32 (i64.eq (memory.grow (i64.const -1)) (i64.const -1)) (i32.const 1))
+    (i32.store (i64.const 0x2fffc) (i32.const 42))
+    (call $assert_test_i32 (i32.load (i64.const 0x2fffc)) (i32.const 42))
+    ;; bulk memory instructions
+    (memory.fill (i64.const 0x20000) (i32.const 7) (i64.const 16))
+    (memory.copy (i64.const 0x20010) (i64.const 0x2000c) (i64.const 8))
+    (call $assert_test_i32 (i32.load (i64.const 0x20014)) (i32.const 0x07070707))
+  )
+)
@@ -0,0 +1,75 @@
+;; Test that memory.grow returns -1 beyond the declared maximum and the hard limit
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+
+  ;; Define a memory of 1 page which can grow up to 3 pages.
+  (memory $0 1 3)
+
+  ;; Store the Hello World (null terminated) string at byte offset 0
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "#Test Failed\n")
+
+  ;; Debug function
+  (func $printSuccess
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 0) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $printFail
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 8) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $assert_test_i32 (param $expected i32) (param $result i32)
+    local.get $expected
+    local.get $result
+    i32.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  ;; no maximum is declared, so the hard limit of 65536 pages applies
+  (memory $m1 1)
+  (memory $m2 i64 1 2)
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 13))
+    (call $assert_test_i32 (memory.grow $0 (i32.const 2)) (i32.const 1))
+    (call $assert_test_i32 (memory.grow $0 (i32.const 1)) (i32.const -1))
+    (call $assert_test_i32 (memory.size $0) (i32.const 3))
+    ;; a failed growth does not change the memory
+    (i32.store $0 (i32.const 196604) (i32.const 5))
+    (call $assert_test_i32 (i32.load $0 (i32.const 196604)) (i32.const 5))
+    (call $assert_test_i32 (memory.grow $0 (i32.const 0)) (i32.const 3))
+    ;; the delta is unsigned
+    (call $assert_test_i32 (memory.grow $0 (i32.const -1)) (i32.const -1))
+    (call $assert_test_i32 (memory.grow $m1 (i32.const 65536)) (i32.const -1))
+    (call $assert_test_i32 (memory.grow $m1 (i32.const 1)) (i32.const 1))
+    (call $assert_test_i32 (memory.size $m1) (i32.const 2))
+    (call $assert_test_i32 (i32.wrap_i64 (memory.grow $m2 (i64.const 2))) (i32.const -1))
+    (call $assert_test_i32 (i32.wrap_i64 (memory.grow $m2 (i64.const 1))) (i32.const 1))
+    (call $assert_test_i32 (i32.wrap_i64 (memory.size $m2)) (i32.const 2))
+  )
+)
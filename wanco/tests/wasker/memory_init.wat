@@ -0,0 +1,71 @@
+;; Test `memory.init` and `data.drop` operators
+(module
+
+  ;; Import our myprint function
+  (import "myenv" "print" (func $print (param i64 i32)))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  (data (i32.const 0) "Test Passed\n")
+  (data (i32.const 16) "#Test Failed\n")
+  (data $passive "\03\01\04\01\05")
+
+  ;; Debug function
+  (func $printSuccess
+    i64.const 0
+    i32.const 12
+    (call $print)
+  )
+
+  (func $printFail
+    i64.const 16
+    i32.const 16
+    (call $print)
+  )
+
+  (func $assert_test_i32 (param $expected i32) (param $result i32)
+    local.get $expected
+    local.get $result
+    i32.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (func $load8_u (param i32) (result i32)
+    (i32.load8_u (local.get 0))
+  )
+
+  (func $test_init
+    ;; copy bytes 1..4 of the passive segment
+    (memory.init $passive (i32.const 32) (i32.const 1) (i32.const 3))
+    (call $assert_test_i32 (call $load8_u (i32.const 31)) (i32.const 0))
+    (call $assert_test_i32 (call $load8_u (i32.const 32)) (i32.const 1))
+    (call $assert_test_i32 (call $load8_u (i32.const 33)) (i32.const 4))
+    (call $assert_test_i32 (call $load8_u (i32.const 34)) (i32.const 1))
+    (call $assert_test_i32 (call $load8_u (i32.const 35)) (i32.const 0))
+    ;; copying the whole segment and copying nothing from its end are allowed
+    (memory.init $passive (i32.const 40) (i32.const 0) (i32.const 5))
+    (memory.init $passive (i32.const 48) (i32.const 5) (i32.const 0))
+    (call $assert_test_i32 (call $load8_u (i32.const 40)) (i32.const 3))
+    (call $assert_test_i32 (call $load8_u (i32.const 44)) (i32.const 5))
+  )
+
+  (func $test_drop
+    (data.drop $passive)
+    ;; a dropped segment is empty, so only zero-length copies succeed
+    (memory.init $passive (i32.const 48) (i32.const 0) (i32.const 0))
+    (call $assert_test_i32 (call $load8_u (i32.const 48)) (i32.const 0))
+  )
+
+  (func (export "_start")
+    (call $test_init)
+    (call $test_drop)
+  )
+)
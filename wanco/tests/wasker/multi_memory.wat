@@ -0,0 +1,84 @@
+;; Test multiple memories with separate sizes, growth and bulk operations
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  ;; Store the Hello World (null terminated) string at byte offset 0
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "#Test Failed\n")
+
+  ;; Debug function
+  (func $printSuccess
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 0) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $printFail
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 8) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $assert_test_i32 (param $expected i32) (param $result i32)
+    local.get $expected
+    local.get $result
+    i32.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (memory $m1 1 3)
+  (data (memory $m1) (i32.const 100) "\2a\00\00\00")
+  (data $passive "\07\08\09")
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 13))
+    ;; an active segment initializes memory 1 only
+    (call $assert_test_i32 (i32.load $m1 (i32.const 100)) (i32.const 42))
+    (call $assert_test_i32 (i32.load $0 (i32.const 100)) (i32.const 0))
+    ;; stores to memory 1 do not affect memory 0
+    (i32.store $m1 (i32.const 16) (i32.const 7))
+    (call $assert_test_i32 (i32.load $0 (i32.const 16)) (i32.const 16))
+    (call $assert_test_i32 (i32.load8_u $m1 (i32.const 16)) (i32.const 7))
+    ;; memory 1 grows independently of memory 0
+    (call $assert_test_i32 (memory.grow $m1 (i32.const 1)) (i32.const 1))
+    (call $assert_test_i32 (memory.size $m1) (i32.const 2))
+    (call $assert_test_i32 (memory.size $0) (i32.const 1))
+    (i64.store $m1 (i32.const 65536) (i64.const 5))
+    (call $assert_test_i32 (i32.wrap_i64 (i64.load $m1 (i32.const 65536))) (i32.const 5))
+    ;; copy between memories
+    (memory.copy $0 $m1 (i32.const 200) (i32.const 100) (i32.const 4))
+    (call $assert_test_i32 (i32.load $0 (i32.const 200)) (i32.const 42))
+    (memory.copy $m1 $0 (i32.const 70000) (i32.const 16) (i32.const 4))
+    (call $assert_test_i32 (i32.load $m1 (i32.const 70000)) (i32.const 16))
+    ;; fill and init on memory 1
+    (memory.fill $m1 (i32.const 300) (i32.const 1) (i32.const 2))
+    (call $assert_test_i32 (i32.load16_u $m1 (i32.const 300)) (i32.const 257))
+    (memory.init $m1 $passive (i32.const 400) (i32.const 1) (i32.const 2))
+    (call $assert_test_i32 (i32.load16_u $m1 (i32.const 400)) (i32.const 2312))
+    (call $assert_test_i32 (i32.load16_u $0 (i32.const 400)) (i32.const 0))
+  )
+)
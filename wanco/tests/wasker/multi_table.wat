@@ -0,0 +1,102 @@
+;; Test multiple tables, imported and exported tables, and table.copy
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+  ;; 10 null elements provided by the runtime
+  (import "spectest" "table" (table $imported 10 funcref))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  ;; Store the Hello World (null terminated) string at byte offset 0
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "#Test Failed\n")
+
+  ;; Debug function
+  (func $printSuccess
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 0) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $printFail
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 8) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $assert_test_i32 (param $expected i32) (param $result i32)
+    local.get $expected
+    local.get $result
+    i32.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (table $t1 2 funcref)
+  (table $t2 (export "t2") 3 funcref)
+  (elem (table $t1) (i32.const 0) func $one $two)
+  (elem (table $t2) (i32.const 1) func $three)
+  (elem (table $imported) (i32.const 9) func $two)
+
+  (type $ret_i32 (func (result i32)))
+  (func $one (result i32) (i32.const 1))
+  (func $two (result i32) (i32.const 2))
+  (func $three (result i32) (i32.const 3))
+
+  (func $call-t1 (param i32) (result i32)
+    (call_indirect $t1 (type $ret_i32) (local.get 0))
+  )
+  (func $call-t2 (param i32) (result i32)
+    (call_indirect $t2 (type $ret_i32) (local.get 0))
+  )
+  (func $call-imported (param i32) (result i32)
+    (call_indirect $imported (type $ret_i32) (local.get 0))
+  )
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 13))
+    (call $assert_test_i32 (table.size $imported) (i32.const 10))
+    (call $assert_test_i32 (table.size $t1) (i32.const 2))
+    (call $assert_test_i32 (table.size $t2) (i32.const 3))
+    (call $assert_test_i32 (call $call-t1 (i32.const 0)) (i32.const 1))
+    (call $assert_test_i32 (call $call-t1 (i32.const 1)) (i32.const 2))
+    (call $assert_test_i32 (call $call-t2 (i32.const 1)) (i32.const 3))
+    (call $assert_test_i32 (call $call-imported (i32.const 9)) (i32.const 2))
+    (call $assert_test_i32 (ref.is_null (table.get $t2 (i32.const 0))) (i32.const 1))
+    ;; copy $t1[0..2] to $t2[0..2] and overwrite $t2[1]
+    (table.copy $t2 $t1 (i32.const 0) (i32.const 0) (i32.const 2))
+    (call $assert_test_i32 (call $call-t2 (i32.const 0)) (i32.const 1))
+    (call $assert_test_i32 (call $call-t2 (i32.const 1)) (i32.const 2))
+    ;; overlapping copy within a table
+    (table.copy $t2 $t2 (i32.const 1) (i32.const 0) (i32.const 2))
+    (call $assert_test_i32 (call $call-t2 (i32.const 1)) (i32.const 1))
+    (call $assert_test_i32 (call $call-t2 (i32.const 2)) (i32.const 2))
+    ;; grow one table without affecting the others
+    (call $assert_test_i32 (table.grow $t1 (ref.null func) (i32.const 3)) (i32.const 2))
+    (call $assert_test_i32 (table.size $t1) (i32.const 5))
+    (call $assert_test_i32 (table.size $t2) (i32.const 3))
+    (table.copy $imported $t1 (i32.const 0) (i32.const 0) (i32.const 5))
+    (call $assert_test_i32 (call $call-imported (i32.const 1)) (i32.const 2))
+    (call $assert_test_i32 (ref.is_null (table.get $imported (i32.const 4))) (i32.const 1))
+  )
+)
@@ -0,0 +1,127 @@
+;; Test multi-value function and block results
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  ;; Store the Hello World (null terminated) string at byte offset 0
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "#Test Failed\n")
+
+  ;; Debug function
+  (func $printSuccess
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 0) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $printFail
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 8) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $assert_test_i32 (param $expected i32) (param $result i32)
+    local.get $expected
+    local.get $result
+    i32.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (func $assert_test_i64 (param $expected i64) (param $result i64)
+    local.get $expected
+    local.get $result
+    i64.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (func $swap (param i32 i32) (result i32 i32)
+    (local.get 1) (local.get 0)
+  )
+  (func $sub-swapped (result i32)
+    (call $swap (i32.const 3) (i32.const 10))
+    (i32.sub)
+  )
+  (func $pair (result i64 i32)
+    (i64.const 1) (i32.const 2)
+  )
+  (func $pair-second (result i64)
+    (call $pair)
+    (drop)
+  )
+  (func $block-pair (result i32)
+    (block (result i32 i32) (i32.const 20) (i32.const 7))
+    (i32.sub)
+  )
+  (func $br-pair (param i32) (result i32)
+    (block (result i32 i32)
+      (i32.const 1) (i32.const 2)
+      (br_if 0 (local.get 0))
+      (drop) (drop)
+      (i32.const 30) (i32.const 4)
+    )
+    (i32.sub)
+  )
+  (func $if-pair (param i32) (result i32 i32)
+    (if (result i32 i32) (local.get 0)
+      (then (i32.const 5) (i32.const 6))
+      (else (i32.const 7) (i32.const 8))
+    )
+  )
+  (func $if-pair-sub (param i32) (result i32)
+    (call $if-pair (local.get 0))
+    (i32.sub)
+  )
+  (func $return-pair (param i32) (result i32 i32)
+    (if (local.get 0)
+      (then (return (i32.const 40) (i32.const 2)))
+    )
+    (i32.const 0) (i32.const 0)
+  )
+  (func $return-pair-sub (param i32) (result i32)
+    (call $return-pair (local.get 0))
+    (i32.sub)
+  )
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 13))
+    (call $assert_test_i32 (call $sub-swapped) (i32.const 7))
+    (call $assert_test_i64 (call $pair-second) (i64.const 1))
+    (call $assert_test_i32 (call $block-pair) (i32.const 13))
+    (call $assert_test_i32 (call $br-pair (i32.const 1)) (i32.const -1))
+    (call $assert_test_i32 (call $br-pair (i32.const 0)) (i32.const 26))
+    (call $assert_test_i32 (call $if-pair-sub (i32.const 1)) (i32.const -1))
+    (call $assert_test_i32 (call $if-pair-sub (i32.const 0)) (i32.const -1))
+    (call $assert_test_i32 (call $return-pair-sub (i32.const 1)) (i32.const 38))
+    (call $assert_test_i32 (call $return-pair-sub (i32.const 0)) (i32.const 0))
+  )
+)
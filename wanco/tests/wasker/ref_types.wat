@@ -0,0 +1,119 @@
+;; Test reference types and table instructions
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  ;; Store the Hello World (null terminated) string at byte offset 0
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "#Test Failed\n")
+
+  ;; Debug function
+  (func $printSuccess
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 0) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $printFail
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 8) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $assert_test_i32 (param $expected i32) (param $result i32)
+    local.get $expected
+    local.get $result
+    i32.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (table $t 2 4 funcref)
+  (elem (i32.const 0) $one)
+  (global $g (mut funcref) (ref.null func))
+
+  (type $ret_i32 (func (result i32)))
+  (func $one (result i32) (i32.const 1))
+  ;; exported so that it can be referenced by ref.func
+  (func $two (export "two") (result i32) (i32.const 2))
+
+  (func $is-null-funcref (param funcref) (result i32)
+    (ref.is_null (local.get 0))
+  )
+  (func $is-null-externref (param externref) (result i32)
+    (ref.is_null (local.get 0))
+  )
+  ;; locals of reference types are initialized with null
+  (func $null-local (result i32)
+    (local funcref)
+    (ref.is_null (local.get 0))
+  )
+  (func $select-ref (param i32) (result funcref)
+    (select (result funcref) (ref.func $one) (ref.null func) (local.get 0))
+  )
+  (func $call-at (param i32) (result i32)
+    (call_indirect (type $ret_i32) (local.get 0))
+  )
+  (func $set-and-call (result i32)
+    (table.set $t (i32.const 1) (ref.func $two))
+    (call $call-at (i32.const 1))
+  )
+  (func $get-is-null (param i32) (result i32)
+    (ref.is_null (table.get $t (local.get 0)))
+  )
+  (func $grow (param i32) (result i32)
+    (table.grow $t (ref.func $two) (local.get 0))
+  )
+  (func $fill-and-call (result i32)
+    (table.fill $t (i32.const 0) (ref.func $two) (table.size $t))
+    (call $call-at (i32.const 0))
+  )
+  (func $global-ref (result i32)
+    (global.set $g (ref.func $one))
+    (ref.is_null (global.get $g))
+  )
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 13))
+    (call $assert_test_i32 (call $is-null-funcref (ref.null func)) (i32.const 1))
+    (call $assert_test_i32 (call $is-null-funcref (ref.func $one)) (i32.const 0))
+    (call $assert_test_i32 (call $is-null-externref (ref.null extern)) (i32.const 1))
+    (call $assert_test_i32 (call $null-local) (i32.const 1))
+    (call $assert_test_i32 (ref.is_null (call $select-ref (i32.const 1))) (i32.const 0))
+    (call $assert_test_i32 (ref.is_null (call $select-ref (i32.const 0))) (i32.const 1))
+    (call $assert_test_i32 (table.size $t) (i32.const 2))
+    (call $assert_test_i32 (call $call-at (i32.const 0)) (i32.const 1))
+    (call $assert_test_i32 (call $get-is-null (i32.const 1)) (i32.const 1))
+    (call $assert_test_i32 (call $set-and-call) (i32.const 2))
+    (call $assert_test_i32 (call $get-is-null (i32.const 1)) (i32.const 0))
+    (call $assert_test_i32 (call $grow (i32.const 2)) (i32.const 2))
+    (call $assert_test_i32 (table.size $t) (i32.const 4))
+    (call $assert_test_i32 (call $call-at (i32.const 3)) (i32.const 2))
+    ;; growing beyond the maximum fails
+    (call $assert_test_i32 (call $grow (i32.const 1)) (i32.const -1))
+    (call $assert_test_i32 (call $fill-and-call) (i32.const 2))
+    (call $assert_test_i32 (call $global-ref) (i32.const 0))
+  )
+)
@@ -0,0 +1,164 @@
+;; Test fixed-width SIMD instructions
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  ;; Store the Hello World (null terminated) string at byte offset 0
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "#Test Failed\n")
+
+  ;; Debug function
+  (func $printSuccess
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 0) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $printFail
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 8) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $assert_test_i32 (param $expected i32) (param $result i32)
+    local.get $expected
+    local.get $result
+    i32.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (global $g (mut v128) (v128.const i32x4 1 2 3 4))
+
+  (func $sum_i32x4 (param $v v128) (result i32)
+    (i32.add
+      (i32.add (i32x4.extract_lane 0 (local.get $v)) (i32x4.extract_lane 1 (local.get $v)))
+      (i32.add (i32x4.extract_lane 2 (local.get $v)) (i32x4.extract_lane 3 (local.get $v))))
+  )
+
+  (func (export "_start")
+    (local $v v128)
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 13))
+    ;; v128 locals, globals and arguments
+    (local.set $v (i32x4.add (global.get $g) (i32x4.splat (i32.const 10))))
+    (global.set $g (local.get $v))
+    (call $assert_test_i32 (call $sum_i32x4 (global.get $g)) (i32.const 50))
+    ;; unaligned loads and stores
+    (v128.store offset=1 (i32.const 256) (v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15))
+    (call $assert_test_i32 (i8x16.extract_lane_u 15 (v128.load (i32.const 257))) (i32.const 15))
+    (call $assert_test_i32 (i32x4.extract_lane 1 (v128.load32_zero (i32.const 257))) (i32.const 0))
+    (call $assert_test_i32 (i16x8.extract_lane_s 3 (v128.load8x8_s (i32.const 257))) (i32.const 3))
+    (call $assert_test_i32 (i8x16.extract_lane_u 9 (v128.load8_splat (i32.const 259))) (i32.const 2))
+    (v128.store8_lane 2 (i32.const 300) (v128.const i8x16 0 0 42 0 0 0 0 0 0 0 0 0 0 0 0 0))
+    (call $assert_test_i32 (i32.load8_u (i32.const 300)) (i32.const 42))
+    ;; lanes
+    (call $assert_test_i32
+      (i8x16.extract_lane_s 0 (i8x16.replace_lane 0 (v128.const i64x2 0 0) (i32.const 255)))
+      (i32.const -1))
+    (call $assert_test_i32
+      (i32x4.extract_lane 0
+        (i8x16.shuffle 4 5 6 7 16 17 18 19 0 0 0 0 0 0 0 0
+          (v128.const i32x4 1 2 3 4) (v128.const i32x4 5 6 7 8)))
+      (i32.const 2))
+    (call $assert_test_i32
+      (i8x16.extract_lane_u 1
+        (i8x16.swizzle (v128.const i8x16 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25)
+          (v128.const i8x16 3 200 0 0 0 0 0 0 0 0 0 0 0 0 0 0)))
+      (i32.const 0))
+    ;; integer arithmetic
+    (call $assert_test_i32
+      (i8x16.extract_lane_u 0 (i8x16.add_sat_u (i8x16.splat (i32.const 200)) (i8x16.splat (i32.const 100))))
+      (i32.const 255))
+    (call $assert_test_i32
+      (i16x8.extract_lane_s 0 (i16x8.sub_sat_s (i16x8.splat (i32.const -30000)) (i16x8.splat (i32.const 10000))))
+      (i32.const -32768))
+    (call $assert_test_i32
+      (i32x4.extract_lane 3 (i32x4.mul (v128.const i32x4 1 2 3 4) (v128.const i32x4 5 6 7 8)))
+      (i32.const 32))
+    (call $assert_test_i32
+      (i32x4.extract_lane 0 (i32x4.shr_s (i32x4.splat (i32.const -16)) (i32.const 34)))
+      (i32.const -4))
+    (call $assert_test_i32
+      (i8x16.extract_lane_u 0 (i8x16.avgr_u (i8x16.splat (i32.const 1)) (i8x16.splat (i32.const 2))))
+      (i32.const 2))
+    (call $assert_test_i32
+      (i32x4.extract_lane 0 (i32x4.dot_i16x8_s (v128.const i16x8 1 2 3 4 5 6 7 8) (v128.const i16x8 1 1 1 1 1 1 1 1)))
+      (i32.const 3))
+    (call $assert_test_i32
+      (i16x8.extract_lane_s 0 (i16x8.q15mulr_sat_s (i16x8.splat (i32.const -32768)) (i16x8.splat (i32.const -32768))))
+      (i32.const 32767))
+    (call $assert_test_i32
+      (i8x16.extract_lane_s 8 (i8x16.narrow_i16x8_s (i16x8.splat (i32.const 1000)) (i16x8.splat (i32.const -1000))))
+      (i32.const -128))
+    (call $assert_test_i32
+      (i32x4.extract_lane 1 (i32x4.extend_high_i16x8_u (v128.const i16x8 0 0 0 0 0 65535 0 0)))
+      (i32.const 65535))
+    (call $assert_test_i32
+      (i32x4.extract_lane 0 (i32x4.extadd_pairwise_i16x8_s (v128.const i16x8 -1 -2 0 0 0 0 0 0)))
+      (i32.const -3))
+    ;; comparisons and reductions
+    (call $assert_test_i32
+      (i8x16.bitmask (i8x16.lt_s (v128.const i8x16 -1 1 -1 1 0 0 0 0 0 0 0 0 0 0 0 -5) (i8x16.splat (i32.const 0))))
+      (i32.const 0x8005))
+    (call $assert_test_i32 (i32x4.all_true (v128.const i32x4 1 2 3 0)) (i32.const 0))
+    (call $assert_test_i32 (i32x4.all_true (v128.const i32x4 1 2 3 4)) (i32.const 1))
+    (call $assert_test_i32 (v128.any_true (v128.const i64x2 0 0x100)) (i32.const 1))
+    (call $assert_test_i32
+      (i32.wrap_i64 (i64x2.extract_lane 0 (i64x2.eq (v128.const i64x2 7 0) (v128.const i64x2 7 1))))
+      (i32.const -1))
+    (call $assert_test_i32
+      (i32x4.extract_lane 0
+        (v128.bitselect (v128.const i32x4 0xff00ff00 0 0 0) (v128.const i32x4 0x00ff00ff 0 0 0) (v128.const i32x4 0xffff0000 0 0 0)))
+      (i32.const 0xff0000ff))
+    ;; floating-point arithmetic
+    (call $assert_test_i32
+      (i32.trunc_f32_s (f32x4.extract_lane 2 (f32x4.mul (v128.const f32x4 1 2 3 4) (f32x4.splat (f32.const 2.5)))))
+      (i32.const 7))
+    (call $assert_test_i32
+      (i32.wrap_i64 (i64.shr_u
+        (i64.reinterpret_f64 (f64x2.extract_lane 0 (f64x2.min (f64x2.splat (f64.const 0)) (f64x2.splat (f64.const -0)))))
+        (i64.const 32)))
+      (i32.const 0x80000000))
+    (call $assert_test_i32
+      (f32.ne (f32x4.extract_lane 0 (f32x4.max (f32x4.splat (f32.const nan)) (f32x4.splat (f32.const 1)))) (f32.const nan))
+      (i32.const 1))
+    (call $assert_test_i32
+      (i32.trunc_f64_s (f64x2.extract_lane 1 (f64x2.sqrt (v128.const f64x2 4 9))))
+      (i32.const 3))
+    (call $assert_test_i32
+      (i32.trunc_f32_s (f32x4.extract_lane 0 (f32x4.nearest (f32x4.splat (f32.const 2.5)))))
+      (i32.const 2))
+    ;; conversions
+    (call $assert_test_i32
+      (i32x4.extract_lane 0 (i32x4.trunc_sat_f32x4_s (f32x4.splat (f32.const 1e10))))
+      (i32.const 0x7fffffff))
+    (call $assert_test_i32
+      (i32x4.extract_lane 2 (i32x4.trunc_sat_f64x2_s_zero (f64x2.splat (f64.const -3.7))))
+      (i32.const 0))
+    (call $assert_test_i32
+      (i32.trunc_f64_s (f64x2.extract_lane 1 (f64x2.convert_low_i32x4_s (v128.const i32x4 1 -2 3 4))))
+      (i32.const -2))
+  )
+)
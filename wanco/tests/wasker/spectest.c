@@ -0,0 +1,5 @@
+// Globals of the spectest module, imported by some of the wasker tests
+#include <stdint.h>
+
+const int32_t wanco_import_8spectest10global_i32_i = 666;
+const int64_t wanco_import_8spectest10global_i64_l = 666;
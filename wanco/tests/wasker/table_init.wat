@@ -0,0 +1,95 @@
+;; Test element segments of every form, table.init and elem.drop
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+  ;; 666 (see spectest.c)
+  (import "spectest" "global_i32" (global $base i32))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  ;; Store the Hello World (null terminated) string at byte offset 0
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "#Test Failed\n")
+
+  ;; Debug function
+  (func $printSuccess
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 0) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $printFail
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 8) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $assert_test_i32 (param $expected i32) (param $result i32)
+    local.get $expected
+    local.get $result
+    i32.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (table $t 6 funcref)
+  ;; active segment at an offset given by an imported global
+  (elem (table $t) (i32.sub (global.get $base) (i32.const 662)) func $three)
+  ;; passive segments
+  (elem $funcs func $one $two $three)
+  (elem $exprs funcref (ref.func $two) (ref.null func) (ref.func $one))
+  ;; declarative segment for ref.func
+  (elem declare func $four)
+
+  (type $ret_i32 (func (result i32)))
+  (func $one (result i32) (i32.const 1))
+  (func $two (result i32) (i32.const 2))
+  (func $three (result i32) (i32.const 3))
+  (func $four (result i32) (i32.const 4))
+
+  (func $call (param i32) (result i32)
+    (call_indirect $t (type $ret_i32) (local.get 0))
+  )
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 13))
+    (call $assert_test_i32 (call $call (i32.const 4)) (i32.const 3))
+    (call $assert_test_i32 (ref.is_null (table.get $t (i32.const 0))) (i32.const 1))
+    ;; copy $funcs[1..3] to $t[0..2]
+    (table.init $t $funcs (i32.const 0) (i32.const 1) (i32.const 2))
+    (call $assert_test_i32 (call $call (i32.const 0)) (i32.const 2))
+    (call $assert_test_i32 (call $call (i32.const 1)) (i32.const 3))
+    ;; expression items may be null
+    (table.init $t $exprs (i32.const 1) (i32.const 0) (i32.const 3))
+    (call $assert_test_i32 (call $call (i32.const 1)) (i32.const 2))
+    (call $assert_test_i32 (ref.is_null (table.get $t (i32.const 2))) (i32.const 1))
+    (call $assert_test_i32 (call $call (i32.const 3)) (i32.const 1))
+    ;; a dropped segment is empty, so only zero-length copies succeed
+    (elem.drop $funcs)
+    (table.init $t $funcs (i32.const 0) (i32.const 0) (i32.const 0))
+    (call $assert_test_i32 (call $call (i32.const 0)) (i32.const 2))
+    ;; functions in a declarative segment can be referenced
+    (table.set $t (i32.const 5) (ref.func $four))
+    (call $assert_test_i32 (call $call (i32.const 5)) (i32.const 4))
+  )
+)
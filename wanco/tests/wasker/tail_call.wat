@@ -0,0 +1,115 @@
+;; Test return_call and return_call_indirect
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  ;; Store the Hello World (null terminated) string at byte offset 0
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "#Test Failed\n")
+
+  ;; Debug function
+  (func $printSuccess
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 0) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $printFail
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 8) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $assert_test_i32 (param $expected i32) (param $result i32)
+    local.get $expected
+    local.get $result
+    i32.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (func $even (param $n i32) (result i32)
+    (if (result i32) (i32.eqz (local.get $n))
+      (then (i32.const 1))
+      (else (return_call $odd (i32.sub (local.get $n) (i32.const 1))))
+    )
+  )
+  (func $odd (param $n i32) (result i32)
+    (if (result i32) (i32.eqz (local.get $n))
+      (then (i32.const 0))
+      (else (return_call $even (i32.sub (local.get $n) (i32.const 1))))
+    )
+  )
+
+  ;; tail calls between functions with different parameters
+  (func $sum (param $n i64) (result i64)
+    (return_call $sum_acc (local.get $n) (i64.const 0) (i32.const 0))
+  )
+  (func $sum_acc (param $n i64) (param $acc i64) (param $unused i32) (result i64)
+    (if (i64.eqz (local.get $n))
+      (then (return (local.get $acc)))
+    )
+    (return_call $sum_step (i64.add (local.get $acc) (local.get $n)) (local.get $n))
+  )
+  (func $sum_step (param $acc i64) (param $n i64) (result i64)
+    (return_call $sum_acc (i64.sub (local.get $n) (i64.const 1)) (local.get $acc) (i32.const 0))
+  )
+
+  ;; tail calls through a table
+  (type $countdown_t (func (param i32) (result i32)))
+  (table $t 2 funcref)
+  (elem (table $t) (i32.const 0) func $countdown $done)
+  (func $countdown (param $n i32) (result i32)
+    (return_call_indirect $t (type $countdown_t)
+      (i32.sub (local.get $n) (i32.const 1))
+      (i32.eqz (local.get $n)))
+  )
+  (func $done (param $n i32) (result i32)
+    (i32.const 42)
+  )
+
+  ;; tail calls returning multiple values
+  (func $swap (param i32 i32) (result i32 i32)
+    (local.get 1)
+    (local.get 0)
+  )
+  (func $swap_tail (param i32 i32) (result i32 i32)
+    (return_call $swap (local.get 0) (local.get 1))
+  )
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 13))
+    ;; deep enough to overflow the stack without tail calls
+    (call $assert_test_i32 (call $even (i32.const 10000000)) (i32.const 1))
+    (call $assert_test_i32 (call $odd (i32.const 10000001)) (i32.const 1))
+    (call $assert_test_i32
+      (i64.eq (call $sum (i64.const 10000000)) (i64.const 50000005000000))
+      (i32.const 1))
+    (call $assert_test_i32 (call $countdown (i32.const 10000000)) (i32.const 42))
+    (call $swap_tail (i32.const 1) (i32.const 2))
+    (call $assert_test_i32 (i32.const 1))
+    (call $assert_test_i32 (i32.const 2))
+  )
+)
@@ -0,0 +1,86 @@
+;; Test saturating float-to-int conversions and non-trapping integer edge cases
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  ;; Store the Hello World (null terminated) string at byte offset 0
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "#Test Failed\n")
+
+  ;; Debug function
+  (func $printSuccess
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 0) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $printFail
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 8) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $assert_test_i32 (param $expected i32) (param $result i32)
+    local.get $expected
+    local.get $result
+    i32.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (func $assert_test_i64 (param $expected i64) (param $result i64)
+    local.get $expected
+    local.get $result
+    i64.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 13))
+    (call $assert_test_i32 (i32.trunc_sat_f32_s (f32.const nan)) (i32.const 0))
+    (call $assert_test_i32 (i32.trunc_sat_f32_s (f32.const 1e10)) (i32.const 0x7fffffff))
+    (call $assert_test_i32 (i32.trunc_sat_f64_s (f64.const -1e10)) (i32.const 0x80000000))
+    (call $assert_test_i32 (i32.trunc_sat_f64_s (f64.const -3.9)) (i32.const -3))
+    (call $assert_test_i32 (i32.trunc_sat_f32_u (f32.const -1)) (i32.const 0))
+    (call $assert_test_i32 (i32.trunc_sat_f64_u (f64.const 1e10)) (i32.const 0xffffffff))
+    (call $assert_test_i64 (i64.trunc_sat_f32_s (f32.const -inf)) (i64.const 0x8000000000000000))
+    (call $assert_test_i64 (i64.trunc_sat_f64_s (f64.const inf)) (i64.const 0x7fffffffffffffff))
+    (call $assert_test_i64 (i64.trunc_sat_f32_u (f32.const nan)) (i64.const 0))
+    (call $assert_test_i64 (i64.trunc_sat_f64_u (f64.const 4294967296)) (i64.const 4294967296))
+    (call $assert_test_i32 (i32.trunc_f32_s (f32.const -2147483648)) (i32.const 0x80000000))
+    (call $assert_test_i32 (i32.trunc_f64_s (f64.const -2147483648.9)) (i32.const 0x80000000))
+    (call $assert_test_i32 (i32.trunc_f64_u (f64.const -0.9)) (i32.const 0))
+    (call $assert_test_i32 (i32.rem_s (i32.const 0x80000000) (i32.const -1)) (i32.const 0))
+    (call $assert_test_i64 (i64.rem_s (i64.const 0x8000000000000000) (i64.const -1)) (i64.const 0))
+    (call $assert_test_i32 (i32.div_s (i32.const -7) (i32.const 2)) (i32.const -3))
+  )
+)
@@ -0,0 +1,139 @@
+;; Test try_table, catch clauses and throw_ref of the exception handling proposal
+(module
+  (type (;0;) (func (param i32 i32 i32 i32) (result i32)))
+
+  ;; Import fd_write function
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type 0)))
+
+  ;; Define a single page memory of 64KB.
+  (memory $0 1)
+
+  ;; Store the Hello World (null terminated) string at byte offset 0
+  (data (i32.const 16) "Test Passed\n")
+  (data (i32.const 32) "#Test Failed\n")
+
+  ;; Debug function
+  (func $printSuccess
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 0) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $printFail
+    (drop (call $fd_write
+            (i32.const 1) ;; file_descriptor - 1 for stdout
+            (i32.const 8) ;; *iovs - The pointer to the iov array, which is stored at memory location 0
+            (i32.const 1) ;; iovs_len - We're printing 1 string stored in an iov - so one.
+            (i32.const 128) ;; nwritten - A place in memory to store the number of bytes written
+    ))
+  )
+
+  (func $assert_test_i32 (param $expected i32) (param $result i32)
+    local.get $expected
+    local.get $result
+    i32.eq
+    (if
+      (then
+        (call $printSuccess)
+      )
+      (else
+        (call $printFail)
+      )
+    )
+  )
+
+  (tag $e_i32 (param i32))
+  (tag $e_empty)
+
+  (func $throw_i32 (param i32)
+    (throw $e_i32 (local.get 0))
+  )
+
+  ;; the exception passes through a function without try_table
+  (func $propagate (param i32)
+    (call $throw_i32 (local.get 0))
+  )
+
+  (func $catch (result i32)
+    (block $h (result i32)
+      (try_table (catch $e_i32 $h)
+        (call $propagate (i32.const 42))
+      )
+      (i32.const 0)
+    )
+  )
+
+  ;; catch_all after a catch clause of another tag
+  (func $catch_all (result i32)
+    (block $h
+      (block $h_i32 (result i32)
+        (try_table (catch $e_i32 $h_i32) (catch_all $h)
+          (throw $e_empty)
+        )
+        (return (i32.const 0))
+      )
+      (return (i32.const 1))
+    )
+    (i32.const 3)
+  )
+
+  (func $catch_ref (result i32)
+    (block $h (result i32 exnref)
+      (try_table (catch_ref $e_i32 $h)
+        (call $throw_i32 (i32.const 7))
+      )
+      (return (i32.const 0))
+    )
+    (drop)
+  )
+
+  ;; an exception caught by catch_all_ref is thrown again to the outer try_table
+  (func $throw_ref (result i32)
+    (block $outer (result i32)
+      (try_table (catch $e_i32 $outer)
+        (block $inner (result exnref)
+          (try_table (catch_all_ref $inner)
+            (call $throw_i32 (i32.const 5))
+          )
+          (return (i32.const 0))
+        )
+        (throw_ref)
+      )
+      (i32.const 0)
+    )
+  )
+
+  (func $catch_in_loop (result i32)
+    (local $i i32)
+    (local $sum i32)
+    (loop $l
+      (block $h (result i32)
+        (try_table (catch $e_i32 $h)
+          (call $throw_i32 (local.get $i))
+        )
+        (i32.const 0)
+      )
+      (local.set $sum (i32.add (local.get $sum)))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br_if $l (i32.lt_u (local.get $i) (i32.const 5)))
+    )
+    (local.get $sum)
+  )
+
+  (func (export "_start")
+    ;; iov.iov_base and iov.iov_len for "success"
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 12))
+    ;; iov.iov_base and iov.iov_len for "fail"
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.const 13))
+    (call $assert_test_i32 (call $catch) (i32.const 42))
+    (call $assert_test_i32 (call $catch_all) (i32.const 3))
+    (call $assert_test_i32 (call $catch_ref) (i32.const 7))
+    (call $assert_test_i32 (call $throw_ref) (i32.const 5))
+    (call $assert_test_i32 (call $catch_in_loop) (i32.const 10))
+  )
+)